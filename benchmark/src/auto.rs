@@ -0,0 +1,492 @@
+//! Searching models and preprocessing together
+//!
+//! `search` tunes the forest with everything else held fixed. `auto` treats the whole pipeline as
+//! the thing to tune instead: which of a dataset's feature sets to use, how to fill in its missing
+//! values, and which model to fit on them, with a few settings each. The pipelines are tried in a
+//! random order, each cross-validated on the same folds with the same seeds, until a time budget
+//! runs out, and the best one found is reported.
+//!
+//! With a `CostMatrix`, the boosting trains to minimize it (the other models can't be weighted
+//! that way, and rely on the metric picking their cheapest labels).
+//!
+//! There's no choice of scaling: the forest's and the boosting's trees split on thresholds, which
+//! scaling doesn't move, and the logistic regression standardizes its own inputs.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use ch4_logistic_regression::logistic_regression::LogisticRegression;
+use metrics::{cost::CostMatrix, metric::Metric};
+use ndarray::{Array1, Array2, Axis, Zip, stack};
+use titanic_ml::{config::RandomForestConfig, seed::Seed, splitter::Splitter};
+
+use crate::{
+    boosting::{Backend, GradientBoosting},
+    cross_validation::{cross_validate, for_every_class},
+    datasets::Dataset,
+    mean_and_std,
+    search::SearchSpace,
+};
+
+/// How many of `SearchSpace::forest`'s forests to try
+const N_FORESTS: usize = 6;
+
+/// How to fill in missing (NaN) values, from the training rows only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Imputation {
+    /// With 0, as `dataframe_to_array2` does
+    Zero,
+    /// With the column's mean
+    Mean,
+    /// With the column's median, which an outlier can't drag
+    Median,
+}
+
+impl Imputation {
+    pub const ALL: [Self; 3] = [Self::Zero, Self::Mean, Self::Median];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::Mean => "mean",
+            Self::Median => "median",
+        }
+    }
+
+    /// The value to fill each column's NaNs with, from the values of `x` that aren't NaN (0 for a
+    /// column without any)
+    pub fn fill_values(self, x: &Array2<f64>) -> Array1<f64> {
+        x.map_axis(Axis(0), |column| {
+            let mut values: Vec<f64> = column.iter().copied().filter(|v| !v.is_nan()).collect();
+            if values.is_empty() {
+                return 0.0;
+            }
+            match self {
+                Self::Zero => 0.0,
+                Self::Mean => values.iter().sum::<f64>() / values.len() as f64,
+                Self::Median => {
+                    values.sort_by(f64::total_cmp);
+                    let middle = values.len() / 2;
+                    if values.len().is_multiple_of(2) {
+                        (values[middle - 1] + values[middle]) / 2.0
+                    } else {
+                        values[middle]
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Imputation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// `x` with the NaNs in each column replaced by that column's fill value
+pub fn impute(x: &Array2<f64>, fill_values: &Array1<f64>) -> Array2<f64> {
+    let mut x = x.clone();
+    Zip::from(x.columns_mut())
+        .and(fill_values)
+        .for_each(|mut column, &fill| column.mapv_inplace(|v| if v.is_nan() { fill } else { v }));
+
+    x
+}
+
+/// A model, with its settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    Forest(RandomForestConfig),
+    GradientBoosting {
+        n_rounds: usize,
+        learning_rate: f64,
+        backend: Backend,
+    },
+    LogisticRegression {
+        l2: f64,
+    },
+}
+
+impl Model {
+    /// The models worth trying on labels with `n_classes` classes: a sample of
+    /// `SearchSpace::forest`'s forests and, for two classes, boosting (on `backend`) and logistic
+    /// regression
+    pub fn candidates(n_classes: usize, backend: Backend, seed: Seed) -> Vec<Self> {
+        let space = SearchSpace::forest();
+        let mut models: Vec<Self> = space
+            .random(N_FORESTS, seed)
+            .into_iter()
+            .map(|values| Self::Forest(space.classifier(&values).config()))
+            .collect();
+
+        if n_classes == 2 {
+            models.extend([
+                Self::GradientBoosting {
+                    n_rounds: 50,
+                    learning_rate: 0.1,
+                    backend,
+                },
+                Self::GradientBoosting {
+                    n_rounds: 150,
+                    learning_rate: 0.05,
+                    backend,
+                },
+                Self::LogisticRegression { l2: 0.0 },
+                Self::LogisticRegression { l2: 0.1 },
+            ]);
+        }
+
+        models
+    }
+
+    /// Fit on the training rows and return the test rows' probabilities, with a column for each
+    /// of `n_classes` classes
+    ///
+    /// The boosting trains to minimize `costs`, if there are any.
+    pub fn fit_predict(
+        &self,
+        x_train: Array2<f64>,
+        y_train: Array1<usize>,
+        x_test: &Array2<f64>,
+        n_classes: usize,
+        costs: Option<&CostMatrix>,
+        seed: Seed,
+    ) -> Result<Array2<f64>> {
+        let positive = match self {
+            Self::Forest(config) => {
+                let model = config.build()?.random_state(seed).fit(x_train, y_train)?;
+                return Ok(for_every_class(
+                    &model.predict_proba(x_test),
+                    model.classes(),
+                    n_classes,
+                ));
+            }
+            Self::GradientBoosting {
+                n_rounds,
+                learning_rate,
+                backend,
+            } => {
+                let mut boosting = GradientBoosting::new()
+                    .n_rounds(*n_rounds)
+                    .learning_rate(*learning_rate)
+                    .backend(*backend)
+                    .seed(seed);
+                if let Some(costs) = costs {
+                    boosting = boosting.costs(costs)?;
+                }
+                boosting.fit(&x_train, &y_train)?.predict_proba(x_test)
+            }
+            Self::LogisticRegression { l2 } => LogisticRegression::new()
+                .l2(*l2)
+                .seed(seed)
+                .fit(&x_train, &y_train)?
+                .predict_proba(x_test),
+        };
+
+        Ok(stack![Axis(1), positive.mapv(|p| 1.0 - p), positive])
+    }
+}
+
+impl fmt::Display for Model {
+    /// e.g. `forest (50 trees, max depth 5, min samples split 10, bootstrap 0.5)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forest(config) => write!(
+                f,
+                "forest ({} trees, max depth {}, min samples split {}, bootstrap {})",
+                config.n_estimators,
+                config.max_depth,
+                config.min_samples_split,
+                config.bootstrap_proportion
+            ),
+            Self::GradientBoosting {
+                n_rounds,
+                learning_rate,
+                backend: Backend::Cpu,
+            } => write!(
+                f,
+                "gradient boosting ({n_rounds} rounds, learning rate {learning_rate})"
+            ),
+            Self::GradientBoosting {
+                n_rounds,
+                learning_rate,
+                backend,
+            } => write!(
+                f,
+                "gradient boosting ({n_rounds} rounds, learning rate {learning_rate}, {backend})"
+            ),
+            Self::LogisticRegression { l2 } => write!(f, "logistic regression (L2 {l2})"),
+        }
+    }
+}
+
+/// A feature set, a way to impute its missing values, and a model to fit on the result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub feature_set: &'static str,
+    pub imputation: Imputation,
+    pub model: Model,
+}
+
+impl fmt::Display for Pipeline {
+    /// e.g. `engineered features, median imputation, logistic regression (L2 0.1)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} features, {} imputation, {}",
+            self.feature_set, self.imputation, self.model
+        )
+    }
+}
+
+/// One pipeline's cross-validation scores
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial {
+    pub pipeline: Pipeline,
+    pub fold_scores: Vec<f64>,
+    /// How long cross-validating it took
+    pub elapsed: Duration,
+}
+
+impl Trial {
+    pub fn mean_score(&self) -> f64 {
+        self.fold_scores.iter().sum::<f64>() / self.fold_scores.len() as f64
+    }
+}
+
+/// The pipelines `auto` got through in its budget, and how many it had to choose from
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoResults {
+    /// What the trials were scored by, e.g. `"accuracy"`
+    pub metric: String,
+    pub higher_is_better: bool,
+    /// In the order they were tried
+    pub trials: Vec<Trial>,
+    pub n_pipelines: usize,
+}
+
+impl AutoResults {
+    /// The trials, best first
+    pub fn ranked(&self) -> Vec<&Trial> {
+        let mut ranked: Vec<&Trial> = self.trials.iter().collect();
+        ranked.sort_by(|a, b| {
+            let order = a.mean_score().total_cmp(&b.mean_score());
+            if self.higher_is_better {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        ranked
+    }
+
+    /// The best pipeline found
+    pub fn best(&self) -> Option<&Trial> {
+        self.ranked().first().copied()
+    }
+}
+
+impl fmt::Display for AutoResults {
+    /// A row per trial, best first, then how much of the space the budget covered
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>4}  {:>17}  {:>7}  pipeline",
+            "rank", self.metric, "seconds"
+        )?;
+        for (rank, trial) in self.ranked().into_iter().enumerate() {
+            let (mean, std) = mean_and_std(trial.fold_scores.iter().copied());
+            writeln!(
+                f,
+                "{:>4}  {:>17}  {:>7.2}  {}",
+                rank + 1,
+                format!("{mean:.4} ± {std:.4}"),
+                trial.elapsed.as_secs_f64(),
+                trial.pipeline
+            )?;
+        }
+        write!(
+            f,
+            "Tried {} of {} pipelines",
+            self.trials.len(),
+            self.n_pipelines
+        )
+    }
+}
+
+/// Cross-validate pipelines over `feature_sets` (which must share their labels, as a
+/// `Registration`'s do) on `splitter`'s folds, scoring by `metric`, until `budget` runs out
+///
+/// Pass the `costs` the metric scores by, if any, for the models that can train on them, and
+/// the `Backend` for the boosting to grow its trees with.
+///
+/// At least one pipeline is always tried, and one started within the budget is finished, so the
+/// search can run a little over.
+pub fn auto(
+    feature_sets: &[(&'static str, Dataset)],
+    splitter: &impl Splitter,
+    metric: &dyn Metric,
+    costs: Option<&CostMatrix>,
+    backend: Backend,
+    budget: Duration,
+    seed: Seed,
+) -> Result<AutoResults> {
+    let n_classes = feature_sets
+        .first()
+        .and_then(|(_, dataset)| dataset.y.iter().max())
+        .map_or(0, |&max| max + 1);
+    let models = Model::candidates(n_classes, backend, seed.derive("forests"));
+    let mut pipelines = Vec::new();
+    for &(feature_set, _) in feature_sets {
+        for imputation in Imputation::ALL {
+            for model in &models {
+                pipelines.push(Pipeline {
+                    feature_set,
+                    imputation,
+                    model: model.clone(),
+                });
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let mut trials = Vec::new();
+    for i in seed.derive("order").permutation(pipelines.len()) {
+        if !trials.is_empty() && start.elapsed() >= budget {
+            break;
+        }
+        let pipeline = &pipelines[i];
+        let (_, dataset) = feature_sets
+            .iter()
+            .find(|(name, _)| *name == pipeline.feature_set)
+            .expect("every pipeline uses one of the feature sets");
+
+        let started = Instant::now();
+        let scores = cross_validate(
+            dataset,
+            splitter,
+            &[metric],
+            seed,
+            |x_train, y_train, x_test, seed| {
+                let fill_values = pipeline.imputation.fill_values(&x_train);
+                pipeline.model.fit_predict(
+                    impute(&x_train, &fill_values),
+                    y_train,
+                    &impute(x_test, &fill_values),
+                    n_classes,
+                    costs,
+                    seed,
+                )
+            },
+        )?;
+
+        trials.push(Trial {
+            pipeline: pipeline.clone(),
+            fold_scores: scores.into_iter().map(|scores| scores[0]).collect(),
+            elapsed: started.elapsed(),
+        });
+    }
+
+    Ok(AutoResults {
+        metric: metric.name().to_string(),
+        higher_is_better: metric.higher_is_better(),
+        trials,
+        n_pipelines: pipelines.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::metric::Accuracy;
+    use ndarray::arr2;
+    use titanic_ml::splitter::KFold;
+
+    #[test]
+    fn test_imputation() {
+        let x = arr2(&[
+            [1.0, f64::NAN],
+            [f64::NAN, f64::NAN],
+            [2.0, f64::NAN],
+            [6.0, f64::NAN],
+        ]);
+
+        assert_eq!(Imputation::Zero.fill_values(&x).to_vec(), [0.0, 0.0]);
+        assert_eq!(Imputation::Mean.fill_values(&x).to_vec(), [3.0, 0.0]);
+        assert_eq!(Imputation::Median.fill_values(&x).to_vec(), [2.0, 0.0]);
+        let filled = impute(&x, &Imputation::Median.fill_values(&x));
+        assert_eq!(filled.column(0).to_vec(), [1.0, 2.0, 2.0, 6.0]);
+        assert_eq!(filled.column(1).to_vec(), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_auto_tries_pipelines_within_the_budget() {
+        // The label is whether the first feature is at least 5, which is missing in every 7th row
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| match (i % 7, j) {
+            (0, 0) => f64::NAN,
+            _ => ((i * (j + 3)) % 10) as f64,
+        });
+        let y = x.column(0).mapv(|v| usize::from(v >= 5.0));
+        let feature_sets = [
+            (
+                "both",
+                Dataset {
+                    x: x.clone(),
+                    y: y.clone(),
+                },
+            ),
+            (
+                "second",
+                Dataset {
+                    x: x.select(Axis(1), &[1]),
+                    y,
+                },
+            ),
+        ];
+        let folds = KFold::new(2).shuffle(1);
+
+        let one = auto(
+            &feature_sets,
+            &folds,
+            &Accuracy,
+            None,
+            Backend::Cpu,
+            Duration::ZERO,
+            Seed::new(1),
+        )
+        .unwrap();
+        assert_eq!(one.trials.len(), 1);
+        assert_eq!(one.n_pipelines, 2 * 3 * (N_FORESTS + 4));
+
+        let all = auto(
+            &feature_sets,
+            &folds,
+            &Accuracy,
+            None,
+            Backend::Cpu,
+            Duration::MAX,
+            Seed::new(1),
+        )
+        .unwrap();
+        assert_eq!(all.trials.len(), all.n_pipelines);
+        // The same seed tries the same pipeline first, and scores it the same
+        assert_eq!(all.trials[0].pipeline, one.trials[0].pipeline);
+        assert_eq!(all.trials[0].fold_scores, one.trials[0].fold_scores);
+        let best = all.best().unwrap();
+        assert_eq!(best.pipeline.feature_set, "both");
+        assert!(best.mean_score() > 0.9, "{all}");
+        for model in ["forest", "gradient boosting", "logistic regression"] {
+            assert!(
+                all.trials
+                    .iter()
+                    .any(|trial| trial.pipeline.model.to_string().starts_with(model))
+            );
+        }
+        assert!(all.to_string().ends_with("Tried 60 of 60 pipelines"));
+    }
+}
@@ -0,0 +1,442 @@
+//! Gradient boosting for 0/1 labels
+//!
+//! Each round fits a shallow regression tree to the gradient of the log loss (the labels minus the
+//! current probabilities) and adds `learning_rate` times its predictions to the log-odds. The
+//! trees are single-tree `RandomForestRegressor`s, each on a bootstrap sample of the rows, which
+//! makes this stochastic gradient boosting: a little randomness per round, as with the forest.
+//!
+//! The log loss can be swapped for any other `Loss`. `CostWeighted` makes training cost-sensitive:
+//! from a `CostMatrix`, it weighs each row's gradient by what misclassifying it costs, so the trees
+//! spend their splits on the rows where mistakes are dear (the missed heart attacks, say). Its
+//! weights shift the log-odds it converges to, which `predict_proba` takes back off, so the
+//! probabilities stay probabilities and `CostMatrix::decide` can still pick the cheapest labels.
+//!
+//! The trees are grown by a `Backend`. The default grows `RandomForestRegressor`s, which try
+//! every split; the others grow `HistogramTree`s, which only try a split between bins of each
+//! feature's values and so scale to far more rows, building the histograms on the CPU or (with
+//! the `gpu` feature) on the GPU.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, bail};
+use metrics::cost::CostMatrix;
+use ndarray::{Array1, Array2};
+use rand::Rng;
+use titanic_ml::{
+    random_forest_regressor::{FittedRandomForestRegressor, RandomForestRegressor},
+    seed::Seed,
+};
+
+use crate::histogram::{Bins, CpuHistograms, HistogramTree, Histograms};
+
+/// How deep each round's tree can grow: deep enough for a few features to interact, shallow
+/// enough that no one round fits the noise
+const MAX_DEPTH: usize = 3;
+
+/// The share of the rows each round's tree is fitted on, drawn with replacement
+const BOOTSTRAP_PROPORTION: f64 = 0.8;
+
+/// What grows each round's tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// A `RandomForestRegressor` of one tree, trying every split
+    #[default]
+    Cpu,
+    /// A `HistogramTree`, its histograms built on the CPU
+    CpuHistogram,
+    /// A `HistogramTree`, its histograms built on the GPU; only in a build with the `gpu`
+    /// feature
+    Gpu,
+}
+
+impl Backend {
+    pub const ALL: [Self; 3] = [Self::Cpu, Self::CpuHistogram, Self::Gpu];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::CpuHistogram => "cpu-histogram",
+            Self::Gpu => "gpu",
+        }
+    }
+
+    /// Something to build histograms over `bins` with, for the histogram backends
+    fn histograms(self, bins: &Bins) -> Result<Box<dyn Histograms + '_>> {
+        match self {
+            Self::Cpu => bail!("The cpu backend doesn't build histograms"),
+            Self::CpuHistogram => Ok(Box::new(CpuHistograms::new(bins))),
+            Self::Gpu => gpu_histograms(bins),
+        }
+    }
+}
+
+#[cfg(feature = "gpu")]
+fn gpu_histograms(bins: &Bins) -> Result<Box<dyn Histograms + '_>> {
+    Ok(Box::new(crate::gpu::GpuHistograms::new(bins)?))
+}
+
+#[cfg(not(feature = "gpu"))]
+fn gpu_histograms(_: &Bins) -> Result<Box<dyn Histograms + '_>> {
+    bail!("This build has no GPU backend; build it with `--features gpu`")
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|backend| backend.name() == s) {
+            Some(backend) => Ok(backend),
+            None => bail!("No boosting backend named {s:?} (try cpu, cpu-histogram or gpu)"),
+        }
+    }
+}
+
+/// What `GradientBoosting` minimizes, given 0/1 labels and predicted probabilities of 1
+pub trait Loss: Send + Sync {
+    /// Minus the loss's derivative with respect to the log-odds, which each round's tree fits
+    fn negative_gradient(&self, label: f64, probability: f64) -> f64;
+
+    /// The constant log-odds that minimizes the loss on `y`, which boosting starts from
+    fn initial_log_odds(&self, y: &Array1<f64>) -> f64 {
+        // Kept finite if the labels are all one class
+        let positive = y.mean().unwrap_or(0.5).clamp(1e-6, 1.0 - 1e-6);
+        (positive / (1.0 - positive)).ln()
+    }
+
+    /// How far minimizing the loss moves the log-odds from the true ones, to be taken back off
+    /// before predicting probabilities
+    fn log_odds_shift(&self) -> f64 {
+        0.0
+    }
+}
+
+/// The log loss, whose negative gradient is the label minus the probability
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logistic;
+
+impl Loss for Logistic {
+    fn negative_gradient(&self, label: f64, probability: f64) -> f64 {
+        label - probability
+    }
+}
+
+/// The log loss of each row, weighted by what misclassifying a row with its label costs
+#[derive(Debug, Clone, Copy)]
+pub struct CostWeighted {
+    /// For labels 0 and 1
+    weights: [f64; 2],
+}
+
+impl CostWeighted {
+    /// Weights from what a false positive and a false negative cost, over a right answer
+    pub fn new(costs: &CostMatrix) -> Result<Self> {
+        if costs.n_classes() != 2 {
+            bail!(
+                "Gradient boosting needs costs for 2 classes, got {}",
+                costs.n_classes()
+            );
+        }
+        let weights = [
+            costs.cost(0, 1) - costs.cost(0, 0),
+            costs.cost(1, 0) - costs.cost(1, 1),
+        ];
+        if !weights.iter().all(|&weight| weight > 0.0) {
+            bail!("Each mistake must cost more than the right answer, got weights {weights:?}");
+        }
+
+        Ok(Self { weights })
+    }
+
+    fn weight(&self, label: f64) -> f64 {
+        self.weights[usize::from(label > 0.5)]
+    }
+}
+
+impl Loss for CostWeighted {
+    fn negative_gradient(&self, label: f64, probability: f64) -> f64 {
+        self.weight(label) * (label - probability)
+    }
+
+    fn initial_log_odds(&self, y: &Array1<f64>) -> f64 {
+        let positive: f64 = y.sum();
+        let weighted_positive = (self.weights[1] * positive).max(1e-6);
+        let weighted_negative = (self.weights[0] * (y.len() as f64 - positive)).max(1e-6);
+        (weighted_positive / weighted_negative).ln()
+    }
+
+    fn log_odds_shift(&self) -> f64 {
+        (self.weights[1] / self.weights[0]).ln()
+    }
+}
+
+/// Settings for fitting a `FittedGradientBoosting`
+pub struct GradientBoosting {
+    n_rounds: usize,
+    learning_rate: f64,
+    loss: Box<dyn Loss>,
+    backend: Backend,
+    seed: Seed,
+}
+
+impl GradientBoosting {
+    pub fn new() -> Self {
+        Self {
+            n_rounds: 100,
+            learning_rate: 0.1,
+            loss: Box::new(Logistic),
+            backend: Backend::Cpu,
+            seed: Seed::new(1),
+        }
+    }
+
+    /// Set the number of trees to add (default: 100)
+    pub fn n_rounds(mut self, n: usize) -> Self {
+        self.n_rounds = n;
+        self
+    }
+
+    /// Set how much of each tree's predictions to add (default: 0.1)
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Set the loss to minimize (default: `Logistic`)
+    pub fn loss(mut self, loss: impl Loss + 'static) -> Self {
+        self.loss = Box::new(loss);
+        self
+    }
+
+    /// Train to minimize `costs` rather than count mistakes, with a `CostWeighted` loss
+    pub fn costs(self, costs: &CostMatrix) -> Result<Self> {
+        Ok(self.loss(CostWeighted::new(costs)?))
+    }
+
+    /// Set what grows the trees (default: `Backend::Cpu`)
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the seed for the trees' bootstrap samples (default: 1)
+    pub fn seed(mut self, seed: impl Into<Seed>) -> Self {
+        self.seed = seed.into();
+        self
+    }
+
+    /// Fit on features `x` and 0/1 labels `y`
+    pub fn fit(&self, x: &Array2<f64>, y: &Array1<usize>) -> Result<FittedGradientBoosting> {
+        if let Some(label) = y.iter().find(|&&label| label > 1) {
+            bail!("Gradient boosting needs 0/1 labels, got {label}");
+        }
+        if self.learning_rate.is_nan() || self.learning_rate <= 0.0 {
+            bail!(
+                "learning_rate must be greater than 0, got {}",
+                self.learning_rate
+            );
+        }
+        let y = y.mapv(|label| label as f64);
+
+        let mut model = FittedGradientBoosting {
+            initial: self.loss.initial_log_odds(&y),
+            shift: self.loss.log_odds_shift(),
+            learning_rate: self.learning_rate,
+            trees: Vec::with_capacity(self.n_rounds),
+        };
+
+        // Binned once, for every round's tree
+        let bins = match self.backend {
+            Backend::Cpu => None,
+            Backend::CpuHistogram | Backend::Gpu => Some(Bins::new(x)?),
+        };
+        let mut histograms = bins
+            .as_ref()
+            .map(|bins| self.backend.histograms(bins))
+            .transpose()?;
+
+        let mut log_odds = Array1::from_elem(x.nrows(), model.initial);
+        for round in 0..self.n_rounds {
+            let mut gradient = log_odds.mapv(sigmoid);
+            gradient.zip_mut_with(&y, |p, &label| {
+                *p = self.loss.negative_gradient(label, *p);
+            });
+            let seed = self.seed.derive(&format!("round {round}"));
+            let tree = match (&bins, &mut histograms) {
+                (Some(bins), Some(histograms)) => Tree::Histogram(HistogramTree::fit(
+                    bins,
+                    histograms.as_mut(),
+                    &gradient,
+                    &bootstrap_counts(x.nrows(), seed),
+                    MAX_DEPTH,
+                )?),
+                _ => Tree::Exact(
+                    RandomForestRegressor::new()
+                        .n_estimators(1)
+                        .max_depth(MAX_DEPTH)
+                        .bootstrap_proportion(BOOTSTRAP_PROPORTION)
+                        .random_state(seed)
+                        .fit(x, &gradient)?,
+                ),
+            };
+
+            log_odds = log_odds + tree.predict(x) * self.learning_rate;
+            model.trees.push(tree);
+        }
+
+        Ok(model)
+    }
+}
+
+impl Default for GradientBoosting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many times each of `n_rows` rows is drawn, with replacement, into a tree's bootstrap
+/// sample
+fn bootstrap_counts(n_rows: usize, seed: Seed) -> Array1<f64> {
+    let mut rng = seed.rng();
+    let mut counts = Array1::zeros(n_rows);
+    for _ in 0..(n_rows as f64 * BOOTSTRAP_PROPORTION).ceil() as usize {
+        counts[rng.gen_range(0..n_rows)] += 1.0;
+    }
+
+    counts
+}
+
+/// One round's tree, as its `Backend` grew it
+enum Tree {
+    Exact(FittedRandomForestRegressor),
+    Histogram(HistogramTree),
+}
+
+impl Tree {
+    fn predict(&self, x: &Array2<f64>) -> Array1<f64> {
+        match self {
+            Self::Exact(tree) => tree.predict(x),
+            Self::Histogram(tree) => tree.predict(x),
+        }
+    }
+}
+
+/// A fitted gradient boosting model
+pub struct FittedGradientBoosting {
+    /// The log-odds every prediction starts from
+    initial: f64,
+    /// Taken off the log-odds before they're turned into probabilities
+    shift: f64,
+    learning_rate: f64,
+    trees: Vec<Tree>,
+}
+
+impl FittedGradientBoosting {
+    /// The probability of class 1 for each sample
+    pub fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        self.trees
+            .iter()
+            .fold(
+                Array1::from_elem(x.nrows(), self.initial),
+                |log_odds, tree| log_odds + tree.predict(x) * self.learning_rate,
+            )
+            .mapv(|log_odds| sigmoid(log_odds - self.shift))
+    }
+}
+
+fn sigmoid(t: f64) -> f64 {
+    1.0 / (1.0 + (-t).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_boosting_learns_a_threshold() {
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| ((i * (j + 7)) % 20) as f64);
+        let y = x.column(0).mapv(|v| usize::from(v >= 8.0));
+
+        let model = GradientBoosting::new().n_rounds(30).fit(&x, &y).unwrap();
+        let predictions = model.predict_proba(&x).mapv(|p| usize::from(p >= 0.5));
+        assert_eq!(predictions, y);
+
+        // More rounds fit the training labels more closely
+        let fewer = GradientBoosting::new().n_rounds(3).fit(&x, &y).unwrap();
+        let confidence = |p: &Array1<f64>| {
+            p.iter()
+                .zip(&y)
+                .map(|(p, &label)| if label == 1 { *p } else { 1.0 - p })
+                .sum::<f64>()
+        };
+        assert!(confidence(&model.predict_proba(&x)) > confidence(&fewer.predict_proba(&x)));
+        assert!(GradientBoosting::new().fit(&x, &(y + 1)).is_err());
+    }
+
+    #[test]
+    fn test_histogram_trees_learn_a_threshold() {
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| ((i * (j + 7)) % 20) as f64);
+        let y = x.column(0).mapv(|v| usize::from(v >= 8.0));
+        let boosting = || {
+            GradientBoosting::new()
+                .n_rounds(30)
+                .backend(Backend::CpuHistogram)
+        };
+
+        let model = boosting().fit(&x, &y).unwrap();
+        assert_eq!(model.predict_proba(&x).mapv(|p| usize::from(p >= 0.5)), y);
+        assert_eq!(
+            boosting().fit(&x, &y).unwrap().predict_proba(&x),
+            model.predict_proba(&x)
+        );
+
+        assert_eq!(
+            "cpu-histogram".parse::<Backend>().unwrap(),
+            Backend::CpuHistogram
+        );
+        assert!("cuda".parse::<Backend>().is_err());
+        if cfg!(not(feature = "gpu")) {
+            let gpu = GradientBoosting::new().backend(Backend::Gpu).fit(&x, &y);
+            assert!(gpu.is_err());
+        }
+    }
+
+    #[test]
+    fn test_costs_lower_the_cost_of_mistakes() {
+        // The label is a coin flip weighted by the first feature, so some mistakes are unavoidable
+        let x = Array2::from_shape_fn((200, 1), |(i, _)| (i % 20) as f64);
+        let mut rng = Seed::new(3).rng();
+        let y = x.column(0).mapv(|v| usize::from(rng.gen_bool(v / 20.0)));
+        let costs = CostMatrix::binary(1.0, 5.0);
+
+        let plain = GradientBoosting::new().n_rounds(30).fit(&x, &y).unwrap();
+        let weighted = GradientBoosting::new()
+            .n_rounds(30)
+            .costs(&costs)
+            .unwrap()
+            .fit(&x, &y)
+            .unwrap();
+        let probabilities = |model: &FittedGradientBoosting| {
+            let p = model.predict_proba(&x);
+            ndarray::stack![ndarray::Axis(1), p.mapv(|p| 1.0 - p), p]
+        };
+        // The weights come back off, so both still predict about the real share of 1s
+        let mean = |model| probabilities(model).column(1).mean().unwrap();
+        assert!((mean(&plain) - mean(&weighted)).abs() < 0.05);
+        // Labelling at 0.5 rather than the cheapest labels costs more
+        let cost = |labels| costs.mean_cost(&labels, &y);
+        let by_half = probabilities(&weighted)
+            .column(1)
+            .mapv(|p| usize::from(p >= 0.5));
+        assert!(cost(costs.decide(&probabilities(&weighted))) < cost(by_half));
+
+        assert!(CostWeighted::new(&CostMatrix::binary(0.0, 5.0)).is_err());
+        assert!(CostWeighted::new(&CostMatrix::new(Array2::ones((3, 3)))).is_err());
+    }
+}
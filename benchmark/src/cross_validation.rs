@@ -0,0 +1,111 @@
+//! Cross-validation
+//!
+//! The folds come from a `Splitter`, and depend only on the labels and the splitter's seed, so
+//! every model benchmarked on a dataset with the same seed is scored on exactly the same splits.
+//! Each fold is scored by whichever `Metric`s the caller asks for.
+
+use anyhow::Result;
+use metrics::metric::Metric;
+use ndarray::{Array1, Array2, Axis};
+use titanic_ml::{seed::Seed, splitter::Splitter};
+
+use crate::datasets::Dataset;
+
+/// Train on the training rows of each fold `splitter` makes with `fit_predict`, and score its
+/// predictions for the test rows by each of `metrics`
+///
+/// `fit_predict` gets the training features and labels, the test features, and a seed of its own
+/// for each fold, and returns the test rows' class probabilities, with a column for every class
+/// in the dataset (see `for_every_class`).
+///
+/// # Returns
+/// Each fold's scores, in the order of `metrics`
+pub fn cross_validate<F>(
+    dataset: &Dataset,
+    splitter: &impl Splitter,
+    metrics: &[&dyn Metric],
+    seed: Seed,
+    fit_predict: F,
+) -> Result<Vec<Vec<f64>>>
+where
+    F: Fn(Array2<f64>, Array1<usize>, &Array2<f64>, Seed) -> Result<Array2<f64>>,
+{
+    splitter
+        .split(&dataset.y)?
+        .enumerate()
+        .map(|(i, fold)| {
+            let x_train = dataset.x.select(Axis(0), &fold.train);
+            let y_train = dataset.y.select(Axis(0), &fold.train);
+            let x_test = dataset.x.select(Axis(0), &fold.test);
+            let y_test = dataset.y.select(Axis(0), &fold.test);
+
+            let probabilities =
+                fit_predict(x_train, y_train, &x_test, seed.derive(&format!("fold {i}")))?;
+
+            Ok(metrics
+                .iter()
+                .map(|metric| metric.compute(&probabilities, &y_test))
+                .collect())
+        })
+        .collect()
+}
+
+/// A model's `probabilities` for the `classes` it was trained on, with a column of zeros added
+/// for each other class below `n_classes`
+///
+/// A fold's training rows can be missing a rare class, leaving the model a column short.
+pub fn for_every_class(
+    probabilities: &Array2<f64>,
+    classes: &[usize],
+    n_classes: usize,
+) -> Array2<f64> {
+    let mut widened = Array2::zeros((probabilities.nrows(), n_classes));
+    for (column, &class) in classes.iter().enumerate() {
+        widened
+            .column_mut(class)
+            .assign(&probabilities.column(column));
+    }
+
+    widened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::metric::{Accuracy, LogLoss, one_hot};
+    use ndarray::arr2;
+    use titanic_ml::splitter::KFold;
+
+    #[test]
+    fn test_cross_validate_scores_each_fold() {
+        let dataset = Dataset {
+            x: Array2::zeros((6, 1)),
+            y: Array1::from(vec![0, 1, 0, 1, 0, 1]),
+        };
+        let folds = KFold::new(2).shuffle(5);
+
+        let metrics: [&dyn Metric; 2] = [&Accuracy, &LogLoss];
+        let scores = cross_validate(
+            &dataset,
+            &folds,
+            &metrics,
+            Seed::new(5),
+            |_, _, x_test, _| Ok(one_hot(&Array1::zeros(x_test.nrows()), 2)),
+        )
+        .unwrap();
+
+        assert_eq!(scores.len(), 2);
+        assert!(scores.iter().all(|score| score.len() == 2));
+        let mean_accuracy = scores.iter().map(|score| score[0]).sum::<f64>() / 2.0;
+        assert!((mean_accuracy - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_probabilities_for_every_class() {
+        let probabilities = arr2(&[[0.25, 0.75]]);
+        assert_eq!(
+            for_every_class(&probabilities, &[0, 2], 3),
+            arr2(&[[0.25, 0.0, 0.75]])
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! The datasets every benchmark runs over
+//!
+//! Each is loaded into the same shape, a feature matrix and `usize` labels, using the same
+//! feature selection as the binary that normally trains on it, so a benchmark score is
+//! comparable with what that binary reports.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use heart_attack_data::{ENGINEERED_FEATURES, HeartAttackData, TARGET};
+use ndarray::{Array1, Array2, Axis};
+use polars::prelude::col;
+use rand::Rng;
+use titanic_ml::{
+    conversions::{dataframe_to_array2, dataframe_to_array2_with_nans, series_to_array1},
+    data::Data,
+    feature_set::Preset,
+    seed::Seed,
+    training_data::TrainingData,
+};
+
+const TITANIC_PATH: &str = "../titanic/data/train.csv";
+const HEART_ATTACK_PATH: &str = "../ml-with-rust-book/data/heart_attack_prediction_dataset.csv";
+
+/// Every dataset a benchmark can run over, in the order they're reported
+pub const REGISTERED: [Registration; 3] = [
+    Registration {
+        name: "titanic",
+        load: titanic,
+        feature_sets: titanic_feature_sets,
+    },
+    Registration {
+        name: "heart-attack",
+        load: heart_attack,
+        feature_sets: |seed| Ok(vec![("engineered", heart_attack(seed)?)]),
+    },
+    Registration {
+        name: "synthetic",
+        load: synthetic,
+        feature_sets: |seed| Ok(vec![("all", synthetic(seed)?)]),
+    },
+];
+
+/// A dataset's name and how to load it
+pub struct Registration {
+    pub name: &'static str,
+    pub load: fn(Seed) -> Result<Dataset>,
+    /// Each set of features worth trying on the dataset, by name, with missing values left as NaN
+    /// for the caller to impute
+    pub feature_sets: fn(Seed) -> Result<FeatureSets>,
+}
+
+/// Several sets of features for the same labels, each by name
+pub type FeatureSets = Vec<(&'static str, Dataset)>;
+
+/// Features and labels, ready for a model
+pub struct Dataset {
+    pub x: Array2<f64>,
+    pub y: Array1<usize>,
+}
+
+/// Kaggle's Titanic `train.csv`, with the features the `titanic-ml` binary uses
+fn titanic(_seed: Seed) -> Result<Dataset> {
+    let data = TrainingData::try_from_path(TITANIC_PATH)?.materialize()?;
+    let x_df = data.get_feature_matrix(Preset::Baseline.features())?;
+
+    Ok(Dataset {
+        x: dataframe_to_array2(&x_df)?,
+        y: series_to_array1(&data.get_col_as_series("Survived")?)?,
+    })
+}
+
+/// The Titanic data with each of the `titanic-ml` binary's feature presets, keeping the missing
+/// ages as NaN rather than 0
+fn titanic_feature_sets(_seed: Seed) -> Result<FeatureSets> {
+    let data = TrainingData::try_from_path(TITANIC_PATH)?.materialize()?;
+    let y = series_to_array1(&data.get_col_as_series("Survived")?)?;
+
+    Preset::ALL
+        .into_iter()
+        .map(|preset| {
+            let x_df = data.get_feature_matrix(preset.features())?;
+            let x = dataframe_to_array2_with_nans(&x_df)?;
+            Ok((preset.name(), Dataset { x, y: y.clone() }))
+        })
+        .collect()
+}
+
+/// The heart-attack dataset, with the engineered features ch2's `predict` uses
+fn heart_attack(_seed: Seed) -> Result<Dataset> {
+    let data = HeartAttackData::try_from_path(HEART_ATTACK_PATH)?;
+    let x_df = data.get_feature_matrix(ENGINEERED_FEATURES.map(col))?;
+
+    Ok(Dataset {
+        x: dataframe_to_array2(&x_df)?,
+        y: series_to_array1(&data.get_col_as_series(TARGET)?)?,
+    })
+}
+
+const SYNTHETIC_SAMPLES: usize = 1000;
+const SYNTHETIC_FEATURES: usize = 8;
+
+/// A generated problem with a known answer
+///
+/// Features are uniform on [-1, 1]. The label is 1 when `x0 + x1 - x2` plus some noise is
+/// positive, so three features matter, the rest don't, and no model can be perfect.
+fn synthetic(seed: Seed) -> Result<Dataset> {
+    let mut rng = seed.rng();
+    let x = Array2::from_shape_simple_fn((SYNTHETIC_SAMPLES, SYNTHETIC_FEATURES), || {
+        rng.gen_range(-1.0..=1.0)
+    });
+    let y = x
+        .rows()
+        .into_iter()
+        .map(|row| {
+            let noise = rng.gen_range(-0.5..=0.5);
+            usize::from(row[0] + row[1] - row[2] + noise > 0.0)
+        })
+        .collect();
+
+    Ok(Dataset { x, y })
+}
+
+/// A random `fraction` of the rows of `x` and `y`, for quicker runs
+///
+/// With `stratified`, each label keeps its share of the rows (rounded per label, but never less
+/// than one row), so a rare class doesn't disappear from a small subsample. Otherwise rows are
+/// drawn regardless of label. Either way the rows keep their original order.
+pub fn subsample(
+    x: &Array2<f64>,
+    y: &Array1<usize>,
+    fraction: f64,
+    stratified: bool,
+    seed: Seed,
+) -> Result<Dataset> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        bail!("The subsample fraction must be above 0 and at most 1, got {fraction}");
+    }
+    if x.nrows() != y.len() {
+        bail!("Got {} samples but {} labels", x.nrows(), y.len());
+    }
+
+    let keep = |n: usize| ((n as f64 * fraction).round() as usize).max(1);
+    let order = seed.permutation(y.len());
+    let mut rows: Vec<usize> = if stratified {
+        let mut by_label = BTreeMap::<usize, Vec<usize>>::new();
+        for &row in &order {
+            by_label.entry(y[row]).or_default().push(row);
+        }
+
+        by_label
+            .into_values()
+            .flat_map(|rows| {
+                let n = keep(rows.len());
+                rows.into_iter().take(n)
+            })
+            .collect()
+    } else {
+        order.into_iter().take(keep(y.len())).collect()
+    };
+    rows.sort_unstable();
+
+    Ok(Dataset {
+        x: x.select(Axis(0), &rows),
+        y: y.select(Axis(0), &rows),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_is_reproducible_and_balanced() {
+        let a = synthetic(Seed::new(3)).unwrap();
+        let b = synthetic(Seed::new(3)).unwrap();
+
+        assert_eq!(a.x, b.x);
+        assert_eq!(a.y, b.y);
+        assert_eq!(a.x.dim(), (SYNTHETIC_SAMPLES, SYNTHETIC_FEATURES));
+
+        // The decision boundary goes through the origin, so neither class should dominate
+        let positives = a.y.sum() as f64 / SYNTHETIC_SAMPLES as f64;
+        assert!((0.4..=0.6).contains(&positives), "{positives}");
+    }
+
+    #[test]
+    fn test_subsample() {
+        // 90 of class 0 and 10 of class 1; the feature is the row number
+        let x = Array2::from_shape_fn((100, 1), |(i, _)| i as f64);
+        let y = Array1::from_shape_fn(100, |i| usize::from(i >= 90));
+
+        let stratified = subsample(&x, &y, 0.2, true, Seed::new(8)).unwrap();
+        assert_eq!(stratified.y.len(), 20);
+        assert_eq!(stratified.y.sum(), 2);
+        assert!(
+            stratified
+                .x
+                .column(0)
+                .windows(2)
+                .into_iter()
+                .all(|w| w[0] < w[1])
+        );
+        assert_eq!(
+            stratified.x,
+            subsample(&x, &y, 0.2, true, Seed::new(8)).unwrap().x
+        );
+
+        // Even a tiny fraction keeps one row of every label
+        assert_eq!(
+            subsample(&x, &y, 0.01, true, Seed::new(8)).unwrap().y.sum(),
+            1
+        );
+        assert_eq!(
+            subsample(&x, &y, 0.3, false, Seed::new(8)).unwrap().y.len(),
+            30
+        );
+
+        assert!(subsample(&x, &y, 0.0, true, Seed::new(8)).is_err());
+        assert!(subsample(&x, &y, 1.5, false, Seed::new(8)).is_err());
+    }
+}
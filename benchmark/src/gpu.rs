@@ -0,0 +1,404 @@
+//! Building `HistogramTree`s' histograms on the GPU, with wgpu
+//!
+//! The bins are uploaded once per fit, and each level of each tree uploads which node every row
+//! is in and the rows' gradients, runs `histogram.wgsl` and reads back only the histograms, which
+//! are a few hundred bins per feature however many rows there are. Finding the splits from them
+//! stays on the CPU.
+//!
+//! The GPU sums in f32, so its histograms can differ from `CpuHistograms`' in the last digits
+//! (and a near tie between two splits can go the other way), but it always sums in the same
+//! order: a seeded fit on the same GPU gives the same trees every time.
+
+use std::sync::{OnceLock, mpsc};
+
+use anyhow::{Context, Result, anyhow, bail};
+use ndarray::Array3;
+use wgpu::util::DeviceExt;
+
+use crate::histogram::{Bins, Histogram, Histograms};
+
+/// Rows each `accumulate` thread sums, at the least
+const MIN_BLOCK_ROWS: usize = 1024;
+
+/// Blocks of rows each feature's histograms are split into, at the most
+const MAX_BLOCKS: usize = 64;
+
+/// Threads in a workgroup, as in `histogram.wgsl`
+const WORKGROUP_SIZE: usize = 64;
+
+/// A device and the shader's pipelines, set up once and shared by every fit
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    accumulate: wgpu::ComputePipeline,
+    reduce: wgpu::ComputePipeline,
+}
+
+impl Gpu {
+    /// The GPU, set up on first use
+    fn get() -> Result<&'static Self> {
+        static GPU: OnceLock<Result<Gpu, String>> = OnceLock::new();
+        GPU.get_or_init(|| pollster::block_on(Self::new()).map_err(|e| format!("{e:#}")))
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    async fn new() -> Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .context("No GPU adapter found")?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("histograms"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await
+            .context("Couldn't open the GPU")?;
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("histograms"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, false),
+                storage(5, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("histograms"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("histogram.wgsl"));
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let (accumulate, reduce) = (pipeline("accumulate"), pipeline("reduce"));
+
+        Ok(Self {
+            device,
+            queue,
+            layout,
+            accumulate,
+            reduce,
+        })
+    }
+
+    fn buffer(&self, label: &str, size: usize, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as u64,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+}
+
+/// The buffers for histograms of up to `n_nodes` nodes at a time
+struct LevelBuffers {
+    n_nodes: usize,
+    n_blocks: usize,
+    partials: wgpu::Buffer,
+    histogram: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Builds histograms on the GPU, for one fit's `Bins`
+pub struct GpuHistograms {
+    gpu: &'static Gpu,
+    n_rows: usize,
+    n_features: usize,
+    n_bins: usize,
+    params: wgpu::Buffer,
+    bins: wgpu::Buffer,
+    node_of_row: wgpu::Buffer,
+    targets: wgpu::Buffer,
+    /// Made for the first level, and again for any level with more nodes
+    level: Option<LevelBuffers>,
+}
+
+impl GpuHistograms {
+    /// Upload `bins`, setting the GPU up if this is the first fit to use it
+    pub fn new(bins: &Bins) -> Result<Self> {
+        let gpu = Gpu::get()?;
+        let limit = gpu.device.limits().max_storage_buffer_binding_size as usize;
+        let (n_rows, n_features) = (bins.n_rows(), bins.n_features());
+        if n_rows == 0 {
+            bail!("There are no rows to build histograms of");
+        }
+        let indices = bins.indices().as_standard_layout();
+        let packed: Vec<u32> = indices
+            .as_slice()
+            .expect("a standard layout array is contiguous")
+            .chunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |word, (i, &bin)| word | u32::from(bin) << (8 * i))
+            })
+            .collect();
+        if packed.len() * 4 > limit {
+            bail!(
+                "{n_rows} rows of {n_features} features are too many for the GPU to hold at once"
+            );
+        }
+
+        let storage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        Ok(Self {
+            gpu,
+            n_rows,
+            n_features,
+            n_bins: bins.n_bins(),
+            params: gpu.buffer(
+                "params",
+                32,
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            ),
+            bins: gpu
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("bins"),
+                    contents: bytemuck::cast_slice(&packed),
+                    usage: wgpu::BufferUsages::STORAGE,
+                }),
+            node_of_row: gpu.buffer("node_of_row", n_rows * 4, storage),
+            targets: gpu.buffer("targets", n_rows * 8, storage),
+            level: None,
+        })
+    }
+
+    /// Make sure `level` has buffers big enough for `n_nodes` nodes' histograms
+    fn reserve(&mut self, n_nodes: usize) -> Result<()> {
+        if self
+            .level
+            .as_ref()
+            .is_none_or(|level| level.n_nodes < n_nodes)
+        {
+            let gpu = self.gpu;
+            let limits = gpu.device.limits();
+            let histogram_size = n_nodes * self.n_features * self.n_bins * 8;
+            let max_threads = limits.max_compute_workgroups_per_dimension as usize * WORKGROUP_SIZE;
+            // As many blocks as there are rows for, up to what the GPU can hold or dispatch
+            let n_blocks = self
+                .n_rows
+                .div_ceil(MIN_BLOCK_ROWS)
+                .min(MAX_BLOCKS)
+                .min(limits.max_storage_buffer_binding_size as usize / histogram_size)
+                .min(max_threads / self.n_features);
+            if n_blocks == 0 || histogram_size / 8 > max_threads {
+                bail!(
+                    "Histograms of {n_nodes} nodes of {} features are too big for the GPU",
+                    self.n_features
+                );
+            }
+
+            let partials = gpu.buffer(
+                "partials",
+                n_blocks * histogram_size,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            );
+            let histogram = gpu.buffer(
+                "histogram",
+                histogram_size,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            );
+            let staging = gpu.buffer(
+                "staging",
+                histogram_size,
+                wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            );
+            let buffers = [
+                &self.params,
+                &self.bins,
+                &self.node_of_row,
+                &self.targets,
+                &partials,
+                &histogram,
+            ];
+            let entries: Vec<_> = buffers
+                .iter()
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("histograms"),
+                layout: &gpu.layout,
+                entries: &entries,
+            });
+
+            self.level = Some(LevelBuffers {
+                n_nodes,
+                n_blocks,
+                partials,
+                histogram,
+                staging,
+                bind_group,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Histograms for GpuHistograms {
+    fn build(
+        &mut self,
+        node_of_row: &[u32],
+        n_nodes: usize,
+        gradients: &[f32],
+        weights: &[f32],
+    ) -> Result<Histogram> {
+        let (n_rows, n_features, n_bins) = (self.n_rows, self.n_features, self.n_bins);
+        self.reserve(n_nodes)?;
+        let level = self.level.as_ref().expect("reserved");
+        let gpu = self.gpu;
+        let size = n_nodes * n_features * n_bins;
+        let block_rows = n_rows.div_ceil(level.n_blocks);
+        let params = [
+            n_rows,
+            n_features,
+            n_bins,
+            n_nodes,
+            level.n_blocks,
+            block_rows,
+            0,
+            0,
+        ]
+        .map(|value| value as u32);
+        let targets: Vec<[f32; 2]> = gradients
+            .iter()
+            .zip(weights)
+            .map(|(&gradient, &weight)| [gradient, weight])
+            .collect();
+        gpu.queue
+            .write_buffer(&self.params, 0, bytemuck::cast_slice(&params));
+        gpu.queue
+            .write_buffer(&self.node_of_row, 0, bytemuck::cast_slice(node_of_row));
+        gpu.queue
+            .write_buffer(&self.targets, 0, bytemuck::cast_slice(&targets));
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.clear_buffer(&level.partials, 0, Some((level.n_blocks * size * 8) as u64));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_bind_group(0, &level.bind_group, &[]);
+            pass.set_pipeline(&gpu.accumulate);
+            let threads = level.n_blocks * n_features;
+            pass.dispatch_workgroups(threads.div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+            pass.set_pipeline(&gpu.reduce);
+            pass.dispatch_workgroups(size.div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+        }
+        let bytes = (size * 8) as u64;
+        encoder.copy_buffer_to_buffer(&level.histogram, 0, &level.staging, 0, bytes);
+        gpu.queue.submit([encoder.finish()]);
+
+        let slice = level.staging.slice(..bytes);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            // The receiver only goes away once it's given up waiting
+            let _ = sender.send(result);
+        });
+        gpu.device.poll(wgpu::PollType::Wait)?;
+        receiver.recv()??;
+        let sums: Vec<[f32; 2]> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        level.staging.unmap();
+
+        let shape = (n_nodes, n_features, n_bins);
+        Ok(Histogram {
+            gradients: Array3::from_shape_fn(shape, |(node, feature, bin)| {
+                f64::from(sums[(node * n_features + feature) * n_bins + bin][0])
+            }),
+            weights: Array3::from_shape_fn(shape, |(node, feature, bin)| {
+                f64::from(sums[(node * n_features + feature) * n_bins + bin][1])
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::histogram::{CpuHistograms, NO_NODE};
+    use ndarray::Array2;
+
+    #[test]
+    fn test_gpu_histograms_match_the_cpu_ones() {
+        let x = Array2::from_shape_fn((5000, 3), |(i, j)| ((i * (j + 3)) % 700) as f64);
+        let bins = Bins::new(&x).unwrap();
+        let mut gpu = match GpuHistograms::new(&bins) {
+            Ok(gpu) => gpu,
+            Err(e) => {
+                // Nothing to compare without a GPU (or a software one, such as llvmpipe)
+                eprintln!("Skipping: {e:#}");
+                return;
+            }
+        };
+
+        let node_of_row: Vec<u32> = (0..5000)
+            .map(|i| if i % 7 == 0 { NO_NODE } else { i % 3 })
+            .collect();
+        let gradients: Vec<f32> = (0..5000).map(|i| (i % 11) as f32 / 10.0 - 0.5).collect();
+        let weights: Vec<f32> = (0..5000).map(|i| (i % 3) as f32).collect();
+        let on_gpu = gpu.build(&node_of_row, 3, &gradients, &weights).unwrap();
+        let on_cpu = CpuHistograms::new(&bins)
+            .build(&node_of_row, 3, &gradients, &weights)
+            .unwrap();
+
+        for (gpu_sums, cpu_sums) in [
+            (&on_gpu.gradients, &on_cpu.gradients),
+            (&on_gpu.weights, &on_cpu.weights),
+        ] {
+            assert_eq!(gpu_sums.dim(), cpu_sums.dim());
+            for (gpu_sum, cpu_sum) in gpu_sums.iter().zip(cpu_sums) {
+                assert!((gpu_sum - cpu_sum).abs() < 1e-3, "{gpu_sum} vs {cpu_sum}");
+            }
+        }
+        // Fewer nodes reuse the buffers, and the same inputs give the same sums
+        let root = vec![0; 5000];
+        let once = gpu.build(&root, 1, &gradients, &weights).unwrap();
+        let again = gpu.build(&root, 1, &gradients, &weights).unwrap();
+        assert_eq!(once.gradients, again.gradients);
+    }
+}
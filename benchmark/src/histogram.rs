@@ -0,0 +1,398 @@
+//! Histogram trees, for gradient boosting on large datasets
+//!
+//! `RandomForestRegressor`'s trees try a split between every two neighbouring values of every
+//! feature. A histogram tree puts each feature's values into at most `MAX_BINS` bins by quantile
+//! instead, once per fit, and finds a node's best split from its histogram: the gradients and
+//! the weight of its rows summed in each bin of each feature. Building the histograms, a pass
+//! over every row and feature for each level of the tree, is where the time goes on a big
+//! dataset, so it's what a `Histograms` backend does: `CpuHistograms` here, or `GpuHistograms`
+//! with the `gpu` feature.
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, Array3, ArrayView1, s};
+
+/// The most bins a feature's values are put in, so a bin index fits in a byte
+pub const MAX_BINS: usize = 256;
+
+/// `node_of_row` for a row that's in none of the nodes being split
+pub const NO_NODE: u32 = u32::MAX;
+
+/// Every feature's values as bin indices, and the thresholds between the bins
+pub struct Bins {
+    /// n_rows × n_features, each below `n_bins`
+    indices: Array2<u8>,
+    /// For each feature, the values that start each bin after the first, ascending
+    thresholds: Vec<Vec<f64>>,
+}
+
+impl Bins {
+    /// Bin the values of `x`, which must have a feature and be finite
+    pub fn new(x: &Array2<f64>) -> Result<Self> {
+        if x.ncols() == 0 {
+            bail!("Histogram trees need at least one feature");
+        }
+        if let Some(value) = x.iter().find(|value| !value.is_finite()) {
+            bail!("Histogram trees need finite features, got {value}");
+        }
+
+        let thresholds: Vec<Vec<f64>> = x.columns().into_iter().map(thresholds).collect();
+        let indices = Array2::from_shape_fn(x.dim(), |(row, feature)| {
+            bin(&thresholds[feature], x[[row, feature]])
+        });
+
+        Ok(Self {
+            indices,
+            thresholds,
+        })
+    }
+
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub fn n_rows(&self) -> usize {
+        self.indices.nrows()
+    }
+
+    pub fn n_features(&self) -> usize {
+        self.indices.ncols()
+    }
+
+    /// How many bins the feature with the most has
+    pub fn n_bins(&self) -> usize {
+        self.thresholds
+            .iter()
+            .map(|t| t.len() + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Which bin each row's value of each feature is in
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub fn indices(&self) -> &Array2<u8> {
+        &self.indices
+    }
+}
+
+/// The values to split `column` at: between each two distinct values if there are few enough,
+/// and otherwise at its quantiles
+fn thresholds(column: ArrayView1<f64>) -> Vec<f64> {
+    let mut values = column.to_vec();
+    values.sort_by(f64::total_cmp);
+    let mut distinct = values.clone();
+    distinct.dedup();
+
+    if distinct.len() <= MAX_BINS {
+        return distinct
+            .windows(2)
+            .map(|pair| (pair[0] + pair[1]) / 2.0)
+            .collect();
+    }
+    let mut quantiles: Vec<f64> = (1..MAX_BINS)
+        .map(|bin| values[bin * values.len() / MAX_BINS])
+        .collect();
+    quantiles.dedup();
+    // A value below the first quantile still has the lowest bin to go in
+    quantiles.retain(|&quantile| quantile > values[0]);
+    quantiles
+}
+
+/// The bin `value` is in, given the values that start each bin after the first
+fn bin(thresholds: &[f64], value: f64) -> u8 {
+    thresholds.partition_point(|&threshold| threshold <= value) as u8
+}
+
+/// What the rows of each node being split add up to in each bin of each feature
+pub struct Histogram {
+    /// n_nodes × n_features × n_bins sums of each row's gradient times its weight
+    pub gradients: Array3<f64>,
+    /// Likewise, of each row's weight
+    pub weights: Array3<f64>,
+}
+
+/// Builds `Histogram`s over a fit's `Bins`, a level of a tree at a time
+pub trait Histograms {
+    /// The histograms of the `n_nodes` nodes of a level, where `node_of_row` is each row's node
+    /// (or `NO_NODE`), and `gradients` and `weights` are each row's
+    fn build(
+        &mut self,
+        node_of_row: &[u32],
+        n_nodes: usize,
+        gradients: &[f32],
+        weights: &[f32],
+    ) -> Result<Histogram>;
+}
+
+/// Builds histograms on the CPU, one row after another
+pub struct CpuHistograms<'a> {
+    bins: &'a Bins,
+}
+
+impl<'a> CpuHistograms<'a> {
+    pub fn new(bins: &'a Bins) -> Self {
+        Self { bins }
+    }
+}
+
+impl Histograms for CpuHistograms<'_> {
+    fn build(
+        &mut self,
+        node_of_row: &[u32],
+        n_nodes: usize,
+        gradients: &[f32],
+        weights: &[f32],
+    ) -> Result<Histogram> {
+        let shape = (n_nodes, self.bins.n_features(), self.bins.n_bins());
+        let mut histogram = Histogram {
+            gradients: Array3::zeros(shape),
+            weights: Array3::zeros(shape),
+        };
+        for (row, bins) in self.bins.indices.rows().into_iter().enumerate() {
+            let node = node_of_row[row];
+            if node == NO_NODE {
+                continue;
+            }
+            let (gradient, weight) = (f64::from(gradients[row]), f64::from(weights[row]));
+            for (feature, &bin) in bins.iter().enumerate() {
+                let i = (node as usize, feature, usize::from(bin));
+                histogram.gradients[i] += gradient * weight;
+                histogram.weights[i] += weight;
+            }
+        }
+
+        Ok(histogram)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Leaf(f64),
+    Split {
+        feature: usize,
+        /// Values below this go left
+        threshold: f64,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// A regression tree fitted from histograms
+#[derive(Debug, Clone)]
+pub struct HistogramTree {
+    /// The root first
+    nodes: Vec<Node>,
+}
+
+impl HistogramTree {
+    /// Fit to `gradients`, with each row counting `weights` times, to at most `max_depth`
+    /// (at least 1) levels of splits
+    ///
+    /// Like `RandomForestRegressor`'s trees, each split is the one that most lowers the squared
+    /// error, a node needs at least two rows' weight to split, and a leaf predicts the weighted
+    /// mean gradient of its rows. Ties go to the lowest feature and then the lowest threshold.
+    pub fn fit(
+        bins: &Bins,
+        histograms: &mut dyn Histograms,
+        gradients: &Array1<f64>,
+        weights: &Array1<f64>,
+        max_depth: usize,
+    ) -> Result<Self> {
+        if max_depth == 0 {
+            bail!("max_depth must be at least 1");
+        }
+        let gradients: Vec<f32> = gradients.iter().map(|&g| g as f32).collect();
+        let weights: Vec<f32> = weights.iter().map(|&w| w as f32).collect();
+        let mut node_of_row: Vec<u32> = weights
+            .iter()
+            .map(|&weight| if weight > 0.0 { 0 } else { NO_NODE })
+            .collect();
+
+        let mut nodes = vec![Node::Leaf(0.0)];
+        // The tree's nodes on the level being split, in the order of their histograms
+        let mut level = vec![0];
+        for _ in 0..max_depth {
+            let histogram = histograms.build(&node_of_row, level.len(), &gradients, &weights)?;
+            let mut next_level = Vec::new();
+            // For each node of this level, its split's feature and last bin on the left, and
+            // where its left child is in the next level
+            let mut splits = Vec::with_capacity(level.len());
+            for (k, &node) in level.iter().enumerate() {
+                // Every row is in one bin of the first feature
+                let total = Sums {
+                    gradient: histogram.gradients.slice(s![k, 0, ..]).sum(),
+                    weight: histogram.weights.slice(s![k, 0, ..]).sum(),
+                };
+                nodes[node] = Node::Leaf(total.mean());
+
+                let split = (total.weight >= 2.0)
+                    .then(|| best_split(&histogram, k, bins))
+                    .flatten();
+                splits.push(split.map(|(feature, bin, left_sums)| {
+                    let (left, right) = (nodes.len(), nodes.len() + 1);
+                    nodes[node] = Node::Split {
+                        feature,
+                        threshold: bins.thresholds[feature][bin],
+                        left,
+                        right,
+                    };
+                    let right_sums = Sums {
+                        gradient: total.gradient - left_sums.gradient,
+                        weight: total.weight - left_sums.weight,
+                    };
+                    nodes.extend([Node::Leaf(left_sums.mean()), Node::Leaf(right_sums.mean())]);
+                    next_level.extend([left, right]);
+                    (feature, bin, next_level.len() as u32 - 2)
+                }));
+            }
+            if next_level.is_empty() {
+                break;
+            }
+
+            for (row, node) in node_of_row.iter_mut().enumerate() {
+                if *node == NO_NODE {
+                    continue;
+                }
+                *node = match splits[*node as usize] {
+                    Some((feature, bin, left)) => {
+                        left + u32::from(usize::from(bins.indices[[row, feature]]) > bin)
+                    }
+                    None => NO_NODE,
+                };
+            }
+            level = next_level;
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// The prediction for each row of `x`
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<f64> {
+        x.rows()
+            .into_iter()
+            .map(|row| {
+                let mut node = 0;
+                loop {
+                    match self.nodes[node] {
+                        Node::Leaf(value) => return value,
+                        Node::Split {
+                            feature,
+                            threshold,
+                            left,
+                            right,
+                        } => {
+                            node = if row[feature] < threshold {
+                                left
+                            } else {
+                                right
+                            }
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// The gradients and weight of some rows, summed
+#[derive(Debug, Clone, Copy)]
+struct Sums {
+    gradient: f64,
+    weight: f64,
+}
+
+impl Sums {
+    /// The weighted mean gradient, what a leaf of these rows predicts
+    fn mean(self) -> f64 {
+        if self.weight > 0.0 {
+            self.gradient / self.weight
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The feature and the last bin on the left of the split of histogram `k` that most lowers the
+/// squared error, with the sums on its left, if any split lowers it
+fn best_split(histogram: &Histogram, k: usize, bins: &Bins) -> Option<(usize, usize, Sums)> {
+    let mut best = None;
+    let mut best_gain = 1e-12;
+    for (feature, thresholds) in bins.thresholds.iter().enumerate() {
+        let gradients = histogram.gradients.slice(s![k, feature, ..]);
+        let weights = histogram.weights.slice(s![k, feature, ..]);
+        let (total_gradient, total_weight) = (gradients.sum(), weights.sum());
+
+        let mut left = Sums {
+            gradient: 0.0,
+            weight: 0.0,
+        };
+        for bin in 0..thresholds.len() {
+            left.gradient += gradients[bin];
+            left.weight += weights[bin];
+            let (right_gradient, right_weight) =
+                (total_gradient - left.gradient, total_weight - left.weight);
+            if left.weight < 1.0 || right_weight < 1.0 {
+                continue;
+            }
+
+            let gain = left.gradient * left.gradient / left.weight
+                + right_gradient * right_gradient / right_weight
+                - total_gradient * total_gradient / total_weight;
+            if gain > best_gain {
+                best = Some((feature, bin, left));
+                best_gain = gain;
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    #[test]
+    fn test_tree_fits_a_step() {
+        let x = arr2(&[[0.0, 5.0], [1.0, 4.0], [2.0, 3.0], [3.0, 2.0], [4.0, 1.0]]);
+        let gradients = Array1::from(vec![-1.0, -1.0, -1.0, 2.0, 2.0]);
+        let bins = Bins::new(&x).unwrap();
+        assert_eq!(bins.n_bins(), 5);
+        assert_eq!(bins.indices().column(1).to_vec(), [4, 3, 2, 1, 0]);
+
+        let tree = HistogramTree::fit(
+            &bins,
+            &mut CpuHistograms::new(&bins),
+            &gradients,
+            &Array1::ones(5),
+            3,
+        )
+        .unwrap();
+        // Both features split the step equally well, so the first does
+        assert!(matches!(
+            tree.nodes[0],
+            Node::Split { feature: 0, threshold, .. } if threshold == 2.5
+        ));
+        assert_eq!(tree.predict(&x), gradients);
+        assert_eq!(
+            tree.predict(&arr2(&[[2.4, 0.0], [2.6, 0.0]])).to_vec(),
+            [-1.0, 2.0]
+        );
+
+        // A row with no weight is left out, so the rest are one leaf
+        let weights = Array1::from(vec![1.0, 1.0, 1.0, 0.0, 0.0]);
+        let tree = HistogramTree::fit(
+            &bins,
+            &mut CpuHistograms::new(&bins),
+            &gradients,
+            &weights,
+            3,
+        )
+        .unwrap();
+        assert_eq!(tree.predict(&x).to_vec(), [-1.0; 5]);
+
+        // Many distinct values are binned by quantile
+        let many = Array2::from_shape_fn((1000, 1), |(i, _)| i as f64);
+        let bins = Bins::new(&many).unwrap();
+        assert_eq!(bins.n_bins(), MAX_BINS);
+        assert_eq!(bins.indices()[[999, 0]] as usize, MAX_BINS - 1);
+        assert!(Bins::new(&arr2(&[[f64::NAN]])).is_err());
+    }
+}
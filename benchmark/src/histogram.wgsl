@@ -0,0 +1,65 @@
+// Gradient histograms for `HistogramTree`, in two passes so that the sums don't depend on how the
+// GPU schedules its threads: `accumulate` sums each block of rows for each feature in row order,
+// into a histogram of the block's own, and `reduce` adds the blocks' histograms up in block order.
+
+struct Params {
+    n_rows: u32,
+    n_features: u32,
+    n_bins: u32,
+    n_nodes: u32,
+    n_blocks: u32,
+    block_rows: u32,
+    _padding: vec2<u32>,
+}
+
+const NO_NODE: u32 = 0xffffffffu;
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Each row's bin of each feature, row by row, four to a word
+@group(0) @binding(1) var<storage, read> bins: array<u32>;
+@group(0) @binding(2) var<storage, read> node_of_row: array<u32>;
+// Each row's gradient and weight
+@group(0) @binding(3) var<storage, read> targets: array<vec2<f32>>;
+// n_blocks histograms of n_nodes × n_features × n_bins (gradient times weight, weight) sums
+@group(0) @binding(4) var<storage, read_write> partials: array<vec2<f32>>;
+@group(0) @binding(5) var<storage, read_write> histogram: array<vec2<f32>>;
+
+// One thread per block of rows and feature
+@compute @workgroup_size(64)
+fn accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let feature = id.x % params.n_features;
+    let block = id.x / params.n_features;
+    if block >= params.n_blocks {
+        return;
+    }
+
+    let size = params.n_nodes * params.n_features * params.n_bins;
+    let start = block * params.block_rows;
+    let end = min(start + params.block_rows, params.n_rows);
+    for (var row = start; row < end; row++) {
+        let node = node_of_row[row];
+        if node == NO_NODE {
+            continue;
+        }
+        let cell = row * params.n_features + feature;
+        let bin = (bins[cell / 4u] >> ((cell % 4u) * 8u)) & 0xffu;
+        let row_target = targets[row];
+        let i = block * size + (node * params.n_features + feature) * params.n_bins + bin;
+        partials[i] += vec2<f32>(row_target.x * row_target.y, row_target.y);
+    }
+}
+
+// One thread per bin of each feature of each node
+@compute @workgroup_size(64)
+fn reduce(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = params.n_nodes * params.n_features * params.n_bins;
+    if id.x >= size {
+        return;
+    }
+
+    var sum = vec2<f32>(0.0, 0.0);
+    for (var block = 0u; block < params.n_blocks; block++) {
+        sum += partials[block * size + id.x];
+    }
+    histogram[id.x] = sum;
+}
@@ -0,0 +1,413 @@
+//! Cross-validate the shared Random Forest on every registered dataset
+//!
+//! A change to the model that helps on Titanic may hurt elsewhere, so this scores it on each
+//! dataset in `datasets::REGISTERED` with the same k-fold splits, next to a majority-class
+//! baseline, and prints one comparison table. Run it from this crate's directory so the data
+//! paths resolve.
+//!
+//! With `--search`, it instead cross-validates a grid (or a random sample) of the forest's
+//! settings on each dataset, and prints the trials with how much each setting mattered. With
+//! `--auto`, it searches whole pipelines (feature set, imputation and model) for each dataset
+//! within a time budget, and prints the best it found.
+//!
+//! With `--false-negative-cost`, two-class datasets are also scored by the mean cost of their
+//! mistakes, labelling each row by its cheapest label, and that's what `--search` and `--auto`
+//! minimize, with the boosting trained on the costs too.
+//!
+//! `--backend` picks what grows the boosting's trees in `--auto`: the exact trees by default, or
+//! histogram trees for bigger datasets, whose histograms are built on the GPU with `gpu` (in a
+//! build with `--features gpu`).
+
+mod auto;
+mod boosting;
+mod cross_validation;
+mod datasets;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod histogram;
+mod search;
+
+use std::time::Duration;
+
+use anyhow::bail;
+use clap::Parser;
+use metrics::{
+    cost::CostMatrix,
+    metric::{Accuracy, Cost, MacroF1, Metric, one_hot},
+};
+use ndarray::Array1;
+use titanic_ml::{random_forest::RandomForestClassifier, seed::Seed, splitter::KFold};
+
+use crate::{
+    boosting::Backend,
+    cross_validation::{cross_validate, for_every_class},
+    datasets::{Dataset, Registration},
+    search::{SearchSpace, Strategy},
+};
+
+#[derive(Parser)]
+#[command(about = "Cross-validate the Random Forest on every registered dataset")]
+struct Cli {
+    /// Only run these datasets (by name; may be repeated). Runs all of them by default
+    #[arg(long = "dataset")]
+    datasets: Vec<String>,
+
+    /// Number of cross-validation folds
+    #[arg(long, default_value_t = 5)]
+    folds: usize,
+
+    /// Number of trees in the forest
+    #[arg(long, default_value_t = 100)]
+    n_estimators: usize,
+
+    /// Maximum depth of each tree
+    #[arg(long, default_value_t = 5)]
+    max_depth: usize,
+
+    /// Minimum number of samples required to split a node
+    #[arg(long, default_value_t = 2)]
+    min_samples_split: usize,
+
+    /// Only use this fraction of each dataset's rows, keeping each label's share, for a quicker
+    /// run
+    #[arg(long)]
+    subsample: Option<f64>,
+
+    /// Train and predict with f32 features instead of f64, for half the memory
+    #[arg(long)]
+    f32: bool,
+
+    /// Seed for every random choice in the run (the synthetic data, the folds and the forests)
+    #[arg(long, default_value = "1")]
+    seed: Seed,
+
+    /// Search the forest's settings (`grid` or `random`) instead of scoring the ones above, and
+    /// print how much each mattered
+    #[arg(long)]
+    search: Option<Strategy>,
+
+    /// Number of settings to try with `--search random`
+    #[arg(long, default_value_t = 20)]
+    trials: usize,
+
+    /// Search feature sets, imputation and models together instead, for the best pipeline
+    #[arg(long, conflicts_with = "search")]
+    auto: bool,
+
+    /// Seconds to spend on each dataset with `--auto`
+    #[arg(long, default_value_t = 60.0)]
+    budget: f64,
+
+    /// What grows the boosting's trees with `--auto`: cpu, cpu-histogram or gpu (which needs a
+    /// build with `--features gpu`)
+    #[arg(long, default_value = "cpu", requires = "auto")]
+    backend: Backend,
+
+    /// What a false negative costs, as a multiple of a false positive's cost (e.g. 5 for a
+    /// missed heart attack), to score two-class datasets by the cost of their mistakes
+    #[arg(long)]
+    false_negative_cost: Option<f64>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    for name in &cli.datasets {
+        if !datasets::REGISTERED
+            .iter()
+            .any(|dataset| dataset.name == name)
+        {
+            let names: Vec<_> = datasets::REGISTERED.iter().map(|d| d.name).collect();
+            bail!(
+                "Unknown dataset {name:?}; expected one of {}",
+                names.join(", ")
+            );
+        }
+    }
+
+    if let Some(cost) = cli.false_negative_cost
+        && !(cost.is_finite() && cost > 0.0)
+    {
+        bail!("The false negative cost must be greater than 0, got {cost}");
+    }
+
+    if let Some(strategy) = cli.search {
+        return search(&cli, strategy);
+    }
+    if cli.auto {
+        return auto(&cli);
+    }
+
+    println!(
+        "Random Forest: {} trees, max depth {}, min samples split {}",
+        cli.n_estimators, cli.max_depth, cli.min_samples_split
+    );
+    println!("{}-fold cross-validation, seed {}", cli.folds, cli.seed);
+    if cli.f32 {
+        println!("Features as f32");
+    }
+    if let Some(fraction) = cli.subsample {
+        println!(
+            "Stratified subsample of {:.0}% of each dataset",
+            fraction * 100.0
+        );
+    }
+    if let Some(cost) = cli.false_negative_cost {
+        println!("False negatives cost {cost} times false positives");
+    }
+    println!();
+    print!(
+        "{:<14} {:>7} {:>8}  {:<13} {:>17} {:>8}",
+        "Dataset", "Samples", "Features", "Model", "Accuracy", "Macro F1"
+    );
+    if cli.false_negative_cost.is_some() {
+        print!(" {:>8}", "Cost");
+    }
+    println!();
+
+    for registration in selected(&cli) {
+        // Seeded by name, so adding a dataset doesn't change another's folds
+        let seed = cli.seed.derive(registration.name);
+        let dataset = load(&cli, registration, seed)?;
+        let folds = KFold::new(cli.folds).shuffle(seed.derive("folds"));
+        let n_classes = dataset.y.iter().max().map_or(0, |&max| max + 1);
+        let cost = costs(&cli, n_classes).map(|costs| Cost { costs });
+        let mut metrics: Vec<&dyn Metric> = vec![&Accuracy, &MacroF1];
+        metrics.extend(cost.as_ref().map(|cost| cost as &dyn Metric));
+
+        let baseline =
+            cross_validate(&dataset, &folds, &metrics, seed, |_, y_train, x_test, _| {
+                let predictions = Array1::from_elem(x_test.nrows(), majority_class(&y_train));
+                Ok(one_hot(&predictions, n_classes))
+            })?;
+        let forest = cross_validate(
+            &dataset,
+            &folds,
+            &metrics,
+            seed,
+            |x_train, y_train, x_test, seed| {
+                let forest = RandomForestClassifier::new()
+                    .n_estimators(cli.n_estimators)
+                    .max_depth(cli.max_depth)
+                    .min_samples_split(cli.min_samples_split)
+                    .random_state(seed);
+
+                if cli.f32 {
+                    let model = forest.fit(x_train.mapv(|v| v as f32), y_train)?;
+                    let probabilities = model.predict_proba(&x_test.mapv(|v| v as f32));
+                    Ok(for_every_class(&probabilities, model.classes(), n_classes))
+                } else {
+                    let model = forest.fit(x_train, y_train)?;
+                    Ok(for_every_class(
+                        &model.predict_proba(x_test),
+                        model.classes(),
+                        n_classes,
+                    ))
+                }
+            },
+        )?;
+
+        for (model, scores) in [("majority", &baseline), ("random forest", &forest)] {
+            let (accuracy, accuracy_std) = mean_and_std(scores.iter().map(|s| s[0]));
+            let (macro_f1, _) = mean_and_std(scores.iter().map(|s| s[1]));
+            print!(
+                "{:<14} {:>7} {:>8}  {model:<13} {:>17} {macro_f1:>8.3}",
+                registration.name,
+                dataset.x.nrows(),
+                dataset.x.ncols(),
+                format!("{:.2}% ± {:.2}%", accuracy * 100.0, accuracy_std * 100.0),
+            );
+            if cli.false_negative_cost.is_some() {
+                // Costs are only for two classes
+                match cost {
+                    Some(_) => print!(" {:>8.3}", mean_and_std(scores.iter().map(|s| s[2])).0),
+                    None => print!(" {:>8}", "-"),
+                }
+            }
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// The datasets picked with `--dataset`, or all of them
+fn selected(cli: &Cli) -> impl Iterator<Item = &'static Registration> {
+    datasets::REGISTERED.iter().filter(|registration| {
+        cli.datasets.is_empty() || cli.datasets.iter().any(|n| n == registration.name)
+    })
+}
+
+/// Load a dataset, subsampled if asked for
+fn load(cli: &Cli, registration: &Registration, seed: Seed) -> anyhow::Result<Dataset> {
+    let dataset = (registration.load)(seed.derive("data"))?;
+    match cli.subsample {
+        Some(fraction) => datasets::subsample(
+            &dataset.x,
+            &dataset.y,
+            fraction,
+            true,
+            seed.derive("subsample"),
+        ),
+        None => Ok(dataset),
+    }
+}
+
+/// `--false-negative-cost` as costs, for a dataset with `n_classes` classes; none for more than
+/// two
+fn costs(cli: &Cli, n_classes: usize) -> Option<CostMatrix> {
+    cli.false_negative_cost
+        .filter(|_| n_classes == 2)
+        .map(|cost| CostMatrix::binary(1.0, cost))
+}
+
+/// What to search by: the mean cost if there are `costs`, and accuracy otherwise
+fn search_metric(costs: Option<&CostMatrix>) -> Box<dyn Metric> {
+    match costs {
+        Some(costs) => Box::new(Cost {
+            costs: costs.clone(),
+        }),
+        None => Box::new(Accuracy),
+    }
+}
+
+/// Search the forest's settings on each dataset, scoring by `search_metric`
+fn search(cli: &Cli, strategy: Strategy) -> anyhow::Result<()> {
+    let space = SearchSpace::forest();
+    println!(
+        "{strategy} search, {}-fold cross-validation, seed {}",
+        cli.folds, cli.seed
+    );
+
+    for registration in selected(cli) {
+        let seed = cli.seed.derive(registration.name);
+        let dataset = load(cli, registration, seed)?;
+        let candidates = match strategy {
+            Strategy::Grid => space.grid(),
+            Strategy::Random => space.random(cli.trials, seed.derive("search")),
+        };
+        let n_classes = dataset.y.iter().max().map_or(0, |&max| max + 1);
+        let metric = search_metric(costs(cli, n_classes).as_ref());
+
+        let results = search::search(
+            &dataset,
+            &KFold::new(cli.folds).shuffle(seed.derive("folds")),
+            metric.as_ref(),
+            &space,
+            candidates,
+            seed,
+            |forest, x_train, y_train, x_test| {
+                let model = forest.fit(x_train, y_train)?;
+                Ok(for_every_class(
+                    &model.predict_proba(x_test),
+                    model.classes(),
+                    n_classes,
+                ))
+            },
+        )?;
+        println!(
+            "\n{} ({} samples, {} features):\n{results}",
+            registration.name,
+            dataset.x.nrows(),
+            dataset.x.ncols()
+        );
+        if let Some(best) = results.best() {
+            let settings: Vec<String> = results
+                .names
+                .iter()
+                .zip(&best.values)
+                .map(|(name, value)| format!("{name} {value}"))
+                .collect();
+            println!("Best: {}", settings.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Search pipelines on each dataset, scoring by `search_metric`
+fn auto(cli: &Cli) -> anyhow::Result<()> {
+    if !(cli.budget >= 0.0 && cli.budget.is_finite()) {
+        bail!("The budget must be a number of seconds, got {}", cli.budget);
+    }
+    println!(
+        "Auto search, {}-fold cross-validation, {}s per dataset, seed {}, boosting on {}",
+        cli.folds, cli.budget, cli.seed, cli.backend
+    );
+
+    for registration in selected(cli) {
+        let seed = cli.seed.derive(registration.name);
+        let mut feature_sets = (registration.feature_sets)(seed.derive("data"))?;
+        if let Some(fraction) = cli.subsample {
+            for (_, dataset) in &mut feature_sets {
+                // The same seed keeps the same rows of every feature set
+                *dataset = datasets::subsample(
+                    &dataset.x,
+                    &dataset.y,
+                    fraction,
+                    true,
+                    seed.derive("subsample"),
+                )?;
+            }
+        }
+
+        let n_classes = feature_sets
+            .first()
+            .and_then(|(_, dataset)| dataset.y.iter().max())
+            .map_or(0, |&max| max + 1);
+        let costs = costs(cli, n_classes);
+        let results = auto::auto(
+            &feature_sets,
+            &KFold::new(cli.folds).shuffle(seed.derive("folds")),
+            search_metric(costs.as_ref()).as_ref(),
+            costs.as_ref(),
+            cli.backend,
+            Duration::from_secs_f64(cli.budget),
+            seed,
+        )?;
+        println!("\n{}:\n{results}", registration.name);
+        if let Some(best) = results.best() {
+            println!("Best: {}", best.pipeline);
+        }
+    }
+
+    Ok(())
+}
+
+/// The most common label, the smallest on a tie
+fn majority_class(y: &Array1<usize>) -> usize {
+    let mut counts = vec![0; y.iter().max().map_or(0, |&max| max + 1)];
+    for &label in y {
+        counts[label] += 1;
+    }
+
+    // `max_by_key` keeps the last maximum, so walk the labels from the top down
+    counts
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, count)| count)
+        .map_or(0, |(label, _)| label)
+}
+
+/// The mean and (population) standard deviation across folds
+fn mean_and_std(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let values: Vec<_> = values.collect();
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    #[test]
+    fn test_majority_class() {
+        assert_eq!(majority_class(&arr1(&[1, 0, 1, 2])), 1);
+        assert_eq!(majority_class(&arr1(&[2, 0, 2, 0])), 0);
+    }
+}
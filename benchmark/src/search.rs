@@ -0,0 +1,404 @@
+//! Hyperparameter search, and which hyperparameters mattered
+//!
+//! A `SearchSpace` lists the values to try for some of the forest's settings. `grid` tries every
+//! combination of them and `random` a sample of the combinations; `search` cross-validates each
+//! on the same folds, with the same seeds, so trials differ only in their settings.
+//!
+//! `SearchResults::importance` then estimates how much of the spread in scores each setting
+//! accounts for, as a first-order fANOVA does: the variance of the mean score across a setting's
+//! values, as a share of the variance across all trials. A setting with a small share scored
+//! about the same whatever its value, so further tuning is better spent on the others.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use anyhow::{Result, bail};
+use metrics::metric::Metric;
+use ndarray::{Array1, Array2};
+use rand::seq::index;
+use titanic_ml::{random_forest::RandomForestClassifier, seed::Seed, splitter::Splitter};
+
+use crate::{cross_validation::cross_validate, datasets::Dataset, mean_and_std};
+
+/// Which combinations of a `SearchSpace` to try
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Every combination
+    Grid,
+    /// A random sample of the combinations
+    Random,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 2] = [Self::Grid, Self::Random];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Grid => "grid",
+            Self::Random => "random",
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|strategy| strategy.name() == s) {
+            Some(strategy) => Ok(strategy),
+            None => bail!("No search strategy named {s:?} (try grid or random)"),
+        }
+    }
+}
+
+/// One of the forest's settings, and the values to try for it
+pub struct Hyperparameter {
+    pub name: &'static str,
+    pub values: Vec<f64>,
+    /// Set a classifier's setting to a value
+    pub apply: fn(RandomForestClassifier, f64) -> RandomForestClassifier,
+}
+
+/// The settings to search over
+pub struct SearchSpace {
+    pub hyperparameters: Vec<Hyperparameter>,
+}
+
+impl SearchSpace {
+    /// The settings worth tuning on a forest, over values around their defaults
+    pub fn forest() -> Self {
+        Self {
+            hyperparameters: vec![
+                Hyperparameter {
+                    name: "n_estimators",
+                    values: vec![25.0, 50.0, 100.0],
+                    apply: |forest, n| forest.n_estimators(n as usize),
+                },
+                Hyperparameter {
+                    name: "max_depth",
+                    values: vec![3.0, 5.0, 8.0, 12.0],
+                    apply: |forest, depth| forest.max_depth(depth as usize),
+                },
+                Hyperparameter {
+                    name: "min_samples_split",
+                    values: vec![2.0, 10.0, 30.0],
+                    apply: |forest, n| forest.min_samples_split(n as usize),
+                },
+                Hyperparameter {
+                    name: "bootstrap_proportion",
+                    values: vec![0.5, 1.0],
+                    apply: RandomForestClassifier::bootstrap_proportion,
+                },
+            ],
+        }
+    }
+
+    /// Every combination of the values, the last hyperparameter's changing fastest
+    pub fn grid(&self) -> Vec<Vec<f64>> {
+        self.hyperparameters
+            .iter()
+            .fold(vec![Vec::new()], |combinations, hyperparameter| {
+                combinations
+                    .into_iter()
+                    .flat_map(|combination| {
+                        hyperparameter.values.iter().map(move |&value| {
+                            let mut combination = combination.clone();
+                            combination.push(value);
+                            combination
+                        })
+                    })
+                    .collect()
+            })
+    }
+
+    /// `n_trials` different combinations picked at random (every one, if there aren't that many),
+    /// in grid order
+    pub fn random(&self, n_trials: usize, seed: Seed) -> Vec<Vec<f64>> {
+        let grid = self.grid();
+        let picked: BTreeSet<usize> =
+            index::sample(&mut seed.rng(), grid.len(), n_trials.min(grid.len()))
+                .into_iter()
+                .collect();
+
+        picked.into_iter().map(|i| grid[i].clone()).collect()
+    }
+
+    /// A classifier with `values` for the hyperparameters, in order, and the rest as defaults
+    pub fn classifier(&self, values: &[f64]) -> RandomForestClassifier {
+        self.hyperparameters.iter().zip(values).fold(
+            RandomForestClassifier::new(),
+            |forest, (hyperparameter, &value)| (hyperparameter.apply)(forest, value),
+        )
+    }
+}
+
+/// One combination's cross-validation scores
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial {
+    /// The value of each hyperparameter, in the search space's order
+    pub values: Vec<f64>,
+    pub fold_scores: Vec<f64>,
+}
+
+impl Trial {
+    pub fn mean_score(&self) -> f64 {
+        self.fold_scores.iter().sum::<f64>() / self.fold_scores.len() as f64
+    }
+}
+
+/// Every trial of a search, and how to read them
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResults {
+    /// The hyperparameters' names, in the order of each trial's values
+    pub names: Vec<&'static str>,
+    /// What the trials were scored by, e.g. `"accuracy"`
+    pub metric: String,
+    pub higher_is_better: bool,
+    /// In the order they were tried
+    pub trials: Vec<Trial>,
+}
+
+impl SearchResults {
+    /// The trials, best first
+    pub fn ranked(&self) -> Vec<&Trial> {
+        let mut ranked: Vec<&Trial> = self.trials.iter().collect();
+        ranked.sort_by(|a, b| {
+            let order = a.mean_score().total_cmp(&b.mean_score());
+            if self.higher_is_better {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        ranked
+    }
+
+    pub fn best(&self) -> Option<&Trial> {
+        self.ranked().first().copied()
+    }
+
+    /// Each hyperparameter's share of the variance of the trials' mean scores that its values
+    /// alone account for, from 0 (it made no difference) to 1 (it made all of it)
+    ///
+    /// The shares of a full grid add up to at most 1; the rest comes from the hyperparameters
+    /// working together. If every trial scored the same, every share is 0.
+    pub fn importance(&self) -> Vec<f64> {
+        let scores: Vec<f64> = self.trials.iter().map(Trial::mean_score).collect();
+        let n = scores.len() as f64;
+        let overall = scores.iter().sum::<f64>() / n;
+        let total_variance = scores.iter().map(|s| (s - overall).powi(2)).sum::<f64>() / n;
+
+        (0..self.names.len())
+            .map(|hyperparameter| {
+                if total_variance <= 0.0 {
+                    return 0.0;
+                }
+                // Each value's trials' mean score, weighted by how many trials there were
+                let mut values: Vec<(f64, f64, usize)> = Vec::new();
+                for (trial, score) in self.trials.iter().zip(&scores) {
+                    let value = trial.values[hyperparameter];
+                    match values.iter_mut().find(|(v, _, _)| *v == value) {
+                        Some((_, sum, count)) => {
+                            *sum += score;
+                            *count += 1;
+                        }
+                        None => values.push((value, *score, 1)),
+                    }
+                }
+                let main_effect = values
+                    .iter()
+                    .map(|&(_, sum, count)| count as f64 * (sum / count as f64 - overall).powi(2))
+                    .sum::<f64>()
+                    / n;
+
+                main_effect / total_variance
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for SearchResults {
+    /// A row per trial, best first, then an `importance` row under the hyperparameters
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths: Vec<usize> = self.names.iter().map(|name| name.len().max(10)).collect();
+        write!(f, "{:>4}", "rank")?;
+        for (name, width) in self.names.iter().zip(&widths) {
+            write!(f, "  {name:>width$}")?;
+        }
+        writeln!(f, "  {:>17}", self.metric)?;
+
+        for (rank, trial) in self.ranked().into_iter().enumerate() {
+            write!(f, "{:>4}", rank + 1)?;
+            for (value, width) in trial.values.iter().zip(&widths) {
+                write!(f, "  {value:>width$}")?;
+            }
+            let (mean, std) = mean_and_std(trial.fold_scores.iter().copied());
+            writeln!(f, "  {:>17}", format!("{mean:.4} ± {std:.4}"))?;
+        }
+
+        write!(f, "{:>4}", "")?;
+        for (importance, width) in self.importance().into_iter().zip(&widths) {
+            write!(f, "  {:>width$}", format!("{:.1}%", importance * 100.0))?;
+        }
+        write!(f, "  {:>17}", "importance")
+    }
+}
+
+/// Cross-validate a classifier for each of `candidates` (from `SearchSpace::grid` or `random`)
+/// on `splitter`'s folds, scoring by `metric`
+///
+/// `fit_predict` gets the classifier (seeded for the fold), the training features and labels and
+/// the test features, and returns the test rows' probabilities, as for `cross_validate`.
+pub fn search<F>(
+    dataset: &Dataset,
+    splitter: &impl Splitter,
+    metric: &dyn Metric,
+    space: &SearchSpace,
+    candidates: Vec<Vec<f64>>,
+    seed: Seed,
+    fit_predict: F,
+) -> Result<SearchResults>
+where
+    F: Fn(RandomForestClassifier, Array2<f64>, Array1<usize>, &Array2<f64>) -> Result<Array2<f64>>,
+{
+    let trials = candidates
+        .into_iter()
+        .map(|values| {
+            let scores = cross_validate(
+                dataset,
+                splitter,
+                &[metric],
+                seed,
+                |x_train, y_train, x_test, seed| {
+                    let forest = space.classifier(&values).random_state(seed);
+                    fit_predict(forest, x_train, y_train, x_test)
+                },
+            )?;
+
+            Ok(Trial {
+                values,
+                fold_scores: scores.into_iter().map(|scores| scores[0]).collect(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SearchResults {
+        names: space.hyperparameters.iter().map(|h| h.name).collect(),
+        metric: metric.name().to_string(),
+        higher_is_better: metric.higher_is_better(),
+        trials,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::metric::Accuracy;
+    use titanic_ml::splitter::KFold;
+
+    #[test]
+    fn test_grid_and_random_candidates() {
+        let space = SearchSpace::forest();
+        let grid = space.grid();
+        assert_eq!(grid.len(), 3 * 4 * 3 * 2);
+        assert_eq!(grid[0], [25.0, 3.0, 2.0, 0.5]);
+        assert_eq!(grid[1], [25.0, 3.0, 2.0, 1.0]);
+
+        let random = space.random(10, Seed::new(2));
+        assert_eq!(random.len(), 10);
+        assert!(random.iter().all(|candidate| grid.contains(candidate)));
+        assert!(random.windows(2).all(|pair| pair[0] != pair[1]));
+        assert_eq!(random, space.random(10, Seed::new(2)));
+        assert_eq!(space.random(1000, Seed::new(2)), grid);
+
+        let config = space.classifier(&[50.0, 5.0, 10.0, 0.5]).config();
+        assert_eq!(
+            (
+                config.n_estimators,
+                config.max_depth,
+                config.min_samples_split
+            ),
+            (50, 5, 10)
+        );
+        assert_eq!(config.bootstrap_proportion, 0.5);
+    }
+
+    #[test]
+    fn test_importance_finds_the_setting_that_mattered() {
+        // The score only depends on the first setting
+        let trials = [(1.0, 1.0), (1.0, 2.0), (2.0, 1.0), (2.0, 2.0)]
+            .map(|(a, b)| Trial {
+                values: vec![a, b],
+                fold_scores: vec![0.5 + a / 10.0, 0.5 + a / 10.0],
+            })
+            .to_vec();
+        let results = SearchResults {
+            names: vec!["a", "b"],
+            metric: "accuracy".to_string(),
+            higher_is_better: true,
+            trials,
+        };
+
+        let importance = results.importance();
+        assert!((importance[0] - 1.0).abs() < 1e-12, "{importance:?}");
+        assert!(importance[1].abs() < 1e-12, "{importance:?}");
+        assert_eq!(results.best().unwrap().values[0], 2.0);
+        let table = results.to_string();
+        assert!(
+            table.ends_with("100.0%        0.0%         importance"),
+            "{table}"
+        );
+    }
+
+    #[test]
+    fn test_search_scores_each_candidate() {
+        let x = Array2::from_shape_fn((40, 2), |(i, j)| ((i * (j + 3)) % 11) as f64);
+        let y = x.column(0).mapv(|v| usize::from(v > 5.0));
+        let dataset = Dataset { x, y };
+        let space = SearchSpace {
+            hyperparameters: SearchSpace::forest().hyperparameters.split_off(1),
+        };
+        let candidates = vec![vec![1.0, 2.0, 1.0], vec![5.0, 2.0, 1.0]];
+
+        let results = search(
+            &dataset,
+            &KFold::new(2).shuffle(1),
+            &Accuracy,
+            &space,
+            candidates,
+            Seed::new(1),
+            |forest, x_train, y_train, x_test| {
+                Ok(forest
+                    .n_estimators(5)
+                    .fit(x_train, y_train)?
+                    .predict_proba(x_test))
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            results.names,
+            ["max_depth", "min_samples_split", "bootstrap_proportion"]
+        );
+        assert_eq!(results.trials.len(), 2);
+        assert!(
+            results
+                .trials
+                .iter()
+                .all(|trial| trial.fold_scores.len() == 2)
+        );
+        let best = results.best().unwrap().mean_score();
+        assert!(best > 0.9, "{results}");
+        assert!(
+            results
+                .trials
+                .iter()
+                .all(|trial| trial.mean_score() <= best)
+        );
+    }
+}
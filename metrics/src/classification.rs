@@ -0,0 +1,596 @@
+//! Metrics for class-label predictions
+//!
+//! Labels are `usize` class indices, as produced by `series_to_array1` and every classifier in
+//! the workspace. For binary problems the positive class is `1`.
+//!
+//! These run over every out-of-fold prediction of every candidate in a hyperparameter search, so
+//! they walk the labels with `ndarray::Zip` (a single tight loop over contiguous data, which the
+//! compiler can vectorize) and derive per-class scores from the confusion matrix with whole-array
+//! operations.
+//!
+//! For data too large to predict in one go, `StreamingAccuracy`, `StreamingConfusionMatrix` and
+//! `StreamingLogLoss` take the predictions a batch at a time (e.g. from
+//! `FittedRandomForest::predict_iter`) and give the same result as the whole-array functions once
+//! finished.
+
+use std::fmt;
+
+use ndarray::{Array1, Array2, Axis, Zip, s};
+
+/// The fraction of predictions that match the actual labels
+///
+/// # Panics
+/// If `predictions` and `actual` have different lengths
+pub fn accuracy(predictions: &Array1<usize>, actual: &Array1<usize>) -> f64 {
+    let correct = Zip::from(predictions)
+        .and(actual)
+        .fold(0, |correct, predicted, actual| {
+            correct + usize::from(predicted == actual)
+        });
+
+    correct as f64 / actual.len() as f64
+}
+
+/// The mean negative log of the probability given to each sample's actual class
+///
+/// Lower is better, and a confident wrong answer costs far more than an unsure one. Column `k` of
+/// `probabilities` is class `k`'s probability, as from `predict_proba`. Probabilities are clipped
+/// to `[1e-15, 1 - 1e-15]`, so one certain mistake doesn't make the loss infinite.
+///
+/// # Panics
+/// If `probabilities` doesn't have a row per label in `actual`
+pub fn log_loss(probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+    sample_log_losses(probabilities, actual) / actual.len() as f64
+}
+
+/// The summed log loss of every row of `probabilities`
+fn sample_log_losses(probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+    const EPSILON: f64 = 1e-15;
+
+    Zip::from(probabilities.rows())
+        .and(actual)
+        .fold(0.0, |sum, row, &actual| {
+            let probability = row.get(actual).copied().unwrap_or_default();
+            sum - probability.clamp(EPSILON, 1.0 - EPSILON).ln()
+        })
+}
+
+/// The F1 score of class `positive`: the harmonic mean of its precision and recall
+pub fn f1_score(predictions: &Array1<usize>, actual: &Array1<usize>, positive: usize) -> f64 {
+    ConfusionMatrix::new(predictions, actual).f1(positive)
+}
+
+/// The area under the ROC curve: the chance that a random row of class `positive` scores higher
+/// than a random row of any other class, counting ties as half
+///
+/// `scores` can be anything that ranks rows by how likely they are to be `positive`, such as that
+/// class's column of `predict_proba`. 1 is a perfect ranking and 0.5 is no better than chance.
+/// It's NaN unless `actual` has rows of both kinds.
+///
+/// # Panics
+/// If `scores` and `actual` have different lengths
+pub fn roc_auc(scores: &Array1<f64>, actual: &Array1<usize>, positive: usize) -> f64 {
+    assert_eq!(
+        scores.len(),
+        actual.len(),
+        "scores and labels differ in length"
+    );
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+
+    // The Mann-Whitney U statistic: the positives' ranks, with tied scores sharing their mean rank
+    let (mut positive_rank_sum, mut n_positive) = (0.0, 0);
+    let mut start = 0;
+    while start < order.len() {
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|&&i| scores[i] == scores[order[start]])
+                .count();
+        let mean_rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            if actual[i] == positive {
+                positive_rank_sum += mean_rank;
+                n_positive += 1;
+            }
+        }
+        start = end;
+    }
+
+    let n_negative = actual.len() - n_positive;
+    let n_positive = n_positive as f64;
+    (positive_rank_sum - n_positive * (n_positive + 1.0) / 2.0) / (n_positive * n_negative as f64)
+}
+
+/// The Kolmogorov-Smirnov statistic: the largest gap between the share of class `positive`'s rows
+/// and the share of the other rows scoring at or below any one score
+///
+/// It's the most that any single threshold on `scores` separates the two classes, which is how
+/// credit scorecards are usually judged, alongside `roc_auc`'s measure of the ranking as a whole.
+/// 1 means some threshold splits them perfectly and 0 means the two distributions of scores are
+/// the same. Like `roc_auc`, it's NaN unless `actual` has rows of both kinds.
+///
+/// # Panics
+/// If `scores` and `actual` have different lengths
+pub fn ks_statistic(scores: &Array1<f64>, actual: &Array1<usize>, positive: usize) -> f64 {
+    assert_eq!(
+        scores.len(),
+        actual.len(),
+        "scores and labels differ in length"
+    );
+
+    let n_positive = actual.iter().filter(|&&label| label == positive).count();
+    let n_negative = actual.len() - n_positive;
+    if n_positive == 0 || n_negative == 0 {
+        return f64::NAN;
+    }
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+
+    // Walk both empirical distributions up through the scores, only comparing them once every row
+    // with a tied score has been counted
+    let (mut positives_below, mut negatives_below) = (0, 0);
+    let mut largest_gap: f64 = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|&&i| scores[i] == scores[order[start]])
+                .count();
+        for &i in &order[start..end] {
+            if actual[i] == positive {
+                positives_below += 1;
+            } else {
+                negatives_below += 1;
+            }
+        }
+        let gap =
+            negatives_below as f64 / n_negative as f64 - positives_below as f64 / n_positive as f64;
+        largest_gap = largest_gap.max(gap.abs());
+        start = end;
+    }
+
+    largest_gap
+}
+
+/// Counts of each (actual, predicted) label pair
+///
+/// # Example
+/// ```ignore
+/// let matrix = ConfusionMatrix::new(&predictions, &y_validation);
+/// println!("{matrix}");
+/// println!("Precision: {:.3}, recall: {:.3}", matrix.precision(1), matrix.recall(1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    /// Rows are actual labels, columns predicted ones
+    counts: Array2<usize>,
+}
+
+impl ConfusionMatrix {
+    /// Count every pair; there's a row and column for each label up to the largest seen, and
+    /// always at least two
+    ///
+    /// # Panics
+    /// If `predictions` and `actual` have different lengths
+    pub fn new(predictions: &Array1<usize>, actual: &Array1<usize>) -> Self {
+        let max = Zip::from(predictions)
+            .and(actual)
+            .fold(0, |max, &predicted, &actual| max.max(predicted).max(actual));
+        let n_classes = (max + 1).max(2);
+
+        // Count into a flat buffer, then view it as rows of actual labels
+        let mut counts = vec![0; n_classes * n_classes];
+        Zip::from(predictions)
+            .and(actual)
+            .for_each(|&predicted, &actual| counts[actual * n_classes + predicted] += 1);
+
+        Self {
+            counts: Array2::from_shape_vec((n_classes, n_classes), counts)
+                .expect("the buffer has n_classes² counts"),
+        }
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.counts.nrows()
+    }
+
+    /// Rows are actual labels, columns predicted ones
+    pub fn counts(&self) -> &Array2<usize> {
+        &self.counts
+    }
+
+    /// The number of samples labelled `actual` that were predicted as `predicted`
+    pub fn count(&self, actual: usize, predicted: usize) -> usize {
+        self.counts
+            .get([actual, predicted])
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        self.counts.diag().sum() as f64 / self.total() as f64
+    }
+
+    /// Of the samples predicted as `class`, the fraction that really are; 0 if none were
+    pub fn precision(&self, class: usize) -> f64 {
+        let predicted = self.column_total(class);
+        ratio(self.count(class, class), predicted)
+    }
+
+    /// Of the samples that really are `class`, the fraction predicted as it; 0 if there are none
+    pub fn recall(&self, class: usize) -> f64 {
+        let actual = self.row_total(class);
+        ratio(self.count(class, class), actual)
+    }
+
+    /// The harmonic mean of `precision` and `recall`; 0 if both are
+    pub fn f1(&self, class: usize) -> f64 {
+        let (precision, recall) = (self.precision(class), self.recall(class));
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+
+    /// Every class's precision, indexed by class
+    pub fn precisions(&self) -> Array1<f64> {
+        ratios(&self.true_positives(), &self.counts.sum_axis(Axis(0)))
+    }
+
+    /// Every class's recall, indexed by class
+    pub fn recalls(&self) -> Array1<f64> {
+        ratios(&self.true_positives(), &self.counts.sum_axis(Axis(1)))
+    }
+
+    /// Every class's F1 score, indexed by class
+    pub fn f1_scores(&self) -> Array1<f64> {
+        let (precisions, recalls) = (self.precisions(), self.recalls());
+        let sums = &precisions + &recalls;
+
+        Zip::from(&(2.0 * &precisions * &recalls))
+            .and(&sums)
+            .map_collect(|&product, &sum| if sum == 0.0 { 0.0 } else { product / sum })
+    }
+
+    /// The unweighted mean of every class's precision
+    pub fn macro_precision(&self) -> f64 {
+        self.precisions().mean().unwrap_or_default()
+    }
+
+    /// The unweighted mean of every class's recall
+    pub fn macro_recall(&self) -> f64 {
+        self.recalls().mean().unwrap_or_default()
+    }
+
+    /// The unweighted mean of every class's F1 score, so rare classes count as much as common
+    /// ones
+    pub fn macro_f1(&self) -> f64 {
+        self.f1_scores().mean().unwrap_or_default()
+    }
+
+    fn true_positives(&self) -> Array1<usize> {
+        self.counts.diag().to_owned()
+    }
+
+    fn row_total(&self, class: usize) -> usize {
+        if class < self.n_classes() {
+            self.counts.row(class).sum()
+        } else {
+            0
+        }
+    }
+
+    fn column_total(&self, class: usize) -> usize {
+        if class < self.n_classes() {
+            self.counts.column(class).sum()
+        } else {
+            0
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// `ratio` element by element
+fn ratios(numerators: &Array1<usize>, denominators: &Array1<usize>) -> Array1<f64> {
+    Zip::from(numerators)
+        .and(denominators)
+        .map_collect(|&numerator, &denominator| ratio(numerator, denominator))
+}
+
+/// `accuracy`, a batch of predictions at a time
+///
+/// # Example
+/// ```ignore
+/// let mut accuracy = StreamingAccuracy::new();
+/// for (predictions, actual) in model.predict_iter(x.axis_chunks_iter(Axis(0), 1000)).zip(labels) {
+///     accuracy.update(&predictions, &actual);
+/// }
+/// println!("Accuracy: {:.2}%", accuracy.finish() * 100.0);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamingAccuracy {
+    correct: usize,
+    total: usize,
+}
+
+impl StreamingAccuracy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a batch of predictions against its actual labels
+    ///
+    /// # Panics
+    /// If `predictions` and `actual` have different lengths
+    pub fn update(&mut self, predictions: &Array1<usize>, actual: &Array1<usize>) {
+        self.correct += Zip::from(predictions)
+            .and(actual)
+            .fold(0, |correct, predicted, actual| {
+                correct + usize::from(predicted == actual)
+            });
+        self.total += actual.len();
+    }
+
+    /// The fraction of all the predictions seen that matched
+    pub fn finish(self) -> f64 {
+        self.correct as f64 / self.total as f64
+    }
+}
+
+/// `ConfusionMatrix::new`, a batch of predictions at a time
+///
+/// The matrix grows as batches bring new labels, so it ends up the same size as one built from
+/// all the labels at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamingConfusionMatrix {
+    counts: Array2<usize>,
+}
+
+impl StreamingConfusionMatrix {
+    pub fn new() -> Self {
+        Self {
+            counts: Array2::zeros((2, 2)),
+        }
+    }
+
+    /// Count a batch of predictions against its actual labels
+    ///
+    /// # Panics
+    /// If `predictions` and `actual` have different lengths
+    pub fn update(&mut self, predictions: &Array1<usize>, actual: &Array1<usize>) {
+        let batch = ConfusionMatrix::new(predictions, actual);
+        let n_classes = batch.n_classes().max(self.counts.nrows());
+        if n_classes > self.counts.nrows() {
+            let mut counts = Array2::zeros((n_classes, n_classes));
+            let n = self.counts.nrows();
+            counts.slice_mut(s![..n, ..n]).assign(&self.counts);
+            self.counts = counts;
+        }
+
+        let n = batch.n_classes();
+        self.counts
+            .slice_mut(s![..n, ..n])
+            .zip_mut_with(&batch.counts, |total, count| {
+                *total += count;
+            });
+    }
+
+    pub fn finish(self) -> ConfusionMatrix {
+        ConfusionMatrix {
+            counts: self.counts,
+        }
+    }
+}
+
+impl Default for StreamingConfusionMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `log_loss`, a batch of probabilities at a time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamingLogLoss {
+    sum: f64,
+    total: usize,
+}
+
+impl StreamingLogLoss {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a batch's probabilities (one row per sample, one column per class) and actual labels
+    ///
+    /// # Panics
+    /// If `probabilities` doesn't have a row per label in `actual`
+    pub fn update(&mut self, probabilities: &Array2<f64>, actual: &Array1<usize>) {
+        self.sum += sample_log_losses(probabilities, actual);
+        self.total += actual.len();
+    }
+
+    /// The mean log loss over every sample seen
+    pub fn finish(self) -> f64 {
+        self.sum / self.total as f64
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Confusion matrix (rows = actual, columns = predicted):")?;
+        write!(f, "{:>6}", "")?;
+        for predicted in 0..self.n_classes() {
+            write!(f, " {predicted:>6}")?;
+        }
+
+        for (actual, row) in self.counts.rows().into_iter().enumerate() {
+            write!(f, "\n{actual:>6}")?;
+            for count in row {
+                write!(f, " {count:>6}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    /// 3 true negatives, 1 false positive, 2 false negatives, 4 true positives
+    fn binary() -> (Array1<usize>, Array1<usize>) {
+        let actual = arr1(&[0, 0, 0, 0, 1, 1, 1, 1, 1, 1]);
+        let predictions = arr1(&[0, 0, 0, 1, 0, 0, 1, 1, 1, 1]);
+
+        (predictions, actual)
+    }
+
+    #[test]
+    fn test_accuracy() {
+        let (predictions, actual) = binary();
+
+        assert_eq!(accuracy(&predictions, &actual), 0.7);
+        assert_eq!(ConfusionMatrix::new(&predictions, &actual).accuracy(), 0.7);
+    }
+
+    #[test]
+    fn test_binary_confusion_matrix() {
+        let (predictions, actual) = binary();
+        let matrix = ConfusionMatrix::new(&predictions, &actual);
+
+        assert_eq!(matrix.counts(), &arr2(&[[3, 1], [2, 4]]));
+        assert_eq!(matrix.precision(1), 0.8);
+        assert!((matrix.recall(1) - 4.0 / 6.0).abs() < 1e-12);
+        assert!((matrix.f1(1) - 8.0 / 11.0).abs() < 1e-12);
+        assert_eq!(f1_score(&predictions, &actual, 1), matrix.f1(1));
+    }
+
+    #[test]
+    fn test_multiclass_and_missing_classes() {
+        let matrix = ConfusionMatrix::new(&arr1(&[0, 2, 2]), &arr1(&[0, 1, 2]));
+
+        assert_eq!(matrix.n_classes(), 3);
+        assert_eq!(matrix.count(1, 2), 1);
+        // Class 1 is never predicted, and class 5 doesn't exist
+        assert_eq!(matrix.precision(1), 0.0);
+        assert_eq!(matrix.recall(5), 0.0);
+        assert_eq!(matrix.f1(5), 0.0);
+        assert!((matrix.macro_precision() - (1.0 + 0.0 + 0.5) / 3.0).abs() < 1e-12);
+        assert!((matrix.macro_recall() - (1.0 + 0.0 + 1.0) / 3.0).abs() < 1e-12);
+        assert!((matrix.macro_f1() - (1.0 + 0.0 + 2.0 / 3.0) / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_per_class_scores_match_the_single_class_ones() {
+        let actual = arr1(&[0, 1, 2, 2, 1, 0, 3, 3, 3, 1]);
+        let predictions = arr1(&[0, 2, 2, 1, 1, 0, 3, 0, 3, 1]);
+        let matrix = ConfusionMatrix::new(&predictions, &actual);
+
+        for class in 0..matrix.n_classes() {
+            assert_eq!(matrix.precisions()[class], matrix.precision(class));
+            assert_eq!(matrix.recalls()[class], matrix.recall(class));
+            assert!((matrix.f1_scores()[class] - matrix.f1(class)).abs() < 1e-12);
+        }
+        assert_eq!(accuracy(&predictions, &actual), matrix.accuracy());
+    }
+
+    #[test]
+    fn test_log_loss() {
+        let probabilities = arr2(&[[0.9, 0.1], [0.2, 0.8], [0.6, 0.4], [1.0, 0.0]]);
+        let actual = arr1(&[0, 1, 1, 1]);
+
+        // The last sample is certain and wrong, so it's clipped rather than infinite
+        let expected = -(0.9f64.ln() + 0.8f64.ln() + 0.4f64.ln() + 1e-15f64.ln()) / 4.0;
+        assert!((log_loss(&probabilities, &actual) - expected).abs() < 1e-12);
+        assert!(log_loss(&arr2(&[[1.0, 0.0]]), &arr1(&[0])) < 1e-12);
+    }
+
+    #[test]
+    fn test_roc_auc() {
+        let actual = arr1(&[0, 0, 1, 1]);
+        assert_eq!(roc_auc(&arr1(&[0.1, 0.4, 0.35, 0.8]), &actual, 1), 0.75);
+        assert_eq!(roc_auc(&arr1(&[0.1, 0.2, 0.3, 0.4]), &actual, 1), 1.0);
+        // A tie between a positive and a negative counts as half
+        assert_eq!(roc_auc(&arr1(&[0.1, 0.5, 0.5, 0.8]), &actual, 1), 0.875);
+        assert!(roc_auc(&arr1(&[0.1, 0.2]), &arr1(&[1, 1]), 1).is_nan());
+    }
+
+    #[test]
+    fn test_ks_statistic() {
+        let actual = arr1(&[0, 0, 1, 1]);
+        // Up to 0.1, half the negatives and none of the positives
+        assert_eq!(ks_statistic(&arr1(&[0.1, 0.4, 0.35, 0.8]), &actual, 1), 0.5);
+        assert_eq!(ks_statistic(&arr1(&[0.1, 0.2, 0.3, 0.4]), &actual, 1), 1.0);
+        // Scores that don't tell the classes apart, tied or not
+        assert_eq!(ks_statistic(&arr1(&[0.5; 4]), &actual, 1), 0.0);
+        assert_eq!(ks_statistic(&arr1(&[0.1, 0.2, 0.1, 0.2]), &actual, 1), 0.0);
+        // Swapping which class is positive doesn't change the gap
+        assert_eq!(ks_statistic(&arr1(&[0.1, 0.4, 0.35, 0.8]), &actual, 0), 0.5);
+        assert!(ks_statistic(&arr1(&[0.1, 0.2]), &arr1(&[1, 1]), 1).is_nan());
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_arrays() {
+        let actual = arr1(&[0, 1, 2, 2, 1, 0, 3, 3, 3, 1]);
+        let predictions = arr1(&[0, 2, 2, 1, 1, 0, 3, 0, 3, 1]);
+        let probabilities =
+            Array2::from_shape_fn(
+                (10, 4),
+                |(i, class)| {
+                    if class == predictions[i] { 0.7 } else { 0.1 }
+                },
+            );
+
+        let mut accuracy = StreamingAccuracy::new();
+        let mut matrix = StreamingConfusionMatrix::new();
+        let mut loss = StreamingLogLoss::new();
+        // The first batch only has classes 0 and 1, so the matrix has to grow
+        for rows in [0..2, 2..7, 7..10] {
+            let (predictions, actual) = (
+                predictions.slice(s![rows.clone()]).to_owned(),
+                actual.slice(s![rows.clone()]).to_owned(),
+            );
+            accuracy.update(&predictions, &actual);
+            matrix.update(&predictions, &actual);
+            loss.update(&probabilities.slice(s![rows, ..]).to_owned(), &actual);
+        }
+
+        assert_eq!(accuracy.finish(), super::accuracy(&predictions, &actual));
+        assert_eq!(matrix.finish(), ConfusionMatrix::new(&predictions, &actual));
+        assert!((loss.finish() - log_loss(&probabilities, &actual)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_display() {
+        let (predictions, actual) = binary();
+
+        assert_eq!(
+            ConfusionMatrix::new(&predictions, &actual).to_string(),
+            [
+                "Confusion matrix (rows = actual, columns = predicted):",
+                "            0      1",
+                "     0      3      1",
+                "     1      2      4",
+            ]
+            .join("\n")
+        );
+    }
+}
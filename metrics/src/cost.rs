@@ -0,0 +1,189 @@
+//! Misclassification costs, for problems where one kind of mistake is worse than another
+//!
+//! Missing a heart attack is far worse than a false alarm, so accuracy, which counts both the
+//! same, isn't what a model for it should be judged (or tuned) on. A `CostMatrix` says what each
+//! (actual, predicted) pair costs, and `mean_cost` is the score to minimize.
+//!
+//! It also says which label to predict. With calibrated probabilities, the cheapest label on
+//! average is the one `CostMatrix::decide` picks, which for two classes is predicting positive
+//! above `CostMatrix::threshold` instead of 0.5. For scores that aren't calibrated,
+//! `best_threshold` finds the cheapest threshold on validation data instead.
+
+use ndarray::{Array1, Array2, Zip, arr2};
+
+/// What predicting each label costs, given the actual one
+///
+/// # Example
+/// ```ignore
+/// // A missed heart attack costs 5 times a false alarm
+/// let costs = CostMatrix::binary(1.0, 5.0);
+/// let predictions = costs.decide(&model.predict_proba(&x_validation));
+/// println!("Mean cost: {:.3}", costs.mean_cost(&predictions, &y_validation));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostMatrix {
+    /// Rows are actual labels, columns predicted ones, as in `ConfusionMatrix`
+    costs: Array2<f64>,
+}
+
+impl CostMatrix {
+    /// # Panics
+    /// If `costs` isn't square, or has a cost that's negative or not finite
+    pub fn new(costs: Array2<f64>) -> Self {
+        assert!(costs.is_square(), "A cost matrix must be square");
+        assert!(
+            costs.iter().all(|cost| cost.is_finite() && *cost >= 0.0),
+            "Costs must be finite and at least 0"
+        );
+
+        Self { costs }
+    }
+
+    /// Two classes, where right answers cost nothing
+    ///
+    /// # Panics
+    /// If either cost is negative or not finite
+    pub fn binary(false_positive: f64, false_negative: f64) -> Self {
+        Self::new(arr2(&[[0.0, false_positive], [false_negative, 0.0]]))
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.costs.nrows()
+    }
+
+    /// What predicting `predicted` costs when the label is `actual`
+    ///
+    /// # Panics
+    /// If either label isn't below `n_classes`
+    pub fn cost(&self, actual: usize, predicted: usize) -> f64 {
+        self.costs[[actual, predicted]]
+    }
+
+    /// The mean cost of `predictions`
+    ///
+    /// # Panics
+    /// If `predictions` and `actual` have different lengths, or a label isn't below `n_classes`
+    pub fn mean_cost(&self, predictions: &Array1<usize>, actual: &Array1<usize>) -> f64 {
+        let total = Zip::from(predictions)
+            .and(actual)
+            .fold(0.0, |total, &predicted, &actual| {
+                total + self.cost(actual, predicted)
+            });
+
+        total / actual.len() as f64
+    }
+
+    /// Each row's label with the lowest expected cost, given `probabilities` (a column per class)
+    ///
+    /// Ties go to the first label, so with costs of 1 for every mistake this is the most likely
+    /// class, as `predicted_labels` gives.
+    ///
+    /// # Panics
+    /// If `probabilities` doesn't have a column per class
+    pub fn decide(&self, probabilities: &Array2<f64>) -> Array1<usize> {
+        assert_eq!(
+            probabilities.ncols(),
+            self.n_classes(),
+            "Need a probability for each class"
+        );
+
+        // Column `k` is each row's expected cost of predicting `k`
+        let expected = probabilities.dot(&self.costs);
+        expected
+            .rows()
+            .into_iter()
+            .map(|row| {
+                (1..row.len()).fold(
+                    0,
+                    |best, label| {
+                        if row[label] < row[best] { label } else { best }
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// For two classes, the probability of class 1 above which predicting it is cheaper on
+    /// average: 0.5 if both mistakes cost the same, and lower the more a false negative costs
+    ///
+    /// # Panics
+    /// If there aren't two classes
+    pub fn threshold(&self) -> f64 {
+        assert_eq!(self.n_classes(), 2, "A threshold needs two classes");
+        let negative_regret = self.cost(0, 1) - self.cost(0, 0);
+        let positive_regret = self.cost(1, 0) - self.cost(1, 1);
+
+        negative_regret / (negative_regret + positive_regret)
+    }
+}
+
+/// The threshold on `scores` (any ranking of how likely each row is to be class 1) that
+/// minimizes `costs.mean_cost` on `actual`, predicting 1 for scores at or above it
+///
+/// Every distinct score is tried, plus one above them all (never predicting 1). Tune it on
+/// validation data, not the rows the model was trained on.
+///
+/// # Panics
+/// If `scores` and `actual` have different lengths, or `costs` isn't for two classes
+pub fn best_threshold(scores: &Array1<f64>, actual: &Array1<usize>, costs: &CostMatrix) -> f64 {
+    assert_eq!(scores.len(), actual.len(), "Need a score for every label");
+    assert_eq!(costs.n_classes(), 2, "A threshold needs two classes");
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    // Start by predicting 0 for every row, then predict 1 for one more score at a time, from the
+    // highest down
+    let mut cost: f64 = actual.iter().map(|&label| costs.cost(label, 0)).sum();
+    let mut best = (cost, f64::INFINITY);
+    let mut start = 0;
+    while start < order.len() {
+        let score = scores[order[start]];
+        let tied = order[start..]
+            .iter()
+            .take_while(|&&i| scores[i] == score)
+            .count();
+        for &i in &order[start..start + tied] {
+            cost += costs.cost(actual[i], 1) - costs.cost(actual[i], 0);
+        }
+        if cost < best.0 {
+            best = (cost, score);
+        }
+        start += tied;
+    }
+
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    #[test]
+    fn test_costs_pick_labels_and_thresholds() {
+        let costs = CostMatrix::binary(1.0, 5.0);
+        assert_eq!(costs.threshold(), 1.0 / 6.0);
+        assert_eq!(CostMatrix::binary(2.0, 2.0).threshold(), 0.5);
+
+        let probabilities = arr2(&[[0.9, 0.1], [0.7, 0.3], [0.2, 0.8]]);
+        assert_eq!(costs.decide(&probabilities), arr1(&[0, 1, 1]));
+        assert_eq!(
+            CostMatrix::binary(1.0, 1.0).decide(&probabilities),
+            arr1(&[0, 0, 1])
+        );
+
+        let actual = arr1(&[0, 1, 1]);
+        // One false positive and one false negative
+        assert_eq!(costs.mean_cost(&arr1(&[1, 0, 1]), &actual), 2.0);
+
+        // Predicting 1 from 0.3 up costs only the false positive at 0.35
+        let scores = arr1(&[0.35, 0.3, 0.8, 0.1]);
+        let actual = arr1(&[0, 1, 1, 0]);
+        assert_eq!(best_threshold(&scores, &actual, &costs), 0.3);
+        assert_eq!(
+            best_threshold(&scores, &actual, &CostMatrix::binary(5.0, 1.0)),
+            0.8
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! Evaluation metrics shared by the workspace's models
+//!
+//! Everything takes plain ndarray arrays, so it doesn't matter which crate or model produced the
+//! predictions.
+
+pub mod classification;
+pub mod cost;
+pub mod metric;
+pub mod regression;
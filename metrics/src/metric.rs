@@ -0,0 +1,275 @@
+//! Metrics as values, so the code that scores models doesn't have to know which score it's after
+//!
+//! Cross-validation, a hyperparameter search and early stopping all need to score predictions
+//! and decide which of two scores is better. Each takes a `Metric` rather than calling `accuracy`
+//! itself, so the same code can optimize F1, AUC, the KS statistic or log loss instead.
+//!
+//! A `Metric` scores class probabilities (column `k` is class `k`, as from `predict_proba`),
+//! since some metrics need them; the label-based ones take each row's most likely class (or, for
+//! `Cost`, its cheapest). A model that only predicts labels can be scored through `one_hot`.
+
+use ndarray::{Array1, Array2};
+
+use crate::{
+    classification::{ConfusionMatrix, f1_score, ks_statistic, log_loss, roc_auc},
+    cost::CostMatrix,
+};
+
+/// A way of scoring predicted class probabilities against the actual labels
+///
+/// # Example
+/// ```ignore
+/// let metric: &dyn Metric = if use_f1 { &F1 { positive: 1 } } else { &Accuracy };
+/// let score = metric.compute(&model.predict_proba(&x_validation), &y_validation);
+/// println!("Validation {}: {score:.3}", metric.name());
+/// ```
+pub trait Metric: Send + Sync {
+    /// What the score is called in reports, e.g. `"accuracy"`
+    fn name(&self) -> &str;
+
+    /// Whether a higher score is better (accuracy) rather than lower (log loss)
+    fn higher_is_better(&self) -> bool {
+        true
+    }
+
+    /// The score of `probabilities` (a row per label in `actual`, a column per class)
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64;
+
+    /// Whether `score` beats `best`; a tie doesn't
+    fn is_better(&self, score: f64, best: f64) -> bool {
+        if self.higher_is_better() {
+            score > best
+        } else {
+            score < best
+        }
+    }
+}
+
+/// `classification::accuracy`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Accuracy;
+
+impl Metric for Accuracy {
+    fn name(&self) -> &str {
+        "accuracy"
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        crate::classification::accuracy(&predicted_labels(probabilities), actual)
+    }
+}
+
+/// `classification::f1_score` of the class `positive`
+#[derive(Debug, Clone, Copy)]
+pub struct F1 {
+    pub positive: usize,
+}
+
+impl Default for F1 {
+    fn default() -> Self {
+        Self { positive: 1 }
+    }
+}
+
+impl Metric for F1 {
+    fn name(&self) -> &str {
+        "f1"
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        f1_score(&predicted_labels(probabilities), actual, self.positive)
+    }
+}
+
+/// `ConfusionMatrix::macro_f1`: the unweighted mean of every class's F1 score
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MacroF1;
+
+impl Metric for MacroF1 {
+    fn name(&self) -> &str {
+        "macro f1"
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        ConfusionMatrix::new(&predicted_labels(probabilities), actual).macro_f1()
+    }
+}
+
+/// `classification::log_loss`, where lower is better
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogLoss;
+
+impl Metric for LogLoss {
+    fn name(&self) -> &str {
+        "log loss"
+    }
+
+    fn higher_is_better(&self) -> bool {
+        false
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        log_loss(probabilities, actual)
+    }
+}
+
+/// `classification::roc_auc`, ranking the rows by the probability of the class `positive`
+#[derive(Debug, Clone, Copy)]
+pub struct RocAuc {
+    pub positive: usize,
+}
+
+impl Default for RocAuc {
+    fn default() -> Self {
+        Self { positive: 1 }
+    }
+}
+
+impl Metric for RocAuc {
+    fn name(&self) -> &str {
+        "roc auc"
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        roc_auc(
+            &class_probabilities(probabilities, self.positive),
+            actual,
+            self.positive,
+        )
+    }
+}
+
+/// `classification::ks_statistic`, comparing the probabilities of the class `positive` given to
+/// its rows with those given to the rest
+#[derive(Debug, Clone, Copy)]
+pub struct KolmogorovSmirnov {
+    pub positive: usize,
+}
+
+impl Default for KolmogorovSmirnov {
+    fn default() -> Self {
+        Self { positive: 1 }
+    }
+}
+
+impl Metric for KolmogorovSmirnov {
+    fn name(&self) -> &str {
+        "ks"
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        ks_statistic(
+            &class_probabilities(probabilities, self.positive),
+            actual,
+            self.positive,
+        )
+    }
+}
+
+/// `class`'s column of `probabilities`; a class the model never saw gets probability 0 everywhere
+fn class_probabilities(probabilities: &Array2<f64>, class: usize) -> Array1<f64> {
+    if class < probabilities.ncols() {
+        probabilities.column(class).to_owned()
+    } else {
+        Array1::zeros(probabilities.nrows())
+    }
+}
+
+/// `CostMatrix::mean_cost`, predicting each row's cheapest label by `CostMatrix::decide`, where
+/// lower is better
+#[derive(Debug, Clone)]
+pub struct Cost {
+    pub costs: CostMatrix,
+}
+
+impl Metric for Cost {
+    fn name(&self) -> &str {
+        "cost"
+    }
+
+    fn higher_is_better(&self) -> bool {
+        false
+    }
+
+    fn compute(&self, probabilities: &Array2<f64>, actual: &Array1<usize>) -> f64 {
+        self.costs
+            .mean_cost(&self.costs.decide(probabilities), actual)
+    }
+}
+
+/// Each row's most likely class; ties go to the first, as in `FittedRandomForest::predict`
+pub fn predicted_labels(probabilities: &Array2<f64>) -> Array1<usize> {
+    probabilities
+        .rows()
+        .into_iter()
+        .map(|row| {
+            (1..row.len()).fold(
+                0,
+                |best, class| {
+                    if row[class] > row[best] { class } else { best }
+                },
+            )
+        })
+        .collect()
+}
+
+/// Labels as probabilities: 1 for each row's label and 0 for the other `n_classes - 1`
+///
+/// # Panics
+/// If a label isn't below `n_classes`
+pub fn one_hot(labels: &Array1<usize>, n_classes: usize) -> Array2<f64> {
+    let mut probabilities = Array2::zeros((labels.len(), n_classes));
+    for (row, &label) in labels.iter().enumerate() {
+        probabilities[[row, label]] = 1.0;
+    }
+
+    probabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_metrics_score_probabilities() {
+        let probabilities = arr2(&[[0.9, 0.1], [0.4, 0.6], [0.3, 0.7], [0.5, 0.5]]);
+        let actual = arr1(&[0, 1, 0, 1]);
+        assert_eq!(predicted_labels(&probabilities), arr1(&[0, 1, 1, 0]));
+
+        let cost = Cost {
+            costs: CostMatrix::binary(1.0, 5.0),
+        };
+        let metrics: [&dyn Metric; 7] = [
+            &Accuracy,
+            &F1::default(),
+            &MacroF1,
+            &LogLoss,
+            &RocAuc::default(),
+            &cost,
+            &KolmogorovSmirnov::default(),
+        ];
+        let scores: Vec<f64> = metrics
+            .iter()
+            .map(|metric| metric.compute(&probabilities, &actual))
+            .collect();
+        assert_eq!(scores[..3], [0.5, 0.5, 0.5]);
+        let expected_log_loss = -(0.9f64.ln() + 0.6f64.ln() + 0.3f64.ln() + 0.5f64.ln()) / 4.0;
+        assert!((scores[3] - expected_log_loss).abs() < 1e-12);
+        // Of the 4 (positive, negative) pairs, 2 rank the positive higher
+        assert_eq!(scores[4], 0.5);
+        // Every row but the first is over the threshold of 1/6, so the third is a false positive
+        assert_eq!(scores[5], 0.25);
+        // Half the negatives score below every positive
+        assert_eq!(scores[6], 0.5);
+
+        assert!(Accuracy.is_better(0.8, 0.7));
+        assert!(!Accuracy.is_better(0.7, 0.7));
+        assert!(LogLoss.is_better(0.3, 0.4));
+        assert!(cost.is_better(0.3, 0.4));
+
+        // Labels-only predictions score the same as their one-hot probabilities
+        let labels = arr1(&[0, 1, 1, 0]);
+        assert_eq!(Accuracy.compute(&one_hot(&labels, 2), &actual), 0.5);
+    }
+}
@@ -0,0 +1,118 @@
+//! Metrics for continuous predictions
+//!
+//! Each takes the predictions first and the actual values second, like `classification`. They
+//! also work on predicted probabilities against 0/1 labels: `mean_squared_error` is then the
+//! Brier score. Empty inputs give NaN.
+
+use ndarray::Array1;
+
+/// The mean of `|prediction - actual|`, in the target's units
+pub fn mean_absolute_error(predictions: &Array1<f64>, actual: &Array1<f64>) -> f64 {
+    mean(predictions.iter().zip(actual).map(|(p, a)| (p - a).abs()))
+}
+
+/// The mean of `(prediction - actual)²`
+pub fn mean_squared_error(predictions: &Array1<f64>, actual: &Array1<f64>) -> f64 {
+    mean(
+        predictions
+            .iter()
+            .zip(actual)
+            .map(|(p, a)| (p - a) * (p - a)),
+    )
+}
+
+/// The square root of `mean_squared_error`, back in the target's units
+pub fn root_mean_squared_error(predictions: &Array1<f64>, actual: &Array1<f64>) -> f64 {
+    mean_squared_error(predictions, actual).sqrt()
+}
+
+/// The mean of `|prediction - actual| / |actual|`, as a fraction (0.1 = 10%)
+///
+/// Actual values of 0 are divided by `f64::EPSILON` instead, as scikit-learn does, so a single 0
+/// makes the result huge; prefer `mean_absolute_error` when the target can be 0.
+pub fn mean_absolute_percentage_error(predictions: &Array1<f64>, actual: &Array1<f64>) -> f64 {
+    mean(
+        predictions
+            .iter()
+            .zip(actual)
+            .map(|(p, a)| (p - a).abs() / a.abs().max(f64::EPSILON)),
+    )
+}
+
+/// The coefficient of determination: the fraction of the actual values' variance the
+/// predictions explain
+///
+/// 1 is perfect, 0 is no better than always predicting the mean, and it's negative for worse.
+/// When the actual values are constant it's 1 for perfect predictions and 0 otherwise.
+pub fn r2_score(predictions: &Array1<f64>, actual: &Array1<f64>) -> f64 {
+    let Some(actual_mean) = actual.mean() else {
+        return f64::NAN;
+    };
+    let residual: f64 = predictions
+        .iter()
+        .zip(actual)
+        .map(|(p, a)| (a - p) * (a - p))
+        .sum();
+    let total: f64 = actual.iter().map(|a| (a - actual_mean).powi(2)).sum();
+
+    if total == 0.0 {
+        if residual == 0.0 { 1.0 } else { 0.0 }
+    } else {
+        1.0 - residual / total
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), v| (sum + v, count + 1));
+    sum / count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    fn data() -> (Array1<f64>, Array1<f64>) {
+        (arr1(&[2.5, 0.0, 2.0, 8.0]), arr1(&[3.0, -0.5, 2.0, 7.0]))
+    }
+
+    #[test]
+    fn test_errors() {
+        let (predictions, actual) = data();
+
+        assert_eq!(mean_absolute_error(&predictions, &actual), 0.5);
+        assert_eq!(mean_squared_error(&predictions, &actual), 0.375);
+        assert_eq!(
+            root_mean_squared_error(&predictions, &actual),
+            0.375_f64.sqrt()
+        );
+        // 0.5/3 + 0.5/0.5 + 0/2 + 1/7, averaged
+        assert!(
+            (mean_absolute_percentage_error(&predictions, &actual)
+                - (0.5 / 3.0 + 1.0 + 1.0 / 7.0) / 4.0)
+                .abs()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn test_r2_score() {
+        let (predictions, actual) = data();
+
+        // The same example as scikit-learn's documentation
+        assert!((r2_score(&predictions, &actual) - 0.948_608_137).abs() < 1e-9);
+        assert_eq!(r2_score(&actual, &actual), 1.0);
+
+        let constant = arr1(&[4.0, 4.0]);
+        assert_eq!(r2_score(&constant, &constant), 1.0);
+        assert_eq!(r2_score(&arr1(&[3.0, 5.0]), &constant), 0.0);
+    }
+
+    #[test]
+    fn test_empty_is_nan() {
+        let empty = Array1::<f64>::zeros(0);
+
+        assert!(mean_squared_error(&empty, &empty).is_nan());
+        assert!(r2_score(&empty, &empty).is_nan());
+    }
+}
@@ -0,0 +1,72 @@
+//! Predict heart attack risk from `heart_attack_prediction_dataset.csv`
+//!
+//! The same pipeline as the Titanic binary: select features, one-hot encode the categorical
+//! ones, hold out a validation split, fit a Random Forest and report how it did. Data loading,
+//! conversions and the model all come from the `titanic-ml` library, and the dataset itself from
+//! `heart-attack-data`.
+
+use heart_attack_data::{ENGINEERED_FEATURES, HeartAttackData, TARGET};
+use metrics::classification::{ConfusionMatrix, accuracy};
+use ndarray::Array1;
+use polars::prelude::col;
+use titanic_ml::{
+    conversions::{Split, dataframe_to_array2, series_to_array1, shuffled_train_test_split},
+    data::Data,
+    random_forest::RandomForestClassifier,
+    seed::Seed,
+};
+
+const SEED: u64 = 1;
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Heart Attack Data ===");
+    let data = HeartAttackData::try_new()?;
+
+    let x_df = data.get_feature_matrix(ENGINEERED_FEATURES.map(col))?;
+    let x = dataframe_to_array2(&x_df)?;
+    let y = series_to_array1(&data.get_col_as_series(TARGET)?)?;
+    println!("Features shape: {:?}", x.dim());
+
+    let seed = Seed::new(SEED);
+    let Split {
+        x_train,
+        x_val: x_validation,
+        y_train,
+        y_val: y_validation,
+        ..
+    } = shuffled_train_test_split(x, y, 0.8, seed.derive("split"));
+    println!(
+        "Training set: {} samples, validation set: {} samples",
+        x_train.nrows(),
+        x_validation.nrows()
+    );
+
+    println!("\n=== Training Random Forest Classifier ===");
+    let model = RandomForestClassifier::new()
+        .n_estimators(100)
+        .max_depth(8)
+        .random_state(seed.derive("forest"))
+        .fit(x_train, y_train)?;
+
+    println!("\n=== Validation Performance ===");
+    let predictions = model.predict(&x_validation);
+    println!(
+        "Accuracy: {:.2}%",
+        accuracy(&predictions, &y_validation) * 100.0
+    );
+
+    // The classes are imbalanced, so compare against always guessing "no risk"
+    let baseline = accuracy(&Array1::zeros(y_validation.len()), &y_validation);
+    println!("Baseline (always 0): {:.2}%", baseline * 100.0);
+
+    let confusion_matrix = ConfusionMatrix::new(&predictions, &y_validation);
+    println!("\n{confusion_matrix}");
+    println!(
+        "Precision: {:.3}, recall: {:.3}, F1: {:.3}",
+        confusion_matrix.precision(1),
+        confusion_matrix.recall(1),
+        confusion_matrix.f1(1)
+    );
+
+    Ok(())
+}
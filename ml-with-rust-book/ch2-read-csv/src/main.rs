@@ -1,60 +1,56 @@
-use std::fs::File;
-
-use csv::Reader;
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
-struct Record {
-    #[serde(rename = "Age")]
-    age: u32,
-    #[serde(rename = "Sex")]
-    gender: String,
-    #[serde(rename = "Heart Rate")]
-    heart_rate: u32,
-}
+use polars::prelude::*;
+use titanic_ml::csv_batches::CsvBatchReader;
+
+const DATA_PATH: &str = "../data/heart_attack_prediction_dataset.csv";
+const BATCH_SIZE: usize = 1_000;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Open the file for the first pass
-    let file = File::open("../data/heart_attack_prediction_dataset.csv")?;
-    let mut reader = Reader::from_reader(file);
-    read_headers_and_rows(&mut reader)?;
+    // Scanning only reads enough of the file to work out the schema; nothing else is loaded until
+    // a query is collected
+    let lazy_frame = LazyCsvReader::new(PlPath::from_str(DATA_PATH))
+        .with_has_header(true)
+        .finish()?;
+
+    read_headers(&lazy_frame)?;
+
+    // Read the file once, keeping just the columns we use, `BATCH_SIZE` rows at a time so memory
+    // use doesn't grow with the size of the file
+    let batches = CsvBatchReader::new(BATCH_SIZE)
+        .columns(["Age", "Sex", "Heart Rate"])
+        .read_path(DATA_PATH)?;
+
+    let mut first_batch = None;
+    for batch in batches {
+        let batch = batch?;
+        read_heart_rates(&batch)?;
+        first_batch.get_or_insert(batch);
+    }
 
-    // Open the file again for the second pass
-    let file = File::open("../data/heart_attack_prediction_dataset.csv")?;
-    let mut reader = Reader::from_reader(file);
-    read_as_records(&mut reader)?;
+    if let Some(first_batch) = first_batch {
+        read_as_records(&first_batch);
+    }
 
     Ok(())
 }
 
-fn read_headers_and_rows(reader: &mut Reader<File>) -> Result<(), Box<dyn std::error::Error>> {
-    let headers = reader.headers()?.clone();
+fn read_headers(lazy_frame: &LazyFrame) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = lazy_frame.clone().collect_schema()?;
+    let headers: Vec<&str> = schema.iter_names().map(|name| name.as_str()).collect();
     println!("Headers: {:?}", headers);
 
-    let header_col = headers
-        .iter()
-        .enumerate()
-        .find_map(|(i, h)| if h == "Heart Rate" { Some(i) } else { None })
-        .unwrap();
-
-    for result in reader.records() {
-        let record = result?;
-        let heart_rate = record[header_col].parse::<u32>()?;
-        println!("Heart Rate: {}", heart_rate);
-    }
-
     Ok(())
 }
 
-fn read_as_records(reader: &mut Reader<File>) -> Result<(), Box<dyn std::error::Error>> {
-    let mut records: Vec<Record> = Vec::new();
+fn read_heart_rates(df: &DataFrame) -> Result<(), Box<dyn std::error::Error>> {
+    let heart_rates = df.column("Heart Rate")?.cast(&DataType::UInt32)?;
 
-    for result in reader.deserialize() {
-        let record: Record = result?;
-        records.push(record);
+    for heart_rate in heart_rates.u32()?.into_iter().flatten() {
+        println!("Heart Rate: {}", heart_rate);
     }
 
-    dbg!(&records[..5]); // Print first 5 records for verification
-
     Ok(())
 }
+
+fn read_as_records(df: &DataFrame) {
+    println!("{}", df.head(Some(5))); // Print first 5 records for verification
+}
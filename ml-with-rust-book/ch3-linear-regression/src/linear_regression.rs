@@ -0,0 +1,222 @@
+//! Linear regression from scratch, two ways
+//!
+//! Both fit `y ≈ x · weights + intercept` by minimizing the mean squared error:
+//!
+//! - `LinearRegression::ols` solves the normal equations `(XᵀX)β = Xᵀy` exactly
+//! - `GradientDescent` walks downhill on the error a small step at a time, which is how models
+//!   too big for a closed-form solution get trained
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, Axis, concatenate, s};
+use titanic_ml::conversions::Standardizer;
+
+/// A fitted linear model
+#[derive(Debug, Clone)]
+pub struct LinearRegression {
+    weights: Array1<f64>,
+    intercept: f64,
+}
+
+impl LinearRegression {
+    /// Ordinary least squares via the normal equations
+    ///
+    /// Fails if `XᵀX` is singular, which happens when a feature is constant or a linear
+    /// combination of the others.
+    pub fn ols(x: &Array2<f64>, y: &Array1<f64>) -> Result<Self> {
+        check_shapes(x, y)?;
+
+        // A column of ones lets the intercept be solved for like any other weight
+        let x = concatenate![Axis(1), Array2::ones((x.nrows(), 1)), *x];
+        let beta = solve(x.t().dot(&x), x.t().dot(y))?;
+
+        Ok(Self {
+            weights: beta.slice(s![1..]).to_owned(),
+            intercept: beta[0],
+        })
+    }
+
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<f64> {
+        x.dot(&self.weights) + self.intercept
+    }
+
+    /// One weight per feature
+    pub fn weights(&self) -> &Array1<f64> {
+        &self.weights
+    }
+
+    pub fn intercept(&self) -> f64 {
+        self.intercept
+    }
+}
+
+/// Fits a `LinearRegression` by batch gradient descent
+///
+/// Features are standardized before fitting (so one learning rate suits all of them) and the
+/// weights are converted back afterwards, so they're directly comparable with `ols`'.
+pub struct GradientDescent {
+    learning_rate: f64,
+    n_iterations: usize,
+}
+
+impl GradientDescent {
+    pub fn new() -> Self {
+        Self {
+            learning_rate: 0.1,
+            n_iterations: 1000,
+        }
+    }
+
+    /// Set the step size (default: 0.1)
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Set the number of steps to take (default: 1000)
+    pub fn n_iterations(mut self, n: usize) -> Self {
+        self.n_iterations = n;
+        self
+    }
+
+    pub fn fit(&self, x: &Array2<f64>, y: &Array1<f64>) -> Result<LinearRegression> {
+        check_shapes(x, y)?;
+
+        let n = x.nrows() as f64;
+        let standardizer = Standardizer::fit(x)?;
+        let z = standardizer.transform(x);
+
+        let mut weights = Array1::<f64>::zeros(x.ncols());
+        let mut intercept = 0.0;
+
+        for _ in 0..self.n_iterations {
+            let error = z.dot(&weights) + intercept - y;
+
+            // Gradients of the mean squared error
+            let weight_gradient = z.t().dot(&error) * (2.0 / n);
+            let intercept_gradient = error.sum() * (2.0 / n);
+
+            weights = weights - weight_gradient * self.learning_rate;
+            intercept -= intercept_gradient * self.learning_rate;
+        }
+
+        // Undo the standardization: w·(x - mean)/std + b = (w/std)·x + (b - (w/std)·mean)
+        let weights = weights / standardizer.std();
+        let intercept = intercept - weights.dot(standardizer.mean());
+
+        Ok(LinearRegression { weights, intercept })
+    }
+}
+
+impl Default for GradientDescent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_shapes(x: &Array2<f64>, y: &Array1<f64>) -> Result<()> {
+    if x.nrows() == 0 {
+        bail!("Can't fit on zero samples");
+    }
+    if x.nrows() != y.len() {
+        bail!("Got {} samples but {} targets", x.nrows(), y.len());
+    }
+
+    Ok(())
+}
+
+/// Solve `a · x = b` by Gaussian elimination with partial pivoting
+fn solve(mut a: Array2<f64>, mut b: Array1<f64>) -> Result<Array1<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        // Swap in the row with the largest pivot, for numerical stability
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[[i, col]].abs().total_cmp(&a[[j, col]].abs()))
+            .expect("col < n");
+        if a[[pivot, col]].abs() < 1e-12 {
+            bail!("XᵀX is singular; is a feature constant or collinear with the others?");
+        }
+        if pivot != col {
+            for k in 0..n {
+                a.swap([col, k], [pivot, k]);
+            }
+            b.swap(col, pivot);
+        }
+
+        // Eliminate this column from the rows below
+        for row in col + 1..n {
+            let factor = a[[row, col]] / a[[col, col]];
+            for k in col..n {
+                a[[row, k]] -= factor * a[[col, k]];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    // Back substitution
+    let mut x = Array1::<f64>::zeros(n);
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[[row, k]] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[[row, row]];
+    }
+
+    Ok(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    /// y = 2·x0 - 3·x1 + 5, exactly
+    fn data() -> (Array2<f64>, Array1<f64>) {
+        let x = arr2(&[
+            [1.0, 2.0],
+            [2.0, 1.0],
+            [3.0, 5.0],
+            [4.0, 3.0],
+            [5.0, 8.0],
+            [6.0, 2.0],
+        ]);
+        let y = x.column(0).mapv(|v| 2.0 * v) - x.column(1).mapv(|v| 3.0 * v) + 5.0;
+
+        (x, y)
+    }
+
+    #[test]
+    fn test_ols() {
+        let (x, y) = data();
+        let model = LinearRegression::ols(&x, &y).unwrap();
+
+        assert!(
+            (model.weights() - &arr1(&[2.0, -3.0]))
+                .iter()
+                .all(|d| d.abs() < 1e-9)
+        );
+        assert!((model.intercept() - 5.0).abs() < 1e-9);
+        assert!((model.predict(&x) - &y).iter().all(|d| d.abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_ols_singular() {
+        let x = arr2(&[[1.0, 2.0], [2.0, 4.0], [3.0, 6.0]]);
+
+        assert!(LinearRegression::ols(&x, &arr1(&[1.0, 2.0, 3.0])).is_err());
+    }
+
+    #[test]
+    fn test_gradient_descent_matches_ols() {
+        let (x, y) = data();
+        let model = GradientDescent::new()
+            .n_iterations(5000)
+            .fit(&x, &y)
+            .unwrap();
+
+        assert!(
+            (model.weights() - &arr1(&[2.0, -3.0]))
+                .iter()
+                .all(|d| d.abs() < 1e-6)
+        );
+        assert!((model.intercept() - 5.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,96 @@
+//! Predict the fare a Titanic passenger paid from their class, age and family size
+//!
+//! Fits the same linear model by ordinary least squares and by gradient descent, and checks that
+//! they agree, then sees how much a Random Forest gains by not having to be linear.
+
+mod linear_regression;
+
+use metrics::regression::{mean_absolute_error, r2_score, root_mean_squared_error};
+use ndarray::{Array1, Array2};
+use polars::prelude::{LazyCsvReader, LazyFileListReader, PlPath, col};
+use titanic_ml::{
+    conversions::{Split, dataframe_to_array2, series_to_array1_f64, shuffled_train_test_split},
+    random_forest_regressor::RandomForestRegressor,
+    seed::Seed,
+};
+
+use crate::linear_regression::{GradientDescent, LinearRegression};
+
+const DATA_PATH: &str = "../../titanic/data/train.csv";
+const FEATURES: [&str; 4] = ["Pclass", "Age", "SibSp", "Parch"];
+const TARGET: &str = "Fare";
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Data ===");
+    // Rows with a missing age are dropped rather than filled, since 0 would be a real age
+    let df = LazyCsvReader::new(PlPath::from_str(DATA_PATH))
+        .with_has_header(true)
+        .finish()?
+        .select(
+            FEATURES
+                .into_iter()
+                .chain([TARGET])
+                .map(col)
+                .collect::<Vec<_>>(),
+        )
+        .drop_nulls(None)
+        .collect()?;
+
+    let x = dataframe_to_array2(&df.select(FEATURES)?)?;
+    let y = series_to_array1_f64(df.column(TARGET)?.as_materialized_series())?;
+    println!("Features shape: {:?}", x.dim());
+
+    let seed = Seed::new(1);
+    let Split {
+        x_train,
+        x_val: x_test,
+        y_train,
+        y_val: y_test,
+        ..
+    } = shuffled_train_test_split(x, y, 0.8, seed.derive("split"));
+
+    println!("\n=== Ordinary Least Squares ===");
+    let ols = LinearRegression::ols(&x_train, &y_train)?;
+    report(&ols, &x_test, &y_test);
+
+    println!("\n=== Gradient Descent ===");
+    let gd = GradientDescent::new()
+        .learning_rate(0.1)
+        .n_iterations(2000)
+        .fit(&x_train, &y_train)?;
+    report(&gd, &x_test, &y_test);
+
+    let max_difference = (ols.weights() - gd.weights())
+        .iter()
+        .fold(0.0_f64, |max, d| max.max(d.abs()));
+    println!("\nLargest weight difference between the two: {max_difference:.2e}");
+
+    println!("\n=== Random Forest ===");
+    let forest = RandomForestRegressor::new()
+        .n_estimators(100)
+        .max_depth(6)
+        .min_samples_split(5)
+        .random_state(seed.derive("forest"))
+        .fit(x_train, y_train)?;
+    print_scores(&forest.predict(&x_test), &y_test);
+
+    Ok(())
+}
+
+fn report(model: &LinearRegression, x_test: &Array2<f64>, y_test: &Array1<f64>) {
+    for (feature, weight) in FEATURES.iter().zip(model.weights()) {
+        println!("{feature:>8}: {weight:>8.3}");
+    }
+    println!("intercept: {:.3}", model.intercept());
+
+    print_scores(&model.predict(x_test), y_test);
+}
+
+fn print_scores(predictions: &Array1<f64>, y_test: &Array1<f64>) {
+    println!("Test MAE: {:.3}", mean_absolute_error(predictions, y_test));
+    println!(
+        "Test RMSE: {:.3}",
+        root_mean_squared_error(predictions, y_test)
+    );
+    println!("Test R²: {:.3}", r2_score(predictions, y_test));
+}
@@ -0,0 +1,4 @@
+//! Binary logistic regression, shared with the benchmark as a simpler model to compare the Random
+//! Forest against
+
+pub mod logistic_regression;
@@ -0,0 +1,272 @@
+//! Binary logistic regression from scratch
+//!
+//! The model is `p(y = 1 | x) = sigmoid(x · weights + intercept)`, fitted by gradient descent on
+//! the log loss plus an L2 penalty on the weights. Each epoch is one pass over the data, either
+//! as a single batch or as shuffled mini-batches.
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, Axis};
+use titanic_ml::{conversions::Standardizer, seed::Seed};
+
+/// Hyperparameters for fitting a `FittedLogisticRegression`
+pub struct LogisticRegression {
+    learning_rate: f64,
+    max_epochs: usize,
+    batch_size: Option<usize>,
+    l2: f64,
+    tolerance: f64,
+    seed: Seed,
+}
+
+impl LogisticRegression {
+    pub fn new() -> Self {
+        Self {
+            learning_rate: 0.1,
+            max_epochs: 1000,
+            batch_size: None,
+            l2: 0.0,
+            tolerance: 1e-6,
+            seed: Seed::new(1),
+        }
+    }
+
+    /// Set the step size (default: 0.1)
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Set the most passes over the data to make (default: 1000)
+    pub fn max_epochs(mut self, n: usize) -> Self {
+        self.max_epochs = n;
+        self
+    }
+
+    /// Update after every `n` samples instead of once per epoch (default: full batch)
+    pub fn batch_size(mut self, n: usize) -> Self {
+        self.batch_size = Some(n);
+        self
+    }
+
+    /// Set the strength of the L2 penalty on the weights (default: 0, i.e. none)
+    pub fn l2(mut self, l2: f64) -> Self {
+        self.l2 = l2;
+        self
+    }
+
+    /// Stop once an epoch improves the loss by less than this (default: 1e-6)
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set the seed used to shuffle mini-batches (default: 1)
+    pub fn seed(mut self, seed: impl Into<Seed>) -> Self {
+        self.seed = seed.into();
+        self
+    }
+
+    /// Fit on features `x` and 0/1 labels `y`
+    pub fn fit(&self, x: &Array2<f64>, y: &Array1<usize>) -> Result<FittedLogisticRegression> {
+        if x.nrows() == 0 {
+            bail!("Can't fit on zero samples");
+        }
+        if x.nrows() != y.len() {
+            bail!("Got {} samples but {} labels", x.nrows(), y.len());
+        }
+        if let Some(label) = y.iter().find(|&&label| label > 1) {
+            bail!("Logistic regression needs 0/1 labels, got {label}");
+        }
+        if self.batch_size == Some(0) {
+            bail!("batch_size must be at least 1");
+        }
+
+        // Standardize so one learning rate suits every feature
+        let standardizer = Standardizer::fit(x)?;
+        let z = standardizer.transform(x);
+        let y = y.mapv(|label| label as f64);
+
+        let mut model = FittedLogisticRegression {
+            weights: Array1::zeros(x.ncols()),
+            intercept: 0.0,
+            standardizer,
+            loss_history: Vec::new(),
+            converged_after: None,
+        };
+
+        let batch_size = self.batch_size.unwrap_or(z.nrows());
+        let mut previous_loss = model.loss(&z, &y, self.l2);
+
+        for epoch in 0..self.max_epochs {
+            let order = self
+                .seed
+                .derive(&format!("epoch {epoch}"))
+                .permutation(z.nrows());
+
+            for batch in order.to_vec().chunks(batch_size) {
+                let z_batch = z.select(Axis(0), batch);
+                let y_batch = y.select(Axis(0), batch);
+
+                // Gradient of the mean log loss, plus the L2 penalty's (the intercept isn't
+                // penalized)
+                let error = model.linear_proba(&z_batch) - &y_batch;
+                let weight_gradient =
+                    z_batch.t().dot(&error) / batch.len() as f64 + &model.weights * self.l2;
+                let intercept_gradient = error.mean().expect("batch isn't empty");
+
+                model.weights = &model.weights - &(weight_gradient * self.learning_rate);
+                model.intercept -= intercept_gradient * self.learning_rate;
+            }
+
+            let loss = model.loss(&z, &y, self.l2);
+            model.loss_history.push(loss);
+
+            if (previous_loss - loss).abs() < self.tolerance {
+                model.converged_after = Some(epoch + 1);
+                break;
+            }
+            previous_loss = loss;
+        }
+
+        Ok(model)
+    }
+}
+
+impl Default for LogisticRegression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fitted logistic regression model
+pub struct FittedLogisticRegression {
+    /// One weight per standardized feature
+    weights: Array1<f64>,
+    intercept: f64,
+    standardizer: Standardizer,
+    loss_history: Vec<f64>,
+    converged_after: Option<usize>,
+}
+
+impl FittedLogisticRegression {
+    /// The probability of class 1 for each sample
+    pub fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        self.linear_proba(&self.standardizer.transform(x))
+    }
+
+    /// 1 where `predict_proba` is at least 0.5, else 0
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<usize> {
+        self.predict_proba(x).mapv(|p| usize::from(p >= 0.5))
+    }
+
+    /// Weights for the standardized features, so their sizes are comparable
+    pub fn weights(&self) -> &Array1<f64> {
+        &self.weights
+    }
+
+    /// The training loss after each epoch
+    pub fn loss_history(&self) -> &[f64] {
+        &self.loss_history
+    }
+
+    /// The number of epochs it took to converge, or `None` if it hit `max_epochs` first
+    pub fn converged_after(&self) -> Option<usize> {
+        self.converged_after
+    }
+
+    fn linear_proba(&self, z: &Array2<f64>) -> Array1<f64> {
+        (z.dot(&self.weights) + self.intercept).mapv(sigmoid)
+    }
+
+    /// Mean log loss plus the L2 penalty, on standardized features
+    fn loss(&self, z: &Array2<f64>, y: &Array1<f64>, l2: f64) -> f64 {
+        // Keep probabilities away from exactly 0 and 1 so the logs stay finite
+        let p = self.linear_proba(z).mapv(|p| p.clamp(1e-15, 1.0 - 1e-15));
+        let log_loss = y
+            .iter()
+            .zip(&p)
+            .map(|(&y, &p)| -(y * p.ln() + (1.0 - y) * (1.0 - p).ln()))
+            .sum::<f64>()
+            / y.len() as f64;
+
+        log_loss + 0.5 * l2 * self.weights.dot(&self.weights)
+    }
+}
+
+fn sigmoid(t: f64) -> f64 {
+    1.0 / (1.0 + (-t).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    /// Class 1 exactly when x0 > x1
+    fn data() -> (Array2<f64>, Array1<usize>) {
+        let x = arr2(&[
+            [1.0, 5.0],
+            [2.0, 4.0],
+            [0.0, 3.0],
+            [3.5, 3.0],
+            [5.0, 1.0],
+            [4.0, 2.0],
+            [3.0, 0.0],
+            [2.0, 2.5],
+        ]);
+        let y = arr1(&[0, 0, 0, 1, 1, 1, 1, 0]);
+
+        (x, y)
+    }
+
+    #[test]
+    fn test_fit_full_batch() {
+        let (x, y) = data();
+        let model = LogisticRegression::new().fit(&x, &y).unwrap();
+
+        assert_eq!(model.predict(&x), y);
+        assert!(model.weights()[0] > 0.0 && model.weights()[1] < 0.0);
+
+        // Full-batch gradient descent with a small step never increases the loss
+        let losses = model.loss_history();
+        assert!(losses.windows(2).all(|w| w[1] <= w[0]));
+    }
+
+    #[test]
+    fn test_fit_mini_batch_is_reproducible() {
+        let (x, y) = data();
+        let fit = || {
+            LogisticRegression::new()
+                .batch_size(3)
+                .max_epochs(50)
+                .seed(7)
+                .fit(&x, &y)
+                .unwrap()
+        };
+
+        assert_eq!(fit().loss_history(), fit().loss_history());
+        assert_eq!(fit().predict(&x), y);
+    }
+
+    #[test]
+    fn test_l2_shrinks_weights() {
+        let (x, y) = data();
+        let plain = LogisticRegression::new().fit(&x, &y).unwrap();
+        let regularized = LogisticRegression::new().l2(1.0).fit(&x, &y).unwrap();
+
+        assert!(
+            regularized.weights().dot(regularized.weights()) < plain.weights().dot(plain.weights())
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_binary_labels() {
+        let (x, _) = data();
+
+        assert!(
+            LogisticRegression::new()
+                .fit(&x, &Array1::from_elem(8, 2))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Logistic regression on the Titanic features, as a baseline for the Random Forest
+//!
+//! Both models see the same features and the same validation split, so their accuracies are
+//! directly comparable.
+
+use ch4_logistic_regression::logistic_regression::{FittedLogisticRegression, LogisticRegression};
+use metrics::classification::accuracy;
+use polars::prelude::col;
+use titanic_ml::{
+    conversions::{Split, dataframe_to_array2, series_to_array1, shuffled_train_test_split},
+    data::Data,
+    random_forest::RandomForestClassifier,
+    seed::Seed,
+    training_data::TrainingData,
+};
+
+const DATA_PATH: &str = "../../titanic/data/train.csv";
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Titanic Data ===");
+    let training_data = TrainingData::try_from_path(DATA_PATH)?;
+
+    let x_df = training_data.get_feature_matrix([
+        col("Pclass"),
+        col("Sex"),
+        col("Age"),
+        col("Fare"),
+        col("SibSp"),
+        col("Parch"),
+    ])?;
+    let x = dataframe_to_array2(&x_df)?;
+    let y = series_to_array1(&training_data.get_col_as_series("Survived")?)?;
+
+    let seed = Seed::new(1);
+    let Split {
+        x_train,
+        x_val: x_validation,
+        y_train,
+        y_val: y_validation,
+        ..
+    } = shuffled_train_test_split(x, y, 0.8, seed.derive("split"));
+    println!(
+        "Training set: {} samples, validation set: {} samples",
+        x_train.nrows(),
+        x_validation.nrows()
+    );
+
+    println!("\n=== Logistic Regression (full batch) ===");
+    let full_batch = LogisticRegression::new()
+        .learning_rate(0.5)
+        .max_epochs(2000)
+        .tolerance(1e-6)
+        .l2(0.01)
+        .fit(&x_train, &y_train)?;
+    report_convergence(&full_batch);
+
+    println!("\n=== Logistic Regression (mini-batches of 32) ===");
+    let mini_batch = LogisticRegression::new()
+        .learning_rate(0.05)
+        .batch_size(32)
+        .l2(0.01)
+        .seed(seed.derive("mini-batches"))
+        .fit(&x_train, &y_train)?;
+    report_convergence(&mini_batch);
+
+    println!("\nStandardized weights (full batch):");
+    for (name, weight) in x_df.get_column_names().iter().zip(full_batch.weights()) {
+        println!("{name:>10}: {weight:>7.3}");
+    }
+
+    println!("\n=== Random Forest ===");
+    let forest = RandomForestClassifier::new()
+        .n_estimators(100)
+        .max_depth(5)
+        .random_state(seed.derive("forest"))
+        .fit(x_train, y_train)?;
+
+    println!("\n=== Validation Accuracy ===");
+    for (name, predictions) in [
+        (
+            "Logistic regression (full batch)",
+            full_batch.predict(&x_validation),
+        ),
+        (
+            "Logistic regression (mini-batch)",
+            mini_batch.predict(&x_validation),
+        ),
+        ("Random Forest", forest.predict(&x_validation)),
+    ] {
+        println!(
+            "{name:<33} {:.2}%",
+            accuracy(&predictions, &y_validation) * 100.0
+        );
+    }
+
+    Ok(())
+}
+
+fn report_convergence(model: &FittedLogisticRegression) {
+    let losses = model.loss_history();
+
+    // Print the loss curve at roughly ten evenly spaced epochs
+    let step = (losses.len() / 10).max(1);
+    for (epoch, loss) in losses.iter().enumerate().step_by(step) {
+        println!("epoch {:>4}: loss {loss:.5}", epoch + 1);
+    }
+
+    match model.converged_after() {
+        Some(epochs) => println!(
+            "Converged after {epochs} epochs, final loss {:.5}",
+            losses[epochs - 1]
+        ),
+        None => println!(
+            "Didn't converge in {} epochs, final loss {:.5}",
+            losses.len(),
+            losses.last().copied().unwrap_or(f64::NAN)
+        ),
+    }
+}
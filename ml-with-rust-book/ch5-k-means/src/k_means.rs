@@ -0,0 +1,248 @@
+//! k-means clustering from scratch
+//!
+//! Lloyd's algorithm: assign every sample to its nearest centroid, move each centroid to the mean
+//! of its samples, and repeat until the centroids stop moving. Starting centroids are picked with
+//! k-means++, which spreads them out and makes bad local minima much less likely than picking
+//! samples uniformly.
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, ArrayView1, Axis};
+use rand::Rng;
+use titanic_ml::seed::Seed;
+
+/// Hyperparameters for fitting a `FittedKMeans`
+pub struct KMeans {
+    n_clusters: usize,
+    max_iterations: usize,
+    tolerance: f64,
+    seed: Seed,
+}
+
+impl KMeans {
+    pub fn new(n_clusters: usize) -> Self {
+        Self {
+            n_clusters,
+            max_iterations: 300,
+            tolerance: 1e-4,
+            seed: Seed::new(1),
+        }
+    }
+
+    /// Set the most assign/update rounds to run (default: 300)
+    pub fn max_iterations(mut self, n: usize) -> Self {
+        self.max_iterations = n;
+        self
+    }
+
+    /// Stop once no centroid moves further than this (default: 1e-4)
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set the seed for picking the starting centroids (default: 1)
+    pub fn seed(mut self, seed: impl Into<Seed>) -> Self {
+        self.seed = seed.into();
+        self
+    }
+
+    pub fn fit(&self, x: &Array2<f64>) -> Result<FittedKMeans> {
+        if self.n_clusters == 0 {
+            bail!("n_clusters must be at least 1");
+        }
+        if x.nrows() < self.n_clusters {
+            bail!(
+                "Can't make {} clusters from {} samples",
+                self.n_clusters,
+                x.nrows()
+            );
+        }
+
+        let mut model = FittedKMeans {
+            centroids: self.k_means_plus_plus(x),
+            inertia_history: Vec::new(),
+        };
+
+        for _ in 0..self.max_iterations {
+            let (labels, inertia) = model.assign(x);
+            model.inertia_history.push(inertia);
+
+            // Move each centroid to the mean of its samples; a centroid that lost all of them
+            // stays put
+            let mut sums = Array2::<f64>::zeros(model.centroids.dim());
+            let mut counts = vec![0usize; self.n_clusters];
+            for (row, &label) in x.rows().into_iter().zip(&labels) {
+                let mut sum = sums.row_mut(label);
+                sum += &row;
+                counts[label] += 1;
+            }
+
+            let mut max_shift: f64 = 0.0;
+            for (cluster, &count) in counts.iter().enumerate() {
+                if count == 0 {
+                    continue;
+                }
+                let new_centroid = &sums.row(cluster) / count as f64;
+                max_shift = max_shift.max(
+                    squared_distance(model.centroids.row(cluster), new_centroid.view()).sqrt(),
+                );
+                model.centroids.row_mut(cluster).assign(&new_centroid);
+            }
+
+            if max_shift < self.tolerance {
+                break;
+            }
+        }
+
+        Ok(model)
+    }
+
+    /// Pick the first centroid uniformly, then each next one with probability proportional to
+    /// its squared distance from the nearest centroid so far
+    fn k_means_plus_plus(&self, x: &Array2<f64>) -> Array2<f64> {
+        let mut rng = self.seed.rng();
+        let mut centroids = Vec::with_capacity(self.n_clusters);
+        centroids.push(rng.gen_range(0..x.nrows()));
+
+        let mut distances: Array1<f64> = x
+            .rows()
+            .into_iter()
+            .map(|row| squared_distance(row, x.row(centroids[0])))
+            .collect();
+
+        while centroids.len() < self.n_clusters {
+            let total: f64 = distances.sum();
+            let next = if total > 0.0 {
+                let mut target = rng.gen_range(0.0..total);
+                distances
+                    .iter()
+                    .position(|&d| {
+                        target -= d;
+                        target < 0.0
+                    })
+                    .unwrap_or(x.nrows() - 1)
+            } else {
+                // Every sample sits on a centroid already, so any choice is as good as another
+                rng.gen_range(0..x.nrows())
+            };
+            centroids.push(next);
+
+            for (distance, row) in distances.iter_mut().zip(x.rows()) {
+                *distance = distance.min(squared_distance(row, x.row(next)));
+            }
+        }
+
+        x.select(Axis(0), &centroids)
+    }
+}
+
+/// A fitted k-means model
+pub struct FittedKMeans {
+    centroids: Array2<f64>,
+    inertia_history: Vec<f64>,
+}
+
+impl FittedKMeans {
+    /// The index of the nearest centroid to each sample
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<usize> {
+        self.assign(x).0
+    }
+
+    /// One row per cluster
+    pub fn centroids(&self) -> &Array2<f64> {
+        &self.centroids
+    }
+
+    /// The sum of squared distances from each training sample to its centroid, after each
+    /// assignment step; never increases
+    pub fn inertia_history(&self) -> &[f64] {
+        &self.inertia_history
+    }
+
+    /// The final inertia
+    pub fn inertia(&self) -> f64 {
+        self.inertia_history.last().copied().unwrap_or_default()
+    }
+
+    /// Labels for `x`, and their inertia
+    fn assign(&self, x: &Array2<f64>) -> (Array1<usize>, f64) {
+        let mut inertia = 0.0;
+        let labels = x
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let (label, distance) = self
+                    .centroids
+                    .rows()
+                    .into_iter()
+                    .map(|centroid| squared_distance(row, centroid))
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("at least one centroid");
+                inertia += distance;
+                label
+            })
+            .collect();
+
+        (labels, inertia)
+    }
+}
+
+fn squared_distance(a: ArrayView1<f64>, b: ArrayView1<f64>) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    /// Two tight, well-separated blobs
+    fn blobs() -> Array2<f64> {
+        arr2(&[
+            [0.0, 0.0],
+            [0.1, 0.2],
+            [0.2, 0.1],
+            [10.0, 10.0],
+            [10.1, 9.9],
+            [9.8, 10.2],
+        ])
+    }
+
+    #[test]
+    fn test_finds_blobs() {
+        let model = KMeans::new(2).fit(&blobs()).unwrap();
+        let labels = model.predict(&blobs());
+
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[1], labels[2]);
+        assert_eq!(labels[3], labels[4]);
+        assert_eq!(labels[4], labels[5]);
+        assert_ne!(labels[0], labels[3]);
+        assert!(model.inertia() < 0.5);
+    }
+
+    #[test]
+    fn test_inertia_never_increases() {
+        let model = KMeans::new(3).seed(42).fit(&blobs()).unwrap();
+
+        assert!(
+            model
+                .inertia_history()
+                .windows(2)
+                .all(|w| w[1] <= w[0] + 1e-12)
+        );
+    }
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let fit = || KMeans::new(3).seed(5).fit(&blobs()).unwrap();
+
+        assert_eq!(fit().centroids(), fit().centroids());
+    }
+
+    #[test]
+    fn test_too_few_samples() {
+        assert!(KMeans::new(7).fit(&blobs()).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Cluster Titanic passengers with k-means and see what the clusters have in common
+//!
+//! Survival isn't used for clustering, only to describe the clusters afterwards.
+
+mod k_means;
+
+use polars::prelude::*;
+use titanic_ml::{
+    conversions::{dataframe_to_array2, standardize},
+    data::Data,
+    seed::Seed,
+    training_data::TrainingData,
+};
+
+use crate::k_means::KMeans;
+
+const DATA_PATH: &str = "../../titanic/data/train.csv";
+const N_CLUSTERS: usize = 4;
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Titanic Data ===");
+    let training_data = TrainingData::try_from_path(DATA_PATH)?;
+
+    let x_df = training_data.get_feature_matrix([
+        col("Pclass"),
+        col("Sex"),
+        col("Age"),
+        col("Fare"),
+        col("SibSp"),
+        col("Parch"),
+    ])?;
+    // Distances would otherwise be dominated by Fare, which has by far the largest range
+    let x = standardize(&dataframe_to_array2(&x_df)?)?;
+    println!("Features shape: {:?}", x.dim());
+
+    // The same seed for every k, so the k = N_CLUSTERS line below matches the final fit
+    let seed = Seed::new(1).derive("k-means");
+
+    println!("\n=== Inertia by Number of Clusters ===");
+    for k in 1..=8 {
+        let model = KMeans::new(k).seed(seed).fit(&x)?;
+        println!("k = {k}: {:>9.2}", model.inertia());
+    }
+
+    println!("\n=== Fitting k = {N_CLUSTERS} ===");
+    let model = KMeans::new(N_CLUSTERS)
+        .max_iterations(100)
+        .tolerance(1e-4)
+        .seed(seed)
+        .fit(&x)?;
+    for (iteration, inertia) in model.inertia_history().iter().enumerate() {
+        println!("iteration {:>2}: inertia {inertia:.2}", iteration + 1);
+    }
+
+    println!("\n=== Centroids (in standard deviations from the mean) ===");
+    let names = x_df.get_column_names();
+    println!(
+        "cluster{}",
+        names
+            .iter()
+            .map(|n| format!(" {n:>10}"))
+            .collect::<String>()
+    );
+    for (cluster, centroid) in model.centroids().rows().into_iter().enumerate() {
+        println!(
+            "{cluster:>7}{}",
+            centroid
+                .iter()
+                .map(|v| format!(" {v:>10.2}"))
+                .collect::<String>()
+        );
+    }
+
+    let clusters: Vec<u32> = model.predict(&x).iter().map(|&c| c as u32).collect();
+
+    println!("\n=== Cluster Summary ===");
+    let summary = training_data
+        .lazy_frame_cloned()
+        .with_column(Series::new("cluster".into(), clusters).lit())
+        .group_by([col("cluster")])
+        .agg([
+            len().alias("passengers"),
+            col("Pclass").mean().alias("mean_class"),
+            col("Sex").eq(lit("female")).mean().alias("female_share"),
+            col("Age").mean().alias("mean_age"),
+            col("Fare").mean().alias("mean_fare"),
+            (col("SibSp") + col("Parch")).mean().alias("mean_family"),
+            col("Survived").mean().alias("survival_rate"),
+        ])
+        .sort(["cluster"], Default::default())
+        .collect()?;
+    println!("{summary}");
+
+    Ok(())
+}
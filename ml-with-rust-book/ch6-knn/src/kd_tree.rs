@@ -0,0 +1,221 @@
+//! A k-d tree for nearest-neighbor search
+//!
+//! Each node splits the points on one coordinate (cycling through the coordinates level by
+//! level) at the median, so the tree stays balanced. A query walks down to the target's side of
+//! each split first, and only crosses a split if a point on the other side could be closer than
+//! the worst neighbor found so far. In low dimensions that skips most of the tree; in high
+//! dimensions almost nothing can be skipped and brute force wins.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use ndarray::{Array2, ArrayView1};
+
+pub struct KdTree {
+    points: Array2<f64>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+struct Node {
+    /// Row of `points`
+    point: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl KdTree {
+    pub fn new(points: Array2<f64>) -> Self {
+        let mut tree = Self {
+            nodes: Vec::with_capacity(points.nrows()),
+            root: None,
+            points,
+        };
+        let mut indices: Vec<usize> = (0..tree.points.nrows()).collect();
+        tree.root = tree.build(&mut indices, 0);
+
+        tree
+    }
+
+    /// The `k` points nearest to `target` as `(row, squared distance)`, nearest first
+    ///
+    /// Ties in distance go to the lower row, so results match `brute_force_nearest` exactly.
+    pub fn nearest(&self, target: ArrayView1<f64>, k: usize) -> Vec<(usize, f64)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.search(self.root, target, k, &mut heap);
+        }
+
+        into_sorted(heap)
+    }
+
+    fn build(&mut self, indices: &mut [usize], depth: usize) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+
+        let axis = depth % self.points.ncols().max(1);
+        let mid = indices.len() / 2;
+        let points = &self.points;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            points[[a, axis]].total_cmp(&points[[b, axis]])
+        });
+
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            point: indices[mid],
+            axis,
+            left: None,
+            right: None,
+        });
+
+        let (left, rest) = indices.split_at_mut(mid);
+        self.nodes[node].left = self.build(left, depth + 1);
+        self.nodes[node].right = self.build(&mut rest[1..], depth + 1);
+
+        Some(node)
+    }
+
+    fn search(
+        &self,
+        node: Option<usize>,
+        target: ArrayView1<f64>,
+        k: usize,
+        heap: &mut BinaryHeap<Neighbor>,
+    ) {
+        let Some(node) = node.map(|n| &self.nodes[n]) else {
+            return;
+        };
+
+        let point = self.points.row(node.point);
+        push_neighbor(
+            heap,
+            k,
+            Neighbor {
+                distance: squared_distance(point, target),
+                index: node.point,
+            },
+        );
+
+        let diff = target[node.axis] - point[node.axis];
+        let (near, far) = if diff < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+
+        self.search(near, target, k, heap);
+
+        // Anything across the split is at least `diff` away along this axis; `<=` rather than
+        // `<` so equally distant points with a lower row aren't skipped
+        let worst = heap.peek().map_or(f64::INFINITY, |n| n.distance);
+        if heap.len() < k || diff * diff <= worst {
+            self.search(far, target, k, heap);
+        }
+    }
+}
+
+/// The `k` rows of `points` nearest to `target`, found by checking every one
+pub fn brute_force_nearest(
+    points: &Array2<f64>,
+    target: ArrayView1<f64>,
+    k: usize,
+) -> Vec<(usize, f64)> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    if k > 0 {
+        for (index, point) in points.rows().into_iter().enumerate() {
+            push_neighbor(
+                &mut heap,
+                k,
+                Neighbor {
+                    distance: squared_distance(point, target),
+                    index,
+                },
+            );
+        }
+    }
+
+    into_sorted(heap)
+}
+
+pub fn squared_distance(a: ArrayView1<f64>, b: ArrayView1<f64>) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Ordered by distance, then row, so the heap's max is the worst neighbor kept so far
+#[derive(PartialEq)]
+struct Neighbor {
+    distance: f64,
+    index: usize,
+}
+
+impl Eq for Neighbor {}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Keep the `k` best neighbors in `heap`
+fn push_neighbor(heap: &mut BinaryHeap<Neighbor>, k: usize, neighbor: Neighbor) {
+    if heap.len() < k {
+        heap.push(neighbor);
+    } else if heap.peek().is_some_and(|worst| neighbor < *worst) {
+        heap.pop();
+        heap.push(neighbor);
+    }
+}
+
+fn into_sorted(heap: BinaryHeap<Neighbor>) -> Vec<(usize, f64)> {
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|n| (n.index, n.distance))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array2, arr1};
+
+    /// A deterministic cloud of points in 3D, with some exact duplicates
+    fn points() -> Array2<f64> {
+        Array2::from_shape_fn((200, 3), |(i, j)| ((i * 7 + j * 13) % 17) as f64)
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let points = points();
+        let tree = KdTree::new(points.clone());
+
+        for target in [
+            arr1(&[0.0, 0.0, 0.0]),
+            arr1(&[8.5, 3.2, 16.0]),
+            arr1(&[5.0, 5.0, 5.0]),
+        ] {
+            for k in [1, 5, 20] {
+                assert_eq!(
+                    tree.nearest(target.view(), k),
+                    brute_force_nearest(&points, target.view(), k)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_k_larger_than_points() {
+        let tree = KdTree::new(Array2::zeros((3, 2)));
+
+        assert_eq!(tree.nearest(arr1(&[1.0, 1.0]).view(), 10).len(), 3);
+        assert!(tree.nearest(arr1(&[1.0, 1.0]).view(), 0).is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! A k-nearest-neighbors classifier
+//!
+//! "Fitting" just stores the training data (in a k-d tree, unless brute force is asked for); a
+//! prediction is the most common label among the `k` nearest training samples.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, ArrayView1};
+
+use crate::kd_tree::{KdTree, brute_force_nearest};
+
+/// How to find the nearest neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Compare against every training sample
+    BruteForce,
+    KdTree,
+}
+
+/// Hyperparameters for fitting a `FittedKNearestNeighbors`
+pub struct KNearestNeighbors {
+    k: usize,
+    algorithm: Algorithm,
+}
+
+impl KNearestNeighbors {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            algorithm: Algorithm::KdTree,
+        }
+    }
+
+    /// Set how neighbors are found (default: `Algorithm::KdTree`)
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn fit(&self, x: Array2<f64>, y: Array1<usize>) -> Result<FittedKNearestNeighbors> {
+        if self.k == 0 {
+            bail!("k must be at least 1");
+        }
+        if x.nrows() < self.k {
+            bail!("Need at least k = {} samples, got {}", self.k, x.nrows());
+        }
+        if x.nrows() != y.len() {
+            bail!("Got {} samples but {} labels", x.nrows(), y.len());
+        }
+
+        let index = match self.algorithm {
+            Algorithm::BruteForce => Index::BruteForce(x),
+            Algorithm::KdTree => Index::KdTree(KdTree::new(x)),
+        };
+
+        Ok(FittedKNearestNeighbors {
+            k: self.k,
+            index,
+            labels: y,
+        })
+    }
+}
+
+enum Index {
+    BruteForce(Array2<f64>),
+    KdTree(KdTree),
+}
+
+/// A KNN classifier holding its training data
+pub struct FittedKNearestNeighbors {
+    k: usize,
+    index: Index,
+    labels: Array1<usize>,
+}
+
+impl FittedKNearestNeighbors {
+    /// The majority label among each sample's `k` nearest neighbors; ties go to the smallest label
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<usize> {
+        x.rows()
+            .into_iter()
+            .map(|row| {
+                let mut votes = BTreeMap::new();
+                for (neighbor, _) in self.nearest(row) {
+                    *votes.entry(self.labels[neighbor]).or_insert(0) += 1;
+                }
+
+                // `max_by_key` returns the last maximum, so iterate from the largest label down
+                votes
+                    .into_iter()
+                    .rev()
+                    .max_by_key(|&(_, count)| count)
+                    .map(|(label, _)| label)
+                    .expect("k >= 1")
+            })
+            .collect()
+    }
+
+    fn nearest(&self, target: ArrayView1<f64>) -> Vec<(usize, f64)> {
+        match &self.index {
+            Index::BruteForce(points) => brute_force_nearest(points, target, self.k),
+            Index::KdTree(tree) => tree.nearest(target, self.k),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_predict() {
+        let x = arr2(&[
+            [0.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 0.0],
+            [5.0, 5.0],
+            [5.0, 6.0],
+            [6.0, 5.0],
+        ]);
+        let y = arr1(&[0, 0, 0, 1, 1, 1]);
+        let queries = arr2(&[[0.5, 0.5], [5.5, 5.5], [2.0, 2.0]]);
+
+        for algorithm in [Algorithm::BruteForce, Algorithm::KdTree] {
+            let model = KNearestNeighbors::new(3)
+                .algorithm(algorithm)
+                .fit(x.clone(), y.clone())
+                .unwrap();
+
+            assert_eq!(model.predict(&queries), arr1(&[0, 1, 0]));
+        }
+    }
+
+    #[test]
+    fn test_ties_go_to_smallest_label() {
+        let x = arr2(&[[0.0], [1.0]]);
+        let model = KNearestNeighbors::new(2).fit(x, arr1(&[1, 0])).unwrap();
+
+        assert_eq!(model.predict(&arr2(&[[0.5]])), arr1(&[0]));
+    }
+
+    #[test]
+    fn test_invalid_k() {
+        assert!(
+            KNearestNeighbors::new(0)
+                .fit(arr2(&[[0.0]]), arr1(&[0]))
+                .is_err()
+        );
+        assert!(
+            KNearestNeighbors::new(2)
+                .fit(arr2(&[[0.0]]), arr1(&[0]))
+                .is_err()
+        );
+    }
+}
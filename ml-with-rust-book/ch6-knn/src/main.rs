@@ -0,0 +1,98 @@
+//! Predict heart attack risk with k-nearest neighbors, and time a k-d tree against brute force
+//!
+//! Both searches find exactly the same neighbors, so the predictions match and only the time
+//! differs. The k-d tree pays off with a handful of features; with every feature it has to visit
+//! most of the tree anyway and ends up no faster than brute force.
+
+mod kd_tree;
+mod knn;
+
+use std::time::{Duration, Instant};
+
+use heart_attack_data::{FEATURES, HeartAttackData, TARGET};
+use metrics::classification::accuracy;
+use ndarray::{Array1, Array2};
+use polars::prelude::col;
+use titanic_ml::{
+    conversions::{
+        Split, dataframe_to_array2, series_to_array1, shuffled_train_test_split, standardize,
+    },
+    data::Data,
+    seed::Seed,
+};
+
+use crate::knn::{Algorithm, KNearestNeighbors};
+
+const K: usize = 15;
+
+/// A few continuous features, where a k-d tree can prune well
+const FEW_FEATURES: [&str; 4] = ["Age", "Cholesterol", "Heart Rate", "BMI"];
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Heart Attack Data ===");
+    let data = HeartAttackData::try_new()?;
+    let y = series_to_array1(&data.get_col_as_series(TARGET)?)?;
+
+    for features in [&FEW_FEATURES[..], &FEATURES[..]] {
+        println!("\n=== {} Features (k = {K}) ===", features.len());
+        let x_df =
+            data.get_feature_matrix(features.iter().copied().map(col).collect::<Vec<_>>())?;
+        // Distances would otherwise be dominated by Cholesterol, Income and Triglycerides
+        let x = standardize(&dataframe_to_array2(&x_df)?)?;
+
+        let Split {
+            x_train,
+            x_val: x_validation,
+            y_train,
+            y_val: y_validation,
+            ..
+        } = shuffled_train_test_split(x, y.clone(), 0.8, Seed::new(1).derive("split"));
+        println!(
+            "Training set: {} x {}, validation set: {} samples",
+            x_train.nrows(),
+            x_train.ncols(),
+            x_validation.nrows()
+        );
+
+        let (brute_force, brute_force_time) =
+            time(Algorithm::BruteForce, &x_train, &y_train, &x_validation)?;
+        let (kd_tree, kd_tree_time) = time(Algorithm::KdTree, &x_train, &y_train, &x_validation)?;
+        assert_eq!(
+            brute_force, kd_tree,
+            "both searches find the same neighbors"
+        );
+
+        println!("Brute force: {brute_force_time:>10.2?}");
+        println!("k-d tree:    {kd_tree_time:>10.2?}");
+        println!(
+            "Speedup:     {:>9.2}x",
+            brute_force_time.as_secs_f64() / kd_tree_time.as_secs_f64()
+        );
+        println!(
+            "Accuracy:    {:>9.2}%",
+            accuracy(&kd_tree, &y_validation) * 100.0
+        );
+    }
+
+    // The classes are imbalanced, so compare against always guessing "no risk"
+    let baseline = accuracy(&Array1::zeros(y.len()), &y);
+    println!("\nBaseline (always 0): {:.2}%", baseline * 100.0);
+
+    Ok(())
+}
+
+/// Fit and predict with `algorithm`, returning the predictions and how long it all took
+fn time(
+    algorithm: Algorithm,
+    x_train: &Array2<f64>,
+    y_train: &Array1<usize>,
+    x_validation: &Array2<f64>,
+) -> anyhow::Result<(Array1<usize>, Duration)> {
+    let start = Instant::now();
+    let model = KNearestNeighbors::new(K)
+        .algorithm(algorithm)
+        .fit(x_train.clone(), y_train.clone())?;
+    let predictions = model.predict(x_validation);
+
+    Ok((predictions, start.elapsed()))
+}
@@ -0,0 +1,89 @@
+//! Train a small neural network on the Titanic features
+//!
+//! The same features and validation split as the logistic regression chapter, so the accuracies
+//! can be compared directly. Two networks of the same shape are trained, one with ReLU hidden
+//! layers and one with sigmoid, to show how the choice changes the loss curve.
+
+mod mlp;
+
+use metrics::classification::accuracy;
+use polars::prelude::col;
+use titanic_ml::{
+    conversions::{Split, dataframe_to_array2, series_to_array1, shuffled_train_test_split},
+    data::Data,
+    seed::Seed,
+    training_data::TrainingData,
+};
+
+use crate::mlp::{Activation, FittedMultilayerPerceptron, MultilayerPerceptron};
+
+const DATA_PATH: &str = "../../titanic/data/train.csv";
+const HIDDEN_LAYERS: [usize; 2] = [16, 8];
+
+fn main() -> anyhow::Result<()> {
+    println!("=== Loading Titanic Data ===");
+    let training_data = TrainingData::try_from_path(DATA_PATH)?;
+
+    let x_df = training_data.get_feature_matrix([
+        col("Pclass"),
+        col("Sex"),
+        col("Age"),
+        col("Fare"),
+        col("SibSp"),
+        col("Parch"),
+    ])?;
+    let x = dataframe_to_array2(&x_df)?;
+    let y = series_to_array1(&training_data.get_col_as_series("Survived")?)?;
+
+    let seed = Seed::new(1);
+    let Split {
+        x_train,
+        x_val: x_validation,
+        y_train,
+        y_val: y_validation,
+        ..
+    } = shuffled_train_test_split(x, y, 0.8, seed.derive("split"));
+    println!(
+        "Training set: {} samples, validation set: {} samples",
+        x_train.nrows(),
+        x_validation.nrows()
+    );
+
+    for activation in [Activation::Relu, Activation::Sigmoid] {
+        println!("\n=== MLP {HIDDEN_LAYERS:?}, {activation:?} hidden layers ===");
+        let model = MultilayerPerceptron::new(HIDDEN_LAYERS)
+            .activation(activation)
+            .learning_rate(0.05)
+            .epochs(300)
+            .batch_size(32)
+            .seed(seed.derive("mlp"))
+            .fit(&x_train, &y_train)?;
+        print_loss_curve(&model);
+
+        println!(
+            "Training accuracy:   {:.2}%",
+            accuracy(&model.predict(&x_train), &y_train) * 100.0
+        );
+        println!(
+            "Validation accuracy: {:.2}%",
+            accuracy(&model.predict(&x_validation), &y_validation) * 100.0
+        );
+    }
+
+    Ok(())
+}
+
+fn print_loss_curve(model: &FittedMultilayerPerceptron) {
+    let losses = model.loss_history();
+
+    // Print the loss at roughly ten evenly spaced epochs, plus the last one
+    let step = (losses.len() / 10).max(1);
+    for (epoch, loss) in losses.iter().enumerate().step_by(step) {
+        println!("epoch {:>4}: loss {loss:.5}", epoch + 1);
+    }
+    if let Some(loss) = losses.last()
+        && !(losses.len() - 1).is_multiple_of(step)
+    {
+        println!("epoch {:>4}: loss {loss:.5}", losses.len());
+    }
+}
@@ -0,0 +1,371 @@
+//! A multilayer perceptron for binary classification, from scratch
+//!
+//! A stack of dense layers: each computes `activation(input · weights + bias)`. The hidden layers
+//! use ReLU or sigmoid, and the output layer is a single sigmoid unit giving `p(y = 1 | x)`.
+//! Training is mini-batch stochastic gradient descent on the log loss, with the gradients worked
+//! out layer by layer from the output back (backpropagation).
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2, Axis};
+use rand::Rng;
+use titanic_ml::{conversions::Standardizer, seed::Seed};
+
+/// The nonlinearity applied by the hidden layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    Relu,
+    Sigmoid,
+}
+
+impl Activation {
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Self::Relu => t.max(0.0),
+            Self::Sigmoid => sigmoid(t),
+        }
+    }
+
+    /// The derivative, in terms of the layer's output `a = apply(t)`
+    fn derivative(self, a: f64) -> f64 {
+        match self {
+            Self::Relu => f64::from(u8::from(a > 0.0)),
+            Self::Sigmoid => a * (1.0 - a),
+        }
+    }
+}
+
+/// Hyperparameters for fitting a `FittedMultilayerPerceptron`
+pub struct MultilayerPerceptron {
+    hidden_layers: Vec<usize>,
+    activation: Activation,
+    learning_rate: f64,
+    epochs: usize,
+    batch_size: usize,
+    seed: Seed,
+}
+
+impl MultilayerPerceptron {
+    /// A network with hidden layers of the given sizes, e.g. `[16, 8]`
+    pub fn new(hidden_layers: impl Into<Vec<usize>>) -> Self {
+        Self {
+            hidden_layers: hidden_layers.into(),
+            activation: Activation::Relu,
+            learning_rate: 0.05,
+            epochs: 200,
+            batch_size: 32,
+            seed: Seed::new(1),
+        }
+    }
+
+    /// Set the hidden layers' nonlinearity (default: `Activation::Relu`)
+    pub fn activation(mut self, activation: Activation) -> Self {
+        self.activation = activation;
+        self
+    }
+
+    /// Set the step size (default: 0.05)
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Set the number of passes over the data (default: 200)
+    pub fn epochs(mut self, n: usize) -> Self {
+        self.epochs = n;
+        self
+    }
+
+    /// Set the number of samples per update (default: 32)
+    pub fn batch_size(mut self, n: usize) -> Self {
+        self.batch_size = n;
+        self
+    }
+
+    /// Set the seed for the starting weights and the mini-batch order (default: 1)
+    pub fn seed(mut self, seed: impl Into<Seed>) -> Self {
+        self.seed = seed.into();
+        self
+    }
+
+    /// Fit on features `x` and 0/1 labels `y`
+    pub fn fit(&self, x: &Array2<f64>, y: &Array1<usize>) -> Result<FittedMultilayerPerceptron> {
+        if x.nrows() == 0 {
+            bail!("Can't fit on zero samples");
+        }
+        if x.nrows() != y.len() {
+            bail!("Got {} samples but {} labels", x.nrows(), y.len());
+        }
+        if let Some(label) = y.iter().find(|&&label| label > 1) {
+            bail!("The MLP needs 0/1 labels, got {label}");
+        }
+        if self.hidden_layers.contains(&0) {
+            bail!("Hidden layers need at least one unit each");
+        }
+        if self.batch_size == 0 {
+            bail!("batch_size must be at least 1");
+        }
+
+        // Standardize so one learning rate suits every feature
+        let standardizer = Standardizer::fit(x)?;
+        let z = standardizer.transform(x);
+        let y = y.mapv(|label| label as f64);
+
+        let mut model = FittedMultilayerPerceptron {
+            layers: self.initial_layers(x.ncols()),
+            standardizer,
+            loss_history: Vec::new(),
+        };
+
+        for epoch in 0..self.epochs {
+            let order = self
+                .seed
+                .derive(&format!("epoch {epoch}"))
+                .permutation(z.nrows());
+
+            for batch in order.to_vec().chunks(self.batch_size) {
+                let gradients =
+                    model.gradients(&z.select(Axis(0), batch), &y.select(Axis(0), batch));
+
+                for (layer, (weight_gradient, bias_gradient)) in
+                    model.layers.iter_mut().zip(gradients)
+                {
+                    layer
+                        .weights
+                        .scaled_add(-self.learning_rate, &weight_gradient);
+                    layer.bias.scaled_add(-self.learning_rate, &bias_gradient);
+                }
+            }
+
+            model.loss_history.push(model.loss(&z, &y));
+        }
+
+        Ok(model)
+    }
+
+    /// Small random weights and zero biases; the weight range depends on the layer's size
+    /// (He initialization for ReLU, Glorot for sigmoid) so activations neither vanish nor blow up
+    /// as they pass through the network
+    fn initial_layers(&self, n_features: usize) -> Vec<Dense> {
+        let mut rng = self.seed.derive("weights").rng();
+        let sizes: Vec<usize> = std::iter::once(n_features)
+            .chain(self.hidden_layers.iter().copied())
+            .chain(std::iter::once(1))
+            .collect();
+
+        sizes
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let (fan_in, fan_out) = (pair[0], pair[1]);
+                let activation = if i == sizes.len() - 2 {
+                    Activation::Sigmoid
+                } else {
+                    self.activation
+                };
+                let limit = match activation {
+                    Activation::Relu => (6.0 / fan_in as f64).sqrt(),
+                    Activation::Sigmoid => (6.0 / (fan_in + fan_out) as f64).sqrt(),
+                };
+
+                Dense {
+                    weights: Array2::from_shape_simple_fn((fan_in, fan_out), || {
+                        rng.gen_range(-limit..limit)
+                    }),
+                    bias: Array1::zeros(fan_out),
+                    activation,
+                }
+            })
+            .collect()
+    }
+}
+
+struct Dense {
+    /// One row per input, one column per unit
+    weights: Array2<f64>,
+    bias: Array1<f64>,
+    activation: Activation,
+}
+
+/// A fitted multilayer perceptron
+pub struct FittedMultilayerPerceptron {
+    layers: Vec<Dense>,
+    standardizer: Standardizer,
+    loss_history: Vec<f64>,
+}
+
+impl FittedMultilayerPerceptron {
+    /// The probability of class 1 for each sample
+    pub fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        self.output(&self.standardizer.transform(x))
+    }
+
+    /// 1 where `predict_proba` is at least 0.5, else 0
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<usize> {
+        self.predict_proba(x).mapv(|p| usize::from(p >= 0.5))
+    }
+
+    /// The training loss after each epoch
+    pub fn loss_history(&self) -> &[f64] {
+        &self.loss_history
+    }
+
+    /// Every layer's output, starting with the (standardized) input itself
+    fn forward(&self, z: &Array2<f64>) -> Vec<Array2<f64>> {
+        let mut outputs = vec![z.clone()];
+        for layer in &self.layers {
+            let input = outputs.last().expect("starts with the input");
+            let output =
+                (input.dot(&layer.weights) + &layer.bias).mapv(|t| layer.activation.apply(t));
+            outputs.push(output);
+        }
+
+        outputs
+    }
+
+    fn output(&self, z: &Array2<f64>) -> Array1<f64> {
+        self.forward(z)
+            .pop()
+            .expect("at least the output layer")
+            .column(0)
+            .to_owned()
+    }
+
+    /// The mean log loss's gradient with respect to each layer's weights and bias
+    fn gradients(&self, z: &Array2<f64>, y: &Array1<f64>) -> Vec<(Array2<f64>, Array1<f64>)> {
+        let outputs = self.forward(z);
+
+        // For a sigmoid output and log loss, the gradient with respect to the output layer's
+        // pre-activation simplifies to `p - y`
+        let p = outputs.last().expect("at least the output layer");
+        let mut delta = (p - &y.view().insert_axis(Axis(1))) / y.len() as f64;
+
+        let mut gradients = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate().rev() {
+            let input = &outputs[i];
+            gradients.push((input.t().dot(&delta), delta.sum_axis(Axis(0))));
+
+            if i > 0 {
+                let previous = &self.layers[i - 1];
+                delta = delta.dot(&layer.weights.t())
+                    * input.mapv(|a| previous.activation.derivative(a));
+            }
+        }
+        gradients.reverse();
+
+        gradients
+    }
+
+    /// Mean log loss on standardized features
+    fn loss(&self, z: &Array2<f64>, y: &Array1<f64>) -> f64 {
+        // Keep probabilities away from exactly 0 and 1 so the logs stay finite
+        let p = self.output(z).mapv(|p| p.clamp(1e-15, 1.0 - 1e-15));
+
+        y.iter()
+            .zip(&p)
+            .map(|(&y, &p)| -(y * p.ln() + (1.0 - y) * (1.0 - p).ln()))
+            .sum::<f64>()
+            / y.len() as f64
+    }
+}
+
+fn sigmoid(t: f64) -> f64 {
+    1.0 / (1.0 + (-t).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    /// XOR, which no linear model can fit
+    fn xor() -> (Array2<f64>, Array1<usize>) {
+        (
+            arr2(&[[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]]),
+            arr1(&[0, 1, 1, 0]),
+        )
+    }
+
+    #[test]
+    fn test_learns_xor() {
+        let (x, y) = xor();
+
+        for activation in [Activation::Relu, Activation::Sigmoid] {
+            let model = MultilayerPerceptron::new([8])
+                .activation(activation)
+                .learning_rate(0.5)
+                .epochs(2000)
+                .batch_size(4)
+                .fit(&x, &y)
+                .unwrap();
+
+            assert_eq!(model.predict(&x), y, "{activation:?}");
+            assert!(
+                model.loss_history().last().unwrap() < &0.1,
+                "{activation:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_gradients_match_finite_differences() {
+        let (x, y) = xor();
+        let y = y.mapv(|label| label as f64);
+        let mut model = MultilayerPerceptron::new([3, 2])
+            .activation(Activation::Sigmoid)
+            .epochs(0)
+            .fit(&x, &y.mapv(|label| label as usize))
+            .unwrap();
+        let z = model.standardizer.transform(&x);
+        let gradients = model.gradients(&z, &y);
+
+        let eps = 1e-6;
+        for (i, (weight_gradient, _)) in gradients.iter().enumerate() {
+            for ((row, col), &analytic) in weight_gradient.indexed_iter() {
+                model.layers[i].weights[[row, col]] += eps;
+                let up = model.loss(&z, &y);
+                model.layers[i].weights[[row, col]] -= 2.0 * eps;
+                let down = model.loss(&z, &y);
+                model.layers[i].weights[[row, col]] += eps;
+
+                let numeric = (up - down) / (2.0 * eps);
+                assert!(
+                    (numeric - analytic).abs() < 1e-6,
+                    "layer {i} [{row}, {col}]"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let (x, y) = xor();
+        let fit = || {
+            MultilayerPerceptron::new([4])
+                .epochs(20)
+                .batch_size(1)
+                .seed(9)
+                .fit(&x, &y)
+                .unwrap()
+        };
+
+        assert_eq!(fit().loss_history(), fit().loss_history());
+    }
+
+    #[test]
+    fn test_rejects_bad_hyperparameters() {
+        let (x, y) = xor();
+
+        assert!(MultilayerPerceptron::new([0]).fit(&x, &y).is_err());
+        assert!(
+            MultilayerPerceptron::new([2])
+                .batch_size(0)
+                .fit(&x, &y)
+                .is_err()
+        );
+        assert!(
+            MultilayerPerceptron::new([2])
+                .fit(&x, &Array1::from_elem(4, 2))
+                .is_err()
+        );
+    }
+}
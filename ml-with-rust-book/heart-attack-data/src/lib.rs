@@ -0,0 +1,99 @@
+//! Loading `heart_attack_prediction_dataset.csv` for the book's chapters
+//!
+//! Shared by every chapter that uses the heart-attack data, so they all see the same columns.
+//! The dataset implements `titanic_ml::data::Data`, so features come out through
+//! `get_feature_matrix` just like the Titanic data's, including the columns derived in
+//! `preprocessing`.
+
+use polars::prelude::LazyFrame;
+use titanic_ml::data::{CsvScanOptions, Data};
+
+pub mod preprocessing;
+
+/// Where the dataset lives, relative to a chapter's crate directory
+pub const DATA_PATH: &str = "../data/heart_attack_prediction_dataset.csv";
+
+/// The 0/1 label column
+pub const TARGET: &str = "Heart Attack Risk";
+
+/// Everything but the id, the target and the location columns, which are too high-cardinality
+/// to one-hot encode usefully
+pub const FEATURES: [&str; 22] = [
+    "Age",
+    "Sex",
+    "Cholesterol",
+    "Systolic",
+    "Diastolic",
+    "Heart Rate",
+    "Diabetes",
+    "Family History",
+    "Smoking",
+    "Obesity",
+    "Alcohol Consumption",
+    "Exercise Hours Per Week",
+    "Diet",
+    "Previous Heart Problems",
+    "Medication Use",
+    "Stress Level",
+    "Sedentary Hours Per Day",
+    "Income",
+    "BMI",
+    "Triglycerides",
+    "Physical Activity Days Per Week",
+    "Sleep Hours Per Day",
+];
+
+/// `FEATURES` with the engineered columns from `preprocessing`: "Diet Score" in place of "Diet",
+/// and "Cholesterol Band" alongside "Cholesterol"
+pub const ENGINEERED_FEATURES: [&str; 23] = [
+    "Age",
+    "Sex",
+    "Cholesterol",
+    "Cholesterol Band",
+    "Systolic",
+    "Diastolic",
+    "Heart Rate",
+    "Diabetes",
+    "Family History",
+    "Smoking",
+    "Obesity",
+    "Alcohol Consumption",
+    "Exercise Hours Per Week",
+    "Diet Score",
+    "Previous Heart Problems",
+    "Medication Use",
+    "Stress Level",
+    "Sedentary Hours Per Day",
+    "Income",
+    "BMI",
+    "Triglycerides",
+    "Physical Activity Days Per Week",
+    "Sleep Hours Per Day",
+];
+
+pub struct HeartAttackData {
+    lazy_frame: LazyFrame,
+}
+
+impl HeartAttackData {
+    pub fn try_new() -> anyhow::Result<Self> {
+        Self::try_from_path(DATA_PATH)
+    }
+
+    pub fn try_from_path(path: &str) -> anyhow::Result<Self> {
+        Self::try_from_path_with(path, &CsvScanOptions::new())
+    }
+
+    /// `try_from_path`, scanning the file with `options`
+    pub fn try_from_path_with(path: &str, options: &CsvScanOptions) -> anyhow::Result<Self> {
+        Ok(Self {
+            lazy_frame: preprocessing::preprocess(options.scan(path)?),
+        })
+    }
+}
+
+impl Data for HeartAttackData {
+    fn lazy_frame_cloned(&self) -> LazyFrame {
+        self.lazy_frame.clone()
+    }
+}
@@ -0,0 +1,124 @@
+//! Feature engineering for the heart-attack dataset
+//!
+//! Each function returns the Polars expressions for one set of derived columns, so they compose
+//! with `LazyFrame::with_columns` and the new columns come out of `get_feature_matrix` like any
+//! other. Numeric columns are used as-is there and string columns are one-hot encoded, so each
+//! derived column's type decides how the models see it.
+
+use polars::prelude::*;
+
+/// "Blood Pressure" is stored as "systolic/diastolic"; split it into "Systolic" and "Diastolic"
+pub fn blood_pressure() -> [Expr; 2] {
+    let part = |idx: i64, name: &str| {
+        col("Blood Pressure")
+            .str()
+            .split(lit("/"))
+            .list()
+            .get(lit(idx), false)
+            .cast(DataType::Int64)
+            .alias(name)
+    };
+
+    [part(0, "Systolic"), part(1, "Diastolic")]
+}
+
+/// "Diet Score": "Diet" as an ordinal, 0 = Healthy, 1 = Average, 2 = Unhealthy
+///
+/// The diets have a natural order, so one number keeps it where one-hot encoding would lose it.
+/// Anything else becomes null.
+pub fn diet_score() -> Expr {
+    let diet = || col("Diet").str().to_lowercase();
+
+    when(diet().eq(lit("healthy")))
+        .then(lit(0i64))
+        .when(diet().eq(lit("average")))
+        .then(lit(1i64))
+        .when(diet().eq(lit("unhealthy")))
+        .then(lit(2i64))
+        .otherwise(lit(NULL).cast(DataType::Int64))
+        .alias("Diet Score")
+}
+
+/// "Cholesterol Band": total cholesterol (mg/dL) in the usual clinical bands
+///
+/// "Desirable" below 200, "Borderline" from 200 to 239 and "High" from 240. A string, so
+/// `get_feature_matrix` one-hot encodes it and the models can treat each band separately.
+pub fn cholesterol_band() -> Expr {
+    let cholesterol = || col("Cholesterol");
+
+    when(cholesterol().is_null())
+        .then(lit(NULL).cast(DataType::String))
+        .when(cholesterol().lt(lit(200)))
+        .then(lit("Desirable"))
+        .when(cholesterol().lt(lit(240)))
+        .then(lit("Borderline"))
+        .otherwise(lit("High"))
+        .alias("Cholesterol Band")
+}
+
+/// Every derived column
+pub fn engineered_columns() -> Vec<Expr> {
+    let mut exprs = blood_pressure().to_vec();
+    exprs.push(diet_score());
+    exprs.push(cholesterol_band());
+
+    exprs
+}
+
+/// Add every derived column to `lazy_frame`
+pub fn preprocess(lazy_frame: LazyFrame) -> LazyFrame {
+    lazy_frame.with_columns(engineered_columns())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw() -> DataFrame {
+        df!(
+            "Blood Pressure" => ["120/80", "158/88", "95/76"],
+            "Diet" => [Some("Healthy"), Some("Unhealthy"), None],
+            "Cholesterol" => [Some(199i64), Some(240), None],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_preprocess() {
+        let df = preprocess(raw().lazy()).collect().unwrap();
+
+        let column = |name: &str| df.column(name).unwrap().as_materialized_series().clone();
+        assert!(column("Systolic").equals(&Series::new("Systolic".into(), [120i64, 158, 95])));
+        assert!(column("Diastolic").equals(&Series::new("Diastolic".into(), [80i64, 88, 76])));
+        assert!(column("Diet Score").equals_missing(&Series::new(
+            "Diet Score".into(),
+            [Some(0i64), Some(2), None]
+        )));
+        assert!(column("Cholesterol Band").equals_missing(&Series::new(
+            "Cholesterol Band".into(),
+            [Some("Desirable"), Some("High"), None]
+        )));
+    }
+
+    #[test]
+    fn test_cholesterol_band_boundaries() {
+        let df = df!("Cholesterol" => [0i64, 200, 239, 240, 400])
+            .unwrap()
+            .lazy()
+            .select([cholesterol_band()])
+            .collect()
+            .unwrap();
+
+        let bands: Vec<_> = df
+            .column("Cholesterol Band")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(
+            bands,
+            ["Desirable", "Borderline", "Borderline", "High", "High"]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC service needs `protoc` on the PATH (or in `PROTOC`)
+    #[cfg(feature = "grpc")]
+    tonic_prost_build::compile_protos("proto/predict.proto")?;
+
+    Ok(())
+}
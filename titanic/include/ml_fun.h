@@ -0,0 +1,43 @@
+/*
+ * C API for the titanic-ml model (build with `cargo build --release --features ffi`, then link
+ * against the `titanic_ml` shared library).
+ */
+#ifndef ML_FUN_H
+#define ML_FUN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ML_FUN_OK 0
+#define ML_FUN_ERR_NULL_POINTER (-1)
+#define ML_FUN_ERR_FEATURE_COUNT (-2)
+#define ML_FUN_ERR_TOO_LARGE (-3)
+
+/* An opaque, loaded model. */
+typedef struct MlFunModel MlFunModel;
+
+/* Load a model saved by `titanic-ml train`. Returns NULL on failure. */
+MlFunModel *ml_fun_load_model(const char *path);
+
+/*
+ * Predict class labels for `n_rows` samples.
+ *
+ * `features` is the row-major, already encoded feature matrix (`n_rows * n_features` doubles, in
+ * the column order used during training). One label per row is written to `out_predictions`.
+ * Returns ML_FUN_OK or one of the ML_FUN_ERR_* codes.
+ */
+int32_t ml_fun_predict(const MlFunModel *model, const double *features, size_t n_rows,
+                       size_t n_features, size_t *out_predictions);
+
+/* Release a model. NULL is ignored. */
+void ml_fun_free(MlFunModel *model);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ML_FUN_H */
@@ -0,0 +1,314 @@
+//! Blending several runs' submissions into one
+//!
+//! The usual last step on Kaggle: combine the predictions of a few different models, seeds or
+//! feature sets, which tends to beat any one of them. `Predictions::read` loads a submission file
+//! written by `SubmissionWriter` (CSV, Parquet or JSON), taking each passenger's `Probability` if
+//! it was written `--with-probability` and their 0/1 label otherwise. `blend` lines the runs up
+//! by `PassengerId` and combines them with weights, either averaging the probabilities or
+//! averaging each passenger's rank within each run.
+//!
+//! Ranks only depend on the order a run puts the passengers in, so a run whose probabilities are
+//! all squashed towards 0.5 counts as much as a confident one. A blended rank isn't a
+//! probability, though, so `BlendMethod::Rank` labels as many passengers survived as averaging
+//! the probabilities would: the highest ranked ones.
+
+use std::{collections::HashMap, fmt, fs::File, path::Path, str::FromStr};
+
+use anyhow::{Result, bail};
+use ndarray::Array1;
+use polars::prelude::*;
+use thiserror::Error;
+
+use crate::submission::{OutputFormat, SubmissionWriter};
+
+/// How `blend` combines each passenger's predictions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMethod {
+    /// The weighted mean of the probabilities
+    #[default]
+    Mean,
+    /// The weighted mean of each run's ranks, scaled to 0 (lowest) to 1 (highest)
+    Rank,
+}
+
+impl BlendMethod {
+    pub const ALL: [Self; 2] = [Self::Mean, Self::Rank];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Rank => "rank",
+        }
+    }
+}
+
+impl fmt::Display for BlendMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A method name that isn't one of `BlendMethod::ALL`
+#[derive(Debug, Error)]
+#[error("No blend method named {0:?} (try mean or rank)")]
+pub struct UnknownBlendMethod(String);
+
+impl FromStr for BlendMethod {
+    type Err = UnknownBlendMethod;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|method| method.name() == s)
+            .ok_or_else(|| UnknownBlendMethod(s.to_string()))
+    }
+}
+
+/// One run's predicted probability of survival for each passenger
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predictions {
+    pub ids: Vec<i64>,
+    pub probabilities: Array1<f64>,
+}
+
+impl Predictions {
+    /// Read a submission file, in the format its extension names (CSV if it names none)
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let df = match OutputFormat::from_path(path) {
+            Some(OutputFormat::Parquet) => ParquetReader::new(File::open(path)?).finish()?,
+            Some(OutputFormat::Json) => JsonReader::new(File::open(path)?).finish()?,
+            Some(OutputFormat::Csv) | None => {
+                LazyCsvReader::new(PlPath::new(&path.to_string_lossy()))
+                    .with_has_header(true)
+                    .finish()?
+                    .collect()?
+            }
+        };
+
+        Self::from_dataframe(&df)
+    }
+
+    /// The ids and probabilities of a submission DataFrame in Kaggle's Titanic format, using its
+    /// labels as probabilities of 0 or 1 if it has no `Probability` column
+    pub fn from_dataframe(df: &DataFrame) -> Result<Self> {
+        let writer = SubmissionWriter::new();
+        let score_column = [
+            writer.probability_column_name(),
+            writer.prediction_column_name(),
+        ]
+        .into_iter()
+        .find(|name| df.column(name).is_ok())
+        .unwrap_or(writer.probability_column_name());
+
+        let ids = df.column(writer.id_column_name())?.cast(&DataType::Int64)?;
+        let probabilities = df.column(score_column)?.cast(&DataType::Float64)?;
+        if ids.null_count() > 0 || probabilities.null_count() > 0 {
+            bail!("The submission has rows without an id or a {score_column}");
+        }
+
+        Ok(Self {
+            ids: ids.i64()?.into_no_null_iter().collect(),
+            probabilities: probabilities.f64()?.into_no_null_iter().collect(),
+        })
+    }
+}
+
+/// Several runs' predictions, combined
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blended {
+    /// In the order of the first run's
+    pub ids: Vec<i64>,
+    /// Blended probabilities for `BlendMethod::Mean`, or blended ranks for `BlendMethod::Rank`
+    pub scores: Array1<f64>,
+    pub labels: Array1<usize>,
+}
+
+impl Blended {
+    /// A submission DataFrame, with the blended scores as its `Probability` column
+    pub fn to_dataframe(&self, writer: &SubmissionWriter) -> Result<DataFrame> {
+        let ids = Series::new(writer.id_column_name().into(), &self.ids);
+        writer.to_dataframe_with_probability(&ids, &self.labels, &self.scores)
+    }
+}
+
+/// Combine `runs` with `weights` (one per run, e.g. each run's validation accuracy, or all 1s)
+///
+/// Every run must have a prediction for the same passengers, in any order. The weights must be at
+/// least 0, and not all 0.
+pub fn blend(runs: &[Predictions], weights: &[f64], method: BlendMethod) -> Result<Blended> {
+    let Some(first) = runs.first() else {
+        bail!("There are no predictions to blend");
+    };
+    if weights.len() != runs.len() {
+        bail!("Got {} weights for {} runs", weights.len(), runs.len());
+    }
+    if let Some(weight) = weights.iter().find(|w| !(w.is_finite() && **w >= 0.0)) {
+        bail!("Weights must be finite and at least 0, got {weight}");
+    }
+    let total_weight: f64 = weights.iter().sum();
+    if total_weight <= 0.0 {
+        bail!("At least one weight must be above 0");
+    }
+
+    // Each run's probabilities, in the first run's order of passengers
+    let rows: HashMap<i64, usize> = first
+        .ids
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| (id, i))
+        .collect();
+    let aligned = runs
+        .iter()
+        .enumerate()
+        .map(|(run, predictions)| {
+            if predictions.ids.len() != first.ids.len() {
+                bail!(
+                    "Run {} has {} predictions but run 1 has {}",
+                    run + 1,
+                    predictions.ids.len(),
+                    first.ids.len()
+                );
+            }
+            let mut aligned = Array1::from_elem(first.ids.len(), f64::NAN);
+            for (id, &probability) in predictions.ids.iter().zip(&predictions.probabilities) {
+                match rows.get(id) {
+                    Some(&row) => aligned[row] = probability,
+                    None => bail!(
+                        "Run {} has a prediction for {id}, which run 1 doesn't",
+                        run + 1
+                    ),
+                }
+            }
+
+            Ok(aligned)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let weighted_mean = |values: &[Array1<f64>]| {
+        values.iter().zip(weights).fold(
+            Array1::<f64>::zeros(first.ids.len()),
+            |sum, (values, &weight)| sum + values * weight,
+        ) / total_weight
+    };
+    let mean = weighted_mean(&aligned);
+    let (scores, labels) = match method {
+        BlendMethod::Mean => {
+            let labels = mean.mapv(|p| usize::from(p >= 0.5));
+            (mean, labels)
+        }
+        BlendMethod::Rank => {
+            let ranks: Vec<Array1<f64>> = aligned.iter().map(scaled_ranks).collect();
+            let scores = weighted_mean(&ranks);
+            let n_survived = mean.iter().filter(|&&p| p >= 0.5).count();
+            let mut order: Vec<usize> = (0..scores.len()).collect();
+            order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+            let mut labels = Array1::zeros(scores.len());
+            for &row in &order[..n_survived] {
+                labels[row] = 1;
+            }
+            (scores, labels)
+        }
+    };
+
+    Ok(Blended {
+        ids: first.ids.clone(),
+        scores,
+        labels,
+    })
+}
+
+/// Each value's rank among `values`, from 0 for the lowest to 1 for the highest, with tied values
+/// sharing the mean of their ranks
+fn scaled_ranks(values: &Array1<f64>) -> Array1<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = Array1::from_elem(values.len(), 0.5);
+    let top = (values.len() - 1).max(1) as f64;
+    let mut start = 0;
+    while start < order.len() {
+        let tied = order[start..]
+            .iter()
+            .take_while(|&&i| values[i] == values[order[start]])
+            .count();
+        let rank = (start + start + tied - 1) as f64 / 2.0;
+        for &i in &order[start..start + tied] {
+            ranks[i] = rank / top;
+        }
+        start += tied;
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    #[test]
+    fn test_blend_runs() {
+        let ids = vec![892, 893, 894, 895];
+        let forest = Predictions {
+            ids: ids.clone(),
+            probabilities: arr1(&[0.9, 0.6, 0.3, 0.1]),
+        };
+        // Another run, in another order, as a plain submission of labels
+        let labels = DataFrame::new(vec![
+            Column::new("PassengerId".into(), [895i64, 894, 893, 892]),
+            Column::new("Survived".into(), [0i64, 1, 0, 1]),
+        ])
+        .unwrap();
+        let labels = Predictions::from_dataframe(&labels).unwrap();
+        assert_eq!(labels.probabilities, arr1(&[0.0, 1.0, 0.0, 1.0]));
+
+        let mean = blend(
+            &[forest.clone(), labels.clone()],
+            &[3.0, 1.0],
+            BlendMethod::Mean,
+        )
+        .unwrap();
+        assert_eq!(mean.ids, ids);
+        let close =
+            |a: &Array1<f64>, b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-12);
+        assert!(
+            close(&mean.scores, &[0.925, 0.45, 0.475, 0.075]),
+            "{}",
+            mean.scores
+        );
+        assert_eq!(mean.labels, arr1(&[1, 0, 0, 0]));
+
+        // The labels' tie for first lifts 894 above 893, and the mean blend has two survivors
+        let rank = blend(
+            &[forest.clone(), labels.clone()],
+            &[1.0, 1.0],
+            BlendMethod::Rank,
+        )
+        .unwrap();
+        let twelfths = [11.0, 5.0, 7.0, 1.0].map(|n| n / 12.0);
+        assert!(close(&rank.scores, &twelfths), "{}", rank.scores);
+        assert_eq!(rank.labels, arr1(&[1, 0, 1, 0]));
+        let df = rank.to_dataframe(&SubmissionWriter::new()).unwrap();
+        assert_eq!(
+            df.get_column_names(),
+            ["PassengerId", "Survived", "Probability"]
+        );
+
+        let missing = Predictions {
+            ids: vec![892, 893, 894, 999],
+            probabilities: arr1(&[0.5; 4]),
+        };
+        assert!(blend(&[forest.clone(), missing], &[1.0, 1.0], BlendMethod::Mean).is_err());
+        assert!(
+            blend(
+                std::slice::from_ref(&forest),
+                &[1.0, 1.0],
+                BlendMethod::Mean
+            )
+            .is_err()
+        );
+        assert!(blend(&[forest], &[0.0], BlendMethod::Mean).is_err());
+        assert!("median".parse::<BlendMethod>().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Saving a forest partway through fitting, so a long run can pick up where it stopped
+//!
+//! `RandomForestClassifier::fit_with_checkpoints` grows trees one at a time and, every so many
+//! trees, writes the trees so far to a checkpoint file along with the state of its random number
+//! generator. Run it again with the same checkpoint file and it carries on from there, drawing
+//! exactly the trees an uninterrupted run would have.
+
+use std::{ffi::OsString, fs, num::NonZeroUsize, path::Path};
+
+use linfa::{Float, Label};
+use ndarray::{ArrayView2, CowArray, Ix1, Ix2};
+use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    error::FitError,
+    missing::MissingFeatures,
+    model_file::{self, ModelFileError},
+    random_forest::{FittedRandomForest, RandomForestClassifier},
+    seed::Seed,
+};
+
+/// The trees grown so far, and what's needed to grow the rest
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<L: Label, F: Float> {
+    /// The tree settings, seed and training data shape the trees were grown for
+    max_depth: usize,
+    min_samples_split: usize,
+    bootstrap_proportion: f64,
+    /// `None` for a checkpoint saved before class weights were checked, as if there were none
+    #[serde(default)]
+    class_weight: Option<Vec<f64>>,
+    random_state: Option<Seed>,
+    n_samples: usize,
+    n_features: usize,
+    forest: FittedRandomForest<L, F>,
+    /// Where the next tree's randomness comes from
+    rng: Xoshiro256Plus,
+}
+
+impl<L: Label + PartialEq + Serialize, F: Float + Serialize> Checkpoint<L, F> {
+    /// Whether more trees grown by `forest` on `x`, with labels `classes` and missing features
+    /// handed over as `missing`, belong with these
+    fn fits(
+        &self,
+        forest: &RandomForestClassifier,
+        x: &ArrayView2<F>,
+        classes: &[L],
+        missing: &MissingFeatures,
+    ) -> bool {
+        self.max_depth == forest.max_depth
+            && self.min_samples_split == forest.min_samples_split
+            && self.bootstrap_proportion == forest.bootstrap_proportion
+            && self.class_weight == forest.class_weight
+            && self.random_state == forest.random_state
+            && (self.n_samples, self.n_features) == x.dim()
+            && self.forest.classes == classes
+            && self.forest.missing == *missing
+    }
+
+    /// Write to a file next to `path` first, so a run stopped mid-write leaves the last
+    /// checkpoint intact
+    fn save(&self, path: &Path) -> Result<(), ModelFileError> {
+        let mut partial = OsString::from(path);
+        partial.push(".partial");
+        model_file::write(self, &partial)?;
+
+        Ok(fs::rename(partial, path)?)
+    }
+}
+
+impl RandomForestClassifier {
+    /// Fit like `fit`, saving the trees so far to `path` every `every` trees, and resuming from
+    /// the checkpoint at `path` if an earlier run left one
+    ///
+    /// Trees are grown one at a time from the same random streams `fit` uses, so a run that's
+    /// stopped and resumed ends with the same trees as one that wasn't, and as `fit` would grow.
+    /// The finished forest is checkpointed too: running again returns it without fitting
+    /// anything, and running again with a larger `n_estimators` grows it further.
+    ///
+    /// A checkpoint from a run with other tree settings, another seed or other training data is
+    /// refused with `FitError::CheckpointMismatch` rather than mixed in.
+    pub fn fit_with_checkpoints<
+        'a,
+        F: Float + Serialize + DeserializeOwned,
+        L: 'static + Clone + Copy + Ord + Label + Serialize + DeserializeOwned,
+    >(
+        &self,
+        x: impl Into<CowArray<'a, F, Ix2>>,
+        y: impl Into<CowArray<'a, L, Ix1>>,
+        path: impl AsRef<Path>,
+        every: NonZeroUsize,
+    ) -> Result<FittedRandomForest<L, F>, FitError> {
+        let path = path.as_ref();
+        let (x, y) = (x.into(), y.into());
+        let classes = self.check(&x.view(), &y.view())?;
+        let x = x.as_standard_layout();
+        let n_features = x.ncols();
+        let missing = MissingFeatures::new(self.missing, x.view());
+
+        let mut checkpoint = if path.exists() {
+            let checkpoint: Checkpoint<L, F> = model_file::read(path)?;
+            if !checkpoint.fits(self, &x.view(), &classes, &missing) {
+                return Err(FitError::CheckpointMismatch {
+                    path: path.to_path_buf(),
+                });
+            }
+            checkpoint
+        } else {
+            Checkpoint {
+                max_depth: self.max_depth,
+                min_samples_split: self.min_samples_split,
+                bootstrap_proportion: self.bootstrap_proportion,
+                class_weight: self.class_weight.clone(),
+                random_state: self.random_state,
+                n_samples: x.nrows(),
+                n_features,
+                forest: FittedRandomForest {
+                    trees: Vec::new(),
+                    node_weights: Vec::new(),
+                    classes,
+                    missing: missing.clone(),
+                    metadata: None,
+                    hooks: self.hooks.clone(),
+                },
+                rng: self.rng(),
+            }
+        };
+
+        let x = missing.encode(x.view());
+        self.hooks
+            .on_fit_start(self.n_estimators, x.nrows(), n_features);
+        while checkpoint.forest.trees.len() < self.n_estimators {
+            let (tree, weights) = self.fit_tree(
+                x.view(),
+                y.view(),
+                &checkpoint.forest.classes,
+                &mut checkpoint.rng,
+            )?;
+            checkpoint.forest.trees.push(tree);
+            checkpoint.forest.node_weights.push(weights);
+
+            let n_trees = checkpoint.forest.trees.len();
+            self.hooks.on_tree_done(n_trees);
+            if n_trees % every == 0 || n_trees == self.n_estimators {
+                checkpoint.save(path)?;
+            }
+        }
+
+        // A checkpoint from a run that asked for more trees has more than are wanted now
+        let mut forest = checkpoint.forest;
+        forest.trees.truncate(self.n_estimators);
+        forest.node_weights.truncate(self.n_estimators);
+        forest.metadata = Some(self.metadata(n_features));
+        forest.hooks = self.hooks.clone();
+        self.hooks.on_fit_done(forest.trees.len());
+
+        Ok(forest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_resume_matches_an_uninterrupted_run() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| (i + j) as f64);
+        let y = Array1::from_shape_fn(30, |i| usize::from(i >= 15));
+        let every = NonZeroUsize::new(2).unwrap();
+        let dir = std::env::temp_dir().join("titanic-ml-checkpoint-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (whole, resumed) = (dir.join("whole.ckpt"), dir.join("resumed.ckpt"));
+        let forest = |n_estimators| {
+            RandomForestClassifier::new()
+                .n_estimators(n_estimators)
+                .max_depth(3)
+                .random_state(Seed::new(8))
+        };
+
+        let uninterrupted = forest(6)
+            .fit_with_checkpoints(&x, &y, &whole, every)
+            .unwrap();
+
+        // "Stop" after 4 trees, then resume to 6
+        forest(4)
+            .fit_with_checkpoints(&x, &y, &resumed, every)
+            .unwrap();
+        let resumed_forest = forest(6)
+            .fit_with_checkpoints(&x, &y, &resumed, every)
+            .unwrap();
+        assert_eq!(resumed_forest.n_trees(), 6);
+        assert_eq!(
+            resumed_forest.predict_proba(&x),
+            uninterrupted.predict_proba(&x)
+        );
+        assert!(!dir.join("resumed.ckpt.partial").exists());
+        assert_eq!(
+            forest(6).fit(&x, &y).unwrap().predict_proba(&x),
+            uninterrupted.predict_proba(&x)
+        );
+
+        // Other settings or other data don't mix with these trees
+        assert!(matches!(
+            forest(6)
+                .max_depth(4)
+                .fit_with_checkpoints(&x, &y, &resumed, every),
+            Err(FitError::CheckpointMismatch { .. })
+        ));
+        assert!(matches!(
+            forest(6)
+                .random_state(Seed::new(9))
+                .fit_with_checkpoints(&x, &y, &resumed, every),
+            Err(FitError::CheckpointMismatch { .. })
+        ));
+        assert!(matches!(
+            forest(6)
+                .class_weight(vec![1.0, 2.0])
+                .fit_with_checkpoints(&x, &y, &resumed, every),
+            Err(FitError::CheckpointMismatch { .. })
+        ));
+        assert!(matches!(
+            forest(6).fit_with_checkpoints(
+                x.slice(ndarray::s![..20, ..]),
+                y.slice(ndarray::s![..20]),
+                &resumed,
+                every
+            ),
+            Err(FitError::CheckpointMismatch { .. })
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+//! How evenly the training labels are spread over the classes
+//!
+//! A forest fitted on labels that are mostly one class leans towards predicting it: on the
+//! Titanic, 62% of the training passengers died, so a forest errs towards "died". `ClassBalance`
+//! counts each class and, when the largest outnumbers the smallest by more than a threshold,
+//! suggests weights for `RandomForestClassifier::class_weight` that make every class count for as
+//! much in total.
+
+use std::fmt;
+
+use crate::{dataset::Dataset, error::DataError};
+
+/// How many times the smallest class the largest can be before the classes are worth weighting
+pub const DEFAULT_IMBALANCE_THRESHOLD: f64 = 1.5;
+
+/// A class and how many samples have it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassCount<L> {
+    pub label: L,
+    pub count: usize,
+}
+
+/// Each class's count, in the order of the sorted labels, as the classifier orders its classes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassBalance<L> {
+    pub classes: Vec<ClassCount<L>>,
+}
+
+impl<L: Ord + Clone> ClassBalance<L> {
+    /// Count the classes of `labels`
+    pub fn new<'a>(labels: impl IntoIterator<Item = &'a L>) -> Self
+    where
+        L: 'a,
+    {
+        let mut labels: Vec<L> = labels.into_iter().cloned().collect();
+        labels.sort();
+        let mut classes: Vec<ClassCount<L>> = Vec::new();
+        for label in labels {
+            match classes.last_mut() {
+                Some(class) if class.label == label => class.count += 1,
+                _ => classes.push(ClassCount { label, count: 1 }),
+            }
+        }
+
+        Self { classes }
+    }
+}
+
+impl<L> ClassBalance<L> {
+    pub fn n_samples(&self) -> usize {
+        self.classes.iter().map(|class| class.count).sum()
+    }
+
+    /// How many times the smallest class the largest is: 1 for evenly spread labels, and NaN for
+    /// no labels at all
+    pub fn imbalance_ratio(&self) -> f64 {
+        let counts = self.classes.iter().map(|class| class.count);
+        match (counts.clone().max(), counts.min()) {
+            (Some(largest), Some(smallest)) => largest as f64 / smallest as f64,
+            _ => f64::NAN,
+        }
+    }
+
+    /// Whether the largest class outnumbers the smallest by more than `threshold` times
+    pub fn is_imbalanced(&self, threshold: f64) -> bool {
+        self.imbalance_ratio() > threshold
+    }
+
+    /// A weight per class that makes each class's samples add up to the same total, as
+    /// scikit-learn's `class_weight="balanced"` does: the number of samples over the number of
+    /// classes times the class's count
+    pub fn balanced_weights(&self) -> Vec<f64> {
+        let per_class = self.n_samples() as f64 / self.classes.len() as f64;
+        self.classes
+            .iter()
+            .map(|class| per_class / class.count as f64)
+            .collect()
+    }
+
+    /// `balanced_weights`, for `RandomForestClassifier::class_weight`, if the classes are
+    /// imbalanced by more than `threshold` (see `DEFAULT_IMBALANCE_THRESHOLD`); `None` if they're
+    /// even enough to leave alone
+    pub fn suggested_weights(&self, threshold: f64) -> Option<Vec<f64>> {
+        self.is_imbalanced(threshold)
+            .then(|| self.balanced_weights())
+    }
+}
+
+impl<L: fmt::Display> fmt::Display for ClassBalance<L> {
+    /// One line per class, e.g. `0: 549 (61.6%)`, then the imbalance
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n_samples = self.n_samples();
+        for class in &self.classes {
+            writeln!(
+                f,
+                "{}: {} ({:.1}%)",
+                class.label,
+                class.count,
+                class.count as f64 / n_samples as f64 * 100.0
+            )?;
+        }
+        write!(
+            f,
+            "The largest class is {:.2} times the smallest",
+            self.imbalance_ratio()
+        )
+    }
+}
+
+impl Dataset {
+    /// The balance of the dataset's labels, or a `DataError::NoLabels` if it doesn't have any
+    pub fn class_balance(&self) -> Result<ClassBalance<usize>, DataError> {
+        Ok(ClassBalance::new(self.labels()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::FitError, random_forest::RandomForestClassifier, seed::Seed};
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_imbalanced_classes_get_balancing_weights() {
+        let y: Array1<usize> = (0..100).map(|i| usize::from(i % 4 == 0)).collect();
+        let balance = ClassBalance::new(&y);
+        assert_eq!(
+            balance.classes,
+            [
+                ClassCount {
+                    label: 0,
+                    count: 75
+                },
+                ClassCount {
+                    label: 1,
+                    count: 25
+                }
+            ]
+        );
+        assert_eq!(balance.imbalance_ratio(), 3.0);
+        assert_eq!(
+            balance.to_string(),
+            "0: 75 (75.0%)\n1: 25 (25.0%)\nThe largest class is 3.00 times the smallest"
+        );
+        let weights = balance
+            .suggested_weights(DEFAULT_IMBALANCE_THRESHOLD)
+            .unwrap();
+        assert_eq!(weights, [100.0 / 150.0, 2.0]);
+        assert_eq!(balance.suggested_weights(3.0), None);
+        assert_eq!(
+            ClassBalance::new(&[2, 1, 1, 2]).suggested_weights(1.0),
+            None
+        );
+
+        // The weights fit straight into the classifier
+        let x = Array2::from_shape_fn((100, 2), |(i, j)| ((i * 7 + j * 3) % 10) as f64);
+        let forest = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(1));
+        let weighted = forest.class_weight(weights);
+        assert_eq!(weighted.fit(&x, &y).unwrap().predict(&x).len(), 100);
+        assert!(matches!(
+            weighted.class_weight(vec![1.0]).fit(&x, &y),
+            Err(FitError::ClassWeightCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            RandomForestClassifier::new()
+                .class_weight(vec![1.0, -1.0])
+                .validate(),
+            Err(FitError::InvalidClassWeight(-1.0))
+        ));
+    }
+}
@@ -0,0 +1,354 @@
+//! A feature saying which cluster each row falls in
+//!
+//! Trees split on one feature at a time and linear models weigh each feature on its own, so
+//! neither easily picks out a group of passengers that stands apart on several features at once,
+//! like young third-class passengers on cheap fares. `ClusterFeature` runs k-means over some
+//! numeric columns of the training data, and the `FittedClusterFeature` it returns adds a column
+//! naming each row's nearest cluster to any data with those columns. The column holds strings
+//! (`"0"`, `"1"`, ...), so the feature matrix turns it into a dummy column per cluster.
+//!
+//! The columns are standardized with the training data's means and standard deviations before
+//! clustering, so `Fare`'s range doesn't drown out `SibSp`'s, and a null counts as the mean.
+
+use polars::prelude::{DataType, IntoLazy, LazyFrame, NamedFrom, Series, col};
+use rand::Rng;
+use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::Data, error::DataError, seed::Seed};
+
+/// The name of the column `FittedClusterFeature::transform` adds, unless told otherwise
+pub const CLUSTER_COLUMN: &str = "Cluster";
+
+/// How to cluster the rows of some data, by some of its numeric columns
+#[derive(Debug, Clone)]
+pub struct ClusterFeature {
+    columns: Vec<String>,
+    n_clusters: usize,
+    name: String,
+    max_iterations: usize,
+    random_state: Option<Seed>,
+}
+
+impl ClusterFeature {
+    /// Cluster by `columns` into `n_clusters` clusters
+    pub fn new<'a>(columns: impl IntoIterator<Item = &'a str>, n_clusters: usize) -> Self {
+        Self {
+            columns: columns.into_iter().map(str::to_string).collect(),
+            n_clusters,
+            name: CLUSTER_COLUMN.to_string(),
+            max_iterations: 100,
+            random_state: None,
+        }
+    }
+
+    /// Set the name of the column the clusters go in (default: `CLUSTER_COLUMN`)
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the most rounds of moving the centers to their clusters' means (default: 100); fewer
+    /// are run if the clusters stop changing
+    pub fn max_iterations(mut self, n: usize) -> Self {
+        self.max_iterations = n;
+        self
+    }
+
+    /// Set the random seed for picking the starting centers, for reproducibility
+    pub fn random_state(mut self, seed: impl Into<Seed>) -> Self {
+        self.random_state = Some(seed.into());
+        self
+    }
+
+    /// Find the clusters of `data`'s rows
+    ///
+    /// A column `data` doesn't have is a `DataError::MissingColumn`, one that isn't numeric a
+    /// `DataError::NonNumericColumn`, and fewer rows than clusters (or no clusters) a
+    /// `DataError::InvalidClusterCount`.
+    pub fn fit(&self, data: &(impl Data + ?Sized)) -> Result<FittedClusterFeature, DataError> {
+        let values = numeric_values(data, &self.columns)?;
+        let n_samples = values.first().map_or(0, Vec::len);
+        if self.n_clusters == 0 || n_samples < self.n_clusters {
+            return Err(DataError::InvalidClusterCount {
+                n_clusters: self.n_clusters,
+                n_samples,
+            });
+        }
+
+        let (means, std_devs): (Vec<f64>, Vec<f64>) = values
+            .iter()
+            .map(|column| {
+                let present: Vec<f64> = column.iter().flatten().copied().collect();
+                let n = present.len() as f64;
+                let mean = if present.is_empty() {
+                    0.0
+                } else {
+                    present.iter().sum::<f64>() / n
+                };
+                let variance = present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                // A column that doesn't vary (or is all null) just doesn't move any row
+                let std_dev = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+                (mean, std_dev)
+            })
+            .unzip();
+        let mut fitted = FittedClusterFeature {
+            name: self.name.clone(),
+            columns: self.columns.clone(),
+            means,
+            std_devs,
+            centers: Vec::new(),
+        };
+        let points = fitted.standardize(&values);
+
+        let mut rng = match self.random_state {
+            Some(seed) => seed.rng(),
+            None => Seed::from_entropy().rng(),
+        };
+        fitted.centers = initial_centers(&points, self.n_clusters, &mut rng);
+        let mut assignments: Vec<usize> = points.iter().map(|p| fitted.nearest(p)).collect();
+        for _ in 0..self.max_iterations {
+            // Move each center to the mean of its cluster; an empty cluster's stays put
+            let mut sums = vec![vec![0.0; self.columns.len()]; self.n_clusters];
+            let mut counts = vec![0usize; self.n_clusters];
+            for (point, &cluster) in points.iter().zip(&assignments) {
+                counts[cluster] += 1;
+                for (sum, value) in sums[cluster].iter_mut().zip(point) {
+                    *sum += value;
+                }
+            }
+            for ((center, sum), count) in fitted.centers.iter_mut().zip(sums).zip(counts) {
+                if count > 0 {
+                    *center = sum.into_iter().map(|sum| sum / count as f64).collect();
+                }
+            }
+
+            let reassigned: Vec<usize> = points.iter().map(|p| fitted.nearest(p)).collect();
+            if reassigned == assignments {
+                break;
+            }
+            assignments = reassigned;
+        }
+
+        Ok(fitted)
+    }
+}
+
+/// The clusters `ClusterFeature::fit` found, and how to put new rows in them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FittedClusterFeature {
+    name: String,
+    columns: Vec<String>,
+    /// The training data's mean of each column
+    means: Vec<f64>,
+    /// The training data's standard deviation of each column (1 for one that doesn't vary)
+    std_devs: Vec<f64>,
+    /// Each cluster's center, in standardized units
+    centers: Vec<Vec<f64>>,
+}
+
+impl FittedClusterFeature {
+    /// The name of the column `transform` adds
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The columns rows are clustered by
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn n_clusters(&self) -> usize {
+        self.centers.len()
+    }
+
+    /// Each cluster's center, in the columns' own units
+    pub fn centers(&self) -> Vec<Vec<f64>> {
+        self.centers
+            .iter()
+            .map(|center| {
+                center
+                    .iter()
+                    .zip(self.means.iter().zip(&self.std_devs))
+                    .map(|(value, (mean, std_dev))| value * std_dev + mean)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The cluster nearest each row of `data`
+    pub fn assign(&self, data: &(impl Data + ?Sized)) -> Result<Vec<usize>, DataError> {
+        let points = self.standardize(&numeric_values(data, &self.columns)?);
+
+        Ok(points.iter().map(|point| self.nearest(point)).collect())
+    }
+
+    /// `data` with a string column, named `name`, of each row's cluster
+    pub fn transform(&self, data: &(impl Data + ?Sized)) -> Result<LazyFrame, DataError> {
+        let clusters: Vec<String> = self
+            .assign(data)?
+            .into_iter()
+            .map(|cluster| cluster.to_string())
+            .collect();
+        let mut df = data.lazy_frame_cloned().collect()?;
+        df.with_column(Series::new(self.name.as_str().into(), clusters))?;
+
+        Ok(df.lazy())
+    }
+
+    /// The rows of `values` (given by column) in standardized units, with nulls at the mean
+    fn standardize(&self, values: &[Vec<Option<f64>>]) -> Vec<Vec<f64>> {
+        let n_samples = values.first().map_or(0, Vec::len);
+        (0..n_samples)
+            .map(|row| {
+                values
+                    .iter()
+                    .zip(self.means.iter().zip(&self.std_devs))
+                    .map(|(column, (mean, std_dev))| {
+                        column[row].map_or(0.0, |value| (value - mean) / std_dev)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn nearest(&self, point: &[f64]) -> usize {
+        let mut nearest = (0, f64::INFINITY);
+        for (cluster, center) in self.centers.iter().enumerate() {
+            let distance = squared_distance(point, center);
+            if distance < nearest.1 {
+                nearest = (cluster, distance);
+            }
+        }
+
+        nearest.0
+    }
+}
+
+/// k-means++: the first center is a random point, and each next one a point picked with
+/// probability proportional to its squared distance from the nearest center so far, so the
+/// centers start spread out
+fn initial_centers(
+    points: &[Vec<f64>],
+    n_clusters: usize,
+    rng: &mut Xoshiro256Plus,
+) -> Vec<Vec<f64>> {
+    let mut centers = vec![points[rng.gen_range(0..points.len())].clone()];
+    while centers.len() < n_clusters {
+        let distances: Vec<f64> = points
+            .iter()
+            .map(|point| {
+                centers
+                    .iter()
+                    .map(|center| squared_distance(point, center))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+        let total: f64 = distances.iter().sum();
+        let next = if total > 0.0 {
+            let mut target = rng.gen_range(0.0..total);
+            distances
+                .iter()
+                .position(|&distance| {
+                    target -= distance;
+                    target < 0.0
+                })
+                .unwrap_or(points.len() - 1)
+        } else {
+            // Every point is already a center: there are fewer distinct points than clusters
+            rng.gen_range(0..points.len())
+        };
+        centers.push(points[next].clone());
+    }
+
+    centers
+}
+
+fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+/// Each of `columns` of `data`, as floats
+fn numeric_values(
+    data: &(impl Data + ?Sized),
+    columns: &[String],
+) -> Result<Vec<Vec<Option<f64>>>, DataError> {
+    data.check_columns_exist(columns.iter().map(String::as_str))?;
+    let schema = data.schema()?;
+    for name in columns {
+        if let Some(dtype) = schema.get(name)
+            && !dtype.is_numeric()
+        {
+            return Err(DataError::NonNumericColumn {
+                name: name.clone(),
+                dtype: dtype.to_string(),
+            });
+        }
+    }
+
+    let df = data
+        .lazy_frame_cloned()
+        .select(
+            columns
+                .iter()
+                .map(|name| col(name.as_str()).cast(DataType::Float64))
+                .collect::<Vec<_>>(),
+        )
+        .collect()?;
+    df.get_columns()
+        .iter()
+        .map(|column| Ok(column.f64()?.into_iter().collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::df;
+
+    #[test]
+    fn test_rows_get_their_nearest_cluster() {
+        // Cheap young passengers and expensive old ones, and one without an age
+        let train = df!(
+            "Age" => [Some(20.0), Some(22.0), Some(19.0), Some(60.0), Some(58.0), None],
+            "Fare" => [7.0, 8.0, 7.5, 200.0, 210.0, 205.0],
+            "Sex" => ["male", "female", "male", "female", "male", "female"],
+        )
+        .unwrap();
+        let clusters = ClusterFeature::new(["Age", "Fare"], 2).random_state(Seed::new(1));
+
+        let fitted = clusters.fit(&train).unwrap();
+        assert_eq!(fitted.n_clusters(), 2);
+        let assigned = fitted.assign(&train).unwrap();
+        assert!(assigned[..3].iter().all(|&cluster| cluster == assigned[0]));
+        assert!(assigned[3..].iter().all(|&cluster| cluster == assigned[3]));
+        assert_ne!(assigned[0], assigned[3]);
+        let young = &fitted.centers()[assigned[0]];
+        assert!((young[1] - 7.5).abs() < 1e-9, "{young:?}");
+
+        // Later data is put in the training data's clusters
+        let test = df!("Fare" => [300.0, 5.0], "Age" => [Some(70.0), None]).unwrap();
+        let transformed = fitted.transform(&test).unwrap().collect().unwrap();
+        let column = transformed.column(CLUSTER_COLUMN).unwrap().str().unwrap();
+        assert_eq!(
+            column
+                .into_iter()
+                .map(|cluster| cluster.map(str::to_string))
+                .collect::<Vec<_>>(),
+            [assigned[3], assigned[0]].map(|cluster| Some(cluster.to_string()))
+        );
+        assert_eq!(clusters.fit(&train).unwrap(), fitted);
+
+        assert!(matches!(
+            ClusterFeature::new(["Age", "Sex"], 2).fit(&train),
+            Err(DataError::NonNumericColumn { name, .. }) if name == "Sex"
+        ));
+        assert!(matches!(
+            ClusterFeature::new(["Age"], 7).fit(&train),
+            Err(DataError::InvalidClusterCount {
+                n_clusters: 7,
+                n_samples: 6
+            })
+        ));
+    }
+}
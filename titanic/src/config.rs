@@ -0,0 +1,251 @@
+//! Model settings as data, for config files and experiment tracking
+//!
+//! The builders (`RandomForestClassifier::new().max_depth(5)...`) are the way to set up a model
+//! in code. A `RandomForestConfig` holds the same settings as plain fields that serde can read
+//! from a JSON or TOML file and write back out, so a run's settings can live in a file next to
+//! its results. `build` turns one into a classifier; `RandomForestClassifier::config` goes the
+//! other way, e.g. to record what a run used.
+
+use std::num::NonZeroUsize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use thiserror::Error;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::random_forest_regressor::{Monotonic, RandomForestRegressor};
+use crate::{error::FitError, missing::Missing, random_forest::RandomForestClassifier, seed::Seed};
+
+/// Why a config file couldn't be loaded
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Couldn't read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Don't know how to read {}; config files end in .json or .toml", path.display())]
+    UnknownFormat { path: PathBuf },
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+}
+
+/// Read a config from `path`, as JSON or TOML depending on its extension
+///
+/// Settings the file leaves out keep their defaults, and a setting it misspells is an error
+/// rather than being ignored.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ConfigError> {
+    let path = path.as_ref();
+    let read = || {
+        fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&read()?)?),
+        Some("toml") => Ok(toml::from_str(&read()?)?),
+        _ => Err(ConfigError::UnknownFormat {
+            path: path.to_path_buf(),
+        }),
+    }
+}
+
+/// `RandomForestClassifier`'s settings, defaulting to the builder's defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RandomForestConfig {
+    pub n_estimators: usize,
+    pub max_depth: usize,
+    pub min_samples_split: usize,
+    pub bootstrap_proportion: f64,
+    /// A weight per class, in the order of the sorted class labels
+    pub class_weight: Option<Vec<f64>>,
+    pub missing: Missing,
+    pub random_state: Option<Seed>,
+    pub n_threads: Option<NonZeroUsize>,
+}
+
+impl Default for RandomForestConfig {
+    fn default() -> Self {
+        RandomForestClassifier::new().config()
+    }
+}
+
+impl RandomForestConfig {
+    /// A classifier with these settings, or the error `validate` gives for them
+    pub fn build(&self) -> Result<RandomForestClassifier, FitError> {
+        let forest = RandomForestClassifier {
+            n_estimators: self.n_estimators,
+            max_depth: self.max_depth,
+            min_samples_split: self.min_samples_split,
+            bootstrap_proportion: self.bootstrap_proportion,
+            class_weight: self.class_weight.clone(),
+            missing: self.missing,
+            random_state: self.random_state,
+            n_threads: self.n_threads,
+            ..RandomForestClassifier::new()
+        };
+        forest.validate()?;
+
+        Ok(forest)
+    }
+}
+
+impl RandomForestClassifier {
+    /// This classifier's settings (everything but its hooks), e.g. to save with a run's results
+    pub fn config(&self) -> RandomForestConfig {
+        RandomForestConfig {
+            n_estimators: self.n_estimators,
+            max_depth: self.max_depth,
+            min_samples_split: self.min_samples_split,
+            bootstrap_proportion: self.bootstrap_proportion,
+            class_weight: self.class_weight.clone(),
+            missing: self.missing,
+            random_state: self.random_state,
+            n_threads: self.n_threads,
+        }
+    }
+}
+
+/// `RandomForestRegressor`'s settings, defaulting to the builder's defaults
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RandomForestRegressorConfig {
+    pub n_estimators: usize,
+    pub max_depth: usize,
+    pub min_samples_split: usize,
+    pub bootstrap_proportion: f64,
+    /// One per feature, in the order of the columns
+    pub monotonic_constraints: Option<Vec<Monotonic>>,
+    pub missing: Missing,
+    pub random_state: Option<Seed>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for RandomForestRegressorConfig {
+    fn default() -> Self {
+        RandomForestRegressor::new().config()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RandomForestRegressorConfig {
+    /// A regressor with these settings, or the error `validate` gives for them
+    pub fn build(&self) -> Result<RandomForestRegressor, FitError> {
+        let forest = RandomForestRegressor {
+            n_estimators: self.n_estimators,
+            max_depth: self.max_depth,
+            min_samples_split: self.min_samples_split,
+            bootstrap_proportion: self.bootstrap_proportion,
+            monotonic_constraints: self.monotonic_constraints.clone(),
+            missing: self.missing,
+            random_state: self.random_state,
+        };
+        forest.validate()?;
+
+        Ok(forest)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RandomForestRegressor {
+    /// This regressor's settings
+    pub fn config(&self) -> RandomForestRegressorConfig {
+        RandomForestRegressorConfig {
+            n_estimators: self.n_estimators,
+            max_depth: self.max_depth,
+            min_samples_split: self.min_samples_split,
+            bootstrap_proportion: self.bootstrap_proportion,
+            monotonic_constraints: self.monotonic_constraints.clone(),
+            missing: self.missing,
+            random_state: self.random_state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configs_round_trip_through_files() {
+        let dir = std::env::temp_dir().join("titanic-ml-config-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("forest.toml");
+        fs::write(&toml_path, "max_depth = 5\nrandom_state = 7\n").unwrap();
+        let config: RandomForestConfig = load(&toml_path).unwrap();
+        assert_eq!(
+            config,
+            RandomForestClassifier::new()
+                .max_depth(5)
+                .random_state(7)
+                .config()
+        );
+        assert_eq!(config.build().unwrap().config(), config);
+
+        let json_path = dir.join("regressor.json");
+        fs::write(
+            &json_path,
+            r#"{"n_estimators": 20, "bootstrap_proportion": 0.5,
+                "monotonic_constraints": ["decreasing", "none"], "missing": "learn"}"#,
+        )
+        .unwrap();
+        let config: RandomForestRegressorConfig = load(&json_path).unwrap();
+        assert_eq!(config.n_estimators, 20);
+        assert_eq!(
+            config.monotonic_constraints,
+            Some(vec![Monotonic::Decreasing, Monotonic::None])
+        );
+        assert_eq!(config.missing, Missing::Learn);
+        assert_eq!(
+            config.max_depth,
+            RandomForestRegressorConfig::default().max_depth
+        );
+        assert_eq!(config.build().unwrap().config(), config);
+
+        // Typos and bad settings are caught when loading and building, not at fit time
+        fs::write(&toml_path, "max_dept = 5\n").unwrap();
+        assert!(matches!(
+            load::<RandomForestConfig>(&toml_path),
+            Err(ConfigError::Toml(_))
+        ));
+        let config = RandomForestConfig {
+            max_depth: 0,
+            ..RandomForestConfig::default()
+        };
+        assert!(matches!(config.build(), Err(FitError::ZeroMaxDepth)));
+        let config = RandomForestRegressorConfig {
+            bootstrap_proportion: 1.5,
+            ..RandomForestRegressorConfig::default()
+        };
+        assert!(matches!(
+            config.build(),
+            Err(FitError::InvalidBootstrapProportion(_))
+        ));
+        assert!(matches!(
+            load::<RandomForestConfig>(dir.join("forest.yaml")),
+            Err(ConfigError::UnknownFormat { .. })
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
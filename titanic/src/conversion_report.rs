@@ -0,0 +1,111 @@
+//! A record of the values conversions had to make up or change
+//!
+//! Nulls become 0s on the way into a feature matrix, and booleans become 0.0 and 1.0. Neither is
+//! an error, but a model trained on a column that was mostly nulls is mostly trained on 0s, so
+//! the `*_with_report` conversions count what they changed, per column, in a `ConversionReport`
+//! that a run can print.
+
+use std::{collections::BTreeMap, fmt};
+
+/// What happened to a value during conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Adjustment {
+    /// A null was replaced with 0
+    Imputed,
+    /// A boolean was turned into 0.0 or 1.0
+    Coerced,
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Imputed => write!(f, "nulls imputed as 0"),
+            Self::Coerced => write!(f, "booleans coerced to 0/1"),
+        }
+    }
+}
+
+/// How many values of each column were adjusted, and how
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionReport {
+    counts: BTreeMap<(String, Adjustment), usize>,
+}
+
+impl ConversionReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `count` values of `column` to the tally for `adjustment`
+    pub fn record(&mut self, column: &str, adjustment: Adjustment, count: usize) {
+        if count > 0 {
+            *self
+                .counts
+                .entry((column.to_string(), adjustment))
+                .or_default() += count;
+        }
+    }
+
+    /// How many values of `column` had `adjustment` applied
+    pub fn count(&self, column: &str, adjustment: Adjustment) -> usize {
+        self.counts
+            .get(&(column.to_string(), adjustment))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// How many values of every column had `adjustment` applied
+    pub fn total(&self, adjustment: Adjustment) -> usize {
+        self.counts
+            .iter()
+            .filter(|((_, a), _)| *a == adjustment)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionReport {
+    /// One line per column and adjustment, e.g. `Age: 177 nulls imputed as 0`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No values were imputed or coerced");
+        }
+
+        for (i, ((column, adjustment), count)) in self.counts.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{column}: {count} {adjustment}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_display() {
+        let mut report = ConversionReport::new();
+        assert_eq!(report.to_string(), "No values were imputed or coerced");
+
+        report.record("Age", Adjustment::Imputed, 2);
+        report.record("Age", Adjustment::Imputed, 1);
+        report.record("Fare", Adjustment::Imputed, 0);
+        report.record("Alone", Adjustment::Coerced, 4);
+
+        assert_eq!(report.count("Age", Adjustment::Imputed), 3);
+        assert_eq!(report.count("Fare", Adjustment::Imputed), 0);
+        assert_eq!(report.total(Adjustment::Imputed), 3);
+        assert_eq!(
+            report.to_string(),
+            "Age: 3 nulls imputed as 0\nAlone: 4 booleans coerced to 0/1"
+        );
+    }
+}
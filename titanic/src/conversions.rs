@@ -4,9 +4,36 @@
 //! and ndarray's Array types, which is necessary when using Polars for data handling
 //! and linfa for machine learning.
 
-use anyhow::Result;
-use ndarray::{Array1, Array2, s};
+use ndarray::{Array1, Array2, ArrayViewMut1, Axis, parallel::prelude::*};
+use num_traits::AsPrimitive;
 use polars::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    conversion_report::{Adjustment, ConversionReport},
+    error::{DataError, check_class_labels},
+    seed::Seed,
+};
+
+/// Why splitting or writing failed
+///
+/// Conversions themselves only fail because of the data, so they return a `DataError`.
+#[derive(Debug, Error)]
+pub enum ConversionError {
+    #[error(
+        "Training ({train}) and validation ({validation}) ratios must be between 0 and 1, and \
+         add up to at most 1"
+    )]
+    InvalidRatios { train: f32, validation: f32 },
+
+    #[error(transparent)]
+    Data(#[from] DataError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+type Result<T, E = DataError> = std::result::Result<T, E>;
 
 /// Convert a Polars DataFrame to an ndarray Array2<f64>
 ///
@@ -20,34 +47,125 @@ use polars::prelude::*;
 /// * `Result<Array2<f64>>` - A 2D array with shape (n_samples, n_features)
 ///
 /// # Notes
-/// - Null values are replaced with 0.0
-/// - All columns are cast to Float64
-/// - Polars stores data in column-major format, so we need to transpose
+/// - Null values are replaced with 0.0 (`dataframe_to_array2_with_report` counts them)
+/// - Numeric and boolean columns are cast to Float64; any other column is a
+///   `DataError::NonNumericColumn`
+/// - Columns are converted in parallel, each read from Polars' own buffers straight into its place
+///   in a row-major array, with no Float64 copy of the column in between. That array is the one
+///   copy of the data the models need: Polars keeps each column in buffers of its own, and the
+///   trees fit on rows.
 ///
 /// # Example
 /// ```ignore
-/// let df = training_data.get_big_x()?;
+/// let df = training_data.get_feature_matrix([col("Pclass"), col("Sex")])?;
 /// let x_train = dataframe_to_array2(&df)?;
 /// println!("Shape: {:?}", x_train.dim());
 /// ```
 pub fn dataframe_to_array2(df: &DataFrame) -> Result<Array2<f64>> {
-    let nrows = df.height();
-    let ncols = df.width();
+    dataframe_to_array2_with_report(df, &mut ConversionReport::new())
+}
 
-    let mut data = Vec::with_capacity(nrows * ncols);
+/// `dataframe_to_array2`, but with nulls as NaN rather than 0.0
+///
+/// For models that handle missing values themselves, such as a `RandomForestRegressor` with
+/// `Missing::Learn`, so a missing Age isn't mistaken for a newborn.
+pub fn dataframe_to_array2_with_nans(df: &DataFrame) -> Result<Array2<f64>> {
+    for col in df.get_columns() {
+        check_numeric(col.as_materialized_series())?;
+    }
 
+    fill_array2(df, f64::NAN)
+}
+
+/// `dataframe_to_array2`, recording the nulls it imputed and the booleans it coerced in `report`
+pub fn dataframe_to_array2_with_report(
+    df: &DataFrame,
+    report: &mut ConversionReport,
+) -> Result<Array2<f64>> {
     for col in df.get_columns() {
-        // Convert each column to f64
-        let col_data = col.cast(&DataType::Float64)?;
-        let ca = col_data.f64()?;
+        check_numeric(col.as_materialized_series())?;
+        report.record(col.name(), Adjustment::Imputed, col.null_count());
+        if col.dtype().is_bool() {
+            report.record(
+                col.name(),
+                Adjustment::Coerced,
+                col.len() - col.null_count(),
+            );
+        }
+    }
+
+    fill_array2(df, 0.0)
+}
+
+/// The (already checked) columns of `df` as an array, with nulls as `null`
+fn fill_array2(df: &DataFrame, null: f64) -> Result<Array2<f64>> {
+    let mut array = Array2::zeros((df.height(), df.width()));
+    array
+        .axis_iter_mut(Axis(1))
+        .into_par_iter()
+        .zip(df.get_columns().par_iter())
+        .try_for_each(|(out, col)| copy_column(col.as_materialized_series(), out, null))?;
+
+    Ok(array)
+}
+
+/// A `NonNumericColumn` error unless the series is numeric or boolean
+///
+/// Polars' own cast would quietly turn strings that don't parse into nulls (and so into 0s).
+fn check_numeric(series: &Series) -> Result<()> {
+    let dtype = series.dtype();
+    if !dtype.is_numeric() && !dtype.is_bool() && !dtype.is_null() {
+        return Err(DataError::NonNumericColumn {
+            name: series.name().to_string(),
+            dtype: dtype.to_string(),
+        });
+    }
+
+    Ok(())
+}
 
-        for val in ca.iter() {
-            data.push(val.unwrap_or(0.0)); // Handle nulls as 0.0
+/// Cast a numeric or boolean series to Float64
+fn cast_numeric(series: &Series) -> Result<Series> {
+    check_numeric(series)?;
+
+    Ok(series.cast(&DataType::Float64)?)
+}
+
+/// Write a numeric or boolean series' values into `out` as f64, with nulls as `null`
+///
+/// The common dtypes are read chunk by chunk as they are; any other is cast to Float64 first.
+fn copy_column(series: &Series, out: ArrayViewMut1<f64>, null: f64) -> Result<()> {
+    match series.dtype() {
+        DataType::Float64 => copy_values(series.f64()?, out, null),
+        DataType::Float32 => copy_values(series.f32()?, out, null),
+        DataType::Int64 => copy_values(series.i64()?, out, null),
+        DataType::Int32 => copy_values(series.i32()?, out, null),
+        DataType::UInt64 => copy_values(series.u64()?, out, null),
+        DataType::UInt32 => copy_values(series.u32()?, out, null),
+        DataType::UInt8 => copy_values(series.u8()?, out, null),
+        DataType::Boolean => {
+            for (out, value) in out.into_iter().zip(series.bool()?) {
+                *out = value.map_or(null, |value| f64::from(u8::from(value)));
+            }
         }
+        _ => copy_values(cast_numeric(series)?.f64()?, out, null),
     }
 
-    // Polars stores column-major, so we need to reshape accordingly
-    Ok(Array2::from_shape_vec((ncols, nrows), data)?.reversed_axes())
+    Ok(())
+}
+
+fn copy_values<T: PolarsNumericType>(values: &ChunkedArray<T>, out: ArrayViewMut1<f64>, null: f64) {
+    for (out, value) in out.into_iter().zip(values) {
+        *out = value.map_or(null, |value| value.as_());
+    }
+}
+
+/// The series' Int64 values, or a `NonIntegerColumn` error naming it
+fn int64_values(series: &Series) -> Result<&Int64Chunked> {
+    series.i64().map_err(|_| DataError::NonIntegerColumn {
+        name: series.name().to_string(),
+        dtype: series.dtype().to_string(),
+    })
 }
 
 /// Convert a Polars Series to an ndarray Array1<usize>
@@ -63,8 +181,9 @@ pub fn dataframe_to_array2(df: &DataFrame) -> Result<Array2<f64>> {
 ///
 /// # Notes
 /// - Expects series to contain i64 values
-/// - Null values are replaced with 0
-/// - Values are cast to usize
+/// - Null values are replaced with 0 (`series_to_array1_with_report` counts them)
+/// - Values are cast to usize; a negative value is a `DataError::NegativeLabel`, and labels that
+///   skip a class (e.g. 0 and 2 but no 1) are a `DataError::MissingClass`
 ///
 /// # Example
 /// ```ignore
@@ -72,15 +191,33 @@ pub fn dataframe_to_array2(df: &DataFrame) -> Result<Array2<f64>> {
 /// let y_train = series_to_array1(&y_series)?;
 /// ```
 pub fn series_to_array1(series: &Series) -> Result<Array1<usize>> {
-    let ca = series.i64()?;
-    let vec: Vec<usize> = ca.iter().map(|v| v.unwrap_or(0) as usize).collect();
+    series_to_array1_with_report(series, &mut ConversionReport::new())
+}
+
+/// `series_to_array1`, recording the nulls it imputed in `report`
+pub fn series_to_array1_with_report(
+    series: &Series,
+    report: &mut ConversionReport,
+) -> Result<Array1<usize>> {
+    let ca = int64_values(series)?;
+    report.record(series.name(), Adjustment::Imputed, ca.null_count());
+    let mut labels = Array1::zeros(ca.len());
+    for (label, value) in labels.iter_mut().zip(ca) {
+        let value = value.unwrap_or(0);
+        *label = usize::try_from(value).map_err(|_| DataError::NegativeLabel {
+            column: series.name().to_string(),
+            value,
+        })?;
+    }
+    check_class_labels(&labels)?;
 
-    Ok(Array1::from_vec(vec))
+    Ok(labels)
 }
 
 /// Convert a Polars Series to an ndarray Array1<i64>
 ///
-/// Similar to `series_to_array1` but preserves i64 type for IDs.
+/// Similar to `series_to_array1` but preserves i64 type for IDs. Chunks without nulls are
+/// copied into the array's storage whole.
 ///
 /// # Arguments
 /// * `series` - The Polars Series to convert
@@ -94,41 +231,46 @@ pub fn series_to_array1(series: &Series) -> Result<Array1<usize>> {
 /// let ids = series_to_array1_i64(&ids_series)?;
 /// ```
 pub fn series_to_array1_i64(series: &Series) -> Result<Array1<i64>> {
-    let ca = series.i64()?;
-    let vec: Vec<i64> = ca.iter().map(|v| v.unwrap_or(0)).collect();
+    let ca = int64_values(series)?;
+    let mut values = Vec::with_capacity(ca.len());
+    for chunk in ca.downcast_iter() {
+        if chunk
+            .validity()
+            .is_none_or(|validity| validity.unset_bits() == 0)
+        {
+            values.extend_from_slice(chunk.values());
+        } else {
+            values.extend(chunk.iter().map(|v| v.copied().unwrap_or(0)));
+        }
+    }
 
-    Ok(Array1::from_vec(vec))
+    Ok(Array1::from_vec(values))
 }
 
-/// Convert ndarray predictions and IDs to a Polars DataFrame
+/// Convert a Polars Series to an ndarray Array1<f64>
 ///
-/// This function creates a DataFrame suitable for Kaggle submission format,
-/// with PassengerId and Survived columns.
+/// For regression targets. Any numeric dtype is cast to f64.
 ///
 /// # Arguments
-/// * `predictions` - Array of predicted class labels (0 or 1)
-/// * `passenger_ids` - Array of passenger IDs corresponding to predictions
+/// * `series` - The Polars Series to convert
 ///
 /// # Returns
-/// * `Result<DataFrame>` - DataFrame with PassengerId and Survived columns
+/// * `Result<Array1<f64>>` - A 1D array of f64 values
+///
+/// # Notes
+/// - Null values are replaced with 0.0, as in `dataframe_to_array2`
+/// - Like a column there, the values are read from Polars' buffers straight into the array
 ///
 /// # Example
 /// ```ignore
-/// let submission_df = array1_to_dataframe(&predictions, &passenger_ids)?;
+/// let fares = training_data.get_col_as_series("Fare")?;
+/// let y = series_to_array1_f64(&fares)?;
 /// ```
-pub fn array1_to_dataframe(
-    predictions: &Array1<usize>,
-    passenger_ids: &Array1<i64>,
-) -> Result<DataFrame> {
-    let survived: Vec<i64> = predictions.iter().map(|&x| x as i64).collect();
-    let ids: Vec<i64> = passenger_ids.iter().copied().collect();
-
-    let df = DataFrame::new(vec![
-        Series::new("PassengerId".into(), ids).into(),
-        Series::new("Survived".into(), survived).into(),
-    ])?;
-
-    Ok(df)
+pub fn series_to_array1_f64(series: &Series) -> Result<Array1<f64>> {
+    let mut array = Array1::zeros(series.len());
+    copy_column(series, array.view_mut(), 0.0)?;
+
+    Ok(array)
 }
 
 /// Write a DataFrame to a CSV file
@@ -144,25 +286,59 @@ pub fn array1_to_dataframe(
 /// ```ignore
 /// write_csv_file(&submission_df, "data/submission.csv")?;
 /// ```
-pub fn write_csv_file(df: &mut DataFrame, path: &str) -> Result<()> {
+pub fn write_csv_file(df: &mut DataFrame, path: &str) -> Result<(), ConversionError> {
     let mut file = std::fs::File::create(path)?;
 
     CsvWriter::new(&mut file)
         .include_header(true)
         .with_separator(b',')
-        .finish(df)?;
+        .finish(df)
+        .map_err(DataError::from)?;
 
     Ok(())
 }
 
 /// Calculate accuracy given predictions and actual labels
+///
+/// The same as `metrics::classification::accuracy`, which has the other classification metrics
+/// too. Code that should work with any of them, such as early stopping, takes a
+/// `metrics::metric::Metric` instead.
 pub fn calculate_accuracy(predictions: &Array1<usize>, actual: &Array1<usize>) -> f64 {
-    let correct = predictions
-        .iter()
-        .zip(actual.iter())
-        .filter(|(pred, act)| pred == act)
-        .count();
-    correct as f64 / actual.len() as f64
+    metrics::classification::accuracy(predictions, actual)
+}
+
+/// The two sets made by `train_test_split` and `shuffled_train_test_split`
+///
+/// Named fields rather than a tuple, so a caller can't swap `x_val` and `y_train` by listing
+/// them in the wrong order.
+#[derive(Debug, Clone)]
+pub struct Split<T> {
+    pub x_train: Array2<f64>,
+    pub x_val: Array2<f64>,
+    pub y_train: Array1<T>,
+    pub y_val: Array1<T>,
+    /// Which rows of the arrays that were split went into the training set, in its order
+    pub train_rows: Vec<usize>,
+    /// Which rows went into the validation set, in its order
+    pub val_rows: Vec<usize>,
+}
+
+impl<T: Clone> Split<T> {
+    /// Cut `x` and `y` after the first `ratio` of the rows in `order`
+    fn of_rows(x: &Array2<f64>, y: &Array1<T>, ratio: f32, mut order: Vec<usize>) -> Self {
+        let split_idx = (order.len() as f32 * ratio) as usize;
+        let val_rows = order.split_off(split_idx);
+        let train_rows = order;
+
+        Self {
+            x_train: x.select(Axis(0), &train_rows),
+            x_val: x.select(Axis(0), &val_rows),
+            y_train: y.select(Axis(0), &train_rows),
+            y_val: y.select(Axis(0), &val_rows),
+            train_rows,
+            val_rows,
+        }
+    }
 }
 
 /// Split arrays into training and validation sets
@@ -172,54 +348,242 @@ pub fn calculate_accuracy(predictions: &Array1<usize>, actual: &Array1<usize>) -
 ///
 /// # Arguments
 /// * `x` - Feature matrix (rows = samples, columns = features)
-/// * `y` - Target vector (class labels)
+/// * `y` - Target vector (class labels or regression targets)
 /// * `ratio` - Proportion for training set (e.g., 0.8 = 80% train, 20% validation)
 ///
 /// # Returns
-/// * Tuple of (x_train, x_val, y_train, y_val)
+/// * A `Split` with the two sets, and the rows each was taken from
 ///
 /// # Example
 /// ```ignore
-/// let (x_train, x_val, y_train, y_val) = train_test_split(x, y, 0.8);
-/// println!("Training samples: {}, Validation samples: {}", x_train.nrows(), x_val.nrows());
+/// let split = train_test_split(x, y, 0.8);
+/// println!(
+///     "Training samples: {}, Validation samples: {}",
+///     split.x_train.nrows(),
+///     split.x_val.nrows()
+/// );
 /// ```
-pub fn train_test_split(
+pub fn train_test_split<T: Clone>(x: Array2<f64>, y: Array1<T>, ratio: f32) -> Split<T> {
+    Split::of_rows(&x, &y, ratio, (0..x.nrows()).collect())
+}
+
+/// Like `train_test_split`, but shuffles the rows first
+///
+/// The same `seed` always gives the same split. `train_rows` and `val_rows` are positions in the
+/// unshuffled arrays, e.g. to look up which passengers ended up in the validation set.
+pub fn shuffled_train_test_split<T: Clone>(
     x: Array2<f64>,
-    y: Array1<usize>,
+    y: Array1<T>,
     ratio: f32,
-) -> (Array2<f64>, Array2<f64>, Array1<usize>, Array1<usize>) {
-    let n_samples = x.nrows();
-    let split_idx = (n_samples as f32 * ratio) as usize;
+    seed: Seed,
+) -> Split<T> {
+    let permutation = seed.permutation(x.nrows()).to_vec();
+
+    Split::of_rows(&x, &y, ratio, permutation)
+}
+
+/// Splits arrays into training, validation and test sets in one go
+///
+/// Train on the training set, compare models or settings on the validation set, and only look at
+/// the test set once, to report how the chosen model does on data nothing was tuned against.
+/// The test set gets whatever the training and validation ratios leave over.
+///
+/// By default the rows are cut in order, like `train_test_split`. `shuffle` shuffles them first,
+/// and `stratify` cuts each label's rows separately, so all three sets have (nearly) the same
+/// label proportions as the whole.
+///
+/// # Example
+/// ```ignore
+/// let split = ThreeWaySplitter::new(0.6, 0.2)
+///     .shuffle(seed.derive("split"))
+///     .stratify()
+///     .split(x, y)?;
+/// println!("Training samples: {}", split.x_train.nrows());
+/// ```
+pub struct ThreeWaySplitter {
+    train_ratio: f32,
+    validation_ratio: f32,
+    shuffle: Option<Seed>,
+    stratify: bool,
+}
+
+impl ThreeWaySplitter {
+    /// Create a splitter with the given proportions for training and validation
+    pub fn new(train_ratio: f32, validation_ratio: f32) -> Self {
+        Self {
+            train_ratio,
+            validation_ratio,
+            shuffle: None,
+            stratify: false,
+        }
+    }
+
+    /// Shuffle the rows before splitting; the same seed always gives the same split
+    pub fn shuffle(mut self, seed: impl Into<Seed>) -> Self {
+        self.shuffle = Some(seed.into());
+        self
+    }
+
+    /// Keep each label's proportion the same in all three sets
+    pub fn stratify(mut self) -> Self {
+        self.stratify = true;
+        self
+    }
+
+    /// Split `x` and `y`, keeping rows and labels together
+    ///
+    /// Set sizes are rounded to the nearest row (per label, when stratifying).
+    pub fn split<T: Clone + PartialEq>(
+        &self,
+        x: Array2<f64>,
+        y: Array1<T>,
+    ) -> Result<ThreeWaySplit<T>, ConversionError> {
+        let (train, validation) = (self.train_ratio, self.validation_ratio);
+        if !(0.0..=1.0).contains(&train)
+            || !(0.0..=1.0).contains(&validation)
+            || train + validation > 1.0
+        {
+            return Err(ConversionError::InvalidRatios { train, validation });
+        }
+        if x.nrows() != y.len() {
+            return Err(DataError::LengthMismatch {
+                samples: x.nrows(),
+                labels: y.len(),
+            }
+            .into());
+        }
+
+        let order = match self.shuffle {
+            Some(seed) => seed.permutation(x.nrows()).to_vec(),
+            None => (0..x.nrows()).collect(),
+        };
+        let groups = if self.stratify {
+            group_by_label(&order, &y)
+        } else {
+            vec![order]
+        };
+
+        let (mut train_rows, mut validation_rows, mut test_rows) = (vec![], vec![], vec![]);
+        for group in groups {
+            let n = group.len() as f32;
+            let train_end = (n * train).round() as usize;
+            let validation_end = ((n * (train + validation)).round() as usize).max(train_end);
+
+            train_rows.extend_from_slice(&group[..train_end]);
+            validation_rows.extend_from_slice(&group[train_end..validation_end]);
+            test_rows.extend_from_slice(&group[validation_end..]);
+        }
 
-    // Split features
-    let x_train = x.slice(s![..split_idx, ..]).to_owned();
-    let x_val = x.slice(s![split_idx.., ..]).to_owned();
+        // Stratifying put each set's rows in label order; mix the labels back together
+        if let (true, Some(seed)) = (self.stratify, self.shuffle) {
+            for (rows, stage) in [
+                (&mut train_rows, "train"),
+                (&mut validation_rows, "validation"),
+                (&mut test_rows, "test"),
+            ] {
+                let permutation = seed.derive(stage).permutation(rows.len());
+                *rows = permutation.iter().map(|&i| rows[i]).collect();
+            }
+        }
 
-    // Split targets
-    let y_train = y.slice(s![..split_idx]).to_owned();
-    let y_val = y.slice(s![split_idx..]).to_owned();
+        Ok(ThreeWaySplit {
+            x_train: x.select(Axis(0), &train_rows),
+            x_validation: x.select(Axis(0), &validation_rows),
+            x_test: x.select(Axis(0), &test_rows),
+            y_train: y.select(Axis(0), &train_rows),
+            y_validation: y.select(Axis(0), &validation_rows),
+            y_test: y.select(Axis(0), &test_rows),
+            test_rows,
+        })
+    }
+}
+
+/// `order` split into one list per distinct label, each in `order`'s order
+pub(crate) fn group_by_label<T: PartialEq>(order: &[usize], y: &Array1<T>) -> Vec<Vec<usize>> {
+    let mut labels: Vec<&T> = Vec::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for &i in order {
+        match labels.iter().position(|&label| *label == y[i]) {
+            Some(group) => groups[group].push(i),
+            None => {
+                labels.push(&y[i]);
+                groups.push(vec![i]);
+            }
+        }
+    }
 
-    (x_train, x_val, y_train, y_val)
+    groups
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ndarray::arr1;
+/// The three sets made by `ThreeWaySplitter::split`
+pub struct ThreeWaySplit<T> {
+    pub x_train: Array2<f64>,
+    pub x_validation: Array2<f64>,
+    pub x_test: Array2<f64>,
+    pub y_train: Array1<T>,
+    pub y_validation: Array1<T>,
+    pub y_test: Array1<T>,
+    /// The positions of the test rows in the arrays that were split, e.g. to look up which
+    /// passengers ended up in the test set
+    pub test_rows: Vec<usize>,
+}
 
-    #[test]
-    fn test_array1_to_dataframe() {
-        let predictions = arr1(&[0, 1, 0, 1]);
-        let ids = arr1(&[892, 893, 894, 895]);
+/// Per-column mean and standard deviation, for scaling features to mean 0 and standard
+/// deviation 1
+///
+/// Fit on the training set and reuse it on anything predicted later, so both are scaled the
+/// same way. Constant columns get a standard deviation of 1, so they become all zeros rather
+/// than NaN.
+///
+/// # Example
+/// ```ignore
+/// let standardizer = Standardizer::fit(&x_train)?;
+/// let z_train = standardizer.transform(&x_train);
+/// let z_val = standardizer.transform(&x_val);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Standardizer {
+    mean: Array1<f64>,
+    std: Array1<f64>,
+}
+
+impl Standardizer {
+    pub fn fit(x: &Array2<f64>) -> Result<Self> {
+        let Some(mean) = x.mean_axis(Axis(0)) else {
+            return Err(DataError::NoSamples);
+        };
+        let std = x
+            .std_axis(Axis(0), 0.0)
+            .mapv(|s| if s > 0.0 { s } else { 1.0 });
+
+        Ok(Self { mean, std })
+    }
 
-        let df = array1_to_dataframe(&predictions, &ids).unwrap();
+    pub fn transform(&self, x: &Array2<f64>) -> Array2<f64> {
+        (x - &self.mean) / &self.std
+    }
 
-        assert_eq!(df.height(), 4);
-        assert_eq!(df.width(), 2);
-        assert!(df.column("PassengerId").is_ok());
-        assert!(df.column("Survived").is_ok());
+    pub fn mean(&self) -> &Array1<f64> {
+        &self.mean
     }
 
+    pub fn std(&self) -> &Array1<f64> {
+        &self.std
+    }
+}
+
+/// Scale every column of `x` to mean 0 and standard deviation 1
+///
+/// Shorthand for `Standardizer::fit(x)?.transform(x)`.
+pub fn standardize(x: &Array2<f64>) -> Result<Array2<f64>> {
+    Ok(Standardizer::fit(x)?.transform(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
     #[test]
     fn test_series_to_array1() {
         let series = Series::new("test".into(), vec![0i64, 1i64, 0i64, 1i64]);
@@ -241,6 +605,148 @@ mod tests {
         assert_eq!(array[2], 0);
     }
 
+    #[test]
+    fn test_dataframe_to_array2() {
+        let df = df!(
+            "Pclass" => [1i64, 3, 2],
+            "Fare" => [71.28, 7.25, 13.0],
+            "Alone" => [false, true, true],
+        )
+        .unwrap();
+
+        let x = dataframe_to_array2(&df).unwrap();
+        assert_eq!(
+            x,
+            arr2(&[[1.0, 71.28, 0.0], [3.0, 7.25, 1.0], [2.0, 13.0, 1.0]])
+        );
+        assert!(x.is_standard_layout());
+    }
+
+    #[test]
+    fn test_dataframe_to_array2_with_nans() {
+        let df = df!(
+            "Age" => [Some(22.0), None, Some(40.0)],
+            "Alone" => [Some(true), Some(false), None],
+        )
+        .unwrap();
+
+        let x = dataframe_to_array2_with_nans(&df).unwrap();
+        assert_eq!(x.row(0).to_vec(), [22.0, 1.0]);
+        assert!(x[[1, 0]].is_nan() && x[[1, 1]] == 0.0);
+        assert!(x[[2, 0]] == 40.0 && x[[2, 1]].is_nan());
+        assert!(dataframe_to_array2_with_nans(&df!("Name" => ["Braund"]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_dataframe_to_array2_reads_every_dtype_and_chunk() {
+        let first = df!(
+            "Pclass" => [1i32, 3],
+            "Age" => [Some(22.5f32), None],
+            "Sex_male" => [1u32, 0],
+            "Embarked" => [Some(2i8), None],
+        )
+        .unwrap();
+        let second = df!(
+            "Pclass" => [2i32],
+            "Age" => [Some(40.0f32)],
+            "Sex_male" => [1u32],
+            "Embarked" => [Some(1i8)],
+        )
+        .unwrap();
+        let df = first.vstack(&second).unwrap();
+        assert_eq!(df.first_col_n_chunks(), 2);
+
+        assert_eq!(
+            dataframe_to_array2(&df).unwrap(),
+            arr2(&[
+                [1.0, 22.5, 1.0, 2.0],
+                [3.0, 0.0, 0.0, 0.0],
+                [2.0, 40.0, 1.0, 1.0]
+            ])
+        );
+    }
+
+    #[test]
+    fn test_conversions_report_what_they_changed() {
+        let df = df!(
+            "Age" => [Some(22.0), None, None],
+            "Alone" => [Some(true), Some(false), None],
+        )
+        .unwrap();
+        let mut report = ConversionReport::new();
+
+        let x = dataframe_to_array2_with_report(&df, &mut report).unwrap();
+        assert_eq!(x.column(0), arr1(&[22.0, 0.0, 0.0]));
+        assert_eq!(report.count("Age", Adjustment::Imputed), 2);
+        assert_eq!(report.count("Alone", Adjustment::Imputed), 1);
+        assert_eq!(report.count("Alone", Adjustment::Coerced), 2);
+
+        let labels = Series::new("Survived".into(), [Some(1i64), None, Some(0)]);
+        series_to_array1_with_report(&labels, &mut report).unwrap();
+        assert_eq!(report.count("Survived", Adjustment::Imputed), 1);
+    }
+
+    #[test]
+    fn test_series_to_array1_f64() {
+        let series = Series::new("test".into(), vec![Some(1i64), None, Some(3i64)]);
+        let array = series_to_array1_f64(&series).unwrap();
+
+        assert_eq!(array, arr1(&[1.0, 0.0, 3.0]));
+    }
+
+    #[test]
+    fn test_series_conversions_read_every_chunk() {
+        let mut ids = Series::new("PassengerId".into(), [892i64, 893]);
+        ids.append(&Series::new("PassengerId".into(), [Some(894i64), None]))
+            .unwrap();
+        assert_eq!(ids.n_chunks(), 2);
+        assert_eq!(
+            series_to_array1_i64(&ids).unwrap(),
+            arr1(&[892, 893, 894, 0])
+        );
+
+        let mut fares = Series::new("Fare".into(), [Some(7.25f32), None]);
+        fares
+            .append(&Series::new("Fare".into(), [Some(71.5f32)]))
+            .unwrap();
+        assert_eq!(
+            series_to_array1_f64(&fares).unwrap(),
+            arr1(&[7.25, 0.0, 71.5])
+        );
+    }
+
+    #[test]
+    fn test_non_numeric_columns_are_errors() {
+        let df = df!("Age" => [22.0, 38.0], "Name" => ["Owen", "Florence"]).unwrap();
+
+        let error = dataframe_to_array2(&df).unwrap_err();
+        assert!(
+            matches!(&error, DataError::NonNumericColumn { name, .. } if name == "Name"),
+            "{error}"
+        );
+
+        let labels = Series::new("Survived".into(), [0.0, 1.0]);
+        assert!(matches!(
+            series_to_array1(&labels),
+            Err(DataError::NonIntegerColumn { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bad_labels_are_errors() {
+        let negative = Series::new("Survived".into(), [0i64, -1, 1]);
+        assert!(matches!(
+            series_to_array1(&negative),
+            Err(DataError::NegativeLabel { value: -1, .. })
+        ));
+
+        let gap = Series::new("Survived".into(), [0i64, 2, 2]);
+        assert!(matches!(
+            series_to_array1(&gap),
+            Err(DataError::MissingClass { class: 1, max: 2 })
+        ));
+    }
+
     #[test]
     fn test_train_test_split() {
         use ndarray::{arr1, arr2};
@@ -248,7 +754,14 @@ mod tests {
         let x = arr2(&[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0], [7.0, 8.0], [9.0, 10.0]]);
         let y = arr1(&[0, 1, 0, 1, 0]);
 
-        let (x_train, x_val, y_train, y_val) = train_test_split(x, y, 0.8);
+        let Split {
+            x_train,
+            x_val,
+            y_train,
+            y_val,
+            train_rows,
+            val_rows,
+        } = train_test_split(x, y, 0.8);
 
         // With 5 samples and 0.8 ratio, we should get 4 training and 1 validation
         assert_eq!(x_train.nrows(), 4);
@@ -261,5 +774,132 @@ mod tests {
         assert_eq!(x_val[[0, 0]], 9.0);
         assert_eq!(y_train[0], 0);
         assert_eq!(y_val[0], 0);
+        assert_eq!(train_rows, [0, 1, 2, 3]);
+        assert_eq!(val_rows, [4]);
+    }
+
+    #[test]
+    fn test_shuffled_train_test_split() {
+        use ndarray::arr2;
+
+        let x = arr2(&[
+            [0.0],
+            [1.0],
+            [2.0],
+            [3.0],
+            [4.0],
+            [5.0],
+            [6.0],
+            [7.0],
+            [8.0],
+            [9.0],
+        ]);
+        let y = arr1(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        let split = shuffled_train_test_split(x.clone(), y.clone(), 0.8, Seed::new(3));
+        let again = shuffled_train_test_split(x, y, 0.8, Seed::new(3));
+
+        assert_eq!(split.x_train, again.x_train);
+        assert_eq!(split.x_train.nrows(), 8);
+        assert_eq!(split.x_val.nrows(), 2);
+
+        // Rows and labels are shuffled together, and the row indices say where each came from
+        for ((row, &label), &index) in split
+            .x_train
+            .rows()
+            .into_iter()
+            .zip(&split.y_train)
+            .zip(&split.train_rows)
+        {
+            assert_eq!(row[0], label as f64);
+            assert_eq!(label, index);
+        }
+        for ((row, &label), &index) in split
+            .x_val
+            .rows()
+            .into_iter()
+            .zip(&split.y_val)
+            .zip(&split.val_rows)
+        {
+            assert_eq!(row[0], label as f64);
+            assert_eq!(label, index);
+        }
+    }
+
+    #[test]
+    fn test_three_way_split_in_order() {
+        let x = Array2::from_shape_fn((10, 1), |(i, _)| i as f64);
+        let y = Array1::from_iter(0..10);
+
+        let split = ThreeWaySplitter::new(0.6, 0.2).split(x, y).unwrap();
+
+        assert_eq!(split.y_train, arr1(&[0, 1, 2, 3, 4, 5]));
+        assert_eq!(split.y_validation, arr1(&[6, 7]));
+        assert_eq!(split.y_test, arr1(&[8, 9]));
+        assert_eq!(split.x_test.column(0), arr1(&[8.0, 9.0]));
+        assert_eq!(split.test_rows, [8, 9]);
+    }
+
+    #[test]
+    fn test_three_way_split_stratified() {
+        // 30% positive
+        let y = Array1::from_shape_fn(20, |i| usize::from(i % 10 < 3));
+        let x = Array2::from_shape_fn((20, 1), |(i, _)| i as f64);
+
+        let splitter = ThreeWaySplitter::new(0.5, 0.25).shuffle(4).stratify();
+        let split = splitter.split(x.clone(), y.clone()).unwrap();
+
+        // 14 negatives cut 7/4/3 (rounded) and 6 positives 3/2/1
+        assert_eq!(split.y_train.len(), 10);
+        assert_eq!(split.y_train.sum(), 3);
+        assert_eq!(split.y_validation.len(), 6);
+        assert_eq!(split.y_validation.sum(), 2);
+        assert_eq!(split.y_test.len(), 4);
+        assert_eq!(split.y_test.sum(), 1);
+
+        // Reproducible, rows stay with their labels, and no row is used twice
+        assert_eq!(splitter.split(x, y.clone()).unwrap().x_train, split.x_train);
+        let mut rows = Vec::new();
+        for (x, y_part) in [
+            (&split.x_train, &split.y_train),
+            (&split.x_validation, &split.y_validation),
+            (&split.x_test, &split.y_test),
+        ] {
+            for (row, &label) in x.column(0).iter().zip(y_part) {
+                assert_eq!(label, y[*row as usize]);
+                rows.push(*row as usize);
+            }
+        }
+        rows.sort_unstable();
+        assert_eq!(rows, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_three_way_split_rejects_bad_ratios() {
+        let x = Array2::zeros((4, 1));
+        let y = arr1(&[0, 1, 0, 1]);
+
+        assert!(
+            ThreeWaySplitter::new(0.8, 0.3)
+                .split(x.clone(), y.clone())
+                .is_err()
+        );
+        assert!(ThreeWaySplitter::new(-0.1, 0.3).split(x, y).is_err());
+    }
+
+    #[test]
+    fn test_standardizer() {
+        use ndarray::arr2;
+
+        let x = arr2(&[[1.0, 5.0], [3.0, 5.0]]);
+        let standardizer = Standardizer::fit(&x).unwrap();
+
+        // The constant column becomes zeros rather than NaN
+        assert_eq!(standardizer.transform(&x), arr2(&[[-1.0, 0.0], [1.0, 0.0]]));
+        assert_eq!(
+            standardizer.transform(&arr2(&[[5.0, 6.0]])),
+            arr2(&[[3.0, 1.0]])
+        );
+        assert!(Standardizer::fit(&Array2::zeros((0, 2))).is_err());
     }
 }
@@ -0,0 +1,196 @@
+//! Reading CSV files in fixed-size batches
+//!
+//! `LazyCsvReader` is the right tool when the result fits in memory. For files that don't,
+//! `CsvBatchReader` hands out the rows `batch_size` at a time, so only about one batch is held in
+//! memory at once (the file itself is memory-mapped, which the OS can page out).
+//!
+//! ```ignore
+//! for batch in CsvBatchReader::new(10_000).columns(["Heart Rate"]).read_path(path)? {
+//!     let batch = batch?;
+//!     // ...
+//! }
+//! ```
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use polars::{io::mmap::MmapBytesReader, prelude::*};
+
+/// Configures how a CSV file is split into batches
+pub struct CsvBatchReader {
+    batch_size: usize,
+    columns: Option<Vec<String>>,
+}
+
+impl CsvBatchReader {
+    /// Read `batch_size` rows at a time (the last batch may be shorter)
+    pub fn new(batch_size: usize) -> Self {
+        Self {
+            batch_size,
+            columns: None,
+        }
+    }
+
+    /// Only read these columns (default: all of them)
+    pub fn columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Read batches from the file at `path`, which must have a header row
+    pub fn read_path(&self, path: impl AsRef<Path>) -> Result<CsvBatches> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        self.read(file)
+    }
+
+    /// Read batches from any in-memory or file-backed CSV source with a header row
+    pub fn read(&self, reader: impl MmapBytesReader + 'static) -> Result<CsvBatches> {
+        if self.batch_size == 0 {
+            bail!("batch_size must be at least 1");
+        }
+
+        let columns = self.columns.as_ref().map(|columns| {
+            columns
+                .iter()
+                .map(|c| c.as_str().into())
+                .collect::<Arc<[_]>>()
+        });
+
+        let reader = CsvReadOptions::default()
+            .with_has_header(true)
+            .with_chunk_size(self.batch_size)
+            .with_columns(columns)
+            .into_reader_with_file_handle(Box::new(reader) as Box<dyn MmapBytesReader>)
+            .batched(None)?;
+
+        Ok(CsvBatches {
+            reader,
+            batch_size: self.batch_size,
+            buffer: DataFrame::empty(),
+            exhausted: false,
+        })
+    }
+}
+
+/// An iterator of DataFrames with exactly `batch_size` rows each, except possibly the last
+pub struct CsvBatches {
+    reader: OwnedBatchedCsvReader,
+    batch_size: usize,
+    /// Rows parsed but not handed out yet
+    buffer: DataFrame,
+    exhausted: bool,
+}
+
+impl Iterator for CsvBatches {
+    type Item = Result<DataFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Polars' chunks don't line up with `batch_size`, so buffer them and slice batches off
+        // the front
+        loop {
+            if self.buffer.height() >= self.batch_size {
+                let batch = self.buffer.slice(0, self.batch_size);
+                self.buffer = self.buffer.slice(self.batch_size as i64, usize::MAX);
+
+                return Some(Ok(batch));
+            }
+
+            if self.exhausted {
+                return (self.buffer.height() > 0).then(|| Ok(std::mem::take(&mut self.buffer)));
+            }
+
+            match self.reader.next_batches(1) {
+                Ok(Some(chunks)) => {
+                    for chunk in chunks {
+                        if let Err(e) = self.append(chunk) {
+                            self.exhausted = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Ok(None) => self.exhausted = true,
+                Err(e) => {
+                    self.exhausted = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+}
+
+impl CsvBatches {
+    fn append(&mut self, chunk: DataFrame) -> Result<()> {
+        if self.buffer.height() == 0 {
+            self.buffer = chunk;
+        } else {
+            self.buffer.vstack_mut(&chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn csv(n_rows: usize) -> Cursor<Vec<u8>> {
+        let mut csv = String::from("id,name,score\n");
+        for i in 0..n_rows {
+            csv.push_str(&format!("{i},row{i},{}.5\n", i * 10));
+        }
+
+        Cursor::new(csv.into_bytes())
+    }
+
+    #[test]
+    fn test_batches_are_fixed_size() {
+        let batches: Vec<DataFrame> = CsvBatchReader::new(3)
+            .read(csv(7))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        let heights: Vec<usize> = batches.iter().map(DataFrame::height).collect();
+        assert_eq!(heights, [3, 3, 1]);
+
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|df| {
+                df.column("id")
+                    .unwrap()
+                    .i64()
+                    .unwrap()
+                    .into_no_null_iter()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(ids, (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut batches = CsvBatchReader::new(100)
+            .columns(["score"])
+            .read(csv(2))
+            .unwrap();
+
+        let batch = batches.next().unwrap().unwrap();
+        assert_eq!(batch.get_column_names(), ["score"]);
+        assert_eq!(batch.height(), 2);
+        assert!(batches.next().is_none());
+    }
+
+    #[test]
+    fn test_zero_batch_size() {
+        assert!(CsvBatchReader::new(0).read(csv(1)).is_err());
+    }
+}
@@ -1,54 +1,823 @@
+use ndarray::Array2;
 use polars::{
+    error::PolarsResult,
     frame::DataFrame,
-    prelude::{DataFrameOps, Expr, LazyFrame, Series, col},
+    prelude::{
+        Column, DataFrameOps, DataType, Expr, FillNullStrategy, IntoLazy, LazyCsvReader,
+        LazyFileListReader, LazyFrame, PlPath, QuantileMethod, SchemaRef, Series,
+        SortMultipleOptions, SortOptions, col, len, lit, when,
+    },
 };
 
+use crate::{
+    conversion_report::{Adjustment, ConversionReport},
+    dataset::Dataset,
+    error::DataError,
+    feature_schema::{EncodedColumn, FeatureSchema},
+    null_report::{ColumnNulls, NullReport},
+    outliers::{ColumnOutliers, OUTLIER_COLUMN, OutlierAction, OutlierReport, OutlierRule},
+};
+
+/// How a data loader scans its CSV file
+///
+/// Polars memory-maps a local file rather than reading it up front, and only parses it when a
+/// query is collected. These settings trade speed for how much of it is held in memory while
+/// that happens, so a very large training file can still be used. Each loader takes its own
+/// options, e.g. `TrainingData::try_from_path_with`.
+#[derive(Debug, Clone, Default)]
+pub struct CsvScanOptions {
+    low_memory: bool,
+    chunk_size: Option<usize>,
+    n_rows: Option<usize>,
+}
+
+impl CsvScanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse in smaller pieces, for a lower peak memory use at some cost in speed
+    pub fn low_memory(mut self, low_memory: bool) -> Self {
+        self.low_memory = low_memory;
+        self
+    }
+
+    /// Parse this many rows at a time (default: chosen by Polars)
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Only read the first `n_rows` rows
+    pub fn n_rows(mut self, n_rows: usize) -> Self {
+        self.n_rows = Some(n_rows);
+        self
+    }
+
+    /// Whether the file should be rescanned for each query rather than kept in memory
+    pub fn is_low_memory(&self) -> bool {
+        self.low_memory
+    }
+
+    /// Scan the CSV (with a header row) at `path`
+    pub fn scan(&self, path: &str) -> PolarsResult<LazyFrame> {
+        let mut reader = LazyCsvReader::new(PlPath::from_str(path))
+            .with_has_header(true)
+            .with_low_memory(self.low_memory)
+            .with_n_rows(self.n_rows);
+        if let Some(chunk_size) = self.chunk_size {
+            reader = reader.with_chunk_size(chunk_size);
+        }
+
+        reader.finish()
+    }
+}
+
+/// How `Data::crosstab` sums up the values that fall in each cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// How many values aren't null
+    Count,
+    Sum,
+    Mean,
+    Median,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    fn apply(self, values: Expr) -> Expr {
+        match self {
+            Self::Count => values.count(),
+            Self::Sum => values.sum(),
+            Self::Mean => values.mean(),
+            Self::Median => values.median(),
+            Self::Min => values.min(),
+            Self::Max => values.max(),
+        }
+    }
+}
+
 pub trait Data {
     fn lazy_frame_cloned(&self) -> LazyFrame;
 
-    fn get_col_as_series(&self, col_name: &str) -> anyhow::Result<Series> {
+    /// Each column's name and dtype, in order, without collecting any rows
+    ///
+    /// Polars works it out from the query plan (for a CSV scan, from the header and the rows it
+    /// infers types from), so it's cheap enough to call before deciding how to treat a column.
+    ///
+    /// # Example
+    /// ```ignore
+    /// if training_data.schema()?.get("Age").is_some_and(DataType::is_numeric) {
+    ///     // ...
+    /// }
+    /// ```
+    fn schema(&self) -> Result<SchemaRef, DataError> {
+        Ok(self.lazy_frame_cloned().collect_schema()?)
+    }
+
+    /// The columns a feature matrix keeps as numbers, in order
+    fn numeric_columns(&self) -> Result<Vec<String>, DataError> {
+        Ok(self
+            .schema()?
+            .iter()
+            .filter(|(_, dtype)| dtype.is_numeric())
+            .map(|(name, _)| name.to_string())
+            .collect())
+    }
+
+    /// The columns a feature matrix turns into dummy columns (strings, booleans and anything
+    /// else that isn't a number), in order
+    fn categorical_columns(&self) -> Result<Vec<String>, DataError> {
+        Ok(self
+            .schema()?
+            .iter()
+            .filter(|(_, dtype)| !dtype.is_numeric())
+            .map(|(name, _)| name.to_string())
+            .collect())
+    }
+
+    /// A `MissingColumn` error for the first of `names` the data doesn't have
+    fn check_columns_exist<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), DataError> {
+        let schema = self.schema()?;
+
+        match names.into_iter().find(|name| !schema.contains(name)) {
+            Some(name) => Err(DataError::MissingColumn {
+                name: name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// How many values of each column are null, with each column's dtype
+    ///
+    /// Only the null counts and the number of rows are collected, not the data itself.
+    fn null_report(&self) -> Result<NullReport, DataError> {
+        let schema = self.schema()?;
+        let counts = self
+            .lazy_frame_cloned()
+            .select([len().alias("rows"), col("*").null_count()])
+            .collect()?;
+        let count = |column: &Column| -> Result<usize, DataError> {
+            Ok(column.get(0)?.extract::<usize>().unwrap_or_default())
+        };
+
+        Ok(NullReport {
+            rows: count(&counts.get_columns()[0])?,
+            columns: schema
+                .iter()
+                .zip(&counts.get_columns()[1..])
+                .map(|((name, dtype), nulls)| {
+                    Ok(ColumnNulls {
+                        name: name.to_string(),
+                        dtype: dtype.clone(),
+                        nulls: count(nulls)?,
+                    })
+                })
+                .collect::<Result<_, DataError>>()?,
+        })
+    }
+
+    /// Find the values of the numeric `columns` outside the bounds `rule` sets from them, and deal
+    /// with the rows they're in as `action` says
+    ///
+    /// Only the bounds and the counts for the report are collected, not the rows: the result is
+    /// still lazy, e.g. to pass to `TrainingData::from_lazy_frame`. Nulls are never outliers.
+    fn screen_outliers<'a>(
+        &self,
+        columns: impl IntoIterator<Item = &'a str>,
+        rule: OutlierRule,
+        action: OutlierAction,
+    ) -> Result<(LazyFrame, OutlierReport), DataError> {
+        let columns: Vec<&str> = columns.into_iter().collect();
+        self.check_columns_exist(columns.iter().copied())?;
+        let schema = self.schema()?;
+        for &name in &columns {
+            match schema.get(name) {
+                Some(dtype) if !dtype.is_numeric() => {
+                    return Err(DataError::NonNumericColumn {
+                        name: name.to_string(),
+                        dtype: dtype.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        let lazy_frame = self.lazy_frame_cloned();
+
+        // The statistics the bounds are set from, two per column, as plain aggregations: Polars
+        // 0.51 can panic on data in more than one chunk when an aggregation repeats another
+        let statistics: Vec<Expr> = columns
+            .iter()
+            .flat_map(|&name| {
+                let values = col(name).cast(DataType::Float64);
+                let (first, second) = match rule {
+                    OutlierRule::ZScore(_) => (values.clone().mean(), values.std(1)),
+                    OutlierRule::Iqr(_) => (
+                        values.clone().quantile(lit(0.25), QuantileMethod::Linear),
+                        values.quantile(lit(0.75), QuantileMethod::Linear),
+                    ),
+                };
+                [
+                    first.alias(format!("{name} 1")),
+                    second.alias(format!("{name} 2")),
+                ]
+            })
+            .collect();
+        let statistics = lazy_frame.clone().select(statistics).collect()?;
+        let statistic = |i: usize| -> Result<Option<f64>, DataError> {
+            Ok(statistics.get_columns()[i].get(0)?.extract::<f64>())
+        };
+        // A column of nulls (or of one value, for a z-score) has no bounds, and is left alone
+        let mut screened = Vec::new();
+        for (i, &name) in columns.iter().enumerate() {
+            let (Some(first), Some(second)) = (statistic(2 * i)?, statistic(2 * i + 1)?) else {
+                continue;
+            };
+            let (lower, upper) = match rule {
+                OutlierRule::ZScore(k) => (first - k * second, first + k * second),
+                OutlierRule::Iqr(_) if first == second => continue,
+                OutlierRule::Iqr(k) => {
+                    let iqr = second - first;
+                    (first - k * iqr, second + k * iqr)
+                }
+            };
+            screened.push((name, lower, upper));
+        }
+
+        let below =
+            |&(name, lower, _): &(&str, f64, f64)| col(name).lt(lit(lower)).fill_null(lit(false));
+        let above =
+            |&(name, _, upper): &(&str, f64, f64)| col(name).gt(lit(upper)).fill_null(lit(false));
+        let any_outlier = screened
+            .iter()
+            .map(|column| below(column).or(above(column)))
+            .reduce(Expr::or)
+            .unwrap_or(lit(false));
+
+        let mut counts = vec![
+            len().alias("rows"),
+            any_outlier.clone().sum().alias("rows with outliers"),
+        ];
+        for column in &screened {
+            counts.push(below(column).sum().alias(format!("{} below", column.0)));
+            counts.push(above(column).sum().alias(format!("{} above", column.0)));
+        }
+        let counts = lazy_frame.clone().select(counts).collect()?;
+        let count = |i: usize| -> Result<usize, DataError> {
+            Ok(counts.get_columns()[i]
+                .get(0)?
+                .extract::<usize>()
+                .unwrap_or_default())
+        };
+
+        let report = OutlierReport {
+            action,
+            rows: count(0)?,
+            rows_with_outliers: count(1)?,
+            columns: screened
+                .iter()
+                .enumerate()
+                .map(|(i, &(name, lower, upper))| {
+                    Ok(ColumnOutliers {
+                        name: name.to_string(),
+                        lower,
+                        upper,
+                        below: count(2 + 2 * i)?,
+                        above: count(3 + 2 * i)?,
+                    })
+                })
+                .collect::<Result<_, DataError>>()?,
+        };
+        let lazy_frame = match action {
+            OutlierAction::Flag => lazy_frame.with_column(any_outlier.alias(OUTLIER_COLUMN)),
+            OutlierAction::Exclude => lazy_frame.filter(any_outlier.not()),
+            OutlierAction::Winsorize => lazy_frame.with_columns(
+                screened
+                    .iter()
+                    .map(|column @ &(name, lower, upper)| {
+                        when(below(column))
+                            .then(lit(lower))
+                            .when(above(column))
+                            .then(lit(upper))
+                            .otherwise(col(name).cast(DataType::Float64))
+                            .alias(name)
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        Ok((lazy_frame, report))
+    }
+
+    /// The column `col_name`
+    ///
+    /// Selecting it before collecting lets Polars push the projection into a CSV scan, so only
+    /// this column is parsed from the file.
+    fn get_col_as_series(&self, col_name: &str) -> Result<Series, DataError> {
+        self.check_columns_exist([col_name])?;
         let df = self.lazy_frame_cloned().select([col(col_name)]).collect()?;
 
-        Ok(df.column(col_name)?.clone().as_series().unwrap().clone())
+        Ok(df.column(col_name)?.as_materialized_series().clone())
+    }
+
+    /// How often `target` (a 0/1 column, such as `Survived`) is 1 in each group of rows that
+    /// have the same values of `by`
+    ///
+    /// `by` can be any expressions, not just columns, e.g. `(col("Age") / lit(10)).floor()` to
+    /// group by decade of age; alias one to name its column. There's a row per group, sorted by
+    /// the groups, with a column per expression of `by`, then `count` (the rows with a `target`)
+    /// and `rate` (the fraction of those that are 1). Rows with a null key make a group of their
+    /// own.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let by_class_and_sex = training_data.rate_by("Survived", [col("Pclass"), col("Sex")])?;
+    /// println!("{by_class_and_sex}");
+    /// ```
+    fn rate_by<E>(&self, target: &str, by: E) -> Result<DataFrame, DataError>
+    where
+        E: AsRef<[Expr]>,
+    {
+        let by = by.as_ref();
+        let sources: Vec<_> = by
+            .iter()
+            .flat_map(|expr| expr.clone().meta().root_names())
+            .collect();
+        self.check_columns_exist(sources.iter().map(|name| name.as_str()).chain([target]))?;
+        let keys: Vec<Expr> = by
+            .iter()
+            .map(|expr| Ok(col(expr.clone().meta().output_name()?)))
+            .collect::<PolarsResult<_>>()?;
+
+        Ok(self
+            .lazy_frame_cloned()
+            .group_by(by)
+            .agg([
+                col(target).count().alias("count"),
+                col(target).cast(DataType::Float64).mean().alias("rate"),
+            ])
+            .sort_by_exprs(keys, SortMultipleOptions::default())
+            .collect()?)
+    }
+
+    /// A pivot table of `value_col`: a row per value of `row_col`, a column per value of
+    /// `col_col`, and each cell the `agg` of the values in both
+    ///
+    /// The rows and columns are sorted; the columns are named after their values as strings, and
+    /// rows whose `col_col` is null are left out. A cell no rows fall in is null, except for
+    /// `Count` and `Sum`, where it's 0.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // The survival rate by class and sex
+    /// let table = training_data.crosstab("Pclass", "Sex", "Survived", Aggregation::Mean)?;
+    /// ```
+    fn crosstab(
+        &self,
+        row_col: &str,
+        col_col: &str,
+        value_col: &str,
+        agg: Aggregation,
+    ) -> Result<DataFrame, DataError> {
+        self.check_columns_exist([row_col, col_col, value_col])?;
+        let lazy_frame = self.lazy_frame_cloned();
+        let column_key = || col(col_col).cast(DataType::String);
+
+        let headers = lazy_frame
+            .clone()
+            .select([column_key()
+                .drop_nulls()
+                .unique()
+                .sort(SortOptions::default())])
+            .collect()?;
+        let cells: Vec<Expr> = headers
+            .column(col_col)?
+            .str()?
+            .into_no_null_iter()
+            .map(|header| {
+                let values = col(value_col).filter(column_key().eq(lit(header)));
+                agg.apply(values).alias(header)
+            })
+            .collect();
+
+        Ok(lazy_frame
+            .group_by([col(row_col)])
+            .agg(cells)
+            .sort([row_col], SortMultipleOptions::default())
+            .collect()?)
+    }
+
+    fn get_feature_matrix<E>(&self, exprs: E) -> Result<DataFrame, DataError>
+    where
+        E: AsRef<[Expr]>,
+    {
+        self.get_feature_matrix_with_report(exprs, &mut ConversionReport::new())
+    }
+
+    /// `get_feature_matrix`, recording the nulls it fills with 0 in `report`
+    ///
+    /// Nulls in string columns aren't counted, since they get a `null` dummy column of their own
+    /// rather than a made-up value.
+    ///
+    /// The data is queried once, for just the columns `exprs` read, and the nulls and dummies are
+    /// dealt with in memory afterwards; a CSV scan parses those columns and no others.
+    fn get_feature_matrix_with_report<E>(
+        &self,
+        exprs: E,
+        report: &mut ConversionReport,
+    ) -> Result<DataFrame, DataError>
+    where
+        E: AsRef<[Expr]>,
+    {
+        Ok(self.fit_feature_matrix(exprs, report)?.0)
+    }
+
+    /// `get_feature_matrix_with_report`, also returning the schema of how the features were
+    /// encoded
+    ///
+    /// Encode other data with `get_encoded_features` and this schema rather than with
+    /// `get_feature_matrix` again: the dummy columns are then the ones trained on, in the same
+    /// order, whatever categories the other data has.
+    fn fit_feature_matrix<E>(
+        &self,
+        exprs: E,
+        report: &mut ConversionReport,
+    ) -> Result<(DataFrame, FeatureSchema), DataError>
+    where
+        E: AsRef<[Expr]>,
+    {
+        encode_feature_matrix(self, exprs.as_ref(), false, report)
     }
 
-    fn get_feature_matrix<E>(&self, exprs: E) -> anyhow::Result<DataFrame>
+    /// `fit_feature_matrix`, but leaving numeric nulls as they are, for a model that handles
+    /// missing values itself (convert with `Dataset::from_frame_keeping_missing`)
+    ///
+    /// The schema keeps them too (see `FeatureSchema::keep_missing`), so other data encoded with
+    /// it gets NaN for a null rather than 0.
+    fn fit_feature_matrix_keeping_missing<E>(
+        &self,
+        exprs: E,
+        report: &mut ConversionReport,
+    ) -> Result<(DataFrame, FeatureSchema), DataError>
     where
         E: AsRef<[Expr]>,
     {
-        let df = self.lazy_frame_cloned().select(exprs.as_ref()).collect()?;
-
-        // Separate categorical and numeric columns
-        let mut result_dfs = Vec::new();
-
-        for col_name in df.get_column_names() {
-            let column = df.column(col_name)?;
-
-            // Only create dummies for string/categorical columns
-            if column.dtype().is_numeric() {
-                // Keep numeric columns as-is, but fill nulls with 0
-                let filled = self
-                    .lazy_frame_cloned()
-                    .select([col(col_name.as_str()).fill_null(0)])
-                    .collect()?;
-                result_dfs.push(filled);
+        encode_feature_matrix(self, exprs.as_ref(), true, report)
+    }
+
+    /// Encode the features the way `schema` says, e.g. the test set with the schema
+    /// `fit_feature_matrix` returned for the training set
+    ///
+    /// Only the schema's source columns are read. A category the schema doesn't have is ignored,
+    /// and one the data doesn't have gets a column of zeros, so the result always has the columns
+    /// the schema was fitted with. Numeric nulls are filled with 0 and recorded in `report`.
+    fn get_encoded_features(
+        &self,
+        schema: &FeatureSchema,
+        report: &mut ConversionReport,
+    ) -> Result<Array2<f64>, DataError> {
+        let sources = schema.sources();
+        self.check_columns_exist(sources.iter().copied())?;
+        let df = self
+            .lazy_frame_cloned()
+            .select(sources.into_iter().map(col).collect::<Vec<_>>())
+            .collect()?;
+
+        schema.encode_frame_with_report(&df, report)
+    }
+
+    /// `get_encoded_features`, as a `Dataset` whose feature names are the schema's columns
+    ///
+    /// Add labels and ids with `Dataset::with_labels` and `Dataset::with_ids`.
+    fn get_dataset(
+        &self,
+        schema: &FeatureSchema,
+        report: &mut ConversionReport,
+    ) -> Result<Dataset, DataError> {
+        Ok(Dataset {
+            x: self.get_encoded_features(schema, report)?,
+            feature_names: schema.names(),
+            y: None,
+            ids: None,
+        })
+    }
+}
+
+/// A DataFrame already in memory, e.g. to fit with `RandomForestClassifier::fit_frame`
+impl Data for DataFrame {
+    fn lazy_frame_cloned(&self) -> LazyFrame {
+        self.clone().lazy()
+    }
+}
+
+/// `Data::fit_feature_matrix`, filling numeric nulls with 0 unless `keep_missing`
+fn encode_feature_matrix<D: Data + ?Sized>(
+    data: &D,
+    exprs: &[Expr],
+    keep_missing: bool,
+    report: &mut ConversionReport,
+) -> Result<(DataFrame, FeatureSchema), DataError> {
+    if exprs.is_empty() {
+        return Err(DataError::NoFeatures);
+    }
+    let roots: Vec<_> = exprs
+        .iter()
+        .flat_map(|expr| expr.clone().meta().root_names())
+        .collect();
+    data.check_columns_exist(roots.iter().map(|name| name.as_str()))?;
+
+    let df = data.lazy_frame_cloned().select(exprs).collect()?;
+
+    // Separate categorical and numeric columns
+    let mut result_dfs = Vec::new();
+    let mut encoded_columns = Vec::new();
+
+    for column in df.get_columns() {
+        let source = column.name().to_string();
+        // Only create dummies for string/categorical columns
+        if column.dtype().is_numeric() {
+            // Keep numeric columns as-is, but fill nulls with 0 unless they're to be kept
+            if keep_missing {
+                result_dfs.push(DataFrame::new(vec![column.clone()])?);
             } else {
-                // Create dummy variables for categorical columns
-                let col_df = self
-                    .lazy_frame_cloned()
-                    .select([col(col_name.as_str())])
-                    .collect()?;
-                let dummies = col_df.to_dummies(None, false, false)?;
-                result_dfs.push(dummies);
+                report.record(&source, Adjustment::Imputed, column.null_count());
+                let filled = column.fill_null(FillNullStrategy::Zero)?;
+                result_dfs.push(DataFrame::new(vec![filled])?);
             }
+            encoded_columns.push(EncodedColumn::Numeric { source });
+        } else {
+            // Create dummy variables for categorical columns, named `<source>_<category>`
+            let col_df = DataFrame::new(vec![column.clone()])?;
+            let dummies = col_df.to_dummies(None, false, false)?;
+            for dummy in dummies.get_column_names() {
+                encoded_columns.push(EncodedColumn::Dummy {
+                    source: source.clone(),
+                    category: dummy[source.len() + 1..].to_string(),
+                });
+            }
+            result_dfs.push(dummies);
         }
+    }
+
+    // Horizontally concatenate all dataframes
+    let mut result = result_dfs[0].clone();
+    for df in &result_dfs[1..] {
+        result = result.hstack(df.get_columns())?;
+    }
+
+    Ok((
+        result,
+        FeatureSchema::new(encoded_columns).keep_missing(keep_missing),
+    ))
+}
 
-        // Horizontally concatenate all dataframes
-        let mut result = result_dfs[0].clone();
-        for df in &result_dfs[1..] {
-            result = result.hstack(df.get_columns())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversions::dataframe_to_array2;
+    use ndarray::arr2;
+    use polars::prelude::{IntoLazy, df};
+
+    struct Frame(LazyFrame);
+
+    impl Data for Frame {
+        fn lazy_frame_cloned(&self) -> LazyFrame {
+            self.0.clone()
         }
+    }
+
+    #[test]
+    fn test_missing_columns_are_named() {
+        let data = Frame(df!("Age" => [22i64, 38]).unwrap().lazy());
+
+        assert!(matches!(
+            data.get_col_as_series("Survived"),
+            Err(DataError::MissingColumn { name }) if name == "Survived"
+        ));
+        assert!(matches!(
+            data.get_feature_matrix([col("Age"), col("Sex").alias("Gender")]),
+            Err(DataError::MissingColumn { name }) if name == "Sex"
+        ));
+        assert!(matches!(
+            data.get_feature_matrix([]),
+            Err(DataError::NoFeatures)
+        ));
+        assert_eq!(data.get_col_as_series("Age").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rates_by_expressions() {
+        let passengers = df!(
+            "Sex" => ["male", "female", "female", "male", "male"],
+            "Age" => [Some(22.0), Some(38.0), Some(35.0), None, Some(24.0)],
+            "Survived" => [0i64, 1, 1, 0, 1],
+        )
+        .unwrap();
+
+        let by_sex = passengers.rate_by("Survived", [col("Sex")]).unwrap();
+        assert_eq!(by_sex.get_column_names(), ["Sex", "count", "rate"]);
+        assert_eq!(
+            by_sex
+                .column("Sex")
+                .unwrap()
+                .str()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            [Some("female"), Some("male")]
+        );
+        let rates = by_sex.column("rate").unwrap().f64().unwrap().to_vec();
+        assert_eq!(rates, [Some(1.0), Some(1.0 / 3.0)]);
+
+        let decade = (col("Age") / lit(10)).floor().alias("AgeDecade");
+        let by_decade = passengers.rate_by("Survived", [decade]).unwrap();
+        assert_eq!(
+            by_decade
+                .column("AgeDecade")
+                .unwrap()
+                .f64()
+                .unwrap()
+                .to_vec(),
+            [None, Some(2.0), Some(3.0)]
+        );
+        assert_eq!(
+            by_decade.column("rate").unwrap().f64().unwrap().to_vec(),
+            [Some(0.0), Some(0.5), Some(1.0)]
+        );
+
+        assert!(matches!(
+            passengers.rate_by("Survived", [col("Pclass")]),
+            Err(DataError::MissingColumn { name }) if name == "Pclass"
+        ));
+    }
+
+    #[test]
+    fn test_crosstab() {
+        let passengers = df!(
+            "Pclass" => [3i64, 1, 3, 1, 3, 2],
+            "Sex" => [
+                Some("male"), Some("female"), Some("female"), Some("male"), Some("male"), None,
+            ],
+            "Survived" => [0i64, 1, 1, 0, 1, 1],
+        )
+        .unwrap();
+
+        let rates = passengers
+            .crosstab("Pclass", "Sex", "Survived", Aggregation::Mean)
+            .unwrap();
+        assert_eq!(rates.get_column_names(), ["Pclass", "female", "male"]);
+        let column = |table: &DataFrame, name| {
+            let values = table
+                .column(name)
+                .unwrap()
+                .cast(&DataType::Float64)
+                .unwrap();
+            values.f64().unwrap().to_vec()
+        };
+        assert_eq!(column(&rates, "Pclass"), [Some(1.0), Some(2.0), Some(3.0)]);
+        assert_eq!(column(&rates, "female"), [Some(1.0), None, Some(1.0)]);
+        assert_eq!(column(&rates, "male"), [Some(0.0), None, Some(0.5)]);
+
+        let counts = passengers
+            .crosstab("Pclass", "Sex", "Survived", Aggregation::Count)
+            .unwrap();
+        assert_eq!(column(&counts, "male"), [Some(1.0), Some(0.0), Some(2.0)]);
+    }
+
+    #[test]
+    fn test_schema_sorts_numeric_from_categorical_columns() {
+        let passengers = df!(
+            "Pclass" => [3i64, 1],
+            "Sex" => ["male", "female"],
+            "Fare" => [7.25, 71.28],
+            "Alone" => [true, false],
+        )
+        .unwrap();
+        // `DataFrame::schema` shadows the trait method of the same name
+        let schema = Data::schema(&passengers).unwrap();
+        assert_eq!(schema.get("Sex"), Some(&DataType::String));
+        assert_eq!(passengers.numeric_columns().unwrap(), ["Pclass", "Fare"]);
+        assert_eq!(passengers.categorical_columns().unwrap(), ["Sex", "Alone"]);
+    }
+
+    #[test]
+    fn test_csv_scan_options() {
+        let path = std::env::temp_dir().join("titanic-ml-scan-options-test.csv");
+        std::fs::write(&path, "Age,Fare\n22,7.25\n38,71.28\n26,7.92\n").unwrap();
+
+        let df = CsvScanOptions::new()
+            .low_memory(true)
+            .chunk_size(1)
+            .n_rows(2)
+            .scan(&path.to_string_lossy())
+            .unwrap()
+            .collect()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(df.shape(), (2, 2));
+    }
+
+    #[test]
+    fn test_feature_matrix_reports_filled_nulls() {
+        let data = Frame(
+            df!(
+                "Age" => [Some(22.0), None, Some(26.0)],
+                "Sex" => [Some("male"), None, Some("female")],
+            )
+            .unwrap()
+            .lazy(),
+        );
+        let mut report = ConversionReport::new();
+
+        let x = data
+            .get_feature_matrix_with_report([col("Age"), col("Sex")], &mut report)
+            .unwrap();
+        assert_eq!(x.column("Age").unwrap().null_count(), 0);
+        assert_eq!(
+            x.get_column_names(),
+            ["Age", "Sex_female", "Sex_male", "Sex_null"]
+        );
+        assert_eq!(report.count("Age", Adjustment::Imputed), 1);
+        assert_eq!(report.total(Adjustment::Imputed), 1);
+    }
+
+    #[test]
+    fn test_encoded_features_follow_the_fitted_schema() {
+        let training = Frame(
+            df!(
+                "Age" => [Some(22.0), None, Some(26.0)],
+                "Sex" => ["male", "female", "male"],
+            )
+            .unwrap()
+            .lazy(),
+        );
+        let (x, schema) = training
+            .fit_feature_matrix([col("Age"), col("Sex")], &mut ConversionReport::new())
+            .unwrap();
+        assert_eq!(schema.width(), x.width());
+        assert_eq!(schema.sources(), ["Age", "Sex"]);
+        assert_eq!(
+            dataframe_to_array2(&x).unwrap(),
+            training
+                .get_encoded_features(&schema, &mut ConversionReport::new())
+                .unwrap()
+        );
+
+        // No women, a category never trained on, and a column the schema doesn't use
+        let testing = Frame(
+            df!(
+                "Name" => ["a", "b"],
+                "Sex" => [Some("male"), None],
+                "Age" => [None, Some(40.0)],
+            )
+            .unwrap()
+            .lazy(),
+        );
+        let mut report = ConversionReport::new();
+        let x_test = testing.get_encoded_features(&schema, &mut report).unwrap();
+        assert_eq!(x_test, arr2(&[[0.0, 0.0, 1.0], [40.0, 0.0, 0.0]]));
+        assert_eq!(report.count("Age", Adjustment::Imputed), 1);
+
+        assert!(matches!(
+            Frame(df!("Age" => [1.0]).unwrap().lazy())
+                .get_encoded_features(&schema, &mut ConversionReport::new()),
+            Err(DataError::MissingColumn { name }) if name == "Sex"
+        ));
+    }
+
+    #[test]
+    fn test_feature_matrix_scans_only_the_columns_it_reads() {
+        let path = std::env::temp_dir().join("titanic-ml-projection-test.csv");
+        std::fs::write(
+            &path,
+            "Name,Age,Sex\n\"Braund, Mr. Owen\",22,male\n\"Cumings, Mrs. John\",,female\n",
+        )
+        .unwrap();
+        let data = Frame(CsvScanOptions::new().scan(&path.to_string_lossy()).unwrap());
+
+        let query = data.lazy_frame_cloned().select([col("Age"), col("Sex")]);
+        assert!(query.describe_optimized_plan().unwrap().contains("2/3"));
+
+        // Aliased columns come from the one query, not from looking their names up again
+        let mut report = ConversionReport::new();
+        let x = data
+            .get_feature_matrix_with_report(
+                [col("Age").alias("Years"), col("Sex").alias("Gender")],
+                &mut report,
+            )
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-        Ok(result)
+        assert_eq!(
+            x.get_column_names(),
+            ["Years", "Gender_female", "Gender_male"]
+        );
+        assert_eq!(x.column("Years").unwrap().null_count(), 0);
+        assert_eq!(report.count("Years", Adjustment::Imputed), 1);
     }
 }
@@ -0,0 +1,412 @@
+//! Features, labels, feature names and row ids, kept together
+//!
+//! Converting a DataFrame into arrays for a model keeps the numbers and drops the rest: which
+//! column of the matrix is which feature, and which row is which passenger. Passing the matrix,
+//! labels, names and ids around separately means keeping four values lined up by hand. A
+//! `Dataset` holds them together, checks they agree in length, and can be fitted and predicted
+//! on directly.
+//!
+//! For a DataFrame of raw columns, `RandomForestClassifier::fit_frame` skips even that: it
+//! encodes every column but the target, fits, and returns a `SavedModel` whose feature schema
+//! encodes any later frame the same way for `SavedModel::predict_frame`, or for
+//! `SavedModel::predict_df` to hand the predictions back as a column of that frame.
+
+use ndarray::{Array1, Array2};
+use polars::{
+    frame::DataFrame,
+    prelude::{NamedFrom, Series, col},
+};
+
+use crate::{
+    conversion_report::ConversionReport,
+    conversions::{
+        dataframe_to_array2_with_nans, dataframe_to_array2_with_report, series_to_array1_i64,
+        series_to_array1_with_report,
+    },
+    data::Data,
+    error::{DataError, FitError},
+    missing::Missing,
+    random_forest::{FittedRandomForest, RandomForestClassifier},
+    saved_model::SavedModel,
+};
+
+/// A feature matrix, with the names of its columns and, where the data has them, a label and an
+/// id for each row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    /// Rows are samples, columns are features
+    pub x: Array2<f64>,
+    /// The name of each column of `x`, e.g. `Sex_male`
+    pub feature_names: Vec<String>,
+    /// One label per row, or `None` for data without labels (e.g. a test set)
+    pub y: Option<Array1<usize>>,
+    /// Each row's id (e.g. its `PassengerId`), or `None` if it wasn't asked for
+    pub ids: Option<Array1<i64>>,
+}
+
+impl Dataset {
+    /// A dataset of the (numeric, already encoded) columns of `df`, without labels or ids,
+    /// recording the values converting it changed in `report`
+    pub fn from_frame(df: &DataFrame, report: &mut ConversionReport) -> Result<Self, DataError> {
+        Ok(Self::with_names(
+            dataframe_to_array2_with_report(df, report)?,
+            df,
+        ))
+    }
+
+    /// `from_frame`, but with nulls as NaN rather than 0.0, for a forest fitted with
+    /// `Missing::Learn` (e.g. on `Data::fit_feature_matrix_keeping_missing`'s frame)
+    pub fn from_frame_keeping_missing(df: &DataFrame) -> Result<Self, DataError> {
+        Ok(Self::with_names(dataframe_to_array2_with_nans(df)?, df))
+    }
+
+    /// Take the labels from `data`'s `column`, which must have one per row
+    pub fn with_labels(
+        mut self,
+        data: &(impl Data + ?Sized),
+        column: &str,
+        report: &mut ConversionReport,
+    ) -> Result<Self, DataError> {
+        let y = series_to_array1_with_report(&data.get_col_as_series(column)?, report)?;
+        self.check_length(y.len())?;
+        self.y = Some(y);
+
+        Ok(self)
+    }
+
+    /// Take the row ids from `data`'s `column`, which must have one per row
+    pub fn with_ids(
+        mut self,
+        data: &(impl Data + ?Sized),
+        column: &str,
+    ) -> Result<Self, DataError> {
+        let ids = series_to_array1_i64(&data.get_col_as_series(column)?)?;
+        self.check_length(ids.len())?;
+        self.ids = Some(ids);
+
+        Ok(self)
+    }
+
+    pub fn n_samples(&self) -> usize {
+        self.x.nrows()
+    }
+
+    pub fn n_features(&self) -> usize {
+        self.x.ncols()
+    }
+
+    /// The labels, or a `DataError::NoLabels` if the dataset doesn't have any
+    pub fn labels(&self) -> Result<&Array1<usize>, DataError> {
+        self.y.as_ref().ok_or(DataError::NoLabels)
+    }
+
+    /// The feature matrix and labels, e.g. to split, or a `DataError::NoLabels`
+    pub fn into_labelled_arrays(self) -> Result<(Array2<f64>, Array1<usize>), DataError> {
+        match self.y {
+            Some(y) => Ok((self.x, y)),
+            None => Err(DataError::NoLabels),
+        }
+    }
+
+    /// `x`, converted from `df`, named after its columns
+    fn with_names(x: Array2<f64>, df: &DataFrame) -> Self {
+        Self {
+            x,
+            feature_names: df
+                .get_column_names()
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            y: None,
+            ids: None,
+        }
+    }
+
+    fn check_length(&self, n_values: usize) -> Result<(), DataError> {
+        if n_values != self.n_samples() {
+            return Err(DataError::LengthMismatch {
+                samples: self.n_samples(),
+                labels: n_values,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl RandomForestClassifier {
+    /// Fit on a dataset's features and labels, like `fit`, keeping its feature names in the
+    /// forest's metadata
+    ///
+    /// A dataset without labels is a `DataError::NoLabels`.
+    pub fn fit_dataset(&self, dataset: &Dataset) -> Result<FittedRandomForest<usize>, FitError> {
+        let forest = self.fit(&dataset.x, dataset.labels()?)?;
+
+        Ok(forest.with_feature_names(dataset.feature_names.clone())?)
+    }
+
+    /// Fit on a DataFrame of raw columns, predicting its `target` column from all the others
+    ///
+    /// The features are encoded as `Data::fit_feature_matrix` encodes them (string columns become
+    /// dummies, numeric nulls become 0, or NaN with `Missing::Learn`), and the returned model keeps
+    /// the schema of that encoding, so `SavedModel::predict_frame` takes frames with the same raw
+    /// columns.
+    pub fn fit_frame(&self, df: &DataFrame, target: &str) -> Result<SavedModel, FitError> {
+        df.check_columns_exist([target])?;
+        let features: Vec<_> = df
+            .get_column_names()
+            .into_iter()
+            .filter(|name| name.as_str() != target)
+            .map(|name| col(name.clone()))
+            .collect();
+
+        let mut report = ConversionReport::new();
+        let (feature_schema, dataset) = if self.missing == Missing::Learn {
+            let (x_df, schema) = df.fit_feature_matrix_keeping_missing(features, &mut report)?;
+            (schema, Dataset::from_frame_keeping_missing(&x_df)?)
+        } else {
+            let (x_df, schema) = df.fit_feature_matrix(features, &mut report)?;
+            (schema, Dataset::from_frame(&x_df, &mut report)?)
+        };
+        let dataset = dataset.with_labels(df, target, &mut report)?;
+
+        Ok(SavedModel::new(feature_schema, self.fit_dataset(&dataset)?))
+    }
+}
+
+impl FittedRandomForest<usize> {
+    /// Predict a label for each row of a dataset, like `predict`
+    ///
+    /// A dataset whose features aren't the ones the forest was fitted on, in the same order, is
+    /// refused (see `check_feature_names`) rather than predicted from the wrong columns.
+    pub fn predict_dataset(&self, dataset: &Dataset) -> Result<Array1<usize>, DataError> {
+        self.check_feature_names(&dataset.feature_names)?;
+
+        Ok(self.predict(&dataset.x))
+    }
+}
+
+impl SavedModel {
+    /// Predict a label for each row of a DataFrame of raw columns, encoded with the model's
+    /// feature schema
+    ///
+    /// Columns the schema doesn't use (such as the target, or an id) are ignored; a column it
+    /// needs and `df` doesn't have is a `DataError::MissingColumn`.
+    pub fn predict_frame(&self, df: &DataFrame) -> Result<Array1<usize>, DataError> {
+        let x = self
+            .feature_schema()
+            .encode_frame_with_report(df, &mut ConversionReport::new())?;
+
+        Ok(self.forest().predict(&x))
+    }
+
+    /// `df` with a `prediction` column on the right, so each prediction stays next to the row's
+    /// other columns (its `PassengerId`, say)
+    pub fn predict_df(&self, df: DataFrame) -> Result<DataFrame, DataError> {
+        self.append_predictions(df, false)
+    }
+
+    /// `predict_df`, with a `probability` column too: the share of trees that voted for the
+    /// prediction
+    ///
+    /// To get every class's probability instead, see `score::score`.
+    pub fn predict_df_with_probability(&self, df: DataFrame) -> Result<DataFrame, DataError> {
+        self.append_predictions(df, true)
+    }
+
+    fn append_predictions(
+        &self,
+        mut df: DataFrame,
+        with_probability: bool,
+    ) -> Result<DataFrame, DataError> {
+        let x = self
+            .feature_schema()
+            .encode_frame_with_report(&df, &mut ConversionReport::new())?;
+        let forest = self.forest();
+        let probabilities = forest.predict_proba(&x);
+        let predictions = forest.most_likely(&probabilities);
+
+        let labels: Vec<i64> = predictions.iter().map(|&p| p as i64).collect();
+        df.with_column(Series::new("prediction".into(), labels))?;
+        if with_probability {
+            // The prediction is the most likely class, so its probability is the row's largest
+            let probability: Vec<f64> = probabilities
+                .rows()
+                .into_iter()
+                .map(|row| row.fold(0.0, |max, &p| p.max(max)))
+                .collect();
+            df.with_column(Series::new("probability".into(), probability))?;
+        }
+
+        Ok(df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed::Seed;
+    use ndarray::arr1;
+    use polars::prelude::{IntoLazy, LazyFrame, col, df};
+
+    struct Frame(LazyFrame);
+
+    impl Data for Frame {
+        fn lazy_frame_cloned(&self) -> LazyFrame {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn test_dataset_keeps_names_labels_and_ids() {
+        let data = Frame(
+            df!(
+                "PassengerId" => [1i64, 2, 3, 4, 5, 6],
+                "Sex" => ["male", "female", "male", "female", "male", "female"],
+                "Age" => [Some(22.0), Some(38.0), None, Some(35.0), Some(54.0), Some(2.0)],
+                "Survived" => [0i64, 1, 0, 1, 0, 1],
+            )
+            .unwrap()
+            .lazy(),
+        );
+        let mut report = ConversionReport::new();
+        let (x_df, schema) = data
+            .fit_feature_matrix([col("Sex"), col("Age")], &mut report)
+            .unwrap();
+
+        let training = Dataset::from_frame(&x_df, &mut report)
+            .unwrap()
+            .with_labels(&data, "Survived", &mut report)
+            .unwrap()
+            .with_ids(&data, "PassengerId")
+            .unwrap();
+        assert_eq!(training.feature_names, ["Sex_female", "Sex_male", "Age"]);
+        assert_eq!(training.n_features(), 3);
+        assert_eq!(training.labels().unwrap(), arr1(&[0, 1, 0, 1, 0, 1]));
+        assert_eq!(training.ids, Some(arr1(&[1, 2, 3, 4, 5, 6])));
+
+        // Encoded with the schema, the same data gets the same names and features
+        let encoded = data.get_dataset(&schema, &mut report).unwrap();
+        assert_eq!(encoded.feature_names, training.feature_names);
+        assert_eq!(encoded.x, training.x);
+        assert!(matches!(encoded.labels(), Err(DataError::NoLabels)));
+
+        let model = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(1))
+            .fit_dataset(&training)
+            .unwrap();
+        assert_eq!(
+            model.predict_dataset(&encoded).unwrap(),
+            model.predict(&training.x)
+        );
+        let metadata = model.metadata().unwrap();
+        assert_eq!(
+            metadata.feature_names.as_deref(),
+            Some(&training.feature_names[..])
+        );
+
+        // The same columns in another order would be predicted from the wrong features
+        let mut shuffled = encoded.clone();
+        shuffled.feature_names.swap(0, 2);
+        assert!(matches!(
+            model.predict_dataset(&shuffled),
+            Err(DataError::FeatureOrder { .. })
+        ));
+        assert!(matches!(
+            RandomForestClassifier::new().fit_dataset(&encoded),
+            Err(FitError::Data(DataError::NoLabels))
+        ));
+    }
+
+    #[test]
+    fn test_fit_and_predict_a_frame() {
+        let train = df!(
+            "Sex" => ["male", "female", "male", "female", "male", "female"],
+            "Age" => [Some(22.0), Some(38.0), None, Some(35.0), Some(54.0), Some(2.0)],
+            "Survived" => [0i64, 1, 0, 1, 0, 1],
+        )
+        .unwrap();
+        let forest = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(1));
+
+        let model = forest.fit_frame(&train, "Survived").unwrap();
+        assert_eq!(
+            model.feature_schema().names(),
+            ["Sex_female", "Sex_male", "Age"]
+        );
+
+        // The same as fitting on the arrays by hand
+        let mut report = ConversionReport::new();
+        let (x_df, _) = train
+            .fit_feature_matrix([col("Sex"), col("Age")], &mut report)
+            .unwrap();
+        let by_hand = Dataset::from_frame(&x_df, &mut report)
+            .unwrap()
+            .with_labels(&train, "Survived", &mut report)
+            .unwrap();
+        assert_eq!(
+            model.predict_frame(&train).unwrap(),
+            forest.fit_dataset(&by_hand).unwrap().predict(&by_hand.x)
+        );
+
+        // Later frames only need the raw feature columns, in any order
+        let test = df!(
+            "Age" => [30.0, 4.0],
+            "Sex" => ["female", "male"],
+        )
+        .unwrap();
+        assert_eq!(model.predict_frame(&test).unwrap().len(), 2);
+        assert!(matches!(
+            model.predict_frame(&test.drop("Sex").unwrap()),
+            Err(DataError::MissingColumn { name }) if name == "Sex"
+        ));
+        assert!(matches!(
+            forest.fit_frame(&train, "Survivors"),
+            Err(FitError::Data(DataError::MissingColumn { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_predictions_are_appended_to_the_frame() {
+        let train = df!(
+            "PassengerId" => [1i64, 2, 3, 4, 5, 6],
+            "Sex" => ["male", "female", "male", "female", "male", "female"],
+            "Survived" => [0i64, 1, 0, 1, 0, 1],
+        )
+        .unwrap();
+        let model = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(1))
+            .fit_frame(&train.drop("PassengerId").unwrap(), "Survived")
+            .unwrap();
+        let test = df!(
+            "PassengerId" => [7i64, 8],
+            "Sex" => ["female", "male"],
+        )
+        .unwrap();
+
+        let predicted = model.predict_df(test.clone()).unwrap();
+        assert_eq!(
+            predicted.get_column_names(),
+            ["PassengerId", "Sex", "prediction"]
+        );
+        let prediction = predicted.column("prediction").unwrap().i64().unwrap();
+        assert_eq!(prediction.to_vec(), [Some(1), Some(0)]);
+
+        let with_probability = model.predict_df_with_probability(test).unwrap();
+        assert_eq!(
+            with_probability.column("prediction").unwrap(),
+            predicted.column("prediction").unwrap()
+        );
+        let probability = with_probability.column("probability").unwrap();
+        assert!(
+            probability
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .all(|p| (0.5..=1.0).contains(&p))
+        );
+    }
+}
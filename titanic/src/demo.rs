@@ -0,0 +1,54 @@
+//! A small Titanic sample built into the crate, so `titanic-ml demo` runs without `data/`
+//!
+//! The first 200 passengers of Kaggle's `train.csv` and the first 50 of `test.csv`, unchanged.
+//! Too few to tune anything seriously, but enough to see every stage of the pipeline run.
+
+use std::io::Cursor;
+
+use anyhow::Result;
+use polars::prelude::*;
+
+use crate::{testing_data::TestingData, training_data::TrainingData};
+
+const TRAIN_CSV: &str = include_str!("../data/demo/train.csv");
+const TEST_CSV: &str = include_str!("../data/demo/test.csv");
+
+/// The embedded sample of `train.csv`, with `Survived` labels
+pub fn training_data() -> Result<TrainingData> {
+    Ok(TrainingData::from_lazy_frame(read_csv(TRAIN_CSV)?))
+}
+
+/// The embedded sample of `test.csv`, without labels
+pub fn testing_data() -> Result<TestingData> {
+    Ok(TestingData::from_lazy_frame(read_csv(TEST_CSV)?))
+}
+
+fn read_csv(csv: &'static str) -> Result<LazyFrame> {
+    let df = CsvReadOptions::default()
+        .with_has_header(true)
+        .into_reader_with_file_handle(Cursor::new(csv.as_bytes()))
+        .finish()?;
+
+    Ok(df.lazy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+
+    #[test]
+    fn test_samples_load() {
+        let survived = training_data()
+            .unwrap()
+            .get_col_as_series("Survived")
+            .unwrap();
+        assert_eq!(survived.len(), 200);
+
+        let ids = testing_data()
+            .unwrap()
+            .get_col_as_series("PassengerId")
+            .unwrap();
+        assert_eq!(ids.len(), 50);
+    }
+}
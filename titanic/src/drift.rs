@@ -0,0 +1,311 @@
+//! Whether the testing data looks like the training data
+//!
+//! A model only knows the data it was trained on. If a column of the test set is on another scale
+//! (fares in another currency, ages in months) or mixes its categories differently, the
+//! predictions can be poor however well the model validated. `compare` measures how far each
+//! column's distribution has moved between two sets of data, before any predictions are made.
+
+use std::{collections::BTreeSet, fmt};
+
+use polars::prelude::DataType;
+
+use crate::{data::Data, error::DataError};
+
+/// How to measure a numeric column's drift
+///
+/// String (and other non-numeric) columns are always compared by `Psi`, over their categories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DriftTest {
+    /// The two-sample Kolmogorov–Smirnov statistic: the largest gap between the columns'
+    /// cumulative distributions, significant if it's one the same distribution would give less
+    /// than 5% of the time
+    #[default]
+    Ks,
+    /// The population stability index over the training values' deciles: the sum, over the bins,
+    /// of the difference in the shares of values in them times the log of their ratio.
+    /// Significant above `PSI_THRESHOLD`
+    Psi,
+}
+
+impl fmt::Display for DriftTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ks => write!(f, "KS"),
+            Self::Psi => write!(f, "PSI"),
+        }
+    }
+}
+
+/// The population stability index above which a column has drifted
+///
+/// The usual rule of thumb: below 0.1 is no real change, 0.1 to 0.2 is some, above 0.2 is a lot.
+pub const PSI_THRESHOLD: f64 = 0.2;
+
+/// The smallest share a bin or category is counted as having, so one that's empty in either
+/// set doesn't make the index infinite
+const MIN_SHARE: f64 = 1e-4;
+
+/// One column's drift
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDrift {
+    pub name: String,
+    pub test: DriftTest,
+    pub statistic: f64,
+    /// How big `statistic` can be before the column counts as drifted
+    pub threshold: f64,
+}
+
+impl ColumnDrift {
+    pub fn drifted(&self) -> bool {
+        self.statistic > self.threshold
+    }
+}
+
+/// Every compared column's drift, in the order asked for
+///
+/// A column that's all null in either set can't be compared, and isn't here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftReport {
+    pub columns: Vec<ColumnDrift>,
+}
+
+impl DriftReport {
+    /// `column`'s drift, if it was compared
+    pub fn column(&self, name: &str) -> Option<&ColumnDrift> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The columns that have drifted
+    pub fn drifted(&self) -> impl Iterator<Item = &ColumnDrift> {
+        self.columns.iter().filter(|column| column.drifted())
+    }
+}
+
+impl fmt::Display for DriftReport {
+    /// One line per column, e.g. `Fare: KS 0.412 (over 0.092: drifted)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}: {} {:.3} ({} {:.3}",
+                column.name,
+                column.test,
+                column.statistic,
+                if column.drifted() { "over" } else { "within" },
+                column.threshold
+            )?;
+            if column.drifted() {
+                write!(f, ": drifted")?;
+            }
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare the distribution of each of `columns` in `training` with the one in `testing`
+///
+/// Nulls are left out of both; `null_report` covers them. Numeric columns are measured by `test`,
+/// and any others by their categories' PSI.
+pub fn compare<'a>(
+    training: &impl Data,
+    testing: &impl Data,
+    columns: impl IntoIterator<Item = &'a str>,
+    test: DriftTest,
+) -> Result<DriftReport, DataError> {
+    let mut drift = Vec::new();
+    for name in columns {
+        let (expected, actual) = (
+            training.get_col_as_series(name)?,
+            testing.get_col_as_series(name)?,
+        );
+        let column = if expected.dtype().is_numeric() && actual.dtype().is_numeric() {
+            let values = |series: &polars::prelude::Series| -> Result<Vec<f64>, DataError> {
+                let mut values: Vec<f64> = series
+                    .cast(&DataType::Float64)?
+                    .f64()?
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                values.sort_by(f64::total_cmp);
+                Ok(values)
+            };
+            let (expected, actual) = (values(&expected)?, values(&actual)?);
+            if expected.is_empty() || actual.is_empty() {
+                continue;
+            }
+            match test {
+                DriftTest::Ks => {
+                    let (n, m) = (expected.len() as f64, actual.len() as f64);
+                    ColumnDrift {
+                        name: name.to_string(),
+                        test,
+                        statistic: ks_statistic(&expected, &actual),
+                        // The 5% critical value, for samples of this size
+                        threshold: 1.358 * ((n + m) / (n * m)).sqrt(),
+                    }
+                }
+                DriftTest::Psi => ColumnDrift {
+                    name: name.to_string(),
+                    test,
+                    statistic: numeric_psi(&expected, &actual),
+                    threshold: PSI_THRESHOLD,
+                },
+            }
+        } else {
+            let categories = |series: &polars::prelude::Series| -> Result<Vec<String>, DataError> {
+                Ok(series
+                    .cast(&DataType::String)?
+                    .str()?
+                    .into_iter()
+                    .flatten()
+                    .map(str::to_string)
+                    .collect())
+            };
+            let (expected, actual) = (categories(&expected)?, categories(&actual)?);
+            if expected.is_empty() || actual.is_empty() {
+                continue;
+            }
+            ColumnDrift {
+                name: name.to_string(),
+                test: DriftTest::Psi,
+                statistic: categorical_psi(&expected, &actual),
+                threshold: PSI_THRESHOLD,
+            }
+        };
+        drift.push(column);
+    }
+
+    Ok(DriftReport { columns: drift })
+}
+
+/// The largest gap between the cumulative distributions of two sorted, non-empty samples
+fn ks_statistic(expected: &[f64], actual: &[f64]) -> f64 {
+    let (n, m) = (expected.len(), actual.len());
+    let (mut i, mut j, mut largest) = (0, 0, 0.0_f64);
+    while i < n && j < m {
+        // Step past every copy of the next value in either sample
+        let value = expected[i].min(actual[j]);
+        while i < n && expected[i] <= value {
+            i += 1;
+        }
+        while j < m && actual[j] <= value {
+            j += 1;
+        }
+        largest = largest.max((i as f64 / n as f64 - j as f64 / m as f64).abs());
+    }
+
+    largest
+}
+
+/// The PSI of two sorted, non-empty samples, over bins split at the training sample's deciles
+fn numeric_psi(expected: &[f64], actual: &[f64]) -> f64 {
+    let mut edges: Vec<f64> = (1..10)
+        .map(|decile| expected[expected.len() * decile / 10])
+        .collect();
+    edges.dedup();
+    // The share of a sample in each bin: up to and including the first edge, then up to and
+    // including each next one, then the rest
+    let shares = |sample: &[f64]| -> Vec<f64> {
+        let mut below = 0;
+        let mut shares = Vec::with_capacity(edges.len() + 1);
+        for edge in &edges {
+            let up_to = sample.partition_point(|value| value <= edge);
+            shares.push((up_to - below) as f64 / sample.len() as f64);
+            below = up_to;
+        }
+        shares.push((sample.len() - below) as f64 / sample.len() as f64);
+        shares
+    };
+
+    psi(&shares(expected), &shares(actual))
+}
+
+/// The PSI of two non-empty samples of categories
+fn categorical_psi(expected: &[String], actual: &[String]) -> f64 {
+    let categories: BTreeSet<&String> = expected.iter().chain(actual).collect();
+    let shares = |sample: &[String]| -> Vec<f64> {
+        categories
+            .iter()
+            .map(|&category| {
+                sample.iter().filter(|&value| value == category).count() as f64
+                    / sample.len() as f64
+            })
+            .collect()
+    };
+
+    psi(&shares(expected), &shares(actual))
+}
+
+fn psi(expected: &[f64], actual: &[f64]) -> f64 {
+    expected
+        .iter()
+        .zip(actual)
+        .map(|(&expected, &actual)| {
+            let (expected, actual) = (expected.max(MIN_SHARE), actual.max(MIN_SHARE));
+            (actual - expected) * (actual / expected).ln()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::df;
+
+    #[test]
+    fn test_a_column_on_another_scale_has_drifted() {
+        let fares: Vec<f64> = (0..100).map(|i| 5.0 + (i % 50) as f64).collect();
+        let training = df!(
+            "Fare" => &fares,
+            "Age" => (0..100).map(|i| Some((i % 60) as f64)).collect::<Vec<_>>(),
+            "Sex" => (0..100)
+                .map(|i| if i % 3 == 0 { "female" } else { "male" })
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        // The same passengers again, with fares in pence and a null age
+        let testing = df!(
+            "Fare" => fares.iter().map(|fare| fare * 100.0).collect::<Vec<_>>(),
+            "Age" => (0..100).map(|i| (i != 7).then_some((i % 60) as f64)).collect::<Vec<_>>(),
+            "Sex" => (0..100)
+                .map(|i| if i % 3 == 0 { "female" } else { "male" })
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let report = compare(&training, &testing, ["Fare", "Age", "Sex"], DriftTest::Ks).unwrap();
+        let fare = report.column("Fare").unwrap();
+        assert!(fare.drifted());
+        assert!(fare.statistic > 0.9);
+        assert!(!report.column("Age").unwrap().drifted());
+        let sex = report.column("Sex").unwrap();
+        assert_eq!((sex.test, sex.statistic), (DriftTest::Psi, 0.0));
+        assert_eq!(
+            report
+                .drifted()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Fare"]
+        );
+        let lines = report.to_string();
+        let lines: Vec<_> = lines.lines().collect();
+        assert_eq!(lines[0], "Fare: KS 1.000 (over 0.192: drifted)");
+        assert_eq!(lines[2], "Sex: PSI 0.000 (within 0.200)");
+
+        let report = compare(&training, &testing, ["Fare", "Age"], DriftTest::Psi).unwrap();
+        assert!(report.column("Fare").unwrap().statistic > PSI_THRESHOLD);
+        assert!(report.column("Age").unwrap().statistic < 0.01);
+    }
+
+    #[test]
+    fn test_ks_statistic() {
+        assert_eq!(ks_statistic(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]), 0.0);
+        assert_eq!(ks_statistic(&[1.0, 2.0], &[3.0, 4.0]), 1.0);
+        assert_eq!(ks_statistic(&[1.0, 2.0, 3.0, 4.0], &[3.0, 4.0]), 0.5);
+    }
+}
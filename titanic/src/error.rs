@@ -0,0 +1,228 @@
+//! The two kinds of failure at the library's boundary
+//!
+//! A `DataError` means the input needs fixing: a missing column, a column of the wrong type, or
+//! arrays that don't line up. A `FitError` means training failed, either because of the data
+//! (wrapping a `DataError`) or because of the model's own settings. Keeping them apart lets a
+//! caller such as the CLI tell the user what to fix instead of printing a chain of errors.
+
+use thiserror::Error;
+
+/// The input data can't be used as given
+#[derive(Debug, Error)]
+pub enum DataError {
+    #[error("No column named {name:?}")]
+    MissingColumn { name: String },
+
+    #[error("No feature columns were asked for")]
+    NoFeatures,
+
+    #[error("Column {name:?} contains {dtype} values, not numbers")]
+    NonNumericColumn { name: String, dtype: String },
+
+    #[error("Column {name:?} contains {dtype} values, but labels and ids must be integers")]
+    NonIntegerColumn { name: String, dtype: String },
+
+    #[error("There are no rows")]
+    NoSamples,
+
+    #[error("Got {samples} samples but {labels} labels")]
+    LengthMismatch { samples: usize, labels: usize },
+
+    #[error("Feature {column} of row {row} is {value}, but features must be finite")]
+    NonFinite {
+        row: usize,
+        column: usize,
+        value: f64,
+    },
+
+    #[error("Column {column:?} has the label {value}, but labels can't be negative")]
+    NegativeLabel { column: String, value: i64 },
+
+    #[error("The labels go up to {max} but there are none of class {class}")]
+    MissingClass { class: usize, max: usize },
+
+    #[error("Labels need at least two classes to learn from, got {n_classes}")]
+    TooFewClasses { n_classes: usize },
+
+    #[error("The data has no labels")]
+    NoLabels,
+
+    #[error("Column {column:?} has {value}, which isn't one of the labels")]
+    UnknownLabel { column: String, value: String },
+
+    #[error("Can't make {n_clusters} clusters of {n_samples} rows")]
+    InvalidClusterCount { n_clusters: usize, n_samples: usize },
+
+    #[error("Quantiles must be between 0 and 1, got {0}")]
+    InvalidQuantile(f64),
+
+    #[error("The model was trained on {expected} features but got {found}")]
+    WrongFeatureCount { expected: usize, found: usize },
+
+    #[error("The model was trained on the features {expected:?}, in that order, but got {found:?}")]
+    FeatureOrder {
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error(transparent)]
+    Polars(#[from] polars::error::PolarsError),
+}
+
+/// Why a model couldn't be fitted
+#[derive(Debug, Error)]
+pub enum FitError {
+    #[error(transparent)]
+    Data(#[from] DataError),
+
+    #[error("n_estimators must be at least 1")]
+    NoEstimators,
+
+    #[error("max_depth must be at least 1")]
+    ZeroMaxDepth,
+
+    #[error("bootstrap_proportion must be greater than 0 and at most 1, got {0}")]
+    InvalidBootstrapProportion(f64),
+
+    #[error("class_weight must be finite and greater than 0, got {0}")]
+    InvalidClassWeight(f64),
+
+    #[error("class_weight has {found} weights but the training data has {expected} classes")]
+    ClassWeightCount { expected: usize, found: usize },
+
+    #[error(
+        "monotonic_constraints has {found} constraints but the training data has \
+         {expected} features"
+    )]
+    MonotonicConstraintCount { expected: usize, found: usize },
+
+    /// linfa rejected the tree settings
+    #[error(transparent)]
+    Linfa(#[from] linfa::Error),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Couldn't read or write the checkpoint: {0}")]
+    Checkpoint(#[from] crate::model_file::ModelFileError),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error(
+        "The checkpoint at {} was saved by a run with other tree settings, another seed or other \
+         training data",
+        path.display()
+    )]
+    CheckpointMismatch { path: std::path::PathBuf },
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Couldn't start the threads to fit with: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+/// Check that `x` and `y` can be trained on: some rows, one target per row, finite features
+pub(crate) fn check_training_data<F: linfa::Float>(
+    x: &ndarray::ArrayView2<F>,
+    n_targets: usize,
+) -> Result<(), DataError> {
+    check_features(x, n_targets, |v| v.is_finite())
+}
+
+/// `check_training_data`, but letting NaN through as a missing value (infinities are still
+/// errors)
+pub(crate) fn check_training_data_allowing_missing<F: linfa::Float>(
+    x: &ndarray::ArrayView2<F>,
+    n_targets: usize,
+) -> Result<(), DataError> {
+    check_features(x, n_targets, |v| v.is_finite() || v.is_nan())
+}
+
+fn check_features<F: linfa::Float>(
+    x: &ndarray::ArrayView2<F>,
+    n_targets: usize,
+    allowed: impl Fn(F) -> bool,
+) -> Result<(), DataError> {
+    if x.nrows() == 0 {
+        return Err(DataError::NoSamples);
+    }
+    if x.nrows() != n_targets {
+        return Err(DataError::LengthMismatch {
+            samples: x.nrows(),
+            labels: n_targets,
+        });
+    }
+    if let Some(((row, column), &value)) = x.indexed_iter().find(|(_, v)| !allowed(**v)) {
+        let value = value.to_f64().unwrap_or(f64::NAN);
+        return Err(DataError::NonFinite { row, column, value });
+    }
+
+    Ok(())
+}
+
+/// Check that class labels are contiguous from 0, so class `k` is the `k`th column of a
+/// probability matrix or confusion matrix
+///
+/// An empty `y` passes; `check_training_data` is what rejects that.
+pub fn check_class_labels(y: &ndarray::Array1<usize>) -> Result<(), DataError> {
+    let Some(&max) = y.iter().max() else {
+        return Ok(());
+    };
+
+    let mut seen = vec![false; max + 1];
+    for &label in y {
+        seen[label] = true;
+    }
+    match seen.iter().position(|&seen| !seen) {
+        Some(class) => Err(DataError::MissingClass { class, max }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_check_training_data() {
+        let x = arr2(&[[1.0, 2.0], [3.0, 4.0]]);
+        assert!(check_training_data(&x.view(), 2).is_ok());
+
+        assert!(matches!(
+            check_training_data(&x.view(), 3),
+            Err(DataError::LengthMismatch {
+                samples: 2,
+                labels: 3
+            })
+        ));
+        assert!(matches!(
+            check_training_data(&ndarray::Array2::<f64>::zeros((0, 2)).view(), 0),
+            Err(DataError::NoSamples)
+        ));
+
+        let x = arr2(&[[1.0, 2.0], [3.0, f64::NAN]]);
+        assert!(matches!(
+            check_training_data(&x.view(), 2),
+            Err(DataError::NonFinite {
+                row: 1,
+                column: 1,
+                ..
+            })
+        ));
+        assert!(check_training_data_allowing_missing(&x.view(), 2).is_ok());
+        let x = arr2(&[[1.0, f64::INFINITY], [3.0, f64::NAN]]);
+        assert!(check_training_data_allowing_missing(&x.view(), 2).is_err());
+    }
+
+    #[test]
+    fn test_check_class_labels() {
+        assert!(check_class_labels(&arr1(&[1, 0, 2, 1])).is_ok());
+        assert!(check_class_labels(&arr1(&[])).is_ok());
+        assert!(matches!(
+            check_class_labels(&arr1(&[0, 3, 1, 3])),
+            Err(DataError::MissingClass { class: 2, max: 3 })
+        ));
+        assert!(matches!(
+            check_class_labels(&arr1(&[1, 1])),
+            Err(DataError::MissingClass { class: 0, max: 1 })
+        ));
+    }
+}
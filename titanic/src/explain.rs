@@ -0,0 +1,670 @@
+//! Looking inside a fitted forest's predictions
+//!
+//! `predict_proba` only says how the trees' votes added up. When a prediction is surprising, the
+//! votes themselves (which trees disagreed, and where each one's walk down the tree ended) are the
+//! place to start, and `explain_prediction` goes on to say which features moved the vote and which
+//! way. `shap_values` answers the same question the way Python's `shap.TreeExplainer` does, with
+//! attributions that don't depend on the order the trees happened to split in.
+//!
+//! linfa's trees don't remember how much of the training data reached each node, so the forest
+//! records it while fitting, as `NodeWeights`.
+//!
+//! A forest fitted with `Missing::Learn` may split on a copy of a feature (see `MissingFeatures`);
+//! its splits are credited to the feature it's a copy of.
+
+use linfa::{Float, Label};
+use linfa_trees::{DecisionTree, TreeNode};
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, AsArray, Axis, Ix1, Ix2};
+use serde::{Deserialize, Serialize};
+
+use crate::{missing::MissingFeatures, random_forest::FittedRandomForest};
+
+/// How one tree voted for one sample, from `FittedRandomForest::tree_votes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeVote<L> {
+    /// Which tree, by its position in the forest
+    pub tree: usize,
+    /// The label it voted for
+    pub label: L,
+    /// The leaf the sample reached, numbered from 0 in breadth-first order across the tree's
+    /// nodes, so samples with the same `leaf` from the same tree ended up in the same place
+    pub leaf: usize,
+}
+
+/// Why the forest predicted what it did for one sample, from
+/// `FittedRandomForest::explain_prediction`
+///
+/// Each split a sample passes through moves the tree's class probabilities from those of the
+/// training rows that reached the split to those of the rows that took the same branch; at the
+/// leaf, they become the tree's vote. That move is credited to the split's feature. Summed over
+/// the trees' paths and averaged over the trees, `bias` plus each feature's contribution is the
+/// sample's row of `predict_proba`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation<F> {
+    /// The class probabilities before looking at the sample: the share of each class in the
+    /// training rows each tree was fitted on, averaged over the trees. Ordered like `classes()`
+    pub bias: Array1<f64>,
+    /// How much each feature (row) moved each class's probability (column)
+    pub contributions: Array2<f64>,
+    /// Every split the sample passed through, tree by tree from the root down
+    pub decisions: Vec<Decision<F>>,
+}
+
+/// One split on a sample's way down a tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision<F> {
+    /// Which tree, by its position in the forest
+    pub tree: usize,
+    /// The feature split on, by its column in the feature matrix
+    pub feature: usize,
+    pub threshold: F,
+    pub direction: Direction,
+    /// How much taking this branch moved each class's probability, divided by the number of
+    /// trees (so it adds up with the other decisions to `Explanation::contributions`)
+    pub contribution: Array1<f64>,
+}
+
+/// Which way a sample went at a split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Its value was below the threshold
+    Below,
+    /// Its value was at or above the threshold
+    AtOrAbove,
+    /// It had no value, and went the way the forest sends missing values at this split
+    Missing,
+}
+
+impl<F> Explanation<F> {
+    /// Each feature's contribution to the probability of the class in column `class` (e.g. 1 for
+    /// survived), largest first; ties keep the features' order
+    pub fn ranked(&self, class: usize) -> Vec<(usize, f64)> {
+        let mut ranked: Vec<(usize, f64)> = self
+            .contributions
+            .column(class)
+            .iter()
+            .copied()
+            .enumerate()
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        ranked
+    }
+}
+
+/// SHAP values of a forest's predicted probability of one class, from
+/// `FittedRandomForest::shap_values`
+///
+/// For each sample, `expected_value` plus the sample's row of `values` is its probability of the
+/// class in `predict_proba`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapValues {
+    /// The probability averaged over the training rows each tree was fitted on
+    pub expected_value: f64,
+    /// How much each feature (column) moved each sample's (row's) probability away from
+    /// `expected_value`
+    pub values: Array2<f64>,
+    /// The names of the columns of `values`, if the forest knows the names of its features
+    pub feature_names: Option<Vec<String>>,
+}
+
+/// How much training weight of each class reached each node of one tree, in breadth-first order
+///
+/// A row drawn twice for the tree's bootstrap sample counts twice, and one not drawn not at all.
+/// The rows are walked as the tree was fitted on them, with any missing values encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct NodeWeights(Vec<Vec<f64>>);
+
+impl NodeWeights {
+    /// Walk every row of `x`, with its bootstrap weight, down `tree`
+    pub(crate) fn new<F: Float, L: Label>(
+        tree: &DecisionTree<F, L>,
+        x: ArrayView2<F>,
+        y: ArrayView1<L>,
+        sample_weights: &Array1<f32>,
+        classes: &[L],
+    ) -> Self {
+        let nodes = flatten(tree);
+        let mut weights = vec![vec![0.0; classes.len()]; nodes.len()];
+        for ((row, label), &weight) in x.rows().into_iter().zip(y).zip(sample_weights) {
+            let Ok(class) = classes.binary_search(label) else {
+                continue;
+            };
+            for node in path(&nodes, row) {
+                weights[node][class] += f64::from(weight);
+            }
+        }
+
+        Self(weights)
+    }
+
+    /// The total weight that reached `node`
+    pub(crate) fn cover(&self, node: usize) -> f64 {
+        self.0[node].iter().sum()
+    }
+
+    /// The share of each class in the weight that reached `node`
+    fn proportions(&self, node: usize) -> Array1<f64> {
+        let cover = self.cover(node);
+        self.0[node]
+            .iter()
+            .map(|&weight| if cover > 0.0 { weight / cover } else { 0.0 })
+            .collect()
+    }
+}
+
+/// A tree node, with its children given by their positions in `flatten`'s list
+pub(crate) struct Node<F, L> {
+    /// The feature and threshold of a split, and the left and right children; `None` at a leaf
+    pub(crate) split: Option<(usize, F, usize, usize)>,
+    /// A leaf's vote
+    pub(crate) label: Option<L>,
+}
+
+/// The nodes of `tree` in breadth-first order, as `DecisionTree::iter_nodes` visits them
+pub(crate) fn flatten<F: Float, L: Label>(tree: &DecisionTree<F, L>) -> Vec<Node<F, L>> {
+    let mut queue = vec![tree.root_node()];
+    let mut nodes = Vec::new();
+    while let Some(&node) = queue.get(nodes.len()) {
+        let split = (!node.is_leaf()).then(|| {
+            let (feature, threshold, _) = node.split();
+            let left = queue.len();
+            queue.extend([child(node, 0), child(node, 1)]);
+            (feature, threshold, left, left + 1)
+        });
+        nodes.push(Node {
+            split,
+            label: node.prediction(),
+        });
+    }
+
+    nodes
+}
+
+/// The left (0) or right (1) child of a node that isn't a leaf
+fn child<F: Float, L: Label>(node: &TreeNode<F, L>, side: usize) -> &TreeNode<F, L> {
+    node.children()[side]
+        .as_deref()
+        .expect("a node that isn't a leaf has two children")
+}
+
+/// The positions of the nodes `sample` passes through, from the root to a leaf
+///
+/// Goes left when the sample's value is below the split threshold, the way linfa predicts.
+pub(crate) fn path<F: Float, L>(nodes: &[Node<F, L>], sample: ArrayView1<F>) -> Vec<usize> {
+    let mut path = vec![0];
+    while let Some((feature, threshold, left, right)) = nodes[path[path.len() - 1]].split {
+        path.push(if sample[feature] < threshold {
+            left
+        } else {
+            right
+        });
+    }
+
+    path
+}
+
+impl<L: Label + Copy, F: Float> FittedRandomForest<L, F> {
+    /// Every tree's vote for `sample`, in the order of the trees
+    ///
+    /// `predict_proba` of the sample is the share of these votes each class gets; looking at them
+    /// one by one shows how split the forest was, and comparing the leaves two samples reached
+    /// shows which trees told them apart.
+    ///
+    /// # Panics
+    /// If `sample` doesn't have as many values as the forest was trained on features, as for
+    /// `predict`
+    pub fn tree_votes<'a>(&self, sample: impl AsArray<'a, F, Ix1>) -> Vec<TreeVote<L>> {
+        let sample = self.checked_sample(sample);
+        let encoded = self.missing.encode(sample.insert_axis(Axis(0)));
+
+        self.trees
+            .iter()
+            .enumerate()
+            .map(|(tree, decision_tree)| {
+                let nodes = flatten(decision_tree);
+                let reached = path(&nodes, encoded.row(0)).pop().unwrap_or_default();
+                TreeVote {
+                    tree,
+                    label: nodes[reached]
+                        .label
+                        .expect("the walk down a tree ends at a leaf"),
+                    leaf: nodes[..reached]
+                        .iter()
+                        .filter(|node| node.split.is_none())
+                        .count(),
+                }
+            })
+            .collect()
+    }
+
+    /// Explain the forest's prediction for `sample` by the splits it passed through, crediting
+    /// each feature with how much its splits moved the class probabilities
+    ///
+    /// # Returns
+    /// The explanation, or `None` for a forest saved before fitted forests kept the training
+    /// weights at their nodes
+    ///
+    /// # Panics
+    /// If `sample` has the wrong number of values, as for `tree_votes`
+    pub fn explain_prediction<'a>(
+        &self,
+        sample: impl AsArray<'a, F, Ix1>,
+    ) -> Option<Explanation<F>> {
+        let sample = self.checked_sample(sample);
+        if self.node_weights.len() != self.trees.len() {
+            return None;
+        }
+        let encoded = self.missing.encode(sample.insert_axis(Axis(0)));
+        let n_trees = self.trees.len() as f64;
+        let mut bias = Array1::zeros(self.classes.len());
+        let mut contributions = Array2::zeros((sample.len(), self.classes.len()));
+        let mut decisions = Vec::new();
+
+        for (tree, (decision_tree, weights)) in
+            self.trees.iter().zip(&self.node_weights).enumerate()
+        {
+            let nodes = flatten(decision_tree);
+            // A leaf's probabilities are its vote, as in `predict_proba`
+            let probabilities = |node: usize| match nodes[node].label {
+                Some(label) => self
+                    .classes
+                    .iter()
+                    .map(|&class| if class == label { 1.0 } else { 0.0 })
+                    .collect(),
+                None => weights.proportions(node),
+            };
+
+            bias += &(probabilities(0) / n_trees);
+            for step in path(&nodes, encoded.row(0)).windows(2) {
+                let (parent, node) = (step[0], step[1]);
+                let Some((split_feature, threshold, left, _)) = nodes[parent].split else {
+                    unreachable!("a node with a child isn't a leaf");
+                };
+                let feature = self.missing.source(split_feature);
+                let contribution = (probabilities(node) - probabilities(parent)) / n_trees;
+                let mut credited = contributions.row_mut(feature);
+                credited += &contribution;
+                decisions.push(Decision {
+                    tree,
+                    feature,
+                    threshold,
+                    direction: if sample[feature].is_nan() {
+                        Direction::Missing
+                    } else if node == left {
+                        Direction::Below
+                    } else {
+                        Direction::AtOrAbove
+                    },
+                    contribution,
+                });
+            }
+        }
+
+        Some(Explanation {
+            bias,
+            contributions,
+            decisions,
+        })
+    }
+
+    /// Exact SHAP values of each row of `x`'s probability of the class in column `class` of
+    /// `predict_proba`, e.g. 1 for survived
+    ///
+    /// Computed with the polynomial-time TreeSHAP algorithm (Lundberg et al., "Consistent
+    /// Individualized Feature Attribution for Tree Ensembles"), in its path-dependent form: a
+    /// feature that's left out follows each split in proportion to the training weight that went
+    /// each way. Each tree's leaves are worth its vote, so the values add up to `predict_proba`.
+    ///
+    /// # Returns
+    /// The SHAP values, or `None` for a forest saved before fitted forests kept the training
+    /// weights at their nodes
+    ///
+    /// # Panics
+    /// If `x` has the wrong number of columns, as for `predict`, or there's no class `class`
+    pub fn shap_values<'a>(&self, x: impl AsArray<'a, F, Ix2>, class: usize) -> Option<ShapValues> {
+        let x = x.into();
+        if let Err(mismatch) = self.check_width(x.ncols()) {
+            panic!("{mismatch}");
+        }
+        let label = self.classes[class];
+        if self.node_weights.len() != self.trees.len() {
+            return None;
+        }
+        let n_trees = self.trees.len() as f64;
+        let mut expected_value = 0.0;
+        let mut values = Array2::zeros(x.dim());
+        let encoded = self.missing.encode(x.view());
+
+        for (decision_tree, weights) in self.trees.iter().zip(&self.node_weights) {
+            let nodes = flatten(decision_tree);
+            let leaf_value = |node: usize| match nodes[node].label {
+                Some(vote) if vote == label => 1.0,
+                _ => 0.0,
+            };
+            let root_cover = weights.cover(0);
+            expected_value += (0..nodes.len())
+                .filter(|&node| nodes[node].split.is_none())
+                .map(|leaf| weights.cover(leaf) / root_cover * leaf_value(leaf))
+                .sum::<f64>()
+                / n_trees;
+
+            for (sample, mut phi) in encoded.rows().into_iter().zip(values.rows_mut()) {
+                let tree = TreeShap {
+                    nodes: &nodes,
+                    weights,
+                    missing: &self.missing,
+                    leaf_value: &leaf_value,
+                    sample,
+                };
+                let mut tree_phi = Array1::zeros(x.ncols());
+                tree.recurse(0, Vec::new(), 1.0, 1.0, None, &mut tree_phi);
+                phi += &(tree_phi / n_trees);
+            }
+        }
+
+        Some(ShapValues {
+            expected_value,
+            values,
+            feature_names: self
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.feature_names.clone()),
+        })
+    }
+
+    /// `sample` as a view, having checked it's as wide as the forest's training data
+    fn checked_sample<'a>(&self, sample: impl AsArray<'a, F, Ix1>) -> ArrayView1<'a, F> {
+        let sample = sample.into();
+        if let Err(mismatch) = self.check_width(sample.len()) {
+            panic!("{mismatch}");
+        }
+        sample
+    }
+}
+
+/// One tree's part in `shap_values` for one sample, encoded as the tree was fitted on it
+struct TreeShap<'t, F, L, V> {
+    nodes: &'t [Node<F, L>],
+    weights: &'t NodeWeights,
+    /// Which feature a split on a copy of one counts as
+    missing: &'t MissingFeatures,
+    leaf_value: &'t V,
+    sample: ArrayView1<'t, F>,
+}
+
+/// A feature on the way from the root to a node, in the TreeSHAP algorithm
+///
+/// `zero` is the share of the training weight a path that leaves the feature out takes through
+/// its splits, `one` whether the sample itself goes that way (1 or 0), and `weight` the share of
+/// the orderings of the features that have it in the path.
+#[derive(Clone, Copy)]
+struct PathElement {
+    /// `None` for the element the root starts the path with
+    feature: Option<usize>,
+    zero: f64,
+    one: f64,
+    weight: f64,
+}
+
+impl<F: Float, L, V: Fn(usize) -> f64> TreeShap<'_, F, L, V> {
+    /// Add the SHAP values of the subtree from `node` to `phi`, arriving at it by a split on
+    /// `feature` with fractions `zero` and `one`
+    fn recurse(
+        &self,
+        node: usize,
+        mut path: Vec<PathElement>,
+        zero: f64,
+        one: f64,
+        feature: Option<usize>,
+        phi: &mut Array1<f64>,
+    ) {
+        extend(&mut path, zero, one, feature);
+
+        let Some((split_feature, threshold, left, right)) = self.nodes[node].split else {
+            let value = (self.leaf_value)(node);
+            for (i, element) in path.iter().enumerate().skip(1) {
+                if let Some(feature) = element.feature {
+                    phi[feature] += unwound_sum(&path, i) * (element.one - element.zero) * value;
+                }
+            }
+            return;
+        };
+
+        let (hot, cold) = if self.sample[split_feature] < threshold {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let split_feature = self.missing.source(split_feature);
+        // A feature split on again higher up only counts once on the path
+        let (mut incoming_zero, mut incoming_one) = (1.0, 1.0);
+        if let Some(i) = path
+            .iter()
+            .position(|element| element.feature == Some(split_feature))
+        {
+            (incoming_zero, incoming_one) = (path[i].zero, path[i].one);
+            unwind(&mut path, i);
+        }
+
+        let cover = self.weights.cover(node);
+        let share = |child: usize| {
+            if cover > 0.0 {
+                self.weights.cover(child) / cover
+            } else {
+                0.0
+            }
+        };
+        self.recurse(
+            hot,
+            path.clone(),
+            share(hot) * incoming_zero,
+            incoming_one,
+            Some(split_feature),
+            phi,
+        );
+        self.recurse(
+            cold,
+            path,
+            share(cold) * incoming_zero,
+            0.0,
+            Some(split_feature),
+            phi,
+        );
+    }
+}
+
+/// Add a feature to the path, updating the weights of the orderings
+fn extend(path: &mut Vec<PathElement>, zero: f64, one: f64, feature: Option<usize>) {
+    let depth = path.len();
+    path.push(PathElement {
+        feature,
+        zero,
+        one,
+        weight: if depth == 0 { 1.0 } else { 0.0 },
+    });
+    let scale = (depth + 1) as f64;
+    for i in (0..depth).rev() {
+        path[i + 1].weight += one * path[i].weight * (i + 1) as f64 / scale;
+        path[i].weight = zero * path[i].weight * (depth - i) as f64 / scale;
+    }
+}
+
+/// Take the `index`th feature back off the path, undoing `extend`
+fn unwind(path: &mut Vec<PathElement>, index: usize) {
+    let depth = path.len() - 1;
+    let PathElement { zero, one, .. } = path[index];
+    let scale = (depth + 1) as f64;
+    let mut next_one = path[depth].weight;
+    for i in (0..depth).rev() {
+        if one != 0.0 {
+            let weight = path[i].weight;
+            path[i].weight = next_one * scale / ((i + 1) as f64 * one);
+            next_one = weight - path[i].weight * zero * (depth - i) as f64 / scale;
+        } else {
+            path[i].weight = path[i].weight * scale / (zero * (depth - i) as f64);
+        }
+    }
+    for i in index..depth {
+        let weight = path[i].weight;
+        path[i] = PathElement {
+            weight,
+            ..path[i + 1]
+        };
+    }
+    path.pop();
+}
+
+/// The total weight the path would have with its `index`th feature unwound, without unwinding it
+fn unwound_sum(path: &[PathElement], index: usize) -> f64 {
+    let depth = path.len() - 1;
+    let PathElement { zero, one, .. } = path[index];
+    let scale = (depth + 1) as f64;
+    let mut next_one = path[depth].weight;
+    let mut total = 0.0;
+    for i in (0..depth).rev() {
+        if one != 0.0 {
+            let weight = next_one * scale / ((i + 1) as f64 * one);
+            total += weight;
+            next_one = path[i].weight - weight * zero * (depth - i) as f64 / scale;
+        } else if zero != 0.0 {
+            total += path[i].weight / zero / ((depth - i) as f64 / scale);
+        }
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array1, Array2, Axis, arr1, arr2, s};
+
+    use super::*;
+    use crate::{random_forest::RandomForestClassifier, seed::Seed};
+
+    #[test]
+    fn test_tree_votes_add_up_to_the_probabilities() {
+        let x = Array2::from_shape_fn((40, 2), |(i, j)| (i * (j + 1)) as f64 / 3.0);
+        let y = Array1::from_shape_fn(40, |i| usize::from(i % 7 < 3));
+        let model = RandomForestClassifier::new()
+            .n_estimators(15)
+            .max_depth(4)
+            .random_state(Seed::new(2))
+            .fit(&x, &y)
+            .unwrap();
+
+        for (i, sample) in x.rows().into_iter().enumerate() {
+            let votes = model.tree_votes(sample);
+            assert_eq!(votes.len(), 15);
+            assert!(
+                votes
+                    .iter()
+                    .enumerate()
+                    .all(|(tree, vote)| vote.tree == tree)
+            );
+
+            let for_one = votes.iter().filter(|vote| vote.label == 1).count();
+            let probability = model.predict_proba(x.slice(s![i..=i, ..]))[[0, 1]];
+            assert!((for_one as f64 / 15.0 - probability).abs() < 1e-12);
+        }
+
+        // The same sample always reaches the same leaves
+        let sample = arr1(&[4.0, 8.0]);
+        assert_eq!(model.tree_votes(&sample), model.tree_votes(&sample));
+    }
+
+    #[test]
+    fn test_explanation_adds_up_to_the_probabilities() {
+        // Only the first feature says anything about the label
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| if j == 0 { i as f64 } else { 1.0 });
+        let y = Array1::from_shape_fn(60, |i| usize::from(i >= 30));
+        let model = RandomForestClassifier::new()
+            .n_estimators(10)
+            .random_state(Seed::new(3))
+            .fit(&x, &y)
+            .unwrap();
+
+        let sample = arr1(&[50.0, 1.0]);
+        let explanation = model.explain_prediction(&sample).unwrap();
+        let probabilities = model.predict_proba(&arr2(&[[50.0, 1.0]]));
+        let total = &explanation.bias + &explanation.contributions.sum_axis(Axis(0));
+        for (explained, predicted) in total.iter().zip(probabilities.row(0)) {
+            assert!((explained - predicted).abs() < 1e-9);
+        }
+
+        // Every split is on the first feature, and the sample is above all of them
+        assert!(!explanation.decisions.is_empty());
+        assert!(explanation.decisions.iter().all(|decision| {
+            decision.feature == 0 && decision.direction == Direction::AtOrAbove
+        }));
+        assert_eq!(explanation.ranked(1)[0].0, 0);
+        assert!(explanation.contributions[[0, 1]] > 0.0);
+        assert_eq!(explanation.contributions.row(1).sum(), 0.0);
+    }
+
+    #[test]
+    fn test_forests_saved_without_node_weights_cant_explain() {
+        let x = arr2(&[[1.0], [2.0], [10.0], [20.0]]);
+        let mut model = RandomForestClassifier::new()
+            .n_estimators(3)
+            .random_state(Seed::new(1))
+            .fit(&x, arr1(&[0usize, 0, 1, 1]))
+            .unwrap();
+        model.node_weights.clear();
+
+        assert_eq!(model.explain_prediction(&arr1(&[5.0])), None);
+        assert_eq!(model.shap_values(&x, 1), None);
+        assert_eq!(model.tree_votes(&arr1(&[5.0])).len(), 3);
+    }
+
+    #[test]
+    fn test_shap_values_add_up_to_the_probabilities() {
+        let x = Array2::from_shape_fn((60, 3), |(i, j)| ((i * (2 * j + 3)) % 17) as f64);
+        let y = Array1::from_shape_fn(60, |i| usize::from(x[[i, 0]] + x[[i, 1]] > 15.0));
+        let model = RandomForestClassifier::new()
+            .n_estimators(8)
+            .max_depth(5)
+            .random_state(Seed::new(5))
+            .fit(&x, &y)
+            .unwrap()
+            .with_feature_names(vec!["a".into(), "b".into(), "c".into()])
+            .unwrap();
+
+        let shap = model.shap_values(&x, 1).unwrap();
+        assert_eq!(shap.values.dim(), (60, 3));
+        assert_eq!(shap.feature_names.unwrap(), ["a", "b", "c"]);
+        let probabilities = model.predict_proba(&x);
+        for (row, probability) in shap.values.rows().into_iter().zip(probabilities.column(1)) {
+            assert!((shap.expected_value + row.sum() - probability).abs() < 1e-9);
+        }
+
+        // The expected values of the classes add up to 1, and so do their SHAP values to 0
+        let other = model.shap_values(&x, 0).unwrap();
+        assert!((shap.expected_value + other.expected_value - 1.0).abs() < 1e-9);
+        assert!(
+            (&shap.values + &other.values)
+                .iter()
+                .all(|v| v.abs() < 1e-9)
+        );
+    }
+
+    #[test]
+    fn test_shap_values_of_a_single_split() {
+        // One split on the first feature, with the training weight evenly on either side: the
+        // first feature moves the probability half way, and the second not at all
+        let x = arr2(&[[0.0, 5.0], [1.0, 5.0], [10.0, 5.0], [11.0, 5.0]]);
+        let model = RandomForestClassifier::new()
+            .n_estimators(1)
+            .max_depth(1)
+            .bootstrap_proportion(1.0)
+            .random_state(Seed::new(0))
+            .fit(&x, arr1(&[0usize, 0, 1, 1]))
+            .unwrap();
+        let weights = &model.node_weights[0];
+
+        let shap = model.shap_values(&arr2(&[[20.0, 5.0]]), 1).unwrap();
+        let right_share = weights.cover(2) / weights.cover(0);
+        assert!((shap.expected_value - right_share).abs() < 1e-12);
+        assert!((shap.values[[0, 0]] - (1.0 - right_share)).abs() < 1e-12);
+        assert_eq!(shap.values[[0, 1]], 0.0);
+    }
+}
@@ -0,0 +1,229 @@
+//! How differently a model treats the groups of a sensitive column, such as `Sex` or `Pclass`
+//!
+//! A model can be accurate overall and still make most of its mistakes on one group. `audit`
+//! splits the predictions by a column's values and gives each group its own confusion matrix,
+//! then sums up the gaps between groups the two usual ways:
+//!
+//! - The demographic parity difference: how far apart the groups' selection rates (the share
+//!   predicted positive) are. 0 means every group is predicted positive as often.
+//! - The equalized odds difference: the larger of how far apart the groups' true positive rates
+//!   and their false positive rates are. 0 means the model is as right about each group's
+//!   positives, and as wrong about its negatives.
+//!
+//! Both are the largest minus the smallest rate, as fairlearn computes them. A group without any
+//! positives (or negatives) has no true (or false) positive rate, and is left out of that gap.
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{Result, bail};
+use metrics::classification::ConfusionMatrix;
+use ndarray::Array1;
+use polars::prelude::*;
+
+/// One group's predictions
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMetrics {
+    /// The column's value, as a string (`null` for a missing one)
+    pub group: String,
+    pub confusion_matrix: ConfusionMatrix,
+    /// The share of the group predicted positive
+    pub selection_rate: f64,
+    /// The share of the group's positives predicted positive; NaN if it has none
+    pub true_positive_rate: f64,
+    /// The share of the group's negatives predicted positive; NaN if it has none
+    pub false_positive_rate: f64,
+}
+
+/// Every group of a column's predictions, and the gaps between them
+#[derive(Debug, Clone, PartialEq)]
+pub struct FairnessReport {
+    pub column: String,
+    /// Sorted by group
+    pub groups: Vec<GroupMetrics>,
+}
+
+impl FairnessReport {
+    /// The largest selection rate minus the smallest
+    pub fn demographic_parity_difference(&self) -> f64 {
+        spread(self.groups.iter().map(|group| group.selection_rate))
+    }
+
+    /// The largest true positive rate minus the smallest
+    pub fn true_positive_rate_gap(&self) -> f64 {
+        spread(self.groups.iter().map(|group| group.true_positive_rate))
+    }
+
+    /// The largest false positive rate minus the smallest
+    pub fn false_positive_rate_gap(&self) -> f64 {
+        spread(self.groups.iter().map(|group| group.false_positive_rate))
+    }
+
+    /// The larger of `true_positive_rate_gap` and `false_positive_rate_gap`
+    pub fn equalized_odds_difference(&self) -> f64 {
+        self.true_positive_rate_gap()
+            .max(self.false_positive_rate_gap())
+    }
+}
+
+impl fmt::Display for FairnessReport {
+    /// A line per group, e.g. `female (64 rows): selection rate 0.719, true positive rate 0.913,
+    /// false positive rate 0.353`, then the gaps
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(
+                f,
+                "{} ({} rows): selection rate {:.3}, true positive rate {:.3}, false positive \
+                 rate {:.3}",
+                group.group,
+                group.confusion_matrix.total(),
+                group.selection_rate,
+                group.true_positive_rate,
+                group.false_positive_rate
+            )?;
+        }
+        writeln!(
+            f,
+            "Demographic parity difference: {:.3}",
+            self.demographic_parity_difference()
+        )?;
+        write!(
+            f,
+            "Equalized odds difference: {:.3} (true positive rates {:.3} apart, false positive \
+             rates {:.3} apart)",
+            self.equalized_odds_difference(),
+            self.true_positive_rate_gap(),
+            self.false_positive_rate_gap()
+        )
+    }
+}
+
+/// Compare `predictions` of the label `positive` across the values of `groups`, which has a
+/// value for each row of `predictions` and `actual`
+pub fn audit(
+    groups: &Series,
+    predictions: &Array1<usize>,
+    actual: &Array1<usize>,
+    positive: usize,
+) -> Result<FairnessReport> {
+    if groups.len() != predictions.len() || predictions.len() != actual.len() {
+        bail!(
+            "Got {} groups for {} predictions of {} labels",
+            groups.len(),
+            predictions.len(),
+            actual.len()
+        );
+    }
+
+    let column = groups.name().to_string();
+    let names = groups.cast(&DataType::String)?;
+    let mut rows: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (row, name) in names.str()?.iter().enumerate() {
+        rows.entry(name.unwrap_or("null").to_string())
+            .or_default()
+            .push(row);
+    }
+
+    let groups = rows
+        .into_iter()
+        .map(|(group, rows)| {
+            let select = |values: &Array1<usize>| rows.iter().map(|&row| values[row]).collect();
+            let (predictions, actual): (Array1<usize>, Array1<usize>) =
+                (select(predictions), select(actual));
+            let (mut positives, mut negatives) = ((0, 0), (0, 0));
+            for (&predicted, &actual) in predictions.iter().zip(&actual) {
+                let counts = if actual == positive {
+                    &mut positives
+                } else {
+                    &mut negatives
+                };
+                counts.0 += usize::from(predicted == positive);
+                counts.1 += 1;
+            }
+
+            GroupMetrics {
+                group,
+                selection_rate: ratio(positives.0 + negatives.0, rows.len()),
+                true_positive_rate: ratio(positives.0, positives.1),
+                false_positive_rate: ratio(negatives.0, negatives.1),
+                confusion_matrix: ConfusionMatrix::new(&predictions, &actual),
+            }
+        })
+        .collect();
+
+    Ok(FairnessReport { column, groups })
+}
+
+/// `numerator / denominator`, or NaN if there's nothing to divide by
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        f64::NAN
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// The largest value minus the smallest, leaving out NaNs; NaN if they're all NaN
+fn spread(values: impl Iterator<Item = f64>) -> f64 {
+    let (min, max) = values
+        .filter(|value| !value.is_nan())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+
+    if min > max { f64::NAN } else { max - min }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    #[test]
+    fn test_audit_compares_groups() {
+        let sex = Series::new(
+            "Sex".into(),
+            ["male", "female", "male", "female", "male", "female"],
+        );
+        let actual = arr1(&[1, 1, 0, 0, 1, 1]);
+        let predictions = arr1(&[0, 1, 0, 1, 1, 1]);
+
+        let report = audit(&sex, &predictions, &actual, 1).unwrap();
+        assert_eq!(report.column, "Sex");
+        let names: Vec<_> = report.groups.iter().map(|g| g.group.as_str()).collect();
+        assert_eq!(names, ["female", "male"]);
+        let (female, male) = (&report.groups[0], &report.groups[1]);
+        assert_eq!(female.confusion_matrix.count(0, 1), 1);
+        assert_eq!(male.confusion_matrix.count(1, 0), 1);
+        assert_eq!(
+            (female.selection_rate, male.selection_rate),
+            (1.0, 1.0 / 3.0)
+        );
+        assert_eq!(
+            (female.true_positive_rate, male.true_positive_rate),
+            (1.0, 0.5)
+        );
+        assert_eq!(
+            (female.false_positive_rate, male.false_positive_rate),
+            (1.0, 0.0)
+        );
+
+        assert!((report.demographic_parity_difference() - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(report.true_positive_rate_gap(), 0.5);
+        assert_eq!(report.equalized_odds_difference(), 1.0);
+        assert!(
+            report
+                .to_string()
+                .ends_with("false positive rates 1.000 apart)")
+        );
+
+        // A group without negatives has no false positive rate, and doesn't count towards its gap
+        let class = Series::new("Pclass".into(), [1i64, 1, 2, 2, 3, 3]);
+        let actual = arr1(&[1, 0, 1, 0, 1, 1]);
+        let predictions = arr1(&[1, 0, 0, 1, 1, 1]);
+        let report = audit(&class, &predictions, &actual, 1).unwrap();
+        assert!(report.groups[2].false_positive_rate.is_nan());
+        assert_eq!(report.false_positive_rate_gap(), 1.0);
+
+        assert!(audit(&class, &arr1(&[1]), &arr1(&[1]), 1).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! The preprocessing state captured when a model is trained
+//!
+//! `Data::get_feature_matrix` turns raw columns into model inputs: numeric columns are kept (nulls
+//! become 0) and string columns are expanded into one-hot dummy columns. A `FeatureSchema` records
+//! the resulting column layout so that raw rows arriving later (e.g. over HTTP) can be encoded into
+//! exactly the same feature order the model was trained on.
+//!
+//! `Data::fit_feature_matrix` returns the schema along with the training features, and
+//! `Data::get_encoded_features` encodes the test set (or anything else) with it, so later data
+//! never goes through `to_dummies` again and always gets the training set's columns.
+//!
+//! For a forest that learns where to send missing values (`Missing::Learn`), `keep_missing` has
+//! the schema encode numeric nulls as NaN instead of 0, in training and whenever it's used later.
+
+use anyhow::{Result, bail};
+use ndarray::Array2;
+#[cfg(not(target_arch = "wasm32"))]
+use polars::{frame::DataFrame, prelude::DataType};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::conversion_report::{Adjustment, ConversionReport};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::DataError;
+
+/// One column of the encoded feature matrix and where its values come from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EncodedColumn {
+    /// A numeric source column, passed through as-is (nulls become 0.0, or NaN if the schema
+    /// keeps missing values)
+    Numeric { source: String },
+    /// A dummy column that is 1.0 when `source` equals `category`, otherwise 0.0
+    Dummy { source: String, category: String },
+}
+
+impl EncodedColumn {
+    /// The column's name in the encoded DataFrame: the source's, or `<source>_<category>` for a
+    /// dummy, as `to_dummies` names them
+    pub fn name(&self) -> String {
+        match self {
+            Self::Numeric { source } => source.clone(),
+            Self::Dummy { source, category } => format!("{source}_{category}"),
+        }
+    }
+}
+
+/// Ordered description of the columns produced by `Data::get_feature_matrix`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureSchema {
+    columns: Vec<EncodedColumn>,
+    /// Whether numeric nulls are encoded as NaN rather than 0.0; not for a schema saved before
+    /// there was a choice
+    #[serde(default)]
+    keep_missing: bool,
+}
+
+impl FeatureSchema {
+    /// A schema with these columns, in this order
+    pub fn new(columns: Vec<EncodedColumn>) -> Self {
+        Self {
+            columns,
+            keep_missing: false,
+        }
+    }
+
+    /// Encode numeric nulls as NaN rather than 0.0 if `keep`, for a model that handles missing
+    /// values itself
+    pub fn keep_missing(mut self, keep: bool) -> Self {
+        self.keep_missing = keep;
+        self
+    }
+
+    /// Whether numeric nulls are encoded as NaN
+    pub fn keeps_missing(&self) -> bool {
+        self.keep_missing
+    }
+
+    /// What a numeric null is encoded as
+    fn null(&self) -> f64 {
+        if self.keep_missing { f64::NAN } else { 0.0 }
+    }
+
+    /// Infer the schema from the raw (pre-encoding) features and the encoded feature matrix
+    ///
+    /// # Arguments
+    /// * `raw` - The selected feature columns before one-hot encoding
+    /// * `encoded` - The output of `Data::get_feature_matrix` for the same features
+    ///
+    /// # Notes
+    /// - Dummy columns are matched back to their source by the `<source>_<category>` naming
+    ///   that Polars' `to_dummies` uses
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn infer(raw: &DataFrame, encoded: &DataFrame) -> Result<Self> {
+        let mut columns = Vec::with_capacity(encoded.width());
+
+        for name in encoded.get_column_names() {
+            if raw.column(name).is_ok_and(|c| c.dtype().is_numeric()) {
+                columns.push(EncodedColumn::Numeric {
+                    source: name.to_string(),
+                });
+                continue;
+            }
+
+            let source = raw
+                .get_columns()
+                .iter()
+                .filter(|c| !c.dtype().is_numeric())
+                .map(|c| c.name().as_str())
+                .find(|source| name.starts_with(&format!("{source}_")));
+
+            match source {
+                Some(source) => columns.push(EncodedColumn::Dummy {
+                    source: source.to_string(),
+                    category: name[source.len() + 1..].to_string(),
+                }),
+                None => bail!("Can't find the source column for encoded column '{name}'"),
+            }
+        }
+
+        Ok(Self::new(columns))
+    }
+
+    /// Number of features the model expects
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn columns(&self) -> &[EncodedColumn] {
+        &self.columns
+    }
+
+    /// Each encoded column's name, in order
+    pub fn names(&self) -> Vec<String> {
+        self.columns.iter().map(EncodedColumn::name).collect()
+    }
+
+    /// The raw columns the features are made from, each once, in the order they're first used
+    pub fn sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = Vec::new();
+        for column in &self.columns {
+            let (EncodedColumn::Numeric { source } | EncodedColumn::Dummy { source, .. }) = column;
+            if !sources.contains(&source.as_str()) {
+                sources.push(source);
+            }
+        }
+
+        sources
+    }
+
+    /// Encode a DataFrame of raw, named features into a feature matrix
+    ///
+    /// # Arguments
+    /// * `df` - Any DataFrame containing the source columns (extra columns are ignored)
+    ///
+    /// # Returns
+    /// * `Result<Array2<f64>>` - A 2D array with shape (df.height(), self.width())
+    ///
+    /// # Notes
+    /// - Nulls get the same treatment as in `encode_json_rows`
+    /// - Numeric columns that can't be cast to f64 are an error rather than being zero-filled
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn encode_frame(&self, df: &DataFrame) -> Result<Array2<f64>> {
+        Ok(self.encode_frame_with_report(df, &mut ConversionReport::new())?)
+    }
+
+    /// `encode_frame`, recording the numeric nulls it fills with 0 (unless it keeps them) and the
+    /// booleans it coerces in `report`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn encode_frame_with_report(
+        &self,
+        df: &DataFrame,
+        report: &mut ConversionReport,
+    ) -> Result<Array2<f64>, DataError> {
+        let mut x = Array2::zeros((df.height(), self.width()));
+        let source_column = |source: &str| {
+            df.column(source).map_err(|_| DataError::MissingColumn {
+                name: source.to_string(),
+            })
+        };
+
+        for (j, column) in self.columns.iter().enumerate() {
+            match column {
+                EncodedColumn::Numeric { source } => {
+                    let raw = source_column(source)?;
+                    let values = raw.strict_cast(&DataType::Float64).map_err(|_| {
+                        DataError::NonNumericColumn {
+                            name: source.to_string(),
+                            dtype: raw.dtype().to_string(),
+                        }
+                    })?;
+                    if !self.keep_missing {
+                        report.record(source, Adjustment::Imputed, raw.null_count());
+                    }
+                    if raw.dtype().is_bool() {
+                        report.record(source, Adjustment::Coerced, raw.len() - raw.null_count());
+                    }
+
+                    for (i, value) in values.f64()?.iter().enumerate() {
+                        x[[i, j]] = value.unwrap_or(self.null());
+                    }
+                }
+                EncodedColumn::Dummy { source, category } => {
+                    let values = source_column(source)?.cast(&DataType::String)?;
+
+                    for (i, value) in values.str()?.iter().enumerate() {
+                        if value.unwrap_or("null") == category {
+                            x[[i, j]] = 1.0;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(x)
+    }
+
+    /// Encode JSON rows of raw, named features into a feature matrix
+    ///
+    /// # Arguments
+    /// * `rows` - One JSON object per sample, keyed by raw column name (e.g. `"Sex": "male"`)
+    ///
+    /// # Returns
+    /// * `Result<Array2<f64>>` - A 2D array with shape (n_samples, self.width())
+    ///
+    /// # Notes
+    /// - Missing keys and `null` are treated like nulls during training: 0.0 (or NaN) for
+    ///   numeric columns, and the `null` category for dummy columns
+    pub fn encode_json_rows(&self, rows: &[Map<String, Value>]) -> Result<Array2<f64>> {
+        let mut data = Vec::with_capacity(rows.len() * self.width());
+
+        for row in rows {
+            for column in &self.columns {
+                let value = match column {
+                    EncodedColumn::Numeric { source } => match row.get(source) {
+                        None | Some(Value::Null) => self.null(),
+                        Some(Value::Number(n)) => n.as_f64().unwrap_or(0.0),
+                        Some(Value::Bool(b)) => f64::from(u8::from(*b)),
+                        Some(other) => bail!("Feature '{source}' must be a number, got {other}"),
+                    },
+                    EncodedColumn::Dummy { source, category } => {
+                        let matches = match row.get(source) {
+                            None | Some(Value::Null) => category == "null",
+                            Some(Value::String(s)) => s == category,
+                            Some(Value::Number(n)) => category.parse::<f64>().ok() == n.as_f64(),
+                            Some(Value::Bool(b)) => category.parse::<bool>() == Ok(*b),
+                            Some(other) => bail!("Feature '{source}' can't be {other}"),
+                        };
+                        if matches { 1.0 } else { 0.0 }
+                    }
+                };
+                data.push(value);
+            }
+        }
+
+        Ok(Array2::from_shape_vec((rows.len(), self.width()), data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::*;
+
+    fn schema() -> FeatureSchema {
+        let raw = df!(
+            "Pclass" => [3i64, 1],
+            "Sex" => ["male", "female"],
+        )
+        .unwrap();
+        let encoded = df!(
+            "Pclass" => [3i64, 1],
+            "Sex_female" => [0i32, 1],
+            "Sex_male" => [1i32, 0],
+        )
+        .unwrap();
+
+        FeatureSchema::infer(&raw, &encoded).unwrap()
+    }
+
+    #[test]
+    fn test_infer() {
+        let schema = schema();
+
+        assert_eq!(schema.width(), 3);
+        assert_eq!(
+            schema.columns[2],
+            EncodedColumn::Dummy {
+                source: "Sex".to_string(),
+                category: "male".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        let df = df!(
+            "Name" => ["a", "b", "c"],
+            "Sex" => [Some("female"), Some("male"), None],
+            "Pclass" => [Some(1i64), None, Some(3)],
+        )
+        .unwrap();
+
+        let x = schema().encode_frame(&df).unwrap();
+
+        assert_eq!(x.dim(), (3, 3));
+        assert_eq!(x.row(0).to_vec(), vec![1.0, 1.0, 0.0]);
+        assert_eq!(x.row(1).to_vec(), vec![0.0, 0.0, 1.0]);
+        assert_eq!(x.row(2).to_vec(), vec![3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_encode_frame_missing_column() {
+        let df = df!("Pclass" => [1i64]).unwrap();
+
+        assert!(schema().encode_frame(&df).is_err());
+    }
+
+    #[test]
+    fn test_encode_json_rows() {
+        let rows: Vec<Map<String, Value>> =
+            serde_json::from_str(r#"[{"Pclass": 2, "Sex": "female"}, {"Sex": "male"}]"#).unwrap();
+
+        let x = schema().encode_json_rows(&rows).unwrap();
+
+        assert_eq!(x.dim(), (2, 3));
+        assert_eq!(x.row(0).to_vec(), vec![2.0, 1.0, 0.0]);
+        assert_eq!(x.row(1).to_vec(), vec![0.0, 0.0, 1.0]); // missing Pclass becomes 0
+    }
+
+    #[test]
+    fn test_encode_json_rows_rejects_strings_for_numeric() {
+        let rows: Vec<Map<String, Value>> =
+            serde_json::from_str(r#"[{"Pclass": "first"}]"#).unwrap();
+
+        assert!(schema().encode_json_rows(&rows).is_err());
+    }
+}
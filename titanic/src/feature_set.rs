@@ -0,0 +1,278 @@
+//! The features a model is trained on, declared once
+//!
+//! A feature is a named Polars expression: a raw column such as `Pclass`, or one worked out from
+//! others, such as a family size from `SibSp` and `Parch`. A `FeatureSet` lists them in order, so
+//! training data, testing data and every binary working on the same problem select the same
+//! features in the same way, rather than each repeating its own list of `col(...)`s.
+//!
+//! A `FeatureSet` is a slice of expressions as far as `Data::fit_feature_matrix` is concerned, so
+//! it can be passed wherever the raw expressions were.
+//!
+//! The Titanic features that have been tried are kept as `Preset`s, chosen by name (e.g.
+//! `--features engineered`, or `"engineered"` in a config file), so a known baseline can be
+//! reproduced without writing its columns out again.
+
+use std::{fmt, str::FromStr};
+
+use polars::{
+    frame::DataFrame,
+    prelude::{DataType, Expr, col, lit},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    conversion_report::ConversionReport, data::Data, dataset::Dataset, error::DataError,
+    feature_schema::FeatureSchema,
+};
+
+/// A named set of Titanic features
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// The tutorial's features: `Pclass`, `Sex`, `Age`, `Fare`, `SibSp` and `Parch`
+    #[default]
+    Baseline,
+    /// The baseline's numeric columns only, without `Sex`
+    Numeric,
+    /// The baseline plus `FamilySize` (`SibSp + Parch + 1`), `IsAlone` and `FarePerPerson`
+    Engineered,
+}
+
+impl Preset {
+    pub const ALL: [Self; 3] = [Self::Baseline, Self::Numeric, Self::Engineered];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Baseline => "baseline",
+            Self::Numeric => "numeric",
+            Self::Engineered => "engineered",
+        }
+    }
+
+    pub fn features(self) -> FeatureSet {
+        let columns = |names: &[&str]| {
+            names
+                .iter()
+                .copied()
+                .fold(FeatureSet::new(), FeatureSet::column)
+        };
+        let family_size = || col("SibSp") + col("Parch") + lit(1);
+
+        match self {
+            Self::Baseline => columns(&["Pclass", "Sex", "Age", "Fare", "SibSp", "Parch"]),
+            Self::Numeric => columns(&["Pclass", "Age", "Fare", "SibSp", "Parch"]),
+            Self::Engineered => Self::Baseline
+                .features()
+                .engineered("FamilySize", family_size())
+                .engineered("IsAlone", family_size().eq(lit(1)).cast(DataType::Int32))
+                .engineered("FarePerPerson", col("Fare") / family_size()),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A preset name that isn't one of `Preset::ALL`
+#[derive(Debug, Error)]
+#[error("No feature preset named {0:?} (try baseline, numeric or engineered)")]
+pub struct UnknownPreset(String);
+
+impl FromStr for Preset {
+    type Err = UnknownPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| UnknownPreset(s.to_string()))
+    }
+}
+
+/// Named feature expressions, in the order their columns appear in the feature matrix
+#[derive(Debug, Clone, Default)]
+pub struct FeatureSet {
+    /// Each aliased to its feature's name
+    exprs: Vec<Expr>,
+}
+
+impl FeatureSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the raw column `name` as it is
+    pub fn column(self, name: &str) -> Self {
+        self.engineered(name, col(name))
+    }
+
+    /// Add a feature called `name`, computed by `expr` (e.g. `col("SibSp") + col("Parch")`)
+    pub fn engineered(mut self, name: &str, expr: Expr) -> Self {
+        self.exprs.push(expr.alias(name));
+        self
+    }
+
+    /// Each feature's name, in order
+    pub fn names(&self) -> Vec<String> {
+        self.exprs
+            .iter()
+            .filter_map(|expr| expr.clone().meta().output_name().ok())
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// The raw columns the features are computed from, each once, in the order they're first used
+    pub fn source_columns(&self) -> Vec<String> {
+        let mut sources: Vec<String> = Vec::new();
+        for name in self
+            .exprs
+            .iter()
+            .flat_map(|expr| expr.clone().meta().root_names())
+        {
+            if !sources.iter().any(|source| source == name.as_str()) {
+                sources.push(name.to_string());
+            }
+        }
+
+        sources
+    }
+
+    /// The features of `data`, unencoded: one column per feature, named after it
+    ///
+    /// Encode the result with the `FeatureSchema` that fitting on the training set's features
+    /// returned, so features computed from raw columns are computed for the testing data too.
+    pub fn select(&self, data: &(impl Data + ?Sized)) -> Result<DataFrame, DataError> {
+        let sources = self.source_columns();
+        data.check_columns_exist(sources.iter().map(String::as_str))?;
+
+        Ok(data.lazy_frame_cloned().select(&self.exprs).collect()?)
+    }
+
+    /// `select`, encoded the way `schema` says, as a `Dataset` whose feature names are the
+    /// schema's columns
+    ///
+    /// Like `Data::get_dataset`, but the features are computed from `data`'s raw columns first,
+    /// so it works for engineered features too.
+    pub fn encode(
+        &self,
+        data: &(impl Data + ?Sized),
+        schema: &FeatureSchema,
+        report: &mut ConversionReport,
+    ) -> Result<Dataset, DataError> {
+        Ok(Dataset {
+            x: schema.encode_frame_with_report(&self.select(data)?, report)?,
+            feature_names: schema.names(),
+            y: None,
+            ids: None,
+        })
+    }
+}
+
+impl AsRef<[Expr]> for FeatureSet {
+    fn as_ref(&self) -> &[Expr] {
+        &self.exprs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::df;
+
+    #[test]
+    fn test_engineered_features_are_computed_for_every_frame() {
+        let features = FeatureSet::new()
+            .column("Sex")
+            .engineered("FamilySize", col("SibSp") + col("Parch") + lit(1));
+        assert_eq!(features.names(), ["Sex", "FamilySize"]);
+        assert_eq!(features.source_columns(), ["Sex", "SibSp", "Parch"]);
+
+        let train = df!(
+            "Sex" => ["male", "female"],
+            "SibSp" => [1i64, 0],
+            "Parch" => [0i64, 2],
+            "Survived" => [0i64, 1],
+        )
+        .unwrap();
+        let (x_df, schema) = train
+            .fit_feature_matrix(&features, &mut ConversionReport::new())
+            .unwrap();
+        assert_eq!(
+            x_df.get_column_names(),
+            ["Sex_female", "Sex_male", "FamilySize"]
+        );
+
+        // The test set has the raw columns, not the engineered one
+        let test = df!(
+            "SibSp" => [3i64],
+            "Parch" => [1i64],
+            "Sex" => ["female"],
+        )
+        .unwrap();
+        let test_set = features
+            .encode(&test, &schema, &mut ConversionReport::new())
+            .unwrap();
+        assert_eq!(
+            test_set.feature_names,
+            ["Sex_female", "Sex_male", "FamilySize"]
+        );
+        assert_eq!(test_set.x.row(0).to_vec(), [1.0, 0.0, 5.0]);
+
+        assert!(matches!(
+            features.select(&test.drop("Parch").unwrap()),
+            Err(DataError::MissingColumn { name }) if name == "Parch"
+        ));
+    }
+
+    #[test]
+    fn test_presets() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.to_string().parse::<Preset>().unwrap(), preset);
+            assert_eq!(
+                serde_json::to_string(&preset).unwrap(),
+                format!("{:?}", preset.name())
+            );
+        }
+        assert!("everything".parse::<Preset>().is_err());
+        assert_eq!(
+            serde_json::from_str::<Preset>(r#""numeric""#).unwrap(),
+            Preset::Numeric
+        );
+
+        let passengers = df!(
+            "Pclass" => [3i64, 1],
+            "Sex" => ["male", "female"],
+            "Age" => [Some(22.0), None],
+            "SibSp" => [1i64, 0],
+            "Parch" => [0i64, 0],
+            "Fare" => [7.25, 30.0],
+        )
+        .unwrap();
+        let numeric = Preset::Numeric.features().select(&passengers).unwrap();
+        assert!(numeric.column("Sex").is_err());
+
+        let engineered = Preset::Engineered.features();
+        assert_eq!(
+            engineered.source_columns(),
+            Preset::Baseline.features().source_columns()
+        );
+        let x = engineered.select(&passengers).unwrap();
+        let column = |name| x.column(name).unwrap().cast(&DataType::Float64).unwrap();
+        assert_eq!(
+            column("FamilySize").f64().unwrap().to_vec(),
+            [Some(2.0), Some(1.0)]
+        );
+        assert_eq!(
+            column("IsAlone").f64().unwrap().to_vec(),
+            [Some(0.0), Some(1.0)]
+        );
+        assert_eq!(
+            column("FarePerPerson").f64().unwrap().to_vec(),
+            [Some(3.625), Some(30.0)]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! C ABI for embedding a saved model in non-Rust applications (`--features ffi`)
+//!
+//! The declarations live in `include/ml_fun.h`. Models are opaque handles created by
+//! `ml_fun_load_model` and released with `ml_fun_free`. `ml_fun_predict` takes the already encoded
+//! feature matrix (the same columns, in the same order, as `Data::get_feature_matrix` produced
+//! during training), row-major.
+
+use std::{
+    ffi::{CStr, c_char},
+    ptr, slice,
+};
+
+use ndarray::ArrayView2;
+
+use crate::saved_model::SavedModel;
+
+/// The call succeeded
+pub const ML_FUN_OK: i32 = 0;
+/// A required pointer argument was NULL
+pub const ML_FUN_ERR_NULL_POINTER: i32 = -1;
+/// `n_features` doesn't match the number of features the model was trained on
+pub const ML_FUN_ERR_FEATURE_COUNT: i32 = -2;
+/// `n_rows * n_features` doubles are more than a buffer can hold
+pub const ML_FUN_ERR_TOO_LARGE: i32 = -3;
+
+/// Load a model saved by `titanic-ml train`
+///
+/// Returns NULL if the path isn't valid UTF-8 or the model can't be read.
+///
+/// # Safety
+/// `path` must be NULL or a valid, NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ml_fun_load_model(path: *const c_char) -> *mut SavedModel {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    // SAFETY: checked for NULL above; the caller guarantees NUL termination
+    let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+        return ptr::null_mut();
+    };
+
+    match SavedModel::load(path) {
+        Ok(model) => Box::into_raw(Box::new(model)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Predict class labels for `n_rows` samples
+///
+/// Writes one label per row into `out_predictions` and returns `ML_FUN_OK`, or an `ML_FUN_ERR_*`
+/// code without touching the output.
+///
+/// # Safety
+/// - `model` must be NULL or a handle returned by `ml_fun_load_model` that hasn't been freed
+/// - `features` must be NULL or point to `n_rows * n_features` doubles
+/// - `out_predictions` must be NULL or point to space for `n_rows` values
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ml_fun_predict(
+    model: *const SavedModel,
+    features: *const f64,
+    n_rows: usize,
+    n_features: usize,
+    out_predictions: *mut usize,
+) -> i32 {
+    if model.is_null() || features.is_null() || out_predictions.is_null() {
+        return ML_FUN_ERR_NULL_POINTER;
+    }
+
+    // SAFETY: checked for NULL above; the caller guarantees it's a live handle
+    let model = unsafe { &*model };
+    if n_features != model.feature_schema().width() {
+        return ML_FUN_ERR_FEATURE_COUNT;
+    }
+
+    // A slice can't span more than `isize::MAX` bytes
+    let Some(len) = n_rows
+        .checked_mul(n_features)
+        .filter(|&len| len <= isize::MAX as usize / size_of::<f64>())
+    else {
+        return ML_FUN_ERR_TOO_LARGE;
+    };
+
+    // SAFETY: the caller guarantees the buffer sizes
+    let features = unsafe { slice::from_raw_parts(features, len) };
+    let out_predictions = unsafe { slice::from_raw_parts_mut(out_predictions, n_rows) };
+
+    let Ok(x) = ArrayView2::from_shape((n_rows, n_features), features) else {
+        return ML_FUN_ERR_FEATURE_COUNT;
+    };
+    let predictions = model.forest().predict(x);
+
+    for (out, prediction) in out_predictions.iter_mut().zip(predictions) {
+        *out = prediction;
+    }
+
+    ML_FUN_OK
+}
+
+/// Release a model handle; NULL is ignored
+///
+/// # Safety
+/// `model` must be NULL or a handle returned by `ml_fun_load_model` that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ml_fun_free(model: *mut SavedModel) {
+    if !model.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` in `ml_fun_load_model`
+        drop(unsafe { Box::from_raw(model) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{feature_schema::FeatureSchema, random_forest::RandomForestClassifier};
+    use ndarray::{arr1, arr2};
+    use polars::prelude::df;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_null_arguments() {
+        unsafe {
+            assert!(ml_fun_load_model(ptr::null()).is_null());
+            assert_eq!(
+                ml_fun_predict(ptr::null(), ptr::null(), 0, 0, ptr::null_mut()),
+                ML_FUN_ERR_NULL_POINTER
+            );
+            ml_fun_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let path = c"does/not/exist.json";
+
+        assert!(unsafe { ml_fun_load_model(path.as_ptr()) }.is_null());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let raw = df!("Fare" => [1.0, 2.0, 10.0, 20.0]).unwrap();
+        let schema = FeatureSchema::infer(&raw, &raw).unwrap();
+        let forest = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(1)
+            .fit(
+                arr2(&[[1.0], [2.0], [10.0], [20.0]]),
+                arr1(&[0usize, 0, 1, 1]),
+            )
+            .unwrap();
+        let model = SavedModel::new(schema, forest);
+        let path = std::env::temp_dir().join("titanic-ml-ffi-test.bin");
+        model.save(&path).unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+
+        let x = arr2(&[[1.5], [15.0], [5.0]]);
+        let mut predictions = [usize::MAX; 3];
+        unsafe {
+            let handle = ml_fun_load_model(path.as_ptr());
+            assert!(!handle.is_null());
+            assert_eq!(
+                ml_fun_predict(handle, x.as_ptr(), 3, 1, predictions.as_mut_ptr()),
+                ML_FUN_OK
+            );
+            assert_eq!(
+                ml_fun_predict(handle, x.as_ptr(), 3, 2, predictions.as_mut_ptr()),
+                ML_FUN_ERR_FEATURE_COUNT
+            );
+            assert_eq!(
+                ml_fun_predict(handle, x.as_ptr(), usize::MAX, 1, predictions.as_mut_ptr()),
+                ML_FUN_ERR_TOO_LARGE
+            );
+            ml_fun_free(handle);
+        }
+
+        assert_eq!(predictions.to_vec(), model.forest().predict(&x).to_vec());
+    }
+}
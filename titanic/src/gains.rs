@@ -0,0 +1,186 @@
+//! Lift and cumulative gains, for judging a ranking the way the people acting on it do
+//!
+//! A model's scores often decide who gets looked at first: which patients get a heart-attack
+//! screening, which passengers a lifeboat. AUC says how good the ranking is on the whole; these
+//! say what it buys at each depth. Rows are sorted by score, highest first, and cut into
+//! `n_bins` equal bins (deciles for 10). `lift_chart` gives each bin's share of positives over
+//! the overall share, so a first-decile lift of 3 means the top 10% of scores find positives at
+//! three times the base rate. `cumulative_gains` gives the share of all the positives found by
+//! taking the top bins, so "the top 30% of scores find 70% of the heart attacks".
+//!
+//! Both are DataFrames, one row per bin, to print, plot or write out as they are.
+
+use anyhow::{Result, bail};
+use ndarray::Array1;
+use polars::prelude::*;
+
+/// One bin's rows and how many of them are positive
+struct Bin {
+    rows: usize,
+    positives: usize,
+}
+
+/// `actual` sorted by `scores`, highest first, in `n_bins` bins of as near the same size as can be
+///
+/// Tied scores are kept in their original order, so a bin boundary through a tie doesn't depend
+/// on the sort.
+fn ranked_bins(
+    scores: &Array1<f64>,
+    actual: &Array1<usize>,
+    positive: usize,
+    n_bins: usize,
+) -> Result<Vec<Bin>> {
+    if scores.len() != actual.len() {
+        bail!("Got {} scores for {} labels", scores.len(), actual.len());
+    }
+    if n_bins == 0 || n_bins > scores.len() {
+        bail!(
+            "Need between 1 and {} bins (one per row), got {n_bins}",
+            scores.len()
+        );
+    }
+    if let Some(score) = scores.iter().find(|score| score.is_nan()) {
+        bail!("Scores can't be NaN, got {score}");
+    }
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    Ok((0..n_bins)
+        .map(|bin| {
+            let rows = &order[bin * order.len() / n_bins..(bin + 1) * order.len() / n_bins];
+            Bin {
+                rows: rows.len(),
+                positives: rows.iter().filter(|&&row| actual[row] == positive).count(),
+            }
+        })
+        .collect())
+}
+
+/// Each bin's `Rows`, `Positives`, `Positive rate` and `Lift` (its positive rate over the
+/// overall one), from the highest scores down
+///
+/// `scores` can be anything that ranks rows by how likely they are to be `positive`, such as that
+/// class's column of `predict_proba`. The lift is NaN if there are no positives.
+pub fn lift_chart(
+    scores: &Array1<f64>,
+    actual: &Array1<usize>,
+    positive: usize,
+    n_bins: usize,
+) -> Result<DataFrame> {
+    let bins = ranked_bins(scores, actual, positive, n_bins)?;
+    let base_rate =
+        bins.iter().map(|bin| bin.positives).sum::<usize>() as f64 / actual.len() as f64;
+    let rates: Vec<f64> = bins
+        .iter()
+        .map(|bin| bin.positives as f64 / bin.rows as f64)
+        .collect();
+    let lifts: Vec<f64> = rates.iter().map(|rate| rate / base_rate).collect();
+
+    Ok(DataFrame::new(vec![
+        Column::new("Bin".into(), (1..=n_bins as u32).collect::<Vec<_>>()),
+        Column::new(
+            "Rows".into(),
+            bins.iter().map(|bin| bin.rows as u32).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "Positives".into(),
+            bins.iter()
+                .map(|bin| bin.positives as u32)
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("Positive rate".into(), rates),
+        Column::new("Lift".into(), lifts),
+    ])?)
+}
+
+/// For the top 1, 2, ... `n_bins` bins together: the `Population` (the share of all rows they
+/// hold), their `Positives`, the `Gain` (the share of all positives they hold) and the
+/// `Cumulative lift` (the gain over the population)
+///
+/// A random ranking's gain is about its population, and a perfect one reaches 1 as soon as the
+/// population covers the positives. The gains are NaN if there are no positives.
+pub fn cumulative_gains(
+    scores: &Array1<f64>,
+    actual: &Array1<usize>,
+    positive: usize,
+    n_bins: usize,
+) -> Result<DataFrame> {
+    let bins = ranked_bins(scores, actual, positive, n_bins)?;
+    let total_positives = bins.iter().map(|bin| bin.positives).sum::<usize>() as f64;
+
+    let (mut rows, mut positives) = (0, 0);
+    let mut population = Vec::with_capacity(n_bins);
+    let mut cumulative_positives = Vec::with_capacity(n_bins);
+    let mut gain = Vec::with_capacity(n_bins);
+    let mut lift = Vec::with_capacity(n_bins);
+    for bin in &bins {
+        rows += bin.rows;
+        positives += bin.positives;
+        let share = rows as f64 / actual.len() as f64;
+        let captured = positives as f64 / total_positives;
+        population.push(share);
+        cumulative_positives.push(positives as u32);
+        gain.push(captured);
+        lift.push(captured / share);
+    }
+
+    Ok(DataFrame::new(vec![
+        Column::new("Bin".into(), (1..=n_bins as u32).collect::<Vec<_>>()),
+        Column::new("Population".into(), population),
+        Column::new("Positives".into(), cumulative_positives),
+        Column::new("Gain".into(), gain),
+        Column::new("Cumulative lift".into(), lift),
+    ])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, s};
+
+    #[test]
+    fn test_lift_and_gains() {
+        // 10 rows, 4 positive; the ranking finds 2 of them first, then a negative, and so on
+        let scores = arr1(&[0.9, 0.8, 0.7, 0.6, 0.5, 0.4, 0.3, 0.2, 0.1, 0.0]);
+        let actual = arr1(&[1, 1, 0, 1, 0, 0, 1, 0, 0, 0]);
+
+        let lift = lift_chart(&scores, &actual, 1, 5).unwrap();
+        assert_eq!(
+            lift.get_column_names(),
+            ["Bin", "Rows", "Positives", "Positive rate", "Lift"]
+        );
+        let values = |df: &DataFrame, name: &str| -> Vec<f64> {
+            df.column(name)
+                .unwrap()
+                .cast(&DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect()
+        };
+        assert_eq!(values(&lift, "Positives"), [2.0, 1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(values(&lift, "Lift"), [2.5, 1.25, 0.0, 1.25, 0.0]);
+
+        let gains = cumulative_gains(&scores, &actual, 1, 5).unwrap();
+        assert_eq!(values(&gains, "Population"), [0.2, 0.4, 0.6, 0.8, 1.0]);
+        assert_eq!(values(&gains, "Gain"), [0.5, 0.75, 0.75, 1.0, 1.0]);
+        assert_eq!(values(&gains, "Cumulative lift")[0], 2.5);
+        assert_eq!(values(&gains, "Cumulative lift")[4], 1.0);
+
+        // The scores' order is all that matters, not the rows'
+        let (reversed_scores, reversed_actual) = (
+            scores.slice(s![..;-1]).to_owned(),
+            actual.slice(s![..;-1]).to_owned(),
+        );
+        assert_eq!(
+            cumulative_gains(&reversed_scores, &reversed_actual, 1, 5).unwrap(),
+            gains
+        );
+
+        assert!(lift_chart(&scores, &actual, 1, 0).is_err());
+        assert!(lift_chart(&scores, &actual, 1, 11).is_err());
+        assert!(cumulative_gains(&arr1(&[0.5]), &actual, 1, 1).is_err());
+    }
+}
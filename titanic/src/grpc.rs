@@ -0,0 +1,109 @@
+//! A tonic gRPC service for batch predictions (`--features grpc`)
+//!
+//! The contract is `proto/predict.proto`. Rows carry the same raw, named features as the HTTP
+//! `POST /predict` endpoint and go through the same encoding.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use serde_json::{Map, Number, Value};
+use tonic::{Request, Response, Status, transport::Server};
+
+use crate::saved_model::SavedModel;
+
+pub mod proto {
+    tonic::include_proto!("mlfun.v1");
+}
+
+use proto::{
+    PredictRequest, PredictResponse, Prediction, Row,
+    predictor_server::{Predictor, PredictorServer},
+    value::Kind,
+};
+
+struct PredictorService {
+    model: Arc<SavedModel>,
+}
+
+#[tonic::async_trait]
+impl Predictor for PredictorService {
+    async fn predict(
+        &self,
+        request: Request<PredictRequest>,
+    ) -> Result<Response<PredictResponse>, Status> {
+        let rows: Vec<Map<String, Value>> = request
+            .into_inner()
+            .rows
+            .into_iter()
+            .map(row_to_json)
+            .collect();
+
+        let predictions = self
+            .model
+            .predict_json_rows(&rows)
+            .map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+
+        Ok(Response::new(PredictResponse {
+            classes: predictions.classes.iter().map(|&c| c as u64).collect(),
+            predictions: predictions
+                .predictions
+                .into_iter()
+                .zip(predictions.probabilities)
+                .map(|(label, probabilities)| Prediction {
+                    label: label as u64,
+                    probabilities,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Serve `model` over gRPC on `addr` until the process is stopped
+pub async fn run(model: Arc<SavedModel>, addr: SocketAddr) -> anyhow::Result<()> {
+    println!("gRPC listening on {addr}");
+    Server::builder()
+        .add_service(PredictorServer::new(PredictorService { model }))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
+
+fn row_to_json(row: Row) -> Map<String, Value> {
+    row.features
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value.kind {
+                Some(Kind::Number(n)) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+                Some(Kind::Text(s)) => Value::String(s),
+                None => Value::Null,
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::Value as ProtoValue;
+
+    #[test]
+    fn test_row_to_json() {
+        let row = Row {
+            features: [
+                ("Pclass", Some(Kind::Number(3.0))),
+                ("Sex", Some(Kind::Text("male".to_string()))),
+                ("Age", None),
+            ]
+            .into_iter()
+            .map(|(name, kind)| (name.to_string(), ProtoValue { kind }))
+            .collect(),
+        };
+
+        let json = row_to_json(row);
+
+        assert_eq!(json["Pclass"], 3.0);
+        assert_eq!(json["Sex"], "male");
+        assert_eq!(json["Age"], Value::Null);
+    }
+}
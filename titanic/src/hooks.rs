@@ -0,0 +1,117 @@
+//! Callbacks for watching a forest fit and predict
+//!
+//! A progress bar, a profiler or an experiment tracker wants to know when fitting starts, as
+//! each tree is done and as batches are predicted. Rather than the forest printing any of that
+//! itself, it calls the `Hooks` it was given with `RandomForestClassifier::hooks`, and a fitted
+//! forest keeps calling them as it predicts.
+
+use std::sync::Arc;
+
+/// What a forest reports as it fits and predicts
+///
+/// Every method does nothing by default, so an implementation only writes the ones it needs.
+/// Trees are fitted in parallel, so the methods take `&self` and may be called from several
+/// threads at once: count with atomics or behind a `Mutex`.
+pub trait Hooks: Send + Sync {
+    /// A forest of `n_trees` trees is about to be fitted on `n_samples` rows of `n_features`
+    /// features
+    fn on_fit_start(&self, n_trees: usize, n_samples: usize, n_features: usize) {
+        let _ = (n_trees, n_samples, n_features);
+    }
+
+    /// Another tree is fitted, making `n_done` so far (counting any a checkpoint resumed with)
+    fn on_tree_done(&self, n_done: usize) {
+        let _ = n_done;
+    }
+
+    /// The forest is fitted, and kept `n_trees` trees (fewer than it fitted if it stopped
+    /// early)
+    fn on_fit_done(&self, n_trees: usize) {
+        let _ = n_trees;
+    }
+
+    /// A fitted forest predicted `n_rows` rows
+    fn on_predict_batch(&self, n_rows: usize) {
+        let _ = n_rows;
+    }
+}
+
+/// Hooks that ignore everything, which a forest has until it's given others
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
+
+pub(crate) fn none() -> Arc<dyn Hooks> {
+    Arc::new(NoHooks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Mutex};
+
+    use ndarray::{Array1, Array2, Axis};
+
+    use super::*;
+    use crate::{random_forest::RandomForestClassifier, seed::Seed};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl Hooks for Recorder {
+        fn on_fit_start(&self, n_trees: usize, n_samples: usize, n_features: usize) {
+            let event = format!("start {n_trees} {n_samples}x{n_features}");
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn on_tree_done(&self, n_done: usize) {
+            self.events.lock().unwrap().push(format!("tree {n_done}"));
+        }
+
+        fn on_fit_done(&self, n_trees: usize) {
+            self.events.lock().unwrap().push(format!("done {n_trees}"));
+        }
+
+        fn on_predict_batch(&self, n_rows: usize) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("predict {n_rows}"));
+        }
+    }
+
+    #[test]
+    fn test_forest_reports_to_its_hooks() {
+        let x = Array2::from_shape_fn((20, 2), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(20, |i| usize::from(i >= 10));
+        let recorder = Arc::new(Recorder::default());
+
+        let model = RandomForestClassifier::new()
+            .n_estimators(4)
+            .random_state(Seed::new(3))
+            // On more threads, trees can finish (and so report) out of order
+            .n_threads(NonZeroUsize::new(1).unwrap())
+            .hooks(recorder.clone())
+            .fit(&x, &y)
+            .unwrap();
+        let _: Vec<_> = model
+            .predict_iter(x.axis_chunks_iter(Axis(0), 15))
+            .collect();
+
+        let events = recorder.events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            [
+                "start 4 20x2",
+                "tree 1",
+                "tree 2",
+                "tree 3",
+                "tree 4",
+                "done 4",
+                "predict 15",
+                "predict 5"
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Looking inside a CSV file without knowing its columns up front
+//!
+//! Column types are inferred by Polars, so this works on any CSV with a header row, not just the
+//! Titanic data:
+//!
+//! ```text
+//! $ titanic-ml inspect data/train.csv --filter "Age > 60 AND Sex = 'female'"
+//! ```
+
+use std::{fmt, path::Path};
+
+use anyhow::{Context, Result};
+use polars::{prelude::*, sql::sql_expr};
+
+/// What a column looks like
+#[derive(Debug)]
+pub struct ColumnSummary {
+    pub name: String,
+    pub dtype: DataType,
+    pub null_count: usize,
+    /// `null_count` as a fraction of the number of rows
+    pub null_rate: f64,
+    /// The first few distinct non-null values, in file order
+    pub examples: Vec<String>,
+}
+
+/// A summary of every column in a file
+#[derive(Debug)]
+pub struct Inspection {
+    pub n_rows: usize,
+    pub columns: Vec<ColumnSummary>,
+}
+
+/// Lazily scan a CSV with a header row, inferring column types
+pub fn scan_csv(path: impl AsRef<Path>) -> Result<LazyFrame> {
+    let path = path.as_ref();
+    let path_str = path
+        .to_str()
+        .with_context(|| format!("Path isn't valid UTF-8: {}", path.display()))?;
+
+    LazyCsvReader::new(PlPath::from_str(path_str))
+        .with_has_header(true)
+        .finish()
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Summarize every column of `lazy_frame`, with up to `n_examples` example values each
+pub fn inspect(lazy_frame: LazyFrame, n_examples: usize) -> Result<Inspection> {
+    let schema = lazy_frame.clone().collect_schema()?;
+
+    // Compute everything in one pass; aliases are by position so odd column names can't clash
+    let mut exprs = vec![len().alias("n_rows")];
+    for (i, name) in schema.iter_names().enumerate() {
+        exprs.push(col(name.clone()).null_count().alias(format!("nulls_{i}")));
+        exprs.push(
+            col(name.clone())
+                .drop_nulls()
+                .unique_stable()
+                .head(Some(n_examples))
+                .implode()
+                .alias(format!("examples_{i}")),
+        );
+    }
+    let stats = lazy_frame.select(exprs).collect()?;
+
+    let n_rows = stats.column("n_rows")?.get(0)?.try_extract::<usize>()?;
+
+    let columns = schema
+        .iter()
+        .enumerate()
+        .map(|(i, (name, dtype))| {
+            let null_count = stats
+                .column(&format!("nulls_{i}"))?
+                .get(0)?
+                .try_extract::<usize>()?;
+            let examples = stats
+                .column(&format!("examples_{i}"))?
+                .list()?
+                .get_as_series(0)
+                .map(|values| -> Result<Vec<String>> {
+                    let values = values.cast(&DataType::String)?;
+                    Ok(values
+                        .str()?
+                        .into_no_null_iter()
+                        .map(String::from)
+                        .collect())
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            Ok(ColumnSummary {
+                name: name.to_string(),
+                dtype: dtype.clone(),
+                null_count,
+                null_rate: if n_rows == 0 {
+                    0.0
+                } else {
+                    null_count as f64 / n_rows as f64
+                },
+                examples,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Inspection { n_rows, columns })
+}
+
+/// Rows matching a SQL `WHERE`-style expression, e.g. `"Age > 60 AND Sex = 'female'"`
+pub fn filter_rows(lazy_frame: LazyFrame, filter: &str, limit: usize) -> Result<DataFrame> {
+    let predicate = sql_expr(filter).with_context(|| format!("Couldn't parse filter: {filter}"))?;
+
+    Ok(lazy_frame
+        .filter(predicate)
+        .limit(limit as IdxSize)
+        .collect()?)
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} rows, {} columns", self.n_rows, self.columns.len())?;
+        writeln!(f)?;
+
+        let name_width = self
+            .columns
+            .iter()
+            .map(|c| c.name.len())
+            .chain(["column".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:name_width$}  {:8}  {:>6}  examples",
+            "column", "type", "nulls"
+        )?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "{:name_width$}  {:8}  {:>5.1}%  {}",
+                column.name,
+                column.dtype.to_string(),
+                column.null_rate * 100.0,
+                column.examples.join(" | ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lazy_frame() -> LazyFrame {
+        df!(
+            "Name" => ["a", "b", "c", "d"],
+            "Age" => [Some(30i64), None, Some(70), Some(30)],
+        )
+        .unwrap()
+        .lazy()
+    }
+
+    #[test]
+    fn test_inspect() {
+        let inspection = inspect(lazy_frame(), 3).unwrap();
+
+        assert_eq!(inspection.n_rows, 4);
+
+        let age = &inspection.columns[1];
+        assert_eq!(age.name, "Age");
+        assert_eq!(age.dtype, DataType::Int64);
+        assert_eq!(age.null_count, 1);
+        assert_eq!(age.null_rate, 0.25);
+        assert_eq!(age.examples, ["30", "70"]);
+    }
+
+    #[test]
+    fn test_filter_rows() {
+        let rows = filter_rows(lazy_frame(), "Age > 40 OR Name = 'a'", 10).unwrap();
+
+        assert_eq!(rows.height(), 2);
+        assert!(filter_rows(lazy_frame(), "Age >", 10).is_err());
+    }
+}
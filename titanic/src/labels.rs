@@ -0,0 +1,156 @@
+//! Labels that say what they mean
+//!
+//! Classes are `usize` indices by default, so a prediction of `1` only means "survived" to someone
+//! who knows the data. The forest fits and predicts any type linfa accepts as a `Label` instead,
+//! such as an enum: `series_to_labels` reads one from an integer column (through `TryFrom<i64>`)
+//! or a string column (through `FromStr`), and `labels_to_series` writes predictions back out by
+//! name. `Survived` is the Titanic's.
+
+use std::{fmt, str::FromStr};
+
+use linfa::Label;
+use ndarray::Array1;
+use polars::prelude::{DataType, NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+
+use crate::error::DataError;
+
+/// Whether a passenger survived, as a label
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Survived {
+    #[default]
+    No,
+    Yes,
+}
+
+impl Label for Survived {}
+
+/// From the `Survived` column's 0 and 1
+impl TryFrom<i64> for Survived {
+    type Error = i64;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::No),
+            1 => Ok(Self::Yes),
+            _ => Err(value),
+        }
+    }
+}
+
+/// From `Display`'s "No" and "Yes"
+impl FromStr for Survived {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "No" => Ok(Self::No),
+            "Yes" => Ok(Self::Yes),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Survived {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::No => "No",
+            Self::Yes => "Yes",
+        })
+    }
+}
+
+/// Read a column of labels: integers through `TryFrom<i64>`, anything else as strings through
+/// `FromStr`
+///
+/// A null, or a value neither conversion accepts, is a `DataError::UnknownLabel` rather than
+/// being filled in, since no label is a safe guess.
+pub fn series_to_labels<L: TryFrom<i64> + FromStr>(
+    series: &Series,
+) -> Result<Array1<L>, DataError> {
+    let unknown = |value: Option<String>| DataError::UnknownLabel {
+        column: series.name().to_string(),
+        value: value.unwrap_or_else(|| "null".to_string()),
+    };
+
+    if series.dtype().is_integer() {
+        let values = series.cast(&DataType::Int64)?;
+        values
+            .i64()?
+            .iter()
+            .map(|value| {
+                value
+                    .and_then(|value| L::try_from(value).ok())
+                    .ok_or_else(|| unknown(value.map(|value| value.to_string())))
+            })
+            .collect()
+    } else {
+        let values = series.cast(&DataType::String)?;
+        values
+            .str()?
+            .iter()
+            .map(|value| {
+                value
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| unknown(value.map(str::to_string)))
+            })
+            .collect()
+    }
+}
+
+/// Labels (e.g. predictions) as a string column called `name`, each written with `Display`
+pub fn labels_to_series<L: fmt::Display>(name: &str, labels: &Array1<L>) -> Series {
+    let labels: Vec<String> = labels.iter().map(ToString::to_string).collect();
+
+    Series::new(name.into(), labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_forest::RandomForestClassifier, seed::Seed};
+    use ndarray::{Array2, arr1};
+
+    #[test]
+    fn test_fit_and_predict_with_an_enum() {
+        let survived = Series::new("Survived".into(), [0i64, 1, 1, 0]);
+        let y: Array1<Survived> = series_to_labels(&survived).unwrap();
+        assert_eq!(
+            y,
+            arr1(&[Survived::No, Survived::Yes, Survived::Yes, Survived::No])
+        );
+
+        let x = Array2::from_shape_fn((20, 1), |(i, _)| i as f64);
+        let y = Array1::from_shape_fn(20, |i| if i >= 10 { Survived::Yes } else { Survived::No });
+        let model = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(3))
+            .fit(&x, &y)
+            .unwrap();
+        assert_eq!(model.classes(), [Survived::No, Survived::Yes]);
+        let predictions = model.predict(&x);
+        assert_eq!(predictions, y);
+
+        // Written out by name, and read back from those names
+        let written = labels_to_series("prediction", &predictions);
+        assert_eq!(written.str().unwrap().get(0), Some("No"));
+        assert_eq!(series_to_labels::<Survived>(&written).unwrap(), y);
+    }
+
+    #[test]
+    fn test_unknown_labels_are_refused() {
+        let survived = Series::new("Survived".into(), [Some(0i64), Some(2), None]);
+        assert!(matches!(
+            series_to_labels::<Survived>(&survived),
+            Err(DataError::UnknownLabel { value, .. }) if value == "2"
+        ));
+
+        let survived = Series::new("Survived".into(), [Some("Yes"), None]);
+        assert!(matches!(
+            series_to_labels::<Survived>(&survived),
+            Err(DataError::UnknownLabel { value, .. }) if value == "null"
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+//! Features that give the answer away
+//!
+//! A feature built from something only known after the fact, like whether a passenger got a
+//! lifeboat, predicts the training labels almost perfectly and the real ones no better than
+//! chance. `Dataset::check_leakage` flags the features that are too good to be true before any
+//! time is spent training on them: ones that correlate almost perfectly with the label, and ones
+//! with a common value whose rows nearly all have the same label.
+
+use std::fmt;
+
+use crate::{dataset::Dataset, error::DataError};
+
+/// A feature with more distinct values than this is treated as continuous, and only checked for
+/// correlation
+const MAX_LEVELS: usize = 10;
+
+/// How good a feature has to be to be suspicious
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeakageThresholds {
+    /// The size of correlation with the label (either way) from which a feature is suspect
+    pub correlation: f64,
+    /// The share of a value's rows with the same label from which it's suspect...
+    pub purity: f64,
+    /// ...if at least this share of all the rows have that value, so a rare value that happens to
+    /// have one label, like a title only six passengers had, isn't
+    pub min_level_share: f64,
+}
+
+impl Default for LeakageThresholds {
+    fn default() -> Self {
+        Self {
+            correlation: 0.9,
+            purity: 0.99,
+            min_level_share: 0.05,
+        }
+    }
+}
+
+/// Why a feature is suspect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Suspicion {
+    /// It correlates with the label this much
+    Correlation(f64),
+    /// `purity` of the `rows` where it's `value` have the label `label`
+    PureLevel {
+        value: f64,
+        rows: usize,
+        label: usize,
+        purity: f64,
+    },
+}
+
+/// A feature that might leak the label
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspectFeature {
+    pub name: String,
+    pub suspicion: Suspicion,
+}
+
+/// The suspect features found by `Dataset::check_leakage`, in the dataset's order
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeakageReport {
+    pub suspects: Vec<SuspectFeature>,
+}
+
+impl LeakageReport {
+    pub fn is_clean(&self) -> bool {
+        self.suspects.is_empty()
+    }
+}
+
+impl fmt::Display for LeakageReport {
+    /// One line per suspect feature, e.g. `Boat_missing: 99.8% of the 549 rows where it's 1 have
+    /// the label 0`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, suspect) in self.suspects.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match suspect.suspicion {
+                Suspicion::Correlation(correlation) => write!(
+                    f,
+                    "{}: correlates {correlation:.3} with the label",
+                    suspect.name
+                )?,
+                Suspicion::PureLevel {
+                    value,
+                    rows,
+                    label,
+                    purity,
+                } => write!(
+                    f,
+                    "{}: {:.1}% of the {rows} rows where it's {value} have the label {label}",
+                    suspect.name,
+                    purity * 100.0
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Dataset {
+    /// Check each feature for too strong an association with the labels (see
+    /// `LeakageThresholds`)
+    ///
+    /// A feature suspect both ways is reported for its correlation. Rows missing a feature (NaN)
+    /// are left out of its checks. A dataset without labels is a `DataError::NoLabels`.
+    pub fn check_leakage(&self, thresholds: LeakageThresholds) -> Result<LeakageReport, DataError> {
+        let y = self.labels()?;
+        let n_classes = y.iter().max().map_or(0, |&max| max + 1);
+
+        let mut suspects = Vec::new();
+        for (name, feature) in self.feature_names.iter().zip(self.x.columns()) {
+            let present: Vec<(f64, usize)> = feature
+                .iter()
+                .zip(y)
+                .filter(|(value, _)| !value.is_nan())
+                .map(|(&value, &label)| (value, label))
+                .collect();
+            let suspicion = correlation(
+                present.iter().map(|&(value, _)| value),
+                present.iter().map(|&(_, label)| label as f64),
+            )
+            .filter(|correlation| correlation.abs() >= thresholds.correlation)
+            .map(Suspicion::Correlation)
+            .or_else(|| {
+                // Each distinct value and how many of its rows have each label
+                let mut levels: Vec<(f64, Vec<usize>)> = Vec::new();
+                for &(value, label) in &present {
+                    match levels.iter().position(|(level, _)| *level == value) {
+                        Some(i) => levels[i].1[label] += 1,
+                        None if levels.len() == MAX_LEVELS => return None,
+                        None => {
+                            let mut counts = vec![0; n_classes];
+                            counts[label] += 1;
+                            levels.push((value, counts));
+                        }
+                    }
+                }
+                levels.into_iter().find_map(|(value, counts)| {
+                    let rows: usize = counts.iter().sum();
+                    let (label, &most) =
+                        counts.iter().enumerate().max_by_key(|&(_, count)| count)?;
+                    let purity = most as f64 / rows as f64;
+                    (rows as f64 >= thresholds.min_level_share * y.len() as f64
+                        && purity >= thresholds.purity)
+                        .then_some(Suspicion::PureLevel {
+                            value,
+                            rows,
+                            label,
+                            purity,
+                        })
+                })
+            });
+            if let Some(suspicion) = suspicion {
+                suspects.push(SuspectFeature {
+                    name: name.clone(),
+                    suspicion,
+                });
+            }
+        }
+
+        Ok(LeakageReport { suspects })
+    }
+}
+
+/// The Pearson correlation of two samples, or `None` if either doesn't vary
+fn correlation(
+    xs: impl Iterator<Item = f64> + Clone,
+    ys: impl Iterator<Item = f64> + Clone,
+) -> Option<f64> {
+    let n = xs.clone().count() as f64;
+    let (x_mean, y_mean) = (xs.clone().sum::<f64>() / n, ys.clone().sum::<f64>() / n);
+    let (mut covariance, mut x_variance, mut y_variance) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.zip(ys) {
+        covariance += (x - x_mean) * (y - y_mean);
+        x_variance += (x - x_mean).powi(2);
+        y_variance += (y - y_mean).powi(2);
+    }
+
+    (x_variance > 0.0 && y_variance > 0.0).then(|| covariance / (x_variance * y_variance).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array1, Array2};
+
+    #[test]
+    fn test_features_that_give_the_label_away_are_suspect() {
+        let y: Array1<usize> = (0..100).map(|i| usize::from(i % 3 == 0)).collect();
+        let x = Array2::from_shape_fn((100, 4), |(i, j)| match j {
+            // Honest: a weak signal
+            0 => i as f64 + 20.0 * y[i] as f64,
+            // The label, rescaled
+            1 => y[i] as f64 * 10.0 - 5.0,
+            // Missing (0) for everyone who didn't survive, and a few who did
+            2 => f64::from(y[i] == 1 && i % 9 != 0),
+            // Only 3 rows have a 1, all with the label 1: too few to count
+            _ => f64::from(i < 9 && y[i] == 1),
+        });
+        let dataset = Dataset {
+            x,
+            feature_names: ["Honest", "Copy", "Boat", "Rare"]
+                .map(String::from)
+                .to_vec(),
+            y: Some(y),
+            ids: None,
+        };
+
+        let report = dataset.check_leakage(LeakageThresholds::default()).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "Copy: correlates 1.000 with the label\n\
+             Boat: 100.0% of the 22 rows where it's 1 have the label 1"
+        );
+        assert!(!report.is_clean());
+
+        let lenient = LeakageThresholds {
+            correlation: 1.1,
+            purity: 1.1,
+            ..LeakageThresholds::default()
+        };
+        assert!(dataset.check_leakage(lenient).unwrap().is_clean());
+        let unlabelled = Dataset { y: None, ..dataset };
+        assert!(matches!(
+            unlabelled.check_leakage(LeakageThresholds::default()),
+            Err(DataError::NoLabels)
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+//! The Kaggle Titanic tutorial, in Rust
+//!
+//! Polars handles the data (in place of pandas) and linfa handles the models (in place of
+//! scikit-learn). The `titanic-ml` binary is a thin driver over these modules.
+//!
+//! On `wasm32` only the prediction path (`config`, `error`, `explain`, `feature_schema`, `hooks`,
+//! `missing`, `multi_output`, `random_forest`, `saved_model`, `seed`) is built, since Polars and
+//! the HTTP server are native-only.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod blend;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checkpoint;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod class_balance;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cluster_feature;
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod conversion_report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod conversions;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod csv_batches;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod data;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dataset;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod demo;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod drift;
+pub mod error;
+pub mod explain;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fairness;
+pub mod feature_schema;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod feature_set;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gains;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hooks;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inspect;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod labels;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod leakage;
+pub mod missing;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod model_file;
+pub mod multi_output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod null_report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod outliers;
+#[cfg(feature = "python")]
+mod python;
+pub mod random_forest;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod random_forest_regressor;
+pub mod saved_model;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod score;
+pub mod seed;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serve;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod slices;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod splitter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod submission;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testing_data;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod threads;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timings;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod training_data;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
@@ -1,18 +1,516 @@
-mod conversions;
-mod data;
-pub mod random_forest;
-mod testing_data;
-mod training_data;
-
-use crate::{
-    conversions::*, data::Data, random_forest::RandomForestClassifier, testing_data::TestingData,
+use std::{
+    fs::{self, File},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use metrics::{classification::ConfusionMatrix, metric::Accuracy};
+use ndarray::{Array1, Axis, CowArray, Ix1, Ix2, concatenate};
+use polars::{
+    prelude::{DataFrame, IdxSize},
+    sql::sql_expr,
+};
+use titanic_ml::{
+    blend::{self, BlendMethod, Predictions},
+    class_balance::{ClassBalance, DEFAULT_IMBALANCE_THRESHOLD},
+    cluster_feature::{CLUSTER_COLUMN, ClusterFeature},
+    conversion_report::ConversionReport,
+    conversions::*,
+    data::{CsvScanOptions, Data},
+    dataset::Dataset,
+    demo,
+    drift::{self, DriftTest},
+    error::{DataError, FitError},
+    fairness,
+    feature_schema::FeatureSchema,
+    feature_set::{FeatureSet, Preset},
+    gains::cumulative_gains,
+    inspect,
+    leakage::LeakageThresholds,
+    missing::Missing,
+    outliers::{OutlierAction, OutlierRule},
+    random_forest::{FittedRandomForest, RandomForestClassifier},
+    saved_model::SavedModel,
+    score,
+    seed::Seed,
+    serve, slices,
+    submission::{OutputFormat, SubmissionWriter},
+    testing_data::TestingData,
+    timings::{Phase, Timings},
     training_data::TrainingData,
+    verify,
 };
-use polars::prelude::col;
 
-fn main() -> anyhow::Result<()> {
+const DEFAULT_MODEL_PATH: &str = "data/model.bin";
+
+/// The tree depths tried on the validation split
+const MAX_DEPTHS: [usize; 3] = [3, 5, 8];
+
+/// How many bins to rank the test split's passengers into for the cumulative gains: deciles
+const GAINS_BINS: usize = 10;
+
+#[derive(Parser)]
+#[command(about = "The Kaggle Titanic tutorial, in Rust")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Options for `train`, which also runs when no command is given (other commands ignore
+    /// them)
+    #[command(flatten)]
+    train: TrainArgs,
+
+    /// How many threads to fit, predict and convert data with (default: RAYON_NUM_THREADS if
+    /// set, else one per core)
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+}
+
+#[derive(Args)]
+struct TrainArgs {
+    /// Where to save the trained model and its preprocessing state
+    #[arg(long, default_value = DEFAULT_MODEL_PATH)]
+    model: PathBuf,
+
+    /// Save the model as plain JSON rather than compressed, for the wasm bindings
+    #[arg(long)]
+    json_model: bool,
+
+    /// Add the predicted survival probability next to each label in data/submission.csv
+    #[arg(long)]
+    with_probability: bool,
+
+    /// Seed for every random choice in the run (the data split and every forest)
+    #[arg(long, default_value = "1")]
+    seed: Seed,
+
+    /// Which features to train on: baseline, numeric or engineered (which adds FamilySize,
+    /// IsAlone and FarePerPerson; CSVs scored or rows served with such a model need those too)
+    #[arg(long, value_name = "PRESET", default_value = "baseline")]
+    features: Preset,
+
+    /// Screen the training data's numeric feature columns for outliers (values more than 1.5
+    /// interquartile ranges outside the quartiles) before training, and flag (only report), exclude
+    /// or winsorize them
+    #[arg(long, value_name = "ACTION")]
+    outliers: Option<OutlierAction>,
+
+    /// What the forests do with a missing Age or Fare: learn which way each split sends it, or
+    /// fill it in with 0 first (zero)
+    #[arg(long, value_name = "POLICY", default_value = "learn")]
+    missing: Missing,
+
+    /// Train even if a feature looks like it leaks the label (it correlates almost perfectly with
+    /// survival, or one of its common values nearly always survives or nearly always doesn't)
+    #[arg(long)]
+    allow_leakage: bool,
+
+    /// Weight the classes so each counts for as much in total, if one outnumbers another by more
+    /// than 1.5 times (on the Titanic, more died than survived)
+    #[arg(long)]
+    balance_classes: bool,
+
+    /// Add a Cluster feature: which of this many k-means clusters of the numeric features a
+    /// passenger is nearest (CSVs scored or rows served with such a model need a Cluster column
+    /// too)
+    #[arg(long, value_name = "N_CLUSTERS")]
+    clusters: Option<usize>,
+
+    /// Compare the test split's predictions across the groups of this column, e.g. Sex or
+    /// Pclass, for a simple bias audit (may be repeated)
+    #[arg(long = "audit", value_name = "COLUMN")]
+    audit_columns: Vec<String>,
+
+    /// Parse the CSVs in small pieces and rescan them as needed instead of keeping them in
+    /// memory; slower, but for files too big to hold
+    #[arg(long)]
+    low_memory: bool,
+
+    /// Prune the final forest to this many trees, keeping the ones that do best together on the
+    /// validation split, for a smaller and faster model
+    #[arg(long, value_name = "N_TREES")]
+    prune_to: Option<usize>,
+
+    /// Grow each forest a tree at a time, stopping once this many trees in a row haven't
+    /// improved validation accuracy, instead of always growing 100
+    #[arg(long, value_name = "N_TREES")]
+    patience: Option<usize>,
+
+    /// Save each forest's trees to this directory as it grows, and carry on from what's there:
+    /// run the same command again to resume a run that was stopped
+    #[arg(long, value_name = "DIR", conflicts_with = "patience")]
+    checkpoint_dir: Option<PathBuf>,
+
+    /// How many trees to grow between checkpoints
+    #[arg(
+        long,
+        value_name = "N_TREES",
+        default_value = "10",
+        requires = "checkpoint_dir"
+    )]
+    checkpoint_every: NonZeroUsize,
+
+    /// Also write the time spent in each phase of the run to this file, as JSON
+    #[arg(long, value_name = "PATH")]
+    timings_json: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Train, write data/submission.csv and save the model (the default)
+    Train(TrainArgs),
+    /// Run the whole pipeline on a small built-in sample, without data/ and without writing files
+    Demo {
+        /// Seed for every random choice in the run
+        #[arg(long, default_value = "1")]
+        seed: Seed,
+    },
+    /// Score any CSV that has the model's feature columns
+    Score {
+        /// The CSV to score
+        input: PathBuf,
+
+        /// Where to write the input rows plus prediction and probability columns
+        #[arg(long, short, default_value = "data/scored.csv")]
+        output: PathBuf,
+
+        /// A model saved by `train`
+        #[arg(long, default_value = DEFAULT_MODEL_PATH)]
+        model: PathBuf,
+    },
+    /// Compare a saved model's metrics on slices of a labelled CSV, to find where it does worst
+    Slices {
+        /// A CSV with a Survived column, e.g. data/train.csv
+        input: PathBuf,
+
+        /// A slice of the rows, as a SQL `WHERE`-style expression, e.g. "Age < 16"; repeat it for
+        /// each slice to compare
+        #[arg(long = "slice", required = true)]
+        slices: Vec<String>,
+
+        /// A model saved by `train`
+        #[arg(long, default_value = DEFAULT_MODEL_PATH)]
+        model: PathBuf,
+    },
+    /// Blend submission files from several runs into one, e.g. ones written with
+    /// `--with-probability` by different models or seeds
+    Blend {
+        /// The submission files to blend (CSV, Parquet or JSON); a file without a Probability
+        /// column counts each label as a probability of 0 or 1
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// A weight per file, in order, e.g. 2,1,1 (default: all equal)
+        #[arg(long, value_delimiter = ',')]
+        weights: Vec<f64>,
+
+        /// How to combine them: mean (the probabilities) or rank (each file's ranking of the
+        /// passengers)
+        #[arg(long, default_value = "mean")]
+        method: BlendMethod,
+
+        /// Where to write the blended submission
+        #[arg(long, short, default_value = "data/blended.csv")]
+        output: PathBuf,
+    },
+    /// Summarize any CSV: inferred column types, null rates and example values
+    Inspect {
+        /// The CSV to inspect
+        input: PathBuf,
+
+        /// Also print rows matching a SQL `WHERE`-style expression, e.g.
+        /// "Age > 60 AND Sex = 'male'"
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// The most matching rows to print
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+
+        /// How many distinct example values to show per column
+        #[arg(long, default_value_t = 3)]
+        examples: usize,
+    },
+    /// Serve a saved model over HTTP (`POST /predict`)
+    Serve {
+        /// A model saved by `train`
+        #[arg(long, default_value = DEFAULT_MODEL_PATH)]
+        model: PathBuf,
+
+        #[arg(long, default_value = "127.0.0.1:3000")]
+        addr: SocketAddr,
+
+        /// Also serve the gRPC `Predictor` service on this address
+        #[cfg(feature = "grpc")]
+        #[arg(long)]
+        grpc_addr: Option<SocketAddr>,
+    },
+    /// Retrain with a fixed seed and check the test-set predictions against a golden file
+    Verify {
+        /// The golden predictions
+        #[arg(long, default_value = verify::GOLDEN_PATH)]
+        golden: PathBuf,
+
+        /// Overwrite the golden file with this run's predictions instead of checking them
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            report(&error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.get())
+            .build_global()?;
+    }
+
+    match cli.command {
+        None => train(&cli.train),
+        Some(Command::Train(args)) => train(&args),
+        Some(Command::Demo { seed }) => demo(seed),
+        Some(Command::Score {
+            input,
+            output,
+            model,
+        }) => {
+            let model = SavedModel::load(model)?;
+            let scored = score::score_csv_file(&model, &input, &output)?;
+            println!("{scored}");
+            println!(
+                "\n✅ Scored {} rows to {}",
+                scored.height(),
+                output.display()
+            );
+
+            Ok(())
+        }
+        Some(Command::Slices {
+            input,
+            slices,
+            model,
+        }) => {
+            let model = SavedModel::load(model)?;
+            let data = TrainingData::from_lazy_frame(inspect::scan_csv(&input)?);
+            let slices = slices
+                .iter()
+                .map(|slice| {
+                    let expr = sql_expr(slice)
+                        .with_context(|| format!("Couldn't parse slice: {slice}"))?;
+                    Ok((slice.clone(), expr))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            println!("{}", slices::evaluate_named_slices(&model, &data, &slices)?);
+
+            Ok(())
+        }
+        Some(Command::Blend {
+            inputs,
+            weights,
+            method,
+            output,
+        }) => {
+            let runs = inputs
+                .iter()
+                .map(|path| {
+                    Predictions::read(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let weights = if weights.is_empty() {
+                vec![1.0; runs.len()]
+            } else {
+                weights
+            };
+            let blended = blend::blend(&runs, &weights, method)?;
+
+            let writer = SubmissionWriter::new()
+                .format(OutputFormat::from_path(&output).unwrap_or(OutputFormat::Csv));
+            writer.write(&mut blended.to_dataframe(&writer)?, &output)?;
+            println!(
+                "✅ Blended {} submissions by {method}: {} of {} passengers survive, in {}",
+                runs.len(),
+                blended.labels.sum(),
+                blended.ids.len(),
+                output.display()
+            );
+
+            Ok(())
+        }
+        Some(Command::Inspect {
+            input,
+            filter,
+            limit,
+            examples,
+        }) => {
+            let lazy_frame = inspect::scan_csv(&input)?;
+            println!("{}", inspect::inspect(lazy_frame.clone(), examples)?);
+
+            if let Some(filter) = filter {
+                let rows = inspect::filter_rows(lazy_frame, &filter, limit)?;
+                println!("Rows matching {filter:?}:\n{rows}");
+            }
+
+            Ok(())
+        }
+        Some(Command::Serve {
+            model,
+            addr,
+            #[cfg(feature = "grpc")]
+            grpc_addr,
+        }) => {
+            let model = Arc::new(SavedModel::load(model)?);
+            let runtime = tokio::runtime::Runtime::new()?;
+
+            #[cfg(feature = "grpc")]
+            if let Some(grpc_addr) = grpc_addr {
+                return runtime.block_on(async {
+                    tokio::try_join!(
+                        serve::run(Arc::clone(&model), addr),
+                        titanic_ml::grpc::run(model, grpc_addr),
+                    )
+                    .map(|_| ())
+                });
+            }
+
+            runtime.block_on(serve::run(model, addr))
+        }
+        Some(Command::Verify { golden, update }) => verify_golden(&golden, update),
+    }
+}
+
+/// Print `error`, saying what to fix when the input data is to blame
+///
+/// Anything else is printed as its full chain, the same as returning it from `main` would.
+fn report(error: &anyhow::Error) {
+    let data_error = error.chain().find_map(|cause| {
+        cause.downcast_ref::<DataError>().or_else(|| {
+            match (
+                cause.downcast_ref::<FitError>(),
+                cause.downcast_ref::<ConversionError>(),
+            ) {
+                (Some(FitError::Data(e)), _) | (_, Some(ConversionError::Data(e))) => Some(e),
+                _ => None,
+            }
+        })
+    });
+
+    if let Some(data_error) = data_error {
+        eprintln!("Error: the input data can't be used: {data_error}");
+        if let Some(hint) = hint(data_error) {
+            eprintln!("Hint: {hint}");
+        }
+    } else if let Some(fit_error) = error.downcast_ref::<FitError>() {
+        eprintln!("Error: the model couldn't be trained: {fit_error}");
+    } else {
+        eprintln!("Error: {error:?}");
+    }
+}
+
+/// What the user can do about a `DataError`
+fn hint(error: &DataError) -> Option<&'static str> {
+    match error {
+        DataError::MissingColumn { .. } => {
+            Some("check the CSV's header row; column names are case-sensitive")
+        }
+        DataError::NonNumericColumn { .. } => {
+            Some("encode the column as numbers first, or leave it out of the features")
+        }
+        DataError::NonFinite { .. } => Some("fill in or drop the missing values first"),
+        DataError::NegativeLabel { .. } | DataError::MissingClass { .. } => {
+            Some("number the classes 0, 1, 2, ... with no gaps")
+        }
+        DataError::TooFewClasses { .. } => {
+            Some("the training data needs examples of at least two classes")
+        }
+        _ => None,
+    }
+}
+
+fn verify_golden(golden: &Path, update: bool) -> anyhow::Result<()> {
+    println!("=== Retraining with seed {} ===", verify::GOLDEN_SEED);
+    let mut predictions = verify::golden_predictions(
+        &TrainingData::try_new()?.materialize()?,
+        &TestingData::try_new()?.materialize()?,
+    )?;
+
+    if update {
+        SubmissionWriter::new().write(&mut predictions, golden)?;
+        println!(
+            "✅ Wrote {} golden predictions to {}",
+            predictions.height(),
+            golden.display()
+        );
+        return Ok(());
+    }
+
+    if !golden.exists() {
+        anyhow::bail!(
+            "No golden predictions at {}; run `verify --update` to record them",
+            golden.display()
+        );
+    }
+    let divergences = verify::compare(&verify::read_golden(golden)?, &predictions)?;
+    if !divergences.is_empty() {
+        for divergence in &divergences {
+            eprintln!("{divergence}");
+        }
+        anyhow::bail!(
+            "{} of {} predictions diverge from {}",
+            divergences.len(),
+            predictions.height(),
+            golden.display()
+        );
+    }
+
+    println!(
+        "✅ All {} predictions match {}",
+        predictions.height(),
+        golden.display()
+    );
+
+    Ok(())
+}
+
+fn train(args: &TrainArgs) -> anyhow::Result<()> {
     println!("=== Loading Titanic Data ===");
-    let training_data = TrainingData::try_new()?;
+    let mut timings = Timings::new();
+    let (training_data, testing_data) = timings.time(Phase::Load, || {
+        let scan_options = CsvScanOptions::new().low_memory(args.low_memory);
+        let mut training_data = TrainingData::try_from_path_with("data/train.csv", &scan_options)?;
+        let mut testing_data = TestingData::try_from_path_with("data/test.csv", &scan_options)?;
+        // The pipeline reads these many times over, so parse each CSV once up front unless
+        // memory is tight, and only the columns it reads
+        if !scan_options.is_low_memory() {
+            let sources = args.features.features().source_columns();
+            let sources = sources.iter().map(String::as_str);
+            training_data =
+                training_data.materialize_columns(sources.clone().chain(["Survived"]))?;
+            testing_data = testing_data.materialize_columns(sources.chain(["PassengerId"]))?;
+        }
+
+        anyhow::Ok((training_data, testing_data))
+    })?;
+    let training_data = match args.outliers {
+        Some(action) => timings.time(Phase::Preprocess, || {
+            screen_outliers(training_data, &args.features.features(), action)
+        })?,
+        None => training_data,
+    };
 
     // println!("\n=== Initial data inspection ===");
     // let first_train = training_data.lazy_frame_cloned().first().collect()?;
@@ -20,97 +518,526 @@ fn main() -> anyhow::Result<()> {
     // training_data.percentage_of_sex_who_survived("female")?;
     // training_data.percentage_of_sex_who_survived("male")?;
 
-    let (x_train, x_validation, y_train, y_validation) =
-        prepare_for_test_train_split(&training_data)?;
+    let checkpoints = match &args.checkpoint_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            Some(Checkpoints {
+                dir: dir.clone(),
+                every: args.checkpoint_every,
+            })
+        }
+        None => None,
+    };
+    let options = PipelineOptions {
+        features: args.features,
+        missing: args.missing,
+        seed: args.seed,
+        with_probability: args.with_probability,
+        prune_to: args.prune_to,
+        patience: args.patience,
+        checkpoints,
+        allow_leakage: args.allow_leakage,
+        balance_classes: args.balance_classes,
+        clusters: args.clusters,
+        audit_columns: args.audit_columns.clone(),
+    };
+    let mut output = run_pipeline(&training_data, &testing_data, options, timings)?;
+
+    // Write to CSV
+    SubmissionWriter::new().write(&mut output.submission_df, "data/submission.csv")?;
+    println!("\n✅ Predictions saved to data/submission.csv");
+
+    if args.json_model {
+        output.model.save_json(&args.model)?;
+    } else {
+        output.model.save(&args.model)?;
+    }
+    println!("✅ Model saved to {}", args.model.display());
+
+    if let Some(path) = &args.timings_json {
+        serde_json::to_writer_pretty(File::create(path)?, &output.timings)?;
+        println!("✅ Timings saved to {}", path.display());
+    }
+
+    print_summary(&output);
+
+    Ok(())
+}
+
+/// Add a `CLUSTER_COLUMN` to both sets of data, of the clusters k-means finds in the training
+/// data's numeric source columns, printing their centers
+fn add_clusters(
+    training_data: &TrainingData,
+    testing_data: &TestingData,
+    features: &FeatureSet,
+    n_clusters: usize,
+    seed: Seed,
+) -> anyhow::Result<(TrainingData, TestingData)> {
+    println!("\n=== Clustering the Passengers ===");
+    let numeric = training_data.numeric_columns()?;
+    let columns = features
+        .source_columns()
+        .into_iter()
+        .filter(|source| numeric.contains(source))
+        .collect::<Vec<_>>();
+    let clusters = ClusterFeature::new(columns.iter().map(String::as_str), n_clusters)
+        .random_state(seed)
+        .fit(training_data)?;
+    println!("Centers ({}):", columns.join(", "));
+    for (cluster, center) in clusters.centers().iter().enumerate() {
+        println!("  {cluster}: {center:.2?}");
+    }
+
+    Ok((
+        TrainingData::from_lazy_frame(clusters.transform(training_data)?),
+        TestingData::from_lazy_frame(clusters.transform(testing_data)?),
+    ))
+}
 
-    // Train the Random Forest Classifier on training split
-    let model = {
-        println!("\n=== Training Random Forest Classifier (on training split) ===");
-        let rf = RandomForestClassifier::new()
-            .n_estimators(100)
-            .max_depth(5)
-            .random_state(1);
+/// Screen the numeric columns `features` are computed from for outliers, printing what was found
+fn screen_outliers(
+    training_data: TrainingData,
+    features: &FeatureSet,
+    action: OutlierAction,
+) -> anyhow::Result<TrainingData> {
+    println!("\n=== Screening the Training Data for Outliers ===");
+    let numeric = training_data.numeric_columns()?;
+    let columns = features
+        .source_columns()
+        .into_iter()
+        .filter(|source| numeric.contains(source))
+        .collect::<Vec<_>>();
+    let (lazy_frame, report) = training_data.screen_outliers(
+        columns.iter().map(String::as_str),
+        OutlierRule::default(),
+        action,
+    )?;
+    for line in report.to_string().lines() {
+        println!("  {line}");
+    }
+
+    Ok(TrainingData::from_lazy_frame(lazy_frame))
+}
 
-        println!("Fitting model with {} trees...", 100);
-        rf.fit(x_train.clone(), y_train.clone())?
+/// Run the whole pipeline on the built-in sample, writing nothing
+fn demo(seed: Seed) -> anyhow::Result<()> {
+    println!("=== Loading the Built-in Demo Sample ===");
+    let mut timings = Timings::new();
+    let (training_data, testing_data) = timings.time(Phase::Load, || {
+        anyhow::Ok((demo::training_data()?, demo::testing_data()?))
+    })?;
+    let options = PipelineOptions {
+        features: Preset::Baseline,
+        missing: Missing::Learn,
+        seed,
+        with_probability: true,
+        prune_to: None,
+        patience: None,
+        checkpoints: None,
+        allow_leakage: false,
+        balance_classes: false,
+        clusters: None,
+        audit_columns: vec!["Sex".to_string()],
     };
-    println!("Model trained successfully!");
+    let output = run_pipeline(&training_data, &testing_data, options, timings)?;
+    print_summary(&output);
 
-    // Evaluate on validation set
-    println!("\n=== Validation Performance ===");
-    let val_predictions = model.predict(&x_validation);
-    let val_accuracy = calculate_accuracy(&val_predictions, &y_validation);
-    println!("Validation accuracy: {:.2}%", val_accuracy * 100.0);
+    println!(
+        "\nThat was a sample of 200 training and {} test passengers. For the full dataset, put \
+         Kaggle's train.csv and test.csv in data/ and run without `demo`.",
+        output.predictions.len()
+    );
+
+    Ok(())
+}
+
+/// How `run_pipeline` trains, besides on what
+struct PipelineOptions {
+    features: Preset,
+    /// What the forests do with missing numeric features
+    missing: Missing,
+    seed: Seed,
+    /// Add a `Probability` column to the submission
+    with_probability: bool,
+    /// Prune the final forest to this many trees
+    prune_to: Option<usize>,
+    /// Stop growing the validation forests once this many trees in a row don't help
+    patience: Option<usize>,
+    checkpoints: Option<Checkpoints>,
+    /// Train on features that look like they leak the label, rather than stopping
+    allow_leakage: bool,
+    /// Weight the classes by their suggested weights, if they're imbalanced
+    balance_classes: bool,
+    /// Add a feature of which of this many clusters each passenger is in
+    clusters: Option<usize>,
+    /// Compare the test split's predictions across the groups of these columns
+    audit_columns: Vec<String>,
+}
+
+/// Where to checkpoint forests as they grow, and how often
+struct Checkpoints {
+    dir: PathBuf,
+    every: NonZeroUsize,
+}
+
+/// What `run_pipeline` produces
+struct PipelineOutput {
+    /// `PassengerId` and `Survived` (and `Probability`, if asked for) for the testing data
+    submission_df: DataFrame,
+    /// The final model, trained on all of the training data (all but the validation split if it
+    /// was pruned)
+    model: SavedModel,
+    predictions: Array1<usize>,
+    /// What converting the training data for the final model changed
+    training_report: ConversionReport,
+    /// What converting the testing data changed
+    testing_report: ConversionReport,
+    /// Time spent in each phase, including any loading done before the pipeline
+    timings: Timings,
+}
+
+/// Choose a depth on a validation split, report the held-out test accuracy, then retrain on all
+/// of `training_data` and predict `testing_data`
+fn run_pipeline(
+    training_data: &TrainingData,
+    testing_data: &TestingData,
+    options: PipelineOptions,
+    mut timings: Timings,
+) -> anyhow::Result<PipelineOutput> {
+    let PipelineOptions {
+        features,
+        missing,
+        seed,
+        with_probability,
+        prune_to,
+        patience,
+        checkpoints,
+        allow_leakage,
+        balance_classes,
+        clusters,
+        audit_columns,
+    } = options;
+    println!("Seed: {seed}");
+    let mut features = features.features();
+    let clustered;
+    let (training_data, testing_data) = match clusters {
+        Some(n_clusters) => {
+            clustered = add_clusters(
+                training_data,
+                testing_data,
+                &features,
+                n_clusters,
+                seed.derive("clusters"),
+            )?;
+            features = features.column(CLUSTER_COLUMN);
+            (&clustered.0, &clustered.1)
+        }
+        None => (training_data, testing_data),
+    };
+
+    // Before anything is filled in, so columns that are mostly missing stand out
+    for (name, report) in [
+        ("training", training_data.null_report()?),
+        ("testing", testing_data.null_report()?),
+    ] {
+        println!("\nMissing values in the {name} data:");
+        for line in report.to_string().lines() {
+            println!("  {line}");
+        }
+    }
+    // A test set on another scale, or with another mix of categories, makes predictions that the
+    // validation scores say nothing about
+    let sources = features.source_columns();
+    let drift = drift::compare(
+        training_data,
+        testing_data,
+        sources.iter().map(String::as_str),
+        DriftTest::Ks,
+    )?;
+    println!("\nDrift between the training and testing data:");
+    for line in drift.to_string().lines() {
+        println!("  {line}");
+    }
+    let split = prepare_three_way_split(
+        training_data,
+        &features,
+        missing,
+        seed.derive("split"),
+        allow_leakage,
+        &mut timings,
+    )?;
+
+    let balance = ClassBalance::new(&split.y_train);
+    println!("\nClasses in the training split:");
+    for line in balance.to_string().lines() {
+        println!("  {line}");
+    }
+    let class_weight = balance.suggested_weights(DEFAULT_IMBALANCE_THRESHOLD);
+    if let Some(weights) = &class_weight {
+        println!("  Suggested class_weight: {weights:.3?}");
+    }
+    let class_weight = class_weight.filter(|_| balance_classes);
+    if class_weight.is_some() {
+        println!("  Weighting the classes by it");
+    }
+    let weigh = |rf: RandomForestClassifier| match &class_weight {
+        Some(weights) => rf.class_weight(weights.clone()),
+        None => rf,
+    };
+
+    // Pick the tree depth on the validation set
+    println!("\n=== Choosing max_depth (on validation split) ===");
+    let mut best: Option<(usize, f64, FittedRandomForest<usize>)> = None;
+    for max_depth in MAX_DEPTHS {
+        let rf = weigh(
+            RandomForestClassifier::new()
+                .n_estimators(100)
+                .max_depth(max_depth)
+                .missing(missing)
+                .random_state(seed.derive("validation forest")),
+        );
+        let (model, stopping) = timings.time(Phase::Fit, || match patience {
+            None => {
+                let name = format!("validation-depth-{max_depth}");
+                let model = fit_forest(&rf, &split.x_train, &split.y_train, &checkpoints, &name)?;
+                anyhow::Ok((model, None))
+            }
+            Some(patience) => {
+                let (model, stopping) = rf.fit_with_early_stopping(
+                    &split.x_train,
+                    &split.y_train,
+                    &split.x_validation,
+                    &split.y_validation,
+                    patience,
+                    &Accuracy,
+                )?;
+                Ok((model, Some(stopping)))
+            }
+        })?;
+        let accuracy = timings.time(Phase::Predict, || {
+            model.score(&split.x_validation, &split.y_validation)
+        });
+        match stopping {
+            None => println!(
+                "max_depth {max_depth:>2}: validation accuracy {:.2}%",
+                accuracy * 100.0
+            ),
+            Some(stopping) => println!(
+                "max_depth {max_depth:>2}: validation accuracy {:.2}% with {} trees (stopped \
+                 after {})",
+                accuracy * 100.0,
+                stopping.n_trees,
+                stopping.n_trees_fitted
+            ),
+        }
+
+        if best
+            .as_ref()
+            .is_none_or(|&(_, best_accuracy, _)| accuracy > best_accuracy)
+        {
+            best = Some((max_depth, accuracy, model));
+        }
+    }
+    let Some((max_depth, _, model)) = best else {
+        unreachable!("MAX_DEPTHS isn't empty");
+    };
+    // Without early stopping, this is the 100 trees every forest gets
+    let n_estimators = model.n_trees();
+    println!("Using max_depth {max_depth}");
+    if patience.is_some() {
+        println!("Using {n_estimators} trees");
+    }
+
+    // The test split wasn't used to choose anything, so it's a fair estimate
+    println!("\n=== Test Performance (on held-out test split) ===");
+    let test_predictions = timings.time(Phase::Predict, || model.predict(&split.x_test));
+    let test_accuracy = calculate_accuracy(&test_predictions, &split.y_test);
+    println!("Test accuracy: {:.2}%", test_accuracy * 100.0);
+
+    let confusion_matrix = ConfusionMatrix::new(&test_predictions, &split.y_test);
+    println!("{confusion_matrix}");
+    println!("Survivor F1 score: {:.3}", confusion_matrix.f1(1));
+
+    // How many of the survivors the passengers with the highest probabilities take in
+    if let Some(survived) = model.classes().iter().position(|&class| class == 1) {
+        let scores = model
+            .predict_proba(&split.x_test)
+            .column(survived)
+            .to_owned();
+        let n_bins = GAINS_BINS.min(split.y_test.len());
+        println!("\n=== Cumulative Gains (on held-out test split) ===");
+        println!("{}", cumulative_gains(&scores, &split.y_test, 1, n_bins)?);
+    }
+
+    let test_rows: Vec<IdxSize> = split.test_rows.iter().map(|&row| row as IdxSize).collect();
+    for column in &audit_columns {
+        let groups = training_data
+            .get_col_as_series(column)?
+            .take_slice(&test_rows)?;
+        let report = fairness::audit(&groups, &test_predictions, &split.y_test, 1)?;
+        println!("\n=== Fairness across {column} (on held-out test split) ===");
+        println!("{report}");
+    }
 
     // Calculate training accuracy on the split
     println!("\n=== Training Accuracy (on training split) ===");
-    let train_predictions = model.predict(&x_train);
-    let train_accuracy = calculate_accuracy(&train_predictions, &y_train);
+    let train_accuracy = timings.time(Phase::Predict, || {
+        model.score(&split.x_train, &split.y_train)
+    });
     println!("Training accuracy: {:.2}%", train_accuracy * 100.0);
 
+    if let Some(n_trees) = prune_to {
+        // What pruning costs, on the same held-out test split
+        println!("\n=== Pruning to {n_trees} Trees (on validation split) ===");
+        let n_all_trees = model.n_trees();
+        let pruned = timings.time(Phase::Fit, || {
+            model.prune(&split.x_validation, &split.y_validation, n_trees)
+        })?;
+        let pruned_accuracy = timings.time(Phase::Predict, || {
+            pruned.score(&split.x_test, &split.y_test)
+        });
+        println!(
+            "Test accuracy with {} of {n_all_trees} trees: {:.2}% (all: {:.2}%)",
+            pruned.n_trees(),
+            pruned_accuracy * 100.0,
+            test_accuracy * 100.0
+        );
+    }
+
     // Now retrain on the FULL dataset for final predictions
     println!("\n=== Retraining on Full Dataset for Final Predictions ===");
-    let x_full_df = training_data.get_feature_matrix([
-        col("Pclass"),
-        col("Sex"),
-        col("Age"),
-        col("Fare"),
-        col("SibSp"),
-        col("Parch"),
-    ])?;
-    let x_full = dataframe_to_array2(&x_full_df)?;
-    let y_full_series = training_data.get_col_as_series("Survived")?;
-    let y_full = series_to_array1(&y_full_series)?;
+    let mut training_report = ConversionReport::new();
+    // The schema records the dummy columns made here, so the test set is encoded into the same
+    // columns and `serve` can encode raw rows the same way later
+    let (full, feature_schema) = training_dataset(
+        training_data,
+        &features,
+        missing,
+        &mut training_report,
+        &mut timings,
+    )?;
 
     let final_model = {
-        let rf = RandomForestClassifier::new()
-            .n_estimators(100)
-            .max_depth(5)
-            .random_state(1);
+        let rf = weigh(
+            RandomForestClassifier::new()
+                .n_estimators(n_estimators)
+                .max_depth(max_depth)
+                .missing(missing)
+                .random_state(seed.derive("final forest")),
+        );
 
-        println!("Training on all {} samples...", x_full.nrows());
-        rf.fit(x_full, y_full)?
-    };
-    println!("Final model trained on full dataset!");
+        match prune_to {
+            None => {
+                println!("Training on all {} samples...", full.n_samples());
+                let model = timings.time(Phase::Fit, || {
+                    fit_forest(&rf, &full.x, full.labels()?, &checkpoints, "final")
+                })?;
+                println!("Final model trained on full dataset!");
+                model
+            }
+            Some(n_trees) => {
+                // Pruning needs samples the forest hasn't seen, so the validation split sits out
+                let x = concatenate![Axis(0), split.x_train, split.x_test];
+                let y = concatenate![Axis(0), split.y_train, split.y_test];
+                println!(
+                    "Training on all but the {} validation samples ({})...",
+                    split.x_validation.nrows(),
+                    x.nrows()
+                );
+                let model = timings.time(Phase::Fit, || {
+                    fit_forest(&rf, x, y, &checkpoints, "final-without-validation")?.prune(
+                        &split.x_validation,
+                        &split.y_validation,
+                        n_trees,
+                    )
+                })?;
+                println!(
+                    "Final model trained and pruned to {} trees!",
+                    model.n_trees()
+                );
+                model
+            }
+        }
+    }
+    .with_feature_names(full.feature_names.clone())?;
+
+    println!("{final_model}");
 
-    let testing_data = TestingData::try_new()?;
     // let first_test = testing_data.lazy_frame_cloned().first().collect()?;
     // println!("First row of test data: {}", first_test);
 
     // Make predictions on the test set
-    let predictions = {
+    let mut testing_report = ConversionReport::new();
+    let (predictions, probabilities) = {
         println!("\n=== Making Predictions on Test Data ===");
-        let x_test_df = testing_data.get_feature_matrix([
-            col("Pclass"),
-            col("Sex"),
-            col("Age"),
-            col("Fare"),
-            col("SibSp"),
-            col("Parch"),
-        ])?;
-        let x_test = dataframe_to_array2(&x_test_df)?;
-        println!("Test features shape: {:?}", x_test.dim());
-
-        final_model.predict(&x_test)
+        let test_set = timings.time(Phase::Preprocess, || {
+            features.encode(testing_data, &feature_schema, &mut testing_report)
+        })?;
+        println!("Test features shape: {:?}", test_set.x.dim());
+
+        timings.time(Phase::Predict, || {
+            anyhow::Ok((
+                final_model.predict_dataset(&test_set)?,
+                final_model.predict_proba(&test_set.x),
+            ))
+        })?
     };
     println!("Generated {} predictions", predictions.len());
     // println!("First 10 predictions: {:?}", &predictions.slice(s![..10]));
 
     // Get PassengerIds from test data for the submission file
-    let mut submission_df = {
-        let passenger_ids_series = testing_data.get_col_as_series("PassengerId")?;
-        let passenger_ids = series_to_array1_i64(&passenger_ids_series)?;
+    let submission_writer = SubmissionWriter::new();
+    let submission_df = {
+        let passenger_ids = testing_data.get_col_as_series("PassengerId")?;
+
+        if with_probability {
+            let survived_idx = final_model
+                .classes()
+                .iter()
+                .position(|&class| class == 1)
+                .ok_or_else(|| anyhow::anyhow!("The model never saw a survivor"))?;
+            let survival_probability = probabilities.column(survived_idx).to_owned();
 
-        // Convert predictions to DataFrame
-        array1_to_dataframe(&predictions, &passenger_ids)?
+            submission_writer.to_dataframe_with_probability(
+                &passenger_ids,
+                &predictions,
+                &survival_probability,
+            )?
+        } else {
+            submission_writer.to_dataframe(&passenger_ids, &predictions)?
+        }
     };
     println!("\nSubmission DataFrame:");
     println!("{}", submission_df);
 
-    // Write to CSV
-    write_csv_file(&mut submission_df, "data/submission.csv")?;
-    println!("\n✅ Predictions saved to data/submission.csv");
+    Ok(PipelineOutput {
+        submission_df,
+        model: SavedModel::new(feature_schema, final_model),
+        predictions,
+        training_report,
+        testing_report,
+        timings,
+    })
+}
+
+/// Fit `rf`, checkpointing it as `name` if there's a checkpoint directory
+fn fit_forest<'a>(
+    rf: &RandomForestClassifier,
+    x: impl Into<CowArray<'a, f64, Ix2>>,
+    y: impl Into<CowArray<'a, usize, Ix1>>,
+    checkpoints: &Option<Checkpoints>,
+    name: &str,
+) -> Result<FittedRandomForest<usize>, FitError> {
+    match checkpoints {
+        Some(Checkpoints { dir, every }) => {
+            rf.fit_with_checkpoints(x, y, dir.join(format!("{name}.ckpt")), *every)
+        }
+        None => rf.fit(x, y),
+    }
+}
 
+fn print_summary(output: &PipelineOutput) {
+    let predictions = &output.predictions;
     println!("\n=== Summary ===");
     println!(
         "Survived predictions: {}",
@@ -121,41 +1048,115 @@ fn main() -> anyhow::Result<()> {
         predictions.iter().filter(|&&x| x == 0).count()
     );
 
-    Ok(())
+    for (name, report) in [
+        ("training", &output.training_report),
+        ("testing", &output.testing_report),
+    ] {
+        println!("\nValues changed while converting the {name} data:");
+        for line in report.to_string().lines() {
+            println!("  {line}");
+        }
+    }
+
+    println!("\nTime spent in each phase:");
+    for line in output.timings.to_string().lines() {
+        println!("  {line}");
+    }
+}
+
+/// The training data's `features` and labels, and the schema that encodes other data the same
+/// way
+///
+/// With `Missing::Learn`, a missing numeric value stays missing (NaN) rather than becoming 0, for
+/// the forest to learn where to send it.
+fn training_dataset(
+    training_data: &TrainingData,
+    features: &FeatureSet,
+    missing: Missing,
+    report: &mut ConversionReport,
+    timings: &mut Timings,
+) -> anyhow::Result<(Dataset, FeatureSchema)> {
+    let (x_df, schema) = timings.time(Phase::Preprocess, || match missing {
+        Missing::Learn => training_data.fit_feature_matrix_keeping_missing(features, report),
+        Missing::Reject | Missing::Zero => training_data.fit_feature_matrix(features, report),
+    })?;
+    let dataset = timings.time(Phase::Convert, || {
+        match missing {
+            Missing::Learn => Dataset::from_frame_keeping_missing(&x_df),
+            Missing::Reject | Missing::Zero => Dataset::from_frame(&x_df, report),
+        }?
+        .with_labels(training_data, "Survived", report)
+    })?;
+
+    Ok((dataset, schema))
 }
 
-fn prepare_for_test_train_split(
+fn prepare_three_way_split(
     training_data: &TrainingData,
-) -> anyhow::Result<(
-    ndarray::Array2<f64>,
-    ndarray::Array2<f64>,
-    ndarray::Array1<usize>,
-    ndarray::Array1<usize>,
-)> {
+    features: &FeatureSet,
+    missing: Missing,
+    seed: Seed,
+    allow_leakage: bool,
+    timings: &mut Timings,
+) -> anyhow::Result<ThreeWaySplit<usize>> {
     // Get features (X) and labels (y) for training
     println!("\n=== Preparing Training Data ===");
-    let x_df = training_data.get_feature_matrix([
-        col("Pclass"),
-        col("Sex"),
-        col("Age"),
-        col("Fare"),
-        col("SibSp"),
-        col("Parch"),
-    ])?;
-    let x = dataframe_to_array2(&x_df)?;
+    let (dataset, _) = training_dataset(
+        training_data,
+        features,
+        missing,
+        &mut ConversionReport::new(),
+        timings,
+    )?;
+    let leakage = dataset.check_leakage(LeakageThresholds::default())?;
+    if !leakage.is_clean() {
+        println!("\nFeatures that might leak the label:");
+        for line in leakage.to_string().lines() {
+            println!("  {line}");
+        }
+        if !allow_leakage {
+            anyhow::bail!(
+                "Stopping before training on features that look too good to be true; check how \
+                 they're made, or pass --allow-leakage to train on them anyway"
+            );
+        }
+    }
+    let (x, y) = dataset.into_labelled_arrays()?;
     println!("Full dataset features shape: {:?}", x.dim());
-
-    let y = {
-        let y_series = training_data.get_col_as_series("Survived")?;
-        series_to_array1(&y_series)?
-    };
     println!("Full dataset labels shape: {:?}", y.dim());
 
-    // Split into training and validation sets
-    println!("\n=== Splitting Data for Validation ===");
-    let (x_train, x_validation, y_train, y_validation) = train_test_split(x, y, 0.8);
-    println!("Training set: {} samples", x_train.nrows());
-    println!("Validation set: {} samples", x_validation.nrows());
+    // Split into training, validation and test sets, each with the same survival rate
+    println!("\n=== Splitting Data for Validation and Testing ===");
+    let split = timings.time(Phase::Preprocess, || {
+        ThreeWaySplitter::new(0.6, 0.2)
+            .shuffle(seed)
+            .stratify()
+            .split(x, y)
+    })?;
+    println!("Training set: {} samples", split.x_train.nrows());
+    println!("Validation set: {} samples", split.x_validation.nrows());
+    println!("Test set: {} samples", split.x_test.nrows());
+
+    Ok(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok((x_train, x_validation, y_train, y_validation))
+    #[test]
+    fn test_threads_go_before_or_after_a_command() {
+        for args in [
+            &["titanic-ml", "--threads", "1", "verify"][..],
+            &["titanic-ml", "verify", "--threads", "1"],
+            &["titanic-ml", "--threads", "2", "score", "in.csv"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert!(cli.command.is_some());
+            assert!(cli.threads.is_some());
+        }
+        let cli = Cli::try_parse_from(["titanic-ml", "--threads", "1"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.threads, NonZeroUsize::new(1));
+    }
 }
@@ -0,0 +1,157 @@
+//! What the forests do with missing (NaN) features
+//!
+//! `RandomForestRegressor` grows its own trees, so it learns a default side for missing values
+//! at each split directly. `RandomForestClassifier`'s trees are linfa's, which only compare a
+//! value with a threshold, so `MissingFeatures` gets the same effect by how it hands them the
+//! data: a feature with missing training values is given to the trees twice, once with them
+//! below every value and once with them above. A split on the first sends missing values left
+//! and one on the second sends them right, so picking the better split picks the better side.
+
+use std::{fmt, str::FromStr};
+
+use linfa::Float;
+use ndarray::{Array2, ArrayView2, Axis, CowArray, Ix2};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::error::{DataError, check_training_data, check_training_data_allowing_missing};
+
+/// What a forest does with missing (NaN) features
+///
+/// Whichever it is, a NaN in a sample to predict goes the way a missing training value would
+/// have: right of every split with `Reject`, as 0.0 with `Zero`, and down each split's learned
+/// side with `Learn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Missing {
+    /// A `DataError::NonFinite`, as for an infinite feature
+    #[default]
+    Reject,
+    /// Treated as 0.0, as `dataframe_to_array2` fills nulls
+    Zero,
+    /// Sent down whichever side of each split fits the training data better
+    Learn,
+}
+
+impl Missing {
+    pub const ALL: [Self; 3] = [Self::Reject, Self::Zero, Self::Learn];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::Zero => "zero",
+            Self::Learn => "learn",
+        }
+    }
+
+    /// Check features to fit on, or score a fit against, letting NaN through unless it's to be
+    /// rejected
+    pub(crate) fn check_training_data<F: Float>(
+        self,
+        x: &ArrayView2<F>,
+        n_targets: usize,
+    ) -> Result<(), DataError> {
+        match self {
+            Self::Reject => check_training_data(x, n_targets),
+            Self::Zero | Self::Learn => check_training_data_allowing_missing(x, n_targets),
+        }
+    }
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A policy name that isn't one of `Missing::ALL`
+#[derive(Debug, Error)]
+#[error("No missing value policy named {0:?} (try reject, zero or learn)")]
+pub struct UnknownMissing(String);
+
+impl FromStr for Missing {
+    type Err = UnknownMissing;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| UnknownMissing(s.to_string()))
+    }
+}
+
+/// How a `FittedRandomForest`'s trees see the features it's given, saved along with it
+///
+/// With `Missing::Learn`, each of the `n_features` columns has its NaNs replaced by the lowest
+/// float, and each of the `learned` columns (those with NaNs in training) is appended again with
+/// its NaNs as the highest. A feature that was never missing in training sends a missing value
+/// left. The default, for a forest saved before there was a choice, passes features through.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct MissingFeatures {
+    policy: Missing,
+    n_features: usize,
+    learned: Vec<usize>,
+}
+
+impl MissingFeatures {
+    /// How to hand `x`, the training features, to trees fitted under `policy`
+    pub(crate) fn new<F: Float>(policy: Missing, x: ArrayView2<F>) -> Self {
+        let learned = match policy {
+            Missing::Learn => x
+                .columns()
+                .into_iter()
+                .enumerate()
+                .filter(|(_, column)| column.iter().any(|value| value.is_nan()))
+                .map(|(feature, _)| feature)
+                .collect(),
+            Missing::Reject | Missing::Zero => Vec::new(),
+        };
+
+        Self {
+            policy,
+            n_features: x.ncols(),
+            learned,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> Missing {
+        self.policy
+    }
+
+    /// How many copies of features the trees see after the features themselves
+    pub(crate) fn n_copies(&self) -> usize {
+        self.learned.len()
+    }
+
+    /// `x` as the trees see it, borrowed if that's how it already is
+    pub(crate) fn encode<'a, F: Float>(&self, x: ArrayView2<'a, F>) -> CowArray<'a, F, Ix2> {
+        let has_missing = || x.iter().any(|value| value.is_nan());
+        match self.policy {
+            Missing::Zero if has_missing() => x
+                .mapv(|value| if value.is_nan() { F::zero() } else { value })
+                .into(),
+            Missing::Learn if !self.learned.is_empty() || has_missing() => {
+                let fill = |value: F, missing: F| if value.is_nan() { missing } else { value };
+                let mut encoded = Array2::zeros((x.nrows(), x.ncols() + self.learned.len()));
+                let (mut features, mut copies) = encoded.view_mut().split_at(Axis(1), x.ncols());
+                features.zip_mut_with(&x, |out, &value| *out = fill(value, F::min_value()));
+                for (mut copy, &feature) in copies.columns_mut().into_iter().zip(&self.learned) {
+                    copy.zip_mut_with(&x.column(feature), |out, &value| {
+                        *out = fill(value, F::max_value())
+                    });
+                }
+                encoded.into()
+            }
+            _ => x.into(),
+        }
+    }
+
+    /// The column of the original features that the trees' feature `feature` is
+    pub(crate) fn source(&self, feature: usize) -> usize {
+        feature
+            .checked_sub(self.n_features)
+            .and_then(|copy| self.learned.get(copy))
+            .copied()
+            .unwrap_or(feature)
+    }
+}
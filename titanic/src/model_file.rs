@@ -0,0 +1,173 @@
+//! The file format saved models are written in
+//!
+//! A model file is a short header followed by the model as zstd-compressed JSON:
+//!
+//! | Bytes  | Contents                                  |
+//! |--------|-------------------------------------------|
+//! | 0..4   | `MAGIC`                                   |
+//! | 4..6   | format version, a little-endian `u16`     |
+//! | 6..    | a zstd frame holding the model's JSON     |
+//!
+//! The version says how to read the rest, so a refactor that changes how a model serializes bumps
+//! `FORMAT_VERSION` and keeps a way to read the older versions, instead of making every model
+//! saved before it unreadable. Files saved before there was a header are bare JSON; they still
+//! load, as version 0. A file from a newer build is refused with its version and the newest this
+//! build reads, rather than misread.
+//!
+//! # Versions
+//!
+//! - 0: bare JSON, before there was a header
+//! - 1: the header and zstd-compressed JSON
+//!
+//! Adding a field that older files can do without (`#[serde(default)]`) doesn't need a new
+//! version: fitted forests gained their `metadata` and their node weights that way, and forests
+//! saved before them load without. A change that older files can't be read as, such as renaming
+//! or restructuring a field, does need one.
+
+use std::{fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+/// The first bytes of every model file
+pub const MAGIC: &[u8; 4] = b"MLFM";
+
+/// The version `to_bytes` writes, and the newest one `from_bytes` reads
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Why a model file couldn't be read or written
+#[derive(Debug, Error)]
+pub enum ModelFileError {
+    #[error(
+        "The model file has format version {found}, but this build only reads up to version \
+         {FORMAT_VERSION}; it was saved by a newer titanic-ml"
+    )]
+    UnsupportedVersion { found: u16 },
+
+    #[error("Not a model file: it has neither a model file header nor JSON")]
+    NotAModelFile,
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+type Result<T, E = ModelFileError> = std::result::Result<T, E>;
+
+/// Encode `model` as a model file, with the current `FORMAT_VERSION`
+pub fn to_bytes<T: Serialize>(model: &T) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+
+    let mut encoder = zstd::Encoder::new(bytes, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    serde_json::to_writer(&mut encoder, model)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Decode a model file of any version up to `FORMAT_VERSION`, or one that's bare JSON
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        // From before the header: version 0
+        return if bytes.trim_ascii_start().starts_with(b"{") {
+            Ok(serde_json::from_slice(bytes)?)
+        } else {
+            Err(ModelFileError::NotAModelFile)
+        };
+    };
+    let (version, payload) = rest
+        .split_first_chunk()
+        .ok_or(ModelFileError::NotAModelFile)?;
+
+    match u16::from_le_bytes(*version) {
+        1 => Ok(serde_json::from_reader(zstd::Decoder::new(payload)?)?),
+        found if found > FORMAT_VERSION => Err(ModelFileError::UnsupportedVersion { found }),
+        _ => Err(ModelFileError::NotAModelFile),
+    }
+}
+
+/// Write `model` to `path` as a model file
+pub fn write<T: Serialize>(model: &T, path: impl AsRef<Path>) -> Result<()> {
+    Ok(fs::write(path, to_bytes(model)?)?)
+}
+
+/// Read a model file (or bare JSON) from `path`
+pub fn read<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    from_bytes(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn model() -> Value {
+        json!({ "trees": vec![[1.5, 2.5, 3.5]; 50], "classes": [0, 1] })
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = to_bytes(&model()).unwrap();
+
+        assert!(bytes.starts_with(b"MLFM\x01\x00"));
+        assert!(bytes.len() < serde_json::to_vec(&model()).unwrap().len());
+        assert_eq!(from_bytes::<Value>(&bytes).unwrap(), model());
+    }
+
+    #[test]
+    fn test_bare_json_still_loads() {
+        let bytes = serde_json::to_vec_pretty(&model()).unwrap();
+
+        assert_eq!(from_bytes::<Value>(&bytes).unwrap(), model());
+    }
+
+    #[test]
+    fn test_models_saved_before_metadata_still_load() {
+        use ndarray::{Array1, Array2};
+
+        use crate::{
+            feature_schema::{EncodedColumn, FeatureSchema},
+            random_forest::RandomForestClassifier,
+            saved_model::SavedModel,
+            seed::Seed,
+        };
+
+        let x = Array2::from_shape_fn((20, 1), |(i, _)| i as f64);
+        let y = Array1::from_shape_fn(20, |i| usize::from(i >= 10));
+        let forest = RandomForestClassifier::new()
+            .n_estimators(3)
+            .random_state(Seed::new(6))
+            .fit(&x, &y)
+            .unwrap();
+        let schema = FeatureSchema::new(vec![EncodedColumn::Numeric {
+            source: "Age".to_string(),
+        }]);
+        let mut saved = serde_json::to_value(SavedModel::new(schema, forest)).unwrap();
+        saved["forest"].as_object_mut().unwrap().remove("metadata");
+
+        let loaded: SavedModel = from_bytes(&to_bytes(&saved).unwrap()).unwrap();
+        assert!(loaded.forest().metadata().is_none());
+        assert_eq!(loaded.forest().predict(&x), y);
+    }
+
+    #[test]
+    fn test_rejects_newer_and_unknown_files() {
+        let mut bytes = to_bytes(&model()).unwrap();
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            from_bytes::<Value>(&bytes),
+            Err(ModelFileError::UnsupportedVersion { found }) if found == FORMAT_VERSION + 1
+        ));
+
+        assert!(matches!(
+            from_bytes::<Value>(b"PK\x03\x04"),
+            Err(ModelFileError::NotAModelFile)
+        ));
+        assert!(matches!(
+            from_bytes::<Value>(b"MLFM"),
+            Err(ModelFileError::NotAModelFile)
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+//! Predicting several targets from the same features
+//!
+//! Some datasets have more than one outcome per row, e.g. whether a passenger survived and which
+//! lifeboat they were in. `RandomForestClassifier::fit_multi_output` takes the targets as the
+//! columns of an `Array2` and fits one forest per column, as scikit-learn's
+//! `MultiOutputClassifier` does, with the same settings (and seed) for each. The fitted
+//! `MultiOutputForest` predicts a row of labels per sample, one per target.
+
+use linfa::{Float, Label};
+use ndarray::{Array1, Array2, ArrayView2, AsArray, CowArray, Ix2, Zip};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{DataError, FitError},
+    random_forest::{FittedRandomForest, RandomForestClassifier},
+};
+
+impl RandomForestClassifier {
+    /// Fit a forest per column of `y`, each like `fit` on `x` and that column
+    ///
+    /// Each target has its own classes, of the same type. A `y` without columns is a
+    /// `DataError::NoLabels`, and one without a row per row of `x` a `DataError::LengthMismatch`.
+    pub fn fit_multi_output<'a, F: Float, L: 'static + Clone + Copy + Ord + Label + Send + Sync>(
+        &self,
+        x: impl Into<CowArray<'a, F, Ix2>>,
+        y: impl AsArray<'a, L, Ix2>,
+    ) -> Result<MultiOutputForest<L, F>, FitError> {
+        let (x, y) = (x.into(), y.into());
+        if y.ncols() == 0 {
+            return Err(DataError::NoLabels.into());
+        }
+        // Every forest trains on the same rows, so lay them out once
+        let x = x.as_standard_layout();
+        let forests = y
+            .columns()
+            .into_iter()
+            .map(|target| self.fit(x.view(), target))
+            .collect::<Result<_, _>>()?;
+
+        Ok(MultiOutputForest { forests })
+    }
+}
+
+/// A forest per target, fitted by `RandomForestClassifier::fit_multi_output`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultiOutputForest<L: Label, F: Float = f64> {
+    forests: Vec<FittedRandomForest<L, F>>,
+}
+
+impl<
+    L: Clone + Copy + Ord + std::hash::Hash + Eq + std::fmt::Debug + Default + Label + Send + Sync,
+    F: Float,
+> MultiOutputForest<L, F>
+{
+    /// Predict a label per target for samples in X
+    ///
+    /// # Returns
+    /// One row per sample and one column per target, like the `y` it was fitted on
+    ///
+    /// # Panics
+    /// If `x` has the wrong number of columns, as `FittedRandomForest::predict` does
+    pub fn predict<'a>(&self, x: impl AsArray<'a, F, Ix2>) -> Array2<L> {
+        let x: ArrayView2<F> = x.into();
+        let predictions: Vec<Array1<L>> = self
+            .forests
+            .iter()
+            .map(|forest| forest.predict(x))
+            .collect();
+
+        Array2::from_shape_fn((x.nrows(), predictions.len()), |(row, target)| {
+            predictions[target][row]
+        })
+    }
+
+    /// Each target's class probabilities for samples in X, as `FittedRandomForest::predict_proba`
+    /// gives them, in the order of the targets
+    pub fn predict_proba<'a>(&self, x: impl AsArray<'a, F, Ix2>) -> Vec<Array2<f64>> {
+        let x: ArrayView2<F> = x.into();
+        self.forests
+            .iter()
+            .map(|forest| forest.predict_proba(x))
+            .collect()
+    }
+
+    /// The fraction of rows of `x` predicted right, for each target (column) of `y`
+    ///
+    /// # Panics
+    /// If `y` doesn't have one row per row of `x` and one column per target
+    pub fn score<'a>(&self, x: impl AsArray<'a, F, Ix2>, y: &Array2<L>) -> Array1<f64> {
+        let predictions = self.predict(x);
+        assert_eq!(predictions.dim(), y.dim(), "y must have a label per target");
+
+        Zip::from(predictions.columns())
+            .and(y.columns())
+            .map_collect(|predicted, actual| {
+                let correct = Zip::from(predicted)
+                    .and(actual)
+                    .fold(0, |correct, predicted, actual| {
+                        correct + usize::from(predicted == actual)
+                    });
+                correct as f64 / actual.len() as f64
+            })
+    }
+
+    pub fn n_outputs(&self) -> usize {
+        self.forests.len()
+    }
+
+    /// The forest for each target, in order, e.g. to look at one target's classes or explain its
+    /// predictions
+    pub fn forests(&self) -> &[FittedRandomForest<L, F>] {
+        &self.forests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed::Seed;
+    use ndarray::arr2;
+
+    #[test]
+    fn test_each_target_gets_its_own_forest() {
+        let x = Array2::from_shape_fn((40, 2), |(i, j)| (i * (j + 1) % 20) as f64);
+        // Whether the first feature is at least 10, and which of 0-6, 7-13 and 14-19 it is in
+        let y = Array2::from_shape_fn((40, 2), |(i, target)| match target {
+            0 => usize::from(x[[i, 0]] >= 10.0),
+            _ => x[[i, 0]] as usize / 7,
+        });
+        let classifier = RandomForestClassifier::new()
+            .n_estimators(10)
+            .random_state(Seed::new(1));
+
+        let forest = classifier.fit_multi_output(&x, &y).unwrap();
+        assert_eq!(forest.n_outputs(), 2);
+        assert_eq!(forest.forests()[0].classes(), [0, 1]);
+        assert_eq!(forest.forests()[1].classes(), [0, 1, 2]);
+
+        let predicted = forest.predict(&arr2(&[[2.0, 4.0], [15.0, 0.0]]));
+        assert_eq!(predicted, arr2(&[[0, 0], [1, 2]]));
+        assert_eq!(forest.score(&x, &y).to_vec(), [1.0, 1.0]);
+        let probabilities = forest.predict_proba(&x);
+        assert_eq!(probabilities[0].dim(), (40, 2));
+        assert_eq!(probabilities[1].dim(), (40, 3));
+
+        // The same as fitting a forest on each column
+        assert_eq!(
+            forest.forests()[1].predict(&x),
+            classifier.fit(&x, y.column(1)).unwrap().predict(&x)
+        );
+        assert!(matches!(
+            classifier.fit_multi_output(&x, &Array2::<usize>::zeros((40, 0))),
+            Err(FitError::Data(DataError::NoLabels))
+        ));
+    }
+}
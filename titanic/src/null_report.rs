@@ -0,0 +1,102 @@
+//! How many values of each column are missing, before anything fills them in
+//!
+//! Feature matrices fill nulls with 0 (counted afterwards in a `ConversionReport`), so a column
+//! that's mostly missing, like the Titanic's `Cabin`, or a fifth missing, like its `Age`, trains
+//! without complaint. `Data::null_report` counts the nulls in the raw data up front, so a run can
+//! show them before training on what's left.
+
+use std::fmt;
+
+use polars::prelude::DataType;
+
+/// One column's missing values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnNulls {
+    pub name: String,
+    pub dtype: DataType,
+    pub nulls: usize,
+}
+
+/// Every column's missing values, in the data's column order
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullReport {
+    /// How many rows the data has
+    pub rows: usize,
+    pub columns: Vec<ColumnNulls>,
+}
+
+impl NullReport {
+    /// `column`'s nulls, if the data has it
+    pub fn column(&self, name: &str) -> Option<&ColumnNulls> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The percentage (0 to 100) of `column`'s values that are null
+    pub fn percentage(&self, column: &ColumnNulls) -> f64 {
+        if self.rows == 0 {
+            0.0
+        } else {
+            column.nulls as f64 / self.rows as f64 * 100.0
+        }
+    }
+
+    /// The columns with any nulls
+    pub fn with_nulls(&self) -> impl Iterator<Item = &ColumnNulls> {
+        self.columns.iter().filter(|column| column.nulls > 0)
+    }
+}
+
+impl fmt::Display for NullReport {
+    /// One line per column, e.g. `Age (f64): 177 of 891 null (19.87%)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{} ({}): {} of {} null ({:.2}%)",
+                column.name,
+                column.dtype,
+                column.nulls,
+                self.rows,
+                self.percentage(column)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+    use polars::prelude::df;
+
+    #[test]
+    fn test_nulls_are_counted_per_column() {
+        let passengers = df!(
+            "Age" => [Some(22.0), None, Some(26.0), None],
+            "Sex" => ["male", "female", "female", "male"],
+        )
+        .unwrap();
+
+        let report = passengers.null_report().unwrap();
+        assert_eq!(report.rows, 4);
+        let age = report.column("Age").unwrap();
+        assert_eq!((age.nulls, &age.dtype), (2, &DataType::Float64));
+        assert_eq!(report.percentage(age), 50.0);
+        assert_eq!(
+            report
+                .with_nulls()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Age"]
+        );
+        assert_eq!(
+            report.to_string(),
+            "Age (f64): 2 of 4 null (50.00%)\nSex (str): 0 of 4 null (0.00%)"
+        );
+    }
+}
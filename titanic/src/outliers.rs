@@ -0,0 +1,198 @@
+//! Finding rows with extreme values before training on them
+//!
+//! A handful of extreme values, like the Titanic's £512 fares, can pull a model towards a few
+//! passengers. `Data::screen_outliers` finds the values of numeric columns that fall outside
+//! bounds an `OutlierRule` sets from the data, then flags the rows they're in, drops those rows or
+//! clips the values to the bounds, and reports what it found in an `OutlierReport`.
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+/// The name of the column `OutlierAction::Flag` adds: true for a row with any outlier
+pub const OUTLIER_COLUMN: &str = "outlier";
+
+/// How a column's bounds are set from its values (nulls aside)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierRule {
+    /// More than this many standard deviations from the mean (3 is usual)
+    ZScore(f64),
+    /// More than this many interquartile ranges below the first quartile or above the third
+    /// (1.5 is usual)
+    ///
+    /// A column whose quartiles are equal, like `Parch`, which is mostly 0, is left alone, rather
+    /// than every value but the commonest being an outlier.
+    Iqr(f64),
+}
+
+impl Default for OutlierRule {
+    fn default() -> Self {
+        Self::Iqr(1.5)
+    }
+}
+
+/// What to do with the outliers found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutlierAction {
+    /// Keep every row as it is, adding an `OUTLIER_COLUMN`
+    #[default]
+    Flag,
+    /// Drop the rows with any outlier
+    Exclude,
+    /// Clip each outlier to the nearer bound; the clipped columns become floats
+    Winsorize,
+}
+
+impl OutlierAction {
+    pub const ALL: [Self; 3] = [Self::Flag, Self::Exclude, Self::Winsorize];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flag => "flag",
+            Self::Exclude => "exclude",
+            Self::Winsorize => "winsorize",
+        }
+    }
+}
+
+impl fmt::Display for OutlierAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An action name that isn't one of `OutlierAction::ALL`
+#[derive(Debug, Error)]
+#[error("No outlier action named {0:?} (try flag, exclude or winsorize)")]
+pub struct UnknownOutlierAction(String);
+
+impl FromStr for OutlierAction {
+    type Err = UnknownOutlierAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| UnknownOutlierAction(s.to_string()))
+    }
+}
+
+/// One column's bounds and the values outside them
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnOutliers {
+    pub name: String,
+    pub lower: f64,
+    pub upper: f64,
+    /// How many values were below `lower`
+    pub below: usize,
+    /// How many values were above `upper`
+    pub above: usize,
+}
+
+/// What `Data::screen_outliers` found, and what it did about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlierReport {
+    pub action: OutlierAction,
+    /// How many rows the data had
+    pub rows: usize,
+    /// How many of them had an outlier in any column
+    pub rows_with_outliers: usize,
+    /// The columns screened, in the order asked for; one left alone by the rule isn't here
+    pub columns: Vec<ColumnOutliers>,
+}
+
+impl OutlierReport {
+    /// `column`'s outliers, if it was screened
+    pub fn column(&self, name: &str) -> Option<&ColumnOutliers> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+impl fmt::Display for OutlierReport {
+    /// One line per column, e.g. `Fare: 116 above 65.63, 0 below -26.72`, then what was done
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for column in &self.columns {
+            writeln!(
+                f,
+                "{}: {} above {:.2}, {} below {:.2}",
+                column.name, column.above, column.upper, column.below, column.lower
+            )?;
+        }
+        let done = match self.action {
+            OutlierAction::Flag => "flagged",
+            OutlierAction::Exclude => "excluded",
+            OutlierAction::Winsorize => "winsorized",
+        };
+        write!(
+            f,
+            "{} of {} rows with outliers {done}",
+            self.rows_with_outliers, self.rows
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::Data, error::DataError};
+    use polars::prelude::df;
+
+    #[test]
+    fn test_outliers_are_flagged_excluded_or_winsorized() {
+        let passengers = df!(
+            "Fare" => [
+                Some(7.0), Some(8.0), Some(9.0), None,
+                Some(10.0), Some(11.0), Some(12.0), Some(500.0),
+            ],
+            "Parch" => [0i64, 0, 0, 0, 0, 0, 0, 2],
+            "Sex" => ["male", "female", "male", "male", "female", "male", "male", "female"],
+        )
+        .unwrap();
+        let fares = |action| {
+            let (screened, report) = passengers
+                .screen_outliers(["Fare", "Parch"], OutlierRule::Iqr(1.5), action)
+                .unwrap();
+            let screened = screened.collect().unwrap();
+            let fares = screened.column("Fare").unwrap().f64().unwrap().to_vec();
+            (screened, report, fares)
+        };
+
+        // The quartiles of the fares are 8.5 and 11.5; Parch's are both 0, so it's left alone
+        let (flagged, report, _) = fares(OutlierAction::Flag);
+        let fare = report.column("Fare").unwrap();
+        assert_eq!((fare.lower, fare.upper), (4.0, 16.0));
+        assert_eq!((fare.below, fare.above), (0, 1));
+        assert!(report.column("Parch").is_none());
+        assert_eq!((report.rows, report.rows_with_outliers), (8, 1));
+        assert_eq!(
+            flagged
+                .column(OUTLIER_COLUMN)
+                .unwrap()
+                .bool()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [false, false, false, false, false, false, false, true].map(Some)
+        );
+        assert_eq!(
+            report.to_string(),
+            "Fare: 1 above 16.00, 0 below 4.00\n1 of 8 rows with outliers flagged"
+        );
+
+        let (excluded, _, _) = fares(OutlierAction::Exclude);
+        assert_eq!(excluded.height(), 7);
+        let (_, _, winsorized) = fares(OutlierAction::Winsorize);
+        assert_eq!(winsorized[3], None);
+        assert_eq!(winsorized[7], Some(16.0));
+
+        let (_, report) = passengers
+            .screen_outliers(["Fare"], OutlierRule::ZScore(2.0), OutlierAction::Flag)
+            .unwrap();
+        assert_eq!(report.rows_with_outliers, 1);
+
+        assert!(matches!(
+            passengers.screen_outliers(["Sex"], OutlierRule::default(), OutlierAction::Flag),
+            Err(DataError::NonNumericColumn { name, .. }) if name == "Sex"
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+//! Python bindings, built with `maturin develop` (which enables the `python` feature)
+//!
+//! ```python
+//! import pandas as pd
+//! import titanic_ml
+//!
+//! train = pd.read_csv("data/train.csv")
+//! x = train[["Pclass", "Age", "Fare", "SibSp", "Parch"]].fillna(0)
+//!
+//! rf = titanic_ml.RandomForestClassifier(n_estimators=100, max_depth=5, random_state=1)
+//! model = rf.fit(x, train["Survived"])
+//! model.predict_proba(x)
+//! ```
+//!
+//! Inputs can be numpy arrays or pandas DataFrames/Series; pandas objects are converted with
+//! `.to_numpy()`.
+
+use ndarray::{Array1, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::check_class_labels,
+    model_file,
+    random_forest::{FittedRandomForest, RandomForestClassifier},
+    saved_model::SavedModel,
+};
+
+#[pyclass(name = "RandomForestClassifier")]
+struct PyRandomForestClassifier {
+    inner: RandomForestClassifier,
+}
+
+#[pymethods]
+impl PyRandomForestClassifier {
+    #[new]
+    #[pyo3(signature = (
+        n_estimators = 100,
+        max_depth = 10,
+        min_samples_split = 2,
+        bootstrap_proportion = 1.0,
+        random_state = None
+    ))]
+    fn new(
+        n_estimators: usize,
+        max_depth: usize,
+        min_samples_split: usize,
+        bootstrap_proportion: f64,
+        random_state: Option<u64>,
+    ) -> PyResult<Self> {
+        let mut inner = RandomForestClassifier::new()
+            .n_estimators(n_estimators)
+            .max_depth(max_depth)
+            .min_samples_split(min_samples_split)
+            .bootstrap_proportion(bootstrap_proportion);
+        if let Some(seed) = random_state {
+            inner = inner.random_state(seed);
+        }
+        inner
+            .validate()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(Self { inner })
+    }
+
+    /// Fit on a 2D feature array and a 1D array of non-negative integer labels
+    fn fit(&self, x: &Bound<'_, PyAny>, y: &Bound<'_, PyAny>) -> PyResult<PyFittedRandomForest> {
+        let inner = self
+            .inner
+            .fit(features_from_py(x)?, labels_from_py(y)?)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(PyFittedRandomForest { inner })
+    }
+}
+
+#[pyclass(name = "FittedRandomForest")]
+struct PyFittedRandomForest {
+    inner: FittedRandomForest<usize>,
+}
+
+#[pymethods]
+impl PyFittedRandomForest {
+    fn predict<'py>(
+        &self,
+        py: Python<'py>,
+        x: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyArray1<usize>>> {
+        Ok(self.inner.predict(&self.features(x)?).into_pyarray(py))
+    }
+
+    /// Vote proportions with shape (n_samples, n_classes), columns ordered like `classes`
+    fn predict_proba<'py>(
+        &self,
+        py: Python<'py>,
+        x: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        Ok(self
+            .inner
+            .predict_proba(&self.features(x)?)
+            .into_pyarray(py))
+    }
+
+    #[getter]
+    fn classes(&self) -> Vec<usize> {
+        self.inner.classes().to_vec()
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        model_file::write(&self.inner, path).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let inner = model_file::read(path).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(Self { inner })
+    }
+}
+
+impl PyFittedRandomForest {
+    /// `x` as features, if it has as many columns as the forest was fitted on; a `ValueError`
+    /// rather than a panic if not
+    fn features(&self, x: &Bound<'_, PyAny>) -> PyResult<Array2<f64>> {
+        let x = features_from_py(x)?;
+        self.inner
+            .check_width(x.ncols())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(x)
+    }
+}
+
+/// A model saved by the `titanic-ml` binary, which also knows how to encode raw features
+#[pyclass(name = "SavedModel")]
+struct PySavedModel {
+    inner: SavedModel,
+}
+
+#[pymethods]
+impl PySavedModel {
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: SavedModel::load(path)?,
+        })
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        Ok(self.inner.save(path)?)
+    }
+
+    /// Predict from a list of dicts of raw features, e.g. `df.to_dict("records")`
+    fn predict_records<'py>(
+        &self,
+        py: Python<'py>,
+        rows: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyArray1<usize>>> {
+        let x = self.encode_records(rows)?;
+
+        Ok(self.inner.forest().predict(&x).into_pyarray(py))
+    }
+
+    fn predict_proba_records<'py>(
+        &self,
+        py: Python<'py>,
+        rows: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let x = self.encode_records(rows)?;
+
+        Ok(self.inner.forest().predict_proba(&x).into_pyarray(py))
+    }
+}
+
+impl PySavedModel {
+    fn encode_records(&self, rows: &Bound<'_, PyAny>) -> PyResult<Array2<f64>> {
+        // Round-trip through JSON so Python values get the same treatment as in `serve`
+        let json: String = PyModule::import(rows.py(), "json")?
+            .call_method1("dumps", (rows,))?
+            .extract()?;
+        let rows: Vec<Map<String, Value>> =
+            serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(self.inner.feature_schema().encode_json_rows(&rows)?)
+    }
+}
+
+/// pandas objects are converted with `.to_numpy()`, anything else with `numpy.asarray`
+fn to_numpy<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    if obj.hasattr("to_numpy")? {
+        obj.call_method0("to_numpy")
+    } else {
+        PyModule::import(obj.py(), "numpy")?.call_method1("asarray", (obj,))
+    }
+}
+
+fn features_from_py(x: &Bound<'_, PyAny>) -> PyResult<Array2<f64>> {
+    let x = to_numpy(x)?.call_method1("astype", ("float64",))?;
+    let x: PyReadonlyArray2<'_, f64> = x.extract()?;
+
+    Ok(x.as_array().to_owned())
+}
+
+fn labels_from_py(y: &Bound<'_, PyAny>) -> PyResult<Array1<usize>> {
+    let y = to_numpy(y)?.call_method1("astype", ("int64",))?;
+    let y: PyReadonlyArray1<'_, i64> = y.extract()?;
+
+    let labels = y
+        .as_array()
+        .iter()
+        .map(|&label| {
+            usize::try_from(label).map_err(|_| {
+                PyValueError::new_err(format!("Labels must be non-negative, got {label}"))
+            })
+        })
+        .collect::<PyResult<Array1<usize>>>()?;
+    check_class_labels(&labels).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    Ok(labels)
+}
+
+#[pymodule]
+fn titanic_ml(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRandomForestClassifier>()?;
+    m.add_class::<PyFittedRandomForest>()?;
+    m.add_class::<PySavedModel>()?;
+
+    Ok(())
+}
@@ -1,20 +1,53 @@
-use anyhow::Result;
-use linfa::{Label, prelude::*};
-use linfa_ensemble::EnsembleLearnerValidParams;
-use linfa_trees::{DecisionTree, SplitQuality};
-use ndarray::{Array1, Array2};
-use rand::SeedableRng;
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use linfa::{Float, Label, prelude::*};
+use linfa_trees::{DecisionTree, DecisionTreeParams, SplitQuality};
+use metrics::metric::Metric;
+#[cfg(not(target_arch = "wasm32"))]
+use ndarray::parallel::prelude::*;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, AsArray, Axis, CowArray, Ix1, Ix2, Zip};
+use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RandomForestConfig,
+    error::{DataError, FitError},
+    explain::NodeWeights,
+    hooks::{self, Hooks},
+    missing::{Missing, MissingFeatures},
+    seed::Seed,
+};
 
 /// A Random Forest Classifier that mimics sklearn's RandomForestClassifier
 ///
-/// This uses linfa's EnsembleLearner with DecisionTree as the base estimator.
+/// Each tree is a linfa DecisionTree fitted on a bootstrap sample of the training data. Any
+/// number of classes (two or more) is supported.
+///
+/// Features can be `f64` or `f32`: the forest fits with whichever float type `x` holds, and the
+/// fitted forest predicts from that type. `f32` halves the memory the training array takes, and
+/// every tree walks it, at the cost of precision a split threshold rarely needs.
+///
+/// Missing (NaN) features are an error by default; see `missing` to have each split learn which
+/// way to send them instead.
 pub struct RandomForestClassifier {
-    n_estimators: usize,
-    max_depth: usize,
-    min_samples_split: usize,
-    bootstrap_proportion: f64,
-    random_state: Option<u64>,
+    pub(crate) n_estimators: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) min_samples_split: usize,
+    pub(crate) bootstrap_proportion: f64,
+    pub(crate) class_weight: Option<Vec<f64>>,
+    pub(crate) missing: Missing,
+    pub(crate) random_state: Option<Seed>,
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) n_threads: Option<NonZeroUsize>,
+    pub(crate) hooks: Arc<dyn Hooks>,
 }
 
 impl RandomForestClassifier {
@@ -25,7 +58,11 @@ impl RandomForestClassifier {
             max_depth: 10,
             min_samples_split: 2,
             bootstrap_proportion: 1.0,
+            class_weight: None,
+            missing: Missing::Reject,
             random_state: None,
+            n_threads: None,
+            hooks: hooks::none(),
         }
     }
 
@@ -35,7 +72,7 @@ impl RandomForestClassifier {
         self
     }
 
-    /// Set the maximum depth of each tree (default: 10)
+    /// Set the maximum depth of each tree, at least 1 (default: 10)
     pub fn max_depth(mut self, depth: usize) -> Self {
         self.max_depth = depth;
         self
@@ -47,59 +84,409 @@ impl RandomForestClassifier {
         self
     }
 
-    /// Set the proportion of samples to use for each bootstrap sample (default: 1.0)
+    /// Set the proportion of samples to use for each bootstrap sample, greater than 0 and at
+    /// most 1 (default: 1.0)
     pub fn bootstrap_proportion(mut self, proportion: f64) -> Self {
         self.bootstrap_proportion = proportion;
         self
     }
 
+    /// Weigh each sample by the weight of its class, in the order of the sorted class labels, so
+    /// a rare class can count for as much as a common one (default: every class weighs 1)
+    ///
+    /// There must be a weight, greater than 0, per class of the training data. For weights that
+    /// balance the classes, see `ClassBalance::suggested_weights`.
+    pub fn class_weight(mut self, weights: Vec<f64>) -> Self {
+        self.class_weight = Some(weights);
+        self
+    }
+
+    /// Set what to do with missing (NaN) features (default: `Missing::Reject`)
+    ///
+    /// With `Missing::Learn`, every split sends them whichever way fits the training data
+    /// better, as XGBoost does, so a missing Age can mean something of its own rather than
+    /// posing as an Age of 0. `dataframe_to_array2_with_nans` keeps nulls as NaN for this.
+    pub fn missing(mut self, policy: Missing) -> Self {
+        self.missing = policy;
+        self
+    }
+
     /// Set the random seed for reproducibility
-    pub fn random_state(mut self, seed: u64) -> Self {
-        self.random_state = Some(seed);
+    pub fn random_state(mut self, seed: impl Into<Seed>) -> Self {
+        self.random_state = Some(seed.into());
+        self
+    }
+
+    /// Fit on `n` threads rather than in the caller's rayon pool (default: the caller's pool;
+    /// see the `threads` module)
+    ///
+    /// Only the speed of fitting changes: a seeded forest is the same on any number of threads.
+    /// Ignored on `wasm32`, which fits on one.
+    pub fn n_threads(mut self, n: NonZeroUsize) -> Self {
+        self.n_threads = Some(n);
         self
     }
 
+    /// Report fitting, and the fitted forest's predictions, to `hooks` (default: nothing is
+    /// reported)
+    pub fn hooks(mut self, hooks: Arc<dyn Hooks>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Check the settings, so a classifier built with nonsensical ones is refused before any
+    /// data is looked at
+    ///
+    /// Every `fit` method checks them too, and returns the same error; this reports a bad
+    /// setting (e.g. from a config file or command line) without fitting anything.
+    pub fn validate(&self) -> Result<(), FitError> {
+        if self.n_estimators == 0 {
+            return Err(FitError::NoEstimators);
+        }
+        if self.max_depth == 0 {
+            return Err(FitError::ZeroMaxDepth);
+        }
+        if !(self.bootstrap_proportion > 0.0 && self.bootstrap_proportion <= 1.0) {
+            return Err(FitError::InvalidBootstrapProportion(
+                self.bootstrap_proportion,
+            ));
+        }
+        if let Some(&weight) = self
+            .class_weight
+            .iter()
+            .flatten()
+            .find(|weight| !(weight.is_finite() && **weight > 0.0))
+        {
+            return Err(FitError::InvalidClassWeight(weight));
+        }
+
+        Ok(())
+    }
+
     /// Fit the Random Forest on training data
     ///
+    /// The trees are fitted in parallel (except on `wasm32`). Each draws from its own stream of
+    /// randomness, picked by its index in the forest rather than by which thread gets to it
+    /// first, so with `random_state` set the forest is identical however many threads fit it.
+    ///
     /// # Arguments
-    /// * `x` - Feature matrix (rows = samples, columns = features)
-    /// * `y` - Target vector (class labels)
+    /// * `x` - Feature matrix (rows = samples, columns = features); an owned array, a view or a
+    ///   reference, so a caller that still needs its arrays can lend them instead of cloning
+    /// * `y` - Target vector (class labels), likewise
     ///
     /// # Returns
     /// A fitted Random Forest model that can be used for prediction
-    pub fn fit<L: 'static + Clone + Copy + Ord + Label>(
+    pub fn fit<'a, F: Float, L: 'static + Clone + Copy + Ord + Label + Send + Sync>(
         &self,
-        x: Array2<f64>,
-        y: Array1<L>,
-    ) -> Result<FittedRandomForest<L>> {
-        // Create the dataset
-        let dataset = Dataset::new(x, y);
-
-        // Configure the decision tree parameters
-        let tree_params = DecisionTree::params()
-            .max_depth(Some(self.max_depth))
-            .min_weight_split(self.min_samples_split as f32)
-            .split_quality(SplitQuality::Gini);
+        x: impl Into<CowArray<'a, F, Ix2>>,
+        y: impl Into<CowArray<'a, L, Ix1>>,
+    ) -> Result<FittedRandomForest<L, F>, FitError> {
+        let (x, y) = (x.into(), y.into());
+        let classes = self.check(&x.view(), &y.view())?;
 
-        // Create RNG
-        let rng = match self.random_state {
-            Some(seed) => Xoshiro256Plus::seed_from_u64(seed),
-            None => Xoshiro256Plus::from_entropy(),
+        // Every tree trains on this one array, so borrowed data is only copied if it's laid out
+        // by column or has missing values to encode
+        let x = x.as_standard_layout();
+        let n_features = x.ncols();
+        let missing = MissingFeatures::new(self.missing, x.view());
+        let x = missing.encode(x.view());
+        self.hooks
+            .on_fit_start(self.n_estimators, x.nrows(), n_features);
+        let mut rng = self.rng();
+        let tree_rngs: Vec<Xoshiro256Plus> = (0..self.n_estimators)
+            .map(|_| {
+                let tree_rng = rng.clone();
+                rng.jump();
+                tree_rng
+            })
+            .collect();
+        let n_done = AtomicUsize::new(0);
+        let fit_tree = |mut tree_rng: Xoshiro256Plus| {
+            let tree = self.fit_bootstrap_tree(x.view(), y.view(), &classes, &mut tree_rng)?;
+            self.hooks
+                .on_tree_done(n_done.fetch_add(1, Ordering::Relaxed) + 1);
+            Ok::<_, FitError>(tree)
         };
 
-        // Configure the ensemble
-        let ensemble_params = EnsembleLearnerValidParams {
-            model_params: tree_params,
-            ensemble_size: self.n_estimators,
-            bootstrap_proportion: self.bootstrap_proportion,
-            rng,
+        #[cfg(not(target_arch = "wasm32"))]
+        let fitted: Vec<_> = crate::threads::install(self.n_threads, || {
+            tree_rngs
+                .into_par_iter()
+                .map(fit_tree)
+                .collect::<Result<_, _>>()
+        })??;
+        #[cfg(target_arch = "wasm32")]
+        let fitted: Vec<_> = tree_rngs
+            .into_iter()
+            .map(fit_tree)
+            .collect::<Result<_, _>>()?;
+        let (trees, node_weights): (Vec<_>, Vec<_>) = fitted.into_iter().unzip();
+        self.hooks.on_fit_done(trees.len());
+
+        Ok(FittedRandomForest {
+            trees,
+            node_weights,
+            classes,
+            missing,
+            metadata: Some(self.metadata(n_features)),
+            hooks: self.hooks.clone(),
+        })
+    }
+
+    /// Fit like `fit`, then predict the training data with the fitted forest
+    ///
+    /// The training predictions show how closely the forest fits what it has seen; compared with
+    /// its predictions on held-out data, they show whether it's overfitting. A column-major `x`
+    /// is laid out by row once, for fitting and predicting both.
+    ///
+    /// # Returns
+    /// The fitted forest, and its predicted label for each row of `x`
+    pub fn fit_predict<
+        'a,
+        F: Float,
+        L: 'static + Copy + Ord + std::hash::Hash + std::fmt::Debug + Default + Label + Send + Sync,
+    >(
+        &self,
+        x: impl Into<CowArray<'a, F, Ix2>>,
+        y: impl Into<CowArray<'a, L, Ix1>>,
+    ) -> Result<(FittedRandomForest<L, F>, Array1<L>), FitError> {
+        let (x, y) = (x.into(), y.into());
+        let x = x.as_standard_layout();
+        let model = self.fit(x.view(), y.view())?;
+        let predictions = model.predict(&x);
+
+        Ok((model, predictions))
+    }
+
+    /// Fit like `fit`, but stop adding trees once they stop helping on a validation set
+    ///
+    /// Trees are fitted one at a time, up to `n_estimators`, and the forest so far is scored on
+    /// (`x_validation`, `y_validation`) by `metric` after each. Once `patience` trees in a row
+    /// haven't improved on the best score, fitting stops and the forest is cut back to the
+    /// smallest size that reached it. The trees are the ones `fit` would grow with the same seed,
+    /// so the result is the first trees of that forest.
+    ///
+    /// # Arguments
+    /// * `x`, `y` - Training data, as for `fit`
+    /// * `x_validation`, `y_validation` - Data to score the growing forest on, which it isn't
+    ///   trained on
+    /// * `patience` - How many trees in a row may fail to improve the validation score
+    /// * `metric` - What to score the forest by, e.g. `&Accuracy` or `&LogLoss`
+    ///
+    /// # Returns
+    /// The fitted forest, and how it was grown
+    pub fn fit_with_early_stopping<'a, 'v, F: Float, L: 'static + Clone + Copy + Ord + Label>(
+        &self,
+        x: impl Into<CowArray<'a, F, Ix2>>,
+        y: impl Into<CowArray<'a, L, Ix1>>,
+        x_validation: impl AsArray<'v, F, Ix2>,
+        y_validation: &Array1<L>,
+        patience: usize,
+        metric: &dyn Metric,
+    ) -> Result<(FittedRandomForest<L, F>, EarlyStopping), FitError> {
+        let (x, y) = (x.into(), y.into());
+        let classes = self.check(&x.view(), &y.view())?;
+        let x_validation = x_validation.into();
+        self.missing
+            .check_training_data(&x_validation, y_validation.len())?;
+        let x = x.as_standard_layout();
+        let n_features = x.ncols();
+        let missing = MissingFeatures::new(self.missing, x.view());
+        let (x, x_validation) = (missing.encode(x.view()), missing.encode(x_validation));
+        self.hooks
+            .on_fit_start(self.n_estimators, x.nrows(), n_features);
+
+        // The right answers as indices into `classes`. A validation label the training data
+        // doesn't have gets a class of its own, which no tree ever votes for
+        let class_index = |label: &L| classes.binary_search(label).ok();
+        let unseen = classes.len();
+        let targets: Array1<usize> = y_validation
+            .iter()
+            .map(|label| class_index(label).unwrap_or(unseen))
+            .collect();
+        let n_columns = if targets.iter().any(|&target| target == unseen) {
+            unseen + 1
+        } else {
+            unseen
         };
+        let mut votes = Array2::<f64>::zeros((x_validation.nrows(), n_columns));
+
+        let mut rng = self.rng();
+        let mut trees: Vec<DecisionTree<F, L>> = Vec::new();
+        let mut node_weights = Vec::new();
+        let (mut best_n_trees, mut best_score) = (0, f64::NAN);
+        loop {
+            let (tree, weights) = self.fit_tree(x.view(), y.view(), &classes, &mut rng)?;
+            for (sample, label) in tree.predict(&x_validation).iter().enumerate() {
+                if let Some(class) = class_index(label) {
+                    votes[[sample, class]] += 1.0;
+                }
+            }
+            trees.push(tree);
+            node_weights.push(weights);
+            self.hooks.on_tree_done(trees.len());
+
+            let score = metric.compute(&(&votes / trees.len() as f64), &targets);
+            if trees.len() == 1 || metric.is_better(score, best_score) {
+                (best_n_trees, best_score) = (trees.len(), score);
+            }
+            if trees.len() == self.n_estimators || trees.len() - best_n_trees >= patience {
+                break;
+            }
+        }
 
-        // Fit the ensemble
-        let model = ensemble_params.fit(&dataset)?;
+        let n_trees_fitted = trees.len();
+        trees.truncate(best_n_trees);
+        node_weights.truncate(best_n_trees);
+        self.hooks.on_fit_done(best_n_trees);
 
-        Ok(FittedRandomForest { model })
+        Ok((
+            FittedRandomForest {
+                trees,
+                node_weights,
+                classes,
+                missing,
+                metadata: Some(self.metadata(n_features)),
+                hooks: self.hooks.clone(),
+            },
+            EarlyStopping {
+                n_trees: best_n_trees,
+                n_trees_fitted,
+                validation_score: best_score,
+            },
+        ))
     }
+
+    /// Fit one tree on a bootstrap sample drawn with `rng`, then move `rng` on to a stream that
+    /// doesn't overlap the one just used
+    ///
+    /// Growing a forest a tree at a time this way draws the same trees as `fit`, however it's
+    /// split up.
+    pub(crate) fn fit_tree<F: Float, L: 'static + Clone + Copy + Ord + Label>(
+        &self,
+        x: ArrayView2<F>,
+        y: ArrayView1<L>,
+        classes: &[L],
+        rng: &mut Xoshiro256Plus,
+    ) -> Result<(DecisionTree<F, L>, NodeWeights), FitError> {
+        let tree = self.fit_bootstrap_tree(x, y, classes, &mut rng.clone())?;
+        rng.jump();
+
+        Ok(tree)
+    }
+
+    /// Fit one tree on a bootstrap sample of `x` and `y` drawn with `rng`
+    ///
+    /// Rather than copying the sampled rows, the sample is passed to linfa as sample weights:
+    /// how many times each row was drawn. A row drawn twice counts twice and a row not drawn
+    /// counts for nothing, so a forest holds no more than the one training array however many
+    /// trees it grows.
+    ///
+    /// Each row's draws are then multiplied by its class's `class_weight`, if there is one.
+    ///
+    /// The tree comes with the weight of each class of `classes` that reached each of its nodes.
+    fn fit_bootstrap_tree<F: Float, L: 'static + Clone + Copy + Ord + Label>(
+        &self,
+        x: ArrayView2<F>,
+        y: ArrayView1<L>,
+        classes: &[L],
+        rng: &mut Xoshiro256Plus,
+    ) -> Result<(DecisionTree<F, L>, NodeWeights), FitError> {
+        let n_samples = x.nrows();
+        let mut weights = Array1::<f32>::zeros(n_samples);
+        for _ in 0..bootstrap_size(n_samples, self.bootstrap_proportion) {
+            weights[rng.gen_range(0..n_samples)] += 1.0;
+        }
+        if let Some(class_weight) = &self.class_weight {
+            for (weight, label) in weights.iter_mut().zip(y) {
+                // `check` found every label among `classes`
+                let class = classes.binary_search(label).unwrap_or_default();
+                *weight *= class_weight[class] as f32;
+            }
+        }
+
+        let dataset = DatasetBase::new(x, y).with_weights(weights);
+        let tree = self.tree_params().fit(&dataset)?;
+        let node_weights = NodeWeights::new(&tree, x, y, &dataset.weights, classes);
+
+        Ok((tree, node_weights))
+    }
+
+    /// What a forest this classifier fits on `n_features` features, now, records about itself
+    pub(crate) fn metadata(&self, n_features: usize) -> ModelMetadata {
+        // `wasm32-unknown-unknown` has no clock to ask
+        #[cfg(not(target_arch = "wasm32"))]
+        let trained_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|since_epoch| since_epoch.as_secs());
+        #[cfg(target_arch = "wasm32")]
+        let trained_at = None;
+
+        ModelMetadata {
+            n_features,
+            feature_names: None,
+            trained_at,
+            config: self.config(),
+        }
+    }
+
+    /// Check the training data and settings, returning the distinct class labels in sorted order
+    pub(crate) fn check<F: Float, L: Clone + Ord>(
+        &self,
+        x: &ArrayView2<F>,
+        y: &ArrayView1<L>,
+    ) -> Result<Vec<L>, FitError> {
+        self.validate()?;
+        self.missing.check_training_data(x, y.len())?;
+
+        // Remember the distinct class labels, in sorted order
+        let mut classes = y.to_vec();
+        classes.sort();
+        classes.dedup();
+        if classes.len() < 2 {
+            return Err(DataError::TooFewClasses {
+                n_classes: classes.len(),
+            }
+            .into());
+        }
+        if let Some(class_weight) = &self.class_weight
+            && class_weight.len() != classes.len()
+        {
+            return Err(FitError::ClassWeightCount {
+                expected: classes.len(),
+                found: class_weight.len(),
+            });
+        }
+
+        Ok(classes)
+    }
+
+    /// The decision tree parameters every tree is fitted with
+    fn tree_params<F: Float, L: Label>(&self) -> DecisionTreeParams<F, L> {
+        DecisionTree::params()
+            .max_depth(Some(self.max_depth))
+            .min_weight_split(self.min_samples_split as f32)
+            .split_quality(SplitQuality::Gini)
+    }
+
+    pub(crate) fn rng(&self) -> Xoshiro256Plus {
+        match self.random_state {
+            Some(seed) => seed.rng(),
+            None => Xoshiro256Plus::from_entropy(),
+        }
+    }
+}
+
+/// How `fit_with_early_stopping` grew a forest
+#[derive(Debug, Clone, PartialEq)]
+pub struct EarlyStopping {
+    /// How many trees were kept: the fewest that reached the best validation score
+    pub n_trees: usize,
+    /// How many trees were fitted before stopping
+    pub n_trees_fitted: usize,
+    /// The kept forest's score on the validation set, by the metric it was grown with
+    pub validation_score: f64,
 }
 
 impl Default for RandomForestClassifier {
@@ -108,20 +495,861 @@ impl Default for RandomForestClassifier {
     }
 }
 
+/// How many rows a bootstrap sample of `n_samples` rows draws, rounding `proportion` of them up
+/// so it's never empty
+pub(crate) fn bootstrap_size(n_samples: usize, proportion: f64) -> usize {
+    (n_samples as f64 * proportion).ceil() as usize
+}
+
 /// A fitted Random Forest model that can make predictions
-pub struct FittedRandomForest<L: Label> {
-    model: linfa_ensemble::EnsembleLearner<DecisionTree<f64, L>>,
+///
+/// `F` is the float type it was trained on, and so the type it predicts from.
+///
+/// Predicting only reads the forest, and it's always `Send + Sync`, so threads scoring with the
+/// same model can share it in an `Arc` rather than each getting a copy. `clone` copies every tree
+/// (but shares the hooks), for when a copy is wanted, e.g. to prune one and keep the other.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FittedRandomForest<L: Label, F: Float = f64> {
+    pub(crate) trees: Vec<DecisionTree<F, L>>,
+    /// The training weight of each class at each node of each tree, for `explain_prediction`;
+    /// empty for a forest saved before they were kept
+    #[serde(default)]
+    pub(crate) node_weights: Vec<NodeWeights>,
+    pub(crate) classes: Vec<L>,
+    /// How the trees see missing features; passes them through for a forest saved before there
+    /// was a choice
+    #[serde(default)]
+    pub(crate) missing: MissingFeatures,
+    /// `None` for a forest saved before fitted forests kept their metadata
+    #[serde(default)]
+    pub(crate) metadata: Option<ModelMetadata>,
+    /// Given by the classifier that fitted it, or `with_hooks`; not saved with the model
+    #[serde(skip, default = "hooks::none")]
+    pub(crate) hooks: Arc<dyn Hooks>,
+}
+
+/// What a forest was trained on and how, saved along with it
+///
+/// The class labels are kept too, and given by `FittedRandomForest::classes`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    /// How many columns the training feature matrix had, and so every matrix to predict must have
+    pub n_features: usize,
+    /// The names of those columns, in order, if the forest was fitted on named features (a
+    /// `Dataset` or a DataFrame) or given them with `with_feature_names`
+    pub feature_names: Option<Vec<String>>,
+    /// When it was fitted, in seconds since the Unix epoch (`None` if fitted on `wasm32`)
+    pub trained_at: Option<u64>,
+    /// The settings of the classifier that fitted it
+    pub config: RandomForestConfig,
+}
+
+/// Compiles only if `T` can be shared between threads
+pub(crate) const fn assert_send_sync<T: Send + Sync>() {}
+
+// `serve`, `grpc` and parallel validation share one model across threads: a field that isn't
+// thread-safe (an `Rc`, a `RefCell`) should fail to compile here rather than over there
+const _: () = {
+    assert_send_sync::<RandomForestClassifier>();
+    assert_send_sync::<FittedRandomForest<usize, f64>>();
+    assert_send_sync::<FittedRandomForest<usize, f32>>();
+};
+
+impl<L: Label, F: Float> FittedRandomForest<L, F> {
+    /// What the forest was trained on and how, or `None` for a forest saved before that was kept
+    pub fn metadata(&self) -> Option<&ModelMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Record the names of the features the forest was fitted on, in the order of the columns of
+    /// the training matrix, so inputs with names can be checked against them
+    ///
+    /// A forest without metadata gets none from this. Giving a name per column is up to the
+    /// caller: a different number of names is a `DataError::WrongFeatureCount`.
+    pub fn with_feature_names(mut self, names: Vec<String>) -> Result<Self, DataError> {
+        if let Some(metadata) = &mut self.metadata {
+            if names.len() != metadata.n_features {
+                return Err(DataError::WrongFeatureCount {
+                    expected: metadata.n_features,
+                    found: names.len(),
+                });
+            }
+            metadata.feature_names = Some(names);
+        }
+
+        Ok(self)
+    }
+
+    /// Check that an input with `n_features` columns can be predicted
+    pub fn check_width(&self, n_features: usize) -> Result<(), DataError> {
+        match &self.metadata {
+            Some(metadata) if metadata.n_features != n_features => {
+                Err(DataError::WrongFeatureCount {
+                    expected: metadata.n_features,
+                    found: n_features,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that an input with these columns can be predicted: as many as the forest was
+    /// trained on, and if it knows their names, those names in the same order
+    pub fn check_feature_names(&self, names: &[String]) -> Result<(), DataError> {
+        self.check_width(names.len())?;
+        match self
+            .metadata
+            .as_ref()
+            .and_then(|m| m.feature_names.as_ref())
+        {
+            Some(expected) if expected != names => Err(DataError::FeatureOrder {
+                expected: expected.clone(),
+                found: names.to_vec(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// How big the forest is and what it was trained on, e.g. to print after fitting or loading
+    pub fn summary(&self) -> ForestSummary<L> {
+        let depths: Vec<usize> = self.trees.iter().map(|tree| tree.max_depth()).collect();
+        let leaves: usize = self.trees.iter().map(|tree| tree.num_leaves()).sum();
+        let mean = |total: usize| total as f64 / self.trees.len().max(1) as f64;
+
+        ForestSummary {
+            n_trees: self.trees.len(),
+            // Every tree is trained on all the columns, and counts them whether or not it splits
+            // on them, along with any copies made for missing values
+            n_features: self.trees.first().map_or(0, |tree| {
+                tree.mean_impurity_decrease().len() - self.missing.n_copies()
+            }),
+            mean_depth: mean(depths.iter().sum()),
+            max_depth: depths.into_iter().max().unwrap_or(0),
+            mean_leaves: mean(leaves),
+            classes: self.classes.clone(),
+        }
+    }
+}
+
+impl<L: Label + fmt::Display, F: Float> fmt::Display for FittedRandomForest<L, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
+/// The shape of a fitted forest, from `FittedRandomForest::summary`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForestSummary<L> {
+    pub n_trees: usize,
+    /// How many feature columns it predicts from
+    pub n_features: usize,
+    /// The trees' depths, on average and at most (a tree that's a single leaf has depth 0)
+    pub mean_depth: f64,
+    pub max_depth: usize,
+    /// The trees' leaf counts, on average
+    pub mean_leaves: f64,
+    /// The class labels it predicts, sorted
+    pub classes: Vec<L>,
+}
+
+impl<L: fmt::Display> fmt::Display for ForestSummary<L> {
+    /// e.g.
+    /// ```text
+    /// Random forest of 100 trees on 8 features
+    /// Classes: 0, 1
+    /// Tree depth: 2.9 on average, 3 at most
+    /// Leaves per tree: 7.6 on average
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Random forest of {} trees on {} features",
+            self.n_trees, self.n_features
+        )?;
+        let classes: Vec<String> = self.classes.iter().map(ToString::to_string).collect();
+        writeln!(f, "Classes: {}", classes.join(", "))?;
+        writeln!(
+            f,
+            "Tree depth: {:.1} on average, {} at most",
+            self.mean_depth, self.max_depth
+        )?;
+        write!(f, "Leaves per tree: {:.1} on average", self.mean_leaves)
+    }
 }
 
-impl<L: Clone + Copy + Ord + std::hash::Hash + Eq + std::fmt::Debug + Label> FittedRandomForest<L> {
+impl<
+    L: Clone + Copy + Ord + std::hash::Hash + Eq + std::fmt::Debug + Default + Label + Send + Sync,
+    F: Float,
+> FittedRandomForest<L, F>
+{
     /// Predict class labels for samples in X
     ///
+    /// Each tree casts one vote per sample and the class with the most votes wins (ties go to
+    /// the smallest label).
+    ///
     /// # Arguments
-    /// * `x` - Feature matrix (rows = samples, columns = features)
+    /// * `x` - Feature matrix (rows = samples, columns = features); a reference to an array, or
+    ///   a view of part of one
     ///
     /// # Returns
     /// Array of predicted class labels
-    pub fn predict(&self, x: &Array2<f64>) -> Array1<L> {
-        self.model.predict(x)
+    ///
+    /// # Panics
+    /// If `x` doesn't have as many columns as the forest was trained on; check input from
+    /// elsewhere with `check_width` (or, if it has names, `check_feature_names`) first
+    pub fn predict<'a>(&self, x: impl AsArray<'a, F, Ix2>) -> Array1<L> {
+        self.most_likely(&self.predict_proba(x))
+    }
+
+    /// The label `predict` picks for each row of `predict_proba`'s probabilities
+    pub(crate) fn most_likely(&self, probabilities: &Array2<f64>) -> Array1<L> {
+        probabilities
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let mut best = 0;
+                for (i, &p) in row.iter().enumerate() {
+                    if p > row[best] {
+                        best = i;
+                    }
+                }
+                self.classes[best]
+            })
+            .collect()
+    }
+
+    /// Predict class probabilities for samples in X
+    ///
+    /// The probability of a class is the proportion of trees that voted for it. The trees vote
+    /// in parallel (except on `wasm32`).
+    ///
+    /// # Arguments
+    /// * `x` - Feature matrix (rows = samples, columns = features), as for `predict`
+    ///
+    /// # Returns
+    /// Array with shape (n_samples, n_classes); columns are ordered like `classes()`
+    ///
+    /// # Panics
+    /// If `x` has the wrong number of columns, as for `predict`
+    pub fn predict_proba<'a>(&self, x: impl AsArray<'a, F, Ix2>) -> Array2<f64> {
+        // Every tree walks every row, so lay a column-major `x` out by row once, up front
+        let x = x.into();
+        let x = x.as_standard_layout();
+        let x = x.view();
+        if let Err(mismatch) = self.check_width(x.ncols()) {
+            panic!("{mismatch}");
+        }
+        let x = self.missing.encode(x);
+        let x = x.view();
+        self.hooks.on_predict_batch(x.nrows());
+        let no_votes = || Array2::<f64>::zeros((x.nrows(), self.classes.len()));
+        let count_votes = |mut votes: Array2<f64>, tree: &DecisionTree<F, L>| {
+            self.add_votes(tree, x, &mut votes);
+            votes
+        };
+
+        // Each thread tallies its share of the trees, then the tallies are added up
+        #[cfg(not(target_arch = "wasm32"))]
+        let votes = self
+            .trees
+            .par_iter()
+            .fold(no_votes, count_votes)
+            .reduce(no_votes, |a, b| a + b);
+        #[cfg(target_arch = "wasm32")]
+        let votes = self.trees.iter().fold(no_votes(), count_votes);
+
+        votes / self.trees.len() as f64
+    }
+
+    /// Add one vote per sample in `x`, from `tree`, to `votes`
+    fn add_votes(&self, tree: &DecisionTree<F, L>, x: ArrayView2<F>, votes: &mut Array2<f64>) {
+        let predictions: Array1<L> = tree.predict(&x);
+
+        for (i, label) in predictions.iter().enumerate() {
+            if let Ok(class_idx) = self.classes.binary_search(label) {
+                votes[[i, class_idx]] += 1.0;
+            }
+        }
+    }
+
+    /// The fraction of rows of `x` whose predicted label is the one in `y`
+    ///
+    /// # Panics
+    /// If `y` doesn't have one label per row of `x`
+    pub fn score<'a>(&self, x: impl AsArray<'a, F, Ix2>, y: &Array1<L>) -> f64 {
+        let correct = Zip::from(&self.predict(x))
+            .and(y)
+            .fold(0, |correct, predicted, actual| {
+                correct + usize::from(predicted == actual)
+            });
+
+        correct as f64 / y.len() as f64
+    }
+
+    /// Predict one label per row, lazily, for rows that aren't in a matrix, e.g. a stream of
+    /// records
+    ///
+    /// Gives the same labels as `predict` on the same rows stacked into a matrix.
+    pub fn predict_rows<'a, 'r>(
+        &'a self,
+        rows: impl IntoIterator<Item = ArrayView1<'r, F>> + 'a,
+    ) -> impl Iterator<Item = L> + 'a {
+        rows.into_iter()
+            .map(|row| self.predict(row.insert_axis(Axis(0)))[0])
+    }
+
+    /// Predict labels one batch of rows at a time, lazily, e.g. for data read in chunks
+    ///
+    /// Each batch's labels are only computed when the iterator gets to it, so a caller that writes
+    /// or scores them as they come never holds more than one batch's predictions. A batch can be
+    /// an owned array, a view or a reference; to predict a large matrix in pieces, pass
+    /// `x.axis_chunks_iter(Axis(0), batch_size)`. Gives the same labels as `predict` on all the
+    /// rows at once.
+    pub fn predict_iter<'a, 'b>(
+        &'a self,
+        batches: impl IntoIterator<Item = impl Into<CowArray<'b, F, Ix2>>> + 'a,
+    ) -> impl Iterator<Item = Array1<L>> + 'a {
+        batches.into_iter().map(|batch| self.predict(&batch.into()))
+    }
+
+    /// Report predictions to `hooks` from now on, e.g. for a forest loaded from a file
+    pub fn with_hooks(mut self, hooks: Arc<dyn Hooks>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// The class labels seen during training, sorted
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// How many trees vote
+    pub fn n_trees(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Keep only the `n_trees` trees that, voting together, predict `y` best from `x`
+    ///
+    /// Trees are picked greedily: each round adds the tree that most improves accuracy on `x`
+    /// given the votes of those already picked, so a tree that mostly repeats their votes is
+    /// dropped in favour of one that corrects them. The result predicts faster and saves smaller,
+    /// usually for little or no loss of accuracy.
+    ///
+    /// `x` should be data the forest wasn't trained on (a validation split); on its training
+    /// data every tree looks good. Asking for as many trees as the forest has, or more, keeps
+    /// them all.
+    ///
+    /// # Arguments
+    /// * `x` - Feature matrix to score the trees on, as for `predict`
+    /// * `y` - Its class labels
+    /// * `n_trees` - How many trees to keep
+    pub fn prune<'a>(
+        mut self,
+        x: impl AsArray<'a, F, Ix2>,
+        y: &Array1<L>,
+        n_trees: usize,
+    ) -> Result<Self, FitError> {
+        let x = x.into();
+        self.missing.policy().check_training_data(&x, y.len())?;
+        if n_trees == 0 {
+            return Err(FitError::NoEstimators);
+        }
+        if n_trees >= self.trees.len() {
+            return Ok(self);
+        }
+
+        // Every tree's vote for every sample, and the right answers, as indices into `classes`
+        let x = self.missing.encode(x);
+        let class_index = |label: &L| self.classes.binary_search(label).ok();
+        let tree_votes: Vec<Vec<Option<usize>>> = self
+            .trees
+            .iter()
+            .map(|tree| tree.predict(&x).iter().map(class_index).collect())
+            .collect();
+        let targets: Vec<Option<usize>> = y.iter().map(class_index).collect();
+
+        let mut votes = Array2::<usize>::zeros((x.nrows(), self.classes.len()));
+        let mut kept = vec![false; self.trees.len()];
+        for _ in 0..n_trees {
+            let mut best: Option<(usize, usize)> = None;
+            for tree in (0..self.trees.len()).filter(|&tree| !kept[tree]) {
+                let n_correct = count_correct(&votes, &tree_votes[tree], &targets);
+                if best.is_none_or(|(_, most_correct)| n_correct > most_correct) {
+                    best = Some((tree, n_correct));
+                }
+            }
+            let Some((tree, _)) = best else {
+                unreachable!("fewer trees are kept than the forest has");
+            };
+
+            kept[tree] = true;
+            for (sample, vote) in tree_votes[tree].iter().enumerate() {
+                if let Some(class) = vote {
+                    votes[[sample, *class]] += 1;
+                }
+            }
+        }
+
+        // The kept trees stay in their original order
+        if self.node_weights.len() == self.trees.len() {
+            let mut kept = kept.iter();
+            self.node_weights
+                .retain(|_| kept.next().copied().unwrap_or_default());
+        }
+        let mut kept = kept.into_iter();
+        self.trees.retain(|_| kept.next().unwrap_or_default());
+
+        Ok(self)
+    }
+}
+
+/// How many samples the tallied `votes`, plus one more vote per sample from `extra_votes`, get
+/// right
+///
+/// As in `predict`, the class with the most votes wins and ties go to the first class.
+fn count_correct(
+    votes: &Array2<usize>,
+    extra_votes: &[Option<usize>],
+    targets: &[Option<usize>],
+) -> usize {
+    votes
+        .rows()
+        .into_iter()
+        .zip(extra_votes)
+        .zip(targets)
+        .filter(|((row, extra_vote), target)| {
+            let tally = |class: usize| row[class] + usize::from(**extra_vote == Some(class));
+            let winner = (1..row.len()).fold(0, |best, class| {
+                if tally(class) > tally(best) {
+                    class
+                } else {
+                    best
+                }
+            });
+            **target == Some(winner)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::metric::{Accuracy, LogLoss};
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_fit_needs_two_classes() {
+        let x = arr2(&[[1.0], [2.0], [3.0]]);
+
+        assert!(matches!(
+            RandomForestClassifier::new().fit(x, arr1(&[1usize, 1, 1])),
+            Err(FitError::Data(DataError::TooFewClasses { n_classes: 1 }))
+        ));
+    }
+
+    #[test]
+    fn test_nonsensical_settings_are_refused() {
+        assert!(RandomForestClassifier::new().validate().is_ok());
+        assert!(matches!(
+            RandomForestClassifier::new().n_estimators(0).validate(),
+            Err(FitError::NoEstimators)
+        ));
+        assert!(matches!(
+            RandomForestClassifier::new().max_depth(0).validate(),
+            Err(FitError::ZeroMaxDepth)
+        ));
+        for proportion in [0.0, -0.5, 1.5, f64::NAN] {
+            assert!(matches!(
+                RandomForestClassifier::new()
+                    .bootstrap_proportion(proportion)
+                    .validate(),
+                Err(FitError::InvalidBootstrapProportion(_))
+            ));
+        }
+
+        // Fitting refuses them before looking at the data
+        assert!(matches!(
+            RandomForestClassifier::new()
+                .max_depth(0)
+                .fit(arr2(&[[1.0]]), arr1(&[1usize])),
+            Err(FitError::ZeroMaxDepth)
+        ));
+    }
+
+    #[test]
+    fn test_multiclass() {
+        // Three well-separated classes along one feature
+        let x = Array2::from_shape_fn((30, 1), |(i, _)| (i / 10) as f64 * 10.0 + (i % 10) as f64);
+        let y = Array1::from_shape_fn(30, |i| i / 10);
+
+        let model = RandomForestClassifier::new()
+            .n_estimators(20)
+            .random_state(Seed::new(4))
+            .fit(x, y)
+            .unwrap();
+        assert_eq!(model.classes(), [0, 1, 2]);
+
+        let x_new = arr2(&[[4.0], [14.0], [24.0]]);
+        let probabilities = model.predict_proba(&x_new);
+        assert_eq!(probabilities.dim(), (3, 3));
+        for row in probabilities.rows() {
+            assert!((row.sum() - 1.0).abs() < 1e-12);
+        }
+        assert_eq!(model.predict(&x_new), arr1(&[0, 1, 2]));
+
+        // A view of some of the rows, and the rows one at a time
+        assert_eq!(
+            model.predict(x_new.slice(ndarray::s![1.., ..])),
+            arr1(&[1, 2])
+        );
+        let labels: Vec<_> = model.predict_rows(x_new.rows()).collect();
+        assert_eq!(labels, [0, 1, 2]);
+
+        // Or a batch at a time, from views or from arrays made as they're needed
+        let batches: Vec<_> = model
+            .predict_iter(x_new.axis_chunks_iter(Axis(0), 2))
+            .collect();
+        assert_eq!(batches, [arr1(&[0, 1]), arr1(&[2])]);
+        let labels: Vec<_> = model
+            .predict_iter((0..3).map(|i| arr2(&[[i as f64 * 10.0 + 4.0]])))
+            .flat_map(|batch| batch.to_vec())
+            .collect();
+        assert_eq!(labels, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let x = Array2::from_shape_fn((30, 3), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(30, |i| i / 10);
+        let model = RandomForestClassifier::new()
+            .n_estimators(5)
+            .max_depth(2)
+            .random_state(Seed::new(1))
+            .fit(&x, &y)
+            .unwrap();
+
+        let summary = model.summary();
+        assert_eq!(summary.n_trees, 5);
+        assert_eq!(summary.n_features, 3);
+        assert_eq!(summary.classes, [0, 1, 2]);
+        assert!(summary.max_depth <= 2);
+        assert!(summary.mean_depth <= summary.max_depth as f64);
+        assert!(summary.mean_leaves >= 1.0);
+
+        let text = model.to_string();
+        assert!(text.starts_with("Random forest of 5 trees on 3 features\nClasses: 0, 1, 2\n"));
+        assert_eq!(text.lines().count(), 4);
+    }
+
+    #[test]
+    fn test_f32_features() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| (i * (j + 1)) as f64 / 4.0);
+        let y = Array1::from_shape_fn(30, |i| usize::from(i >= 15));
+        let forest = RandomForestClassifier::new()
+            .n_estimators(10)
+            .max_depth(3)
+            .random_state(Seed::new(6));
+
+        // These values are exact in f32 too, so both forests grow the same trees
+        let x_f32 = x.mapv(|v| v as f32);
+        let model = forest.fit(&x_f32, &y).unwrap();
+        assert_eq!(
+            model.predict_proba(&x_f32),
+            forest.fit(&x, &y).unwrap().predict_proba(&x)
+        );
+        assert_eq!(model.predict(&x_f32), y);
+
+        let mut x_bad = x_f32;
+        x_bad[[3, 1]] = f32::NAN;
+        assert!(matches!(
+            forest.fit(&x_bad, &y),
+            Err(FitError::Data(DataError::NonFinite { row: 3, column: 1, value })) if value.is_nan()
+        ));
+    }
+
+    #[test]
+    fn test_thread_count_doesnt_change_the_forest() {
+        let x = Array2::from_shape_fn((40, 3), |(i, j)| ((i * 7 + j * 3) % 11) as f64);
+        // The classes overlap, so leaves are impure and some of them tie
+        let y = Array1::from_shape_fn(40, |i| (i / 3) % 2);
+        let forest = || {
+            RandomForestClassifier::new()
+                .n_estimators(12)
+                .max_depth(4)
+                .random_state(Seed::new(4))
+        };
+        let proba = |n_threads| {
+            forest()
+                .n_threads(NonZeroUsize::new(n_threads).unwrap())
+                .fit(&x, &y)
+                .unwrap()
+                .predict_proba(&x)
+        };
+
+        let on_one_thread = proba(1);
+        assert_eq!(proba(4), on_one_thread);
+        assert_eq!(
+            forest().fit(&x, &y).unwrap().predict_proba(&x),
+            on_one_thread
+        );
+    }
+
+    #[test]
+    fn test_same_seed_grows_the_same_forest() {
+        // Each value of the feature has as many samples of one class as the other, so many
+        // leaves tie and only the tie-break decides what they predict
+        let x = Array2::from_shape_fn((48, 1), |(i, _)| (i % 6) as f64);
+        let y = Array1::from_shape_fn(48, |i| (i / 6) % 2);
+        let forest = RandomForestClassifier::new()
+            .n_estimators(30)
+            .max_depth(2)
+            .random_state(Seed::new(1));
+
+        let first = forest.fit(&x, &y).unwrap().predict_proba(&x);
+        for _ in 0..5 {
+            assert_eq!(forest.fit(&x, &y).unwrap().predict_proba(&x), first);
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        let x = Array2::from_shape_fn((20, 3), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(20, |i| usize::from(i >= 10));
+        let forest = RandomForestClassifier::new()
+            .n_estimators(4)
+            .max_depth(3)
+            .random_state(Seed::new(2));
+        let model = forest.fit(&x, &y).unwrap();
+
+        let metadata = model.metadata().unwrap();
+        assert_eq!(metadata.n_features, 3);
+        assert_eq!(metadata.feature_names, None);
+        assert!(metadata.trained_at.is_some());
+        assert_eq!(metadata.config, forest.config());
+
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        assert!(matches!(
+            model.clone().with_feature_names(names(&["a", "b"])),
+            Err(DataError::WrongFeatureCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        let model = model.with_feature_names(names(&["a", "b", "c"])).unwrap();
+        assert!(model.check_feature_names(&names(&["a", "b", "c"])).is_ok());
+        assert!(matches!(
+            model.check_feature_names(&names(&["c", "b", "a"])),
+            Err(DataError::FeatureOrder { .. })
+        ));
+        assert!(matches!(
+            model.check_width(2),
+            Err(DataError::WrongFeatureCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        let too_narrow = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            model.predict(x.slice(ndarray::s![.., ..2]))
+        }));
+        assert!(too_narrow.is_err());
+
+        // The metadata is saved with the model, and a model saved without any still loads
+        let json = serde_json::to_value(&model).unwrap();
+        let reloaded: FittedRandomForest<usize> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(reloaded.metadata(), model.metadata());
+        let mut old = json;
+        old.as_object_mut().unwrap().remove("metadata");
+        let old: FittedRandomForest<usize> = serde_json::from_value(old).unwrap();
+        assert_eq!(old.metadata(), None);
+        assert_eq!(old.predict(&x), model.predict(&x));
+    }
+
+    #[test]
+    fn test_shared_and_cloned_models_predict_alike() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(30, |i| usize::from(i >= 15));
+        let model = RandomForestClassifier::new()
+            .n_estimators(8)
+            .random_state(Seed::new(5))
+            .fit(&x, &y)
+            .unwrap();
+        let expected = model.predict_proba(&x);
+
+        let copy = model.clone();
+        let shared = Arc::new(model);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..3)
+                .map(|_| {
+                    let (shared, x) = (Arc::clone(&shared), &x);
+                    scope.spawn(move || shared.predict_proba(x))
+                })
+                .collect();
+            for worker in workers {
+                assert_eq!(worker.join().unwrap(), expected);
+            }
+        });
+        assert_eq!(copy.predict_proba(&x), expected);
+    }
+
+    #[test]
+    fn test_fit_predict() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(30, |i| usize::from(i >= 15));
+        let forest = RandomForestClassifier::new()
+            .n_estimators(8)
+            .random_state(Seed::new(12));
+
+        let (model, predictions) = forest.fit_predict(&x, &y).unwrap();
+        assert_eq!(predictions, model.predict(&x));
+        assert_eq!(
+            model.score(&x, &y),
+            metrics::classification::accuracy(&predictions, &y)
+        );
+        assert_eq!(
+            model.score(&x, &y.mapv(|label| 1 - label)),
+            1.0 - model.score(&x, &y)
+        );
+        assert_eq!(
+            model.predict_proba(&x),
+            forest.fit(&x, &y).unwrap().predict_proba(&x)
+        );
+    }
+
+    #[test]
+    fn test_column_major_input() {
+        let x = Array2::from_shape_fn((20, 2), |(i, j)| (i * (j + 1)) as f64);
+        let y = Array1::from_shape_fn(20, |i| usize::from(i >= 10));
+        let x_by_column = x.t().as_standard_layout().into_owned().reversed_axes();
+        assert!(!x_by_column.is_standard_layout());
+        assert_eq!(x_by_column, x);
+
+        let forest = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(Seed::new(2));
+        let model = forest.fit(&x, &y).unwrap();
+        let by_column_model = forest.fit(&x_by_column, &y).unwrap();
+
+        assert_eq!(model.predict(&x), by_column_model.predict(&x));
+        assert_eq!(model.predict_proba(&x), model.predict_proba(&x_by_column));
+    }
+
+    #[test]
+    fn test_fit_with_early_stopping() {
+        let x = Array2::from_shape_fn((40, 1), |(i, _)| i as f64);
+        let y = Array1::from_shape_fn(40, |i| usize::from(i >= 20));
+        let x_validation = arr2(&[[3.0], [12.5], [18.5], [21.5], [27.5], [36.0]]);
+        let y_validation = arr1(&[0, 0, 0, 1, 1, 1]);
+
+        let (model, stopping) = RandomForestClassifier::new()
+            .n_estimators(50)
+            .random_state(Seed::new(5))
+            .fit_with_early_stopping(&x, &y, &x_validation, &y_validation, 3, &Accuracy)
+            .unwrap();
+
+        // The classes are easy to separate, so a few trees do as well as any number
+        assert_eq!(stopping.validation_score, 1.0);
+        assert_eq!(stopping.n_trees_fitted, stopping.n_trees + 3);
+        assert_eq!(model.n_trees(), stopping.n_trees);
+        assert_eq!(model.predict(&x_validation), y_validation);
+
+        // Any metric can decide when to stop; the score is the kept forest's
+        let (by_log_loss, stopping_by_log_loss) = RandomForestClassifier::new()
+            .n_estimators(50)
+            .random_state(Seed::new(5))
+            .fit_with_early_stopping(&x, &y, &x_validation, &y_validation, 3, &LogLoss)
+            .unwrap();
+        assert_eq!(
+            stopping_by_log_loss.validation_score,
+            LogLoss.compute(&by_log_loss.predict_proba(&x_validation), &y_validation)
+        );
+
+        // The trees kept are the first ones `fit` grows with the same seed, in parallel or not
+        let fitted = RandomForestClassifier::new()
+            .n_estimators(stopping.n_trees)
+            .random_state(Seed::new(5))
+            .fit(&x, &y)
+            .unwrap();
+        assert_eq!(fitted.predict_proba(&x), model.predict_proba(&x));
+    }
+
+    #[test]
+    fn test_prune() {
+        // Two classes that overlap a little along one feature
+        let x = Array2::from_shape_fn((40, 1), |(i, _)| i as f64);
+        let y = Array1::from_shape_fn(40, |i| usize::from(i >= 18 && i != 25));
+        let model = RandomForestClassifier::new()
+            .n_estimators(15)
+            .max_depth(3)
+            .random_state(Seed::new(9))
+            .fit(&x, &y)
+            .unwrap();
+        assert_eq!(model.n_trees(), 15);
+
+        let x_validation = arr2(&[[2.0], [10.0], [17.0], [19.0], [30.0], [38.0]]);
+        let y_validation = arr1(&[0, 0, 0, 1, 1, 1]);
+        assert!(matches!(
+            RandomForestClassifier::new()
+                .n_estimators(3)
+                .fit(&x, &y)
+                .unwrap()
+                .prune(&x_validation, &y_validation, 0),
+            Err(FitError::NoEstimators)
+        ));
+
+        let pruned = model.prune(&x_validation, &y_validation, 4).unwrap();
+        assert_eq!(pruned.n_trees(), 4);
+        assert_eq!(pruned.classes(), [0, 1]);
+        assert_eq!(pruned.predict(&x_validation), y_validation);
+
+        // Keeping more trees than there are keeps them all
+        assert_eq!(
+            pruned
+                .prune(&x_validation, &y_validation, 10)
+                .unwrap()
+                .n_trees(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_missing_values_learn_their_own_branch() {
+        // The label follows x, except that the rows missing it all survived
+        let x = Array2::from_shape_fn((35, 1), |(i, _)| match i {
+            0..15 => 0.0,
+            15..25 => i as f64,
+            _ => f64::NAN,
+        });
+        let y = Array1::from_shape_fn(35, |i| usize::from(i >= 15));
+        let forest = || {
+            RandomForestClassifier::new()
+                .n_estimators(10)
+                .random_state(Seed::new(2))
+        };
+        let samples = arr2(&[[f64::NAN], [0.0], [20.0]]);
+
+        assert!(matches!(
+            forest().fit(&x, &y),
+            Err(FitError::Data(DataError::NonFinite { row: 25, .. }))
+        ));
+
+        let learned = forest().missing(Missing::Learn).fit(&x, &y).unwrap();
+        assert_eq!(learned.predict(&samples), arr1(&[1, 0, 1]));
+        assert_eq!(learned.summary().n_features, 1);
+        // The trees split on a copy of x, but the explanations are about x
+        let explanation = learned.explain_prediction(samples.row(0)).unwrap();
+        assert!(explanation.decisions.iter().all(|decision| {
+            decision.feature == 0 && decision.direction == crate::explain::Direction::Missing
+        }));
+        let shap = learned.shap_values(&samples, 1).unwrap();
+        assert!(
+            (shap.expected_value + shap.values.row(0).sum()
+                - learned.predict_proba(&samples)[[0, 1]])
+            .abs()
+                < 1e-9
+        );
+
+        // Filled in with 0, they look like the rows with an x of 0, which didn't survive
+        let zeroed = forest().missing(Missing::Zero).fit(&x, &y).unwrap();
+        assert_eq!(zeroed.predict(&samples), arr1(&[0, 0, 1]));
     }
 }
@@ -0,0 +1,803 @@
+//! A Random Forest for continuous targets
+//!
+//! linfa's decision trees only classify, so the regression trees here are grown directly: each
+//! split is the one that most reduces the squared error (the target's variance) within the two
+//! halves, and each leaf predicts the mean target of its samples. The forest averages its trees.
+//!
+//! Each leaf also keeps the targets of its samples, so the forest can predict quantiles of the
+//! target as well as its mean, as a quantile regression forest (Meinshausen, 2006) does: a
+//! sample's 10th and 90th percentiles bound where 80% of targets like it fell.
+//!
+//! A feature can also be constrained to only ever raise, or only ever lower, the prediction as it
+//! grows (see `Monotonic`), as regulated uses often require: e.g. a fare that never lowers
+//! predicted survival. The constraint is enforced while growing each tree, the way LightGBM's
+//! basic method does. A split on a constrained feature is only made if its halves' means are in
+//! the right order, and it bounds everything below it: every prediction on the low side stays
+//! below every prediction on the high side. A forest of such trees is monotonic too. The leaves'
+//! targets aren't bounded, though, so `predict_quantiles` isn't constrained.
+//!
+//! Missing features (NaN, as `dataframe_to_array2_with_nans` gives for nulls) are an error by
+//! default. With `Missing::Learn`, each split instead learns a default side for them, as XGBoost
+//! does: the side that reduces the squared error more, given the missing rows reaching that node.
+//! A missing Age can then mean something of its own, rather than posing as an Age of 0.
+
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, CowArray, Ix1, Ix2};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{DataError, FitError},
+    missing::Missing,
+    random_forest::{assert_send_sync, bootstrap_size},
+    seed::Seed,
+};
+
+/// Which way a feature may move a `RandomForestRegressor`'s predictions
+///
+/// `RandomForestClassifier` doesn't take constraints, since linfa picks its trees' splits. For a
+/// probability of a 0/1 label that's monotonic in a feature, fit the regressor on the labels:
+/// its predictions are then the share of 1s in the leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Monotonic {
+    /// Either way
+    #[default]
+    None,
+    /// A larger value never lowers the prediction
+    Increasing,
+    /// A larger value never raises the prediction
+    Decreasing,
+}
+
+/// A Random Forest Regressor, with the same builder as `RandomForestClassifier`
+pub struct RandomForestRegressor {
+    pub(crate) n_estimators: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) min_samples_split: usize,
+    pub(crate) bootstrap_proportion: f64,
+    pub(crate) monotonic_constraints: Option<Vec<Monotonic>>,
+    pub(crate) missing: Missing,
+    pub(crate) random_state: Option<Seed>,
+}
+
+impl RandomForestRegressor {
+    /// Create a new Random Forest Regressor with default parameters
+    pub fn new() -> Self {
+        Self {
+            n_estimators: 100,
+            max_depth: 10,
+            min_samples_split: 2,
+            bootstrap_proportion: 1.0,
+            monotonic_constraints: None,
+            missing: Missing::Reject,
+            random_state: None,
+        }
+    }
+
+    /// Set the number of trees in the forest (default: 100)
+    pub fn n_estimators(mut self, n: usize) -> Self {
+        self.n_estimators = n;
+        self
+    }
+
+    /// Set the maximum depth of each tree (default: 10)
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Set the minimum number of samples required to split a node (default: 2)
+    pub fn min_samples_split(mut self, n: usize) -> Self {
+        self.min_samples_split = n;
+        self
+    }
+
+    /// Set the proportion of samples to use for each bootstrap sample, greater than 0 and at
+    /// most 1 (default: 1.0)
+    pub fn bootstrap_proportion(mut self, proportion: f64) -> Self {
+        self.bootstrap_proportion = proportion;
+        self
+    }
+
+    /// Constrain how each feature may move the predictions, one `Monotonic` per feature in the
+    /// order of the columns (default: no constraints)
+    pub fn monotonic_constraints(mut self, constraints: Vec<Monotonic>) -> Self {
+        self.monotonic_constraints = Some(constraints);
+        self
+    }
+
+    /// Set what to do with missing (NaN) features (default: `Missing::Reject`)
+    pub fn missing(mut self, policy: Missing) -> Self {
+        self.missing = policy;
+        self
+    }
+
+    /// Set the random seed for reproducibility
+    pub fn random_state(mut self, seed: impl Into<Seed>) -> Self {
+        self.random_state = Some(seed.into());
+        self
+    }
+
+    /// Check the settings, so a regressor built with nonsensical ones is refused before any
+    /// data is looked at
+    ///
+    /// `fit` checks them too, and returns the same error. The monotonic constraints can only be
+    /// checked against the data, so `fit` checks those.
+    pub fn validate(&self) -> Result<(), FitError> {
+        if self.n_estimators == 0 {
+            return Err(FitError::NoEstimators);
+        }
+        if self.max_depth == 0 {
+            return Err(FitError::ZeroMaxDepth);
+        }
+        if !(self.bootstrap_proportion > 0.0 && self.bootstrap_proportion <= 1.0) {
+            return Err(FitError::InvalidBootstrapProportion(
+                self.bootstrap_proportion,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fit the Random Forest on training data
+    ///
+    /// # Arguments
+    /// * `x` - Feature matrix (rows = samples, columns = features); an owned array, a view or a
+    ///   reference, so a caller that still needs its arrays can lend them instead of cloning
+    /// * `y` - Target vector (continuous values), likewise
+    ///
+    /// # Returns
+    /// A fitted Random Forest model that can be used for prediction
+    pub fn fit<'a>(
+        &self,
+        x: impl Into<CowArray<'a, f64, Ix2>>,
+        y: impl Into<CowArray<'a, f64, Ix1>>,
+    ) -> Result<FittedRandomForestRegressor, FitError> {
+        let (x, y) = (x.into(), y.into());
+        let (x, y) = (x.view(), y.view());
+        self.validate()?;
+        self.missing.check_training_data(&x, y.len())?;
+        let filled;
+        let x = if self.missing == Missing::Zero {
+            filled = x.mapv(|v| if v.is_nan() { 0.0 } else { v });
+            filled.view()
+        } else {
+            x
+        };
+        let constraints = self.monotonic_constraints.as_deref().unwrap_or_default();
+        if !constraints.is_empty() && constraints.len() != x.ncols() {
+            return Err(FitError::MonotonicConstraintCount {
+                expected: x.ncols(),
+                found: constraints.len(),
+            });
+        }
+        let settings = TreeSettings {
+            max_depth: self.max_depth,
+            min_samples_split: self.min_samples_split,
+            constraints,
+            missing: self.missing,
+        };
+
+        let mut rng = match self.random_state {
+            Some(seed) => seed.rng(),
+            None => Xoshiro256Plus::from_entropy(),
+        };
+
+        // Each tree sees its own bootstrap sample: rows drawn with replacement
+        let n_bootstrap = bootstrap_size(x.nrows(), self.bootstrap_proportion);
+        let trees = (0..self.n_estimators)
+            .map(|_| {
+                let sample = (0..n_bootstrap)
+                    .map(|_| rng.gen_range(0..x.nrows()))
+                    .collect();
+                RegressionTree::fit(&x, &y, sample, &settings)
+            })
+            .collect();
+
+        Ok(FittedRandomForestRegressor { trees })
+    }
+}
+
+impl Default for RandomForestRegressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fitted Random Forest Regressor that can make predictions
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FittedRandomForestRegressor {
+    trees: Vec<RegressionTree>,
+}
+
+const _: () = {
+    assert_send_sync::<RandomForestRegressor>();
+    assert_send_sync::<FittedRandomForestRegressor>();
+};
+
+impl FittedRandomForestRegressor {
+    /// Predict targets for samples in X
+    ///
+    /// # Arguments
+    /// * `x` - Feature matrix (rows = samples, columns = features)
+    ///
+    /// # Returns
+    /// The mean of every tree's prediction, for each sample
+    pub fn predict(&self, x: &Array2<f64>) -> Array1<f64> {
+        x.rows()
+            .into_iter()
+            .map(|row| {
+                self.trees.iter().map(|tree| tree.predict(row)).sum::<f64>()
+                    / self.trees.len() as f64
+            })
+            .collect()
+    }
+
+    /// Predict `quantiles` of the target for samples in X, e.g. `[0.1, 0.5, 0.9]` for an 80%
+    /// prediction interval and the median
+    ///
+    /// Every tree shares its vote equally among the training targets in the leaf the sample
+    /// reaches, and a quantile is the smallest of those targets whose votes, with the smaller
+    /// ones', reach that share of all of them. A quantile outside 0 to 1 is a
+    /// `DataError::InvalidQuantile`, and a forest saved before its leaves kept their targets
+    /// predicts NaN.
+    ///
+    /// # Returns
+    /// One row per sample and one column per quantile
+    pub fn predict_quantiles(
+        &self,
+        x: &Array2<f64>,
+        quantiles: &[f64],
+    ) -> Result<Array2<f64>, DataError> {
+        if let Some(&quantile) = quantiles.iter().find(|q| !(0.0..=1.0).contains(*q)) {
+            return Err(DataError::InvalidQuantile(quantile));
+        }
+
+        let mut predictions = Array2::from_elem((x.nrows(), quantiles.len()), f64::NAN);
+        for (row, mut prediction) in x.rows().into_iter().zip(predictions.rows_mut()) {
+            let mut votes: Vec<(f64, f64)> = Vec::new();
+            for tree in &self.trees {
+                let targets = tree.leaf_targets(row);
+                let vote = 1.0 / targets.len() as f64;
+                votes.extend(targets.iter().map(|&target| (target, vote)));
+            }
+            votes.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            let total: f64 = votes.iter().map(|(_, vote)| vote).sum();
+
+            for (&quantile, prediction) in quantiles.iter().zip(prediction.iter_mut()) {
+                // Allowing for rounding in the running total, so the median of [1, 2] is 1
+                let wanted = quantile * total - 1e-9;
+                let mut so_far = 0.0;
+                if let Some(&(target, _)) = votes.iter().find(|(_, vote)| {
+                    so_far += vote;
+                    so_far >= wanted
+                }) {
+                    *prediction = target;
+                }
+            }
+        }
+
+        Ok(predictions)
+    }
+}
+
+/// A binary tree stored as a flat list of nodes; the root is the first
+#[derive(Clone, Serialize, Deserialize)]
+struct RegressionTree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+enum Node {
+    Leaf {
+        value: f64,
+        /// The targets of the leaf's samples, sorted, for `predict_quantiles`
+        #[serde(default)]
+        targets: Vec<f64>,
+    },
+    /// Samples with `x[feature] <= threshold` go left, and those missing it go left if
+    /// `missing_left`
+    Split {
+        feature: usize,
+        threshold: f64,
+        #[serde(default)]
+        missing_left: bool,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// How a `RegressionTree` is grown
+struct TreeSettings<'a> {
+    max_depth: usize,
+    min_samples_split: usize,
+    /// One per feature, or none at all
+    constraints: &'a [Monotonic],
+    missing: Missing,
+}
+
+/// The lowest and highest value a node's leaves may predict, to keep the tree monotonic
+#[derive(Clone, Copy)]
+struct Bounds {
+    lower: f64,
+    upper: f64,
+}
+
+impl Bounds {
+    const NONE: Self = Self {
+        lower: f64::NEG_INFINITY,
+        upper: f64::INFINITY,
+    };
+
+    fn clamp(self, value: f64) -> f64 {
+        value.clamp(self.lower, self.upper)
+    }
+}
+
+/// Where `best_split` would split a node
+struct Split {
+    feature: usize,
+    threshold: f64,
+    missing_left: bool,
+    /// The halves' means, within the node's bounds
+    left_value: f64,
+    right_value: f64,
+}
+
+impl RegressionTree {
+    /// Grow a tree on the rows of `x` and `y` listed in `sample` (which may repeat)
+    fn fit(
+        x: &ArrayView2<f64>,
+        y: &ArrayView1<f64>,
+        sample: Vec<usize>,
+        settings: &TreeSettings,
+    ) -> Self {
+        let mut tree = Self { nodes: Vec::new() };
+        tree.grow(x, y, sample, 0, Bounds::NONE, settings);
+
+        tree
+    }
+
+    /// Add a node for `rows` and everything below it, predicting within `bounds`, and return its
+    /// index
+    fn grow(
+        &mut self,
+        x: &ArrayView2<f64>,
+        y: &ArrayView1<f64>,
+        mut rows: Vec<usize>,
+        depth: usize,
+        bounds: Bounds,
+        settings: &TreeSettings,
+    ) -> usize {
+        let node = self.nodes.len();
+        let mut targets: Vec<f64> = rows.iter().map(|&i| y[i]).collect();
+        let value = bounds.clamp(targets.iter().sum::<f64>() / targets.len() as f64);
+        self.nodes.push(Node::Leaf {
+            value,
+            targets: Vec::new(),
+        });
+
+        let split = if depth >= settings.max_depth || rows.len() < settings.min_samples_split.max(2)
+        {
+            None
+        } else {
+            best_split(x, y, &mut rows, bounds, settings.constraints)
+        };
+        let Some(split) = split else {
+            targets.sort_by(f64::total_cmp);
+            self.nodes[node] = Node::Leaf { value, targets };
+            return node;
+        };
+        let Split {
+            feature, threshold, ..
+        } = split;
+        // With `Missing::Zero` the training data has no NaNs left, but a sample to predict might
+        let missing_left = match settings.missing {
+            Missing::Zero => 0.0 <= threshold,
+            Missing::Reject | Missing::Learn => split.missing_left,
+        };
+
+        // Below a constrained split, the low side predicts no higher than halfway between the
+        // halves' means (when increasing) and the high side no lower, or the other way round
+        let middle = (split.left_value + split.right_value) / 2.0;
+        let (left_bounds, right_bounds) = match settings.constraints.get(feature) {
+            Some(Monotonic::Increasing) => (
+                Bounds {
+                    upper: middle,
+                    ..bounds
+                },
+                Bounds {
+                    lower: middle,
+                    ..bounds
+                },
+            ),
+            Some(Monotonic::Decreasing) => (
+                Bounds {
+                    lower: middle,
+                    ..bounds
+                },
+                Bounds {
+                    upper: middle,
+                    ..bounds
+                },
+            ),
+            Some(Monotonic::None) | None => (bounds, bounds),
+        };
+
+        let (left, right): (Vec<usize>, Vec<usize>) = rows
+            .into_iter()
+            .partition(|&i| goes_left(x[[i, feature]], threshold, missing_left));
+        let left = self.grow(x, y, left, depth + 1, left_bounds, settings);
+        let right = self.grow(x, y, right, depth + 1, right_bounds, settings);
+        self.nodes[node] = Node::Split {
+            feature,
+            threshold,
+            missing_left,
+            left,
+            right,
+        };
+
+        node
+    }
+
+    fn predict(&self, row: ArrayView1<f64>) -> f64 {
+        match self.leaf(row) {
+            Node::Leaf { value, .. } => *value,
+            Node::Split { .. } => unreachable!("`leaf` ends at a leaf"),
+        }
+    }
+
+    fn leaf_targets(&self, row: ArrayView1<f64>) -> &[f64] {
+        match self.leaf(row) {
+            Node::Leaf { targets, .. } => targets,
+            Node::Split { .. } => unreachable!("`leaf` ends at a leaf"),
+        }
+    }
+
+    /// The leaf `row` ends up in
+    fn leaf(&self, row: ArrayView1<f64>) -> &Node {
+        let mut node = 0;
+        loop {
+            match self.nodes[node] {
+                Node::Leaf { .. } => return &self.nodes[node],
+                Node::Split {
+                    feature,
+                    threshold,
+                    missing_left,
+                    left,
+                    right,
+                } => {
+                    node = if goes_left(row[feature], threshold, missing_left) {
+                        left
+                    } else {
+                        right
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether a sample with `value` goes left of a split
+fn goes_left(value: f64, threshold: f64, missing_left: bool) -> bool {
+    value <= threshold || (value.is_nan() && missing_left)
+}
+
+/// The split that most reduces the squared error of `rows`, if any split does
+///
+/// Minimizing the two halves' squared error is the same as maximizing
+/// `sum_left² / n_left + sum_right² / n_right`, which a single sweep over the rows sorted by each
+/// feature can track. Thresholds fall halfway between neighboring distinct values.
+///
+/// Rows missing the feature are scored on each side of every threshold in turn, and on their own
+/// against every row that has it, and go wherever scores best.
+///
+/// A split on a feature with a `Monotonic` constraint is skipped unless its halves' means,
+/// within `bounds`, go the way the constraint allows.
+fn best_split(
+    x: &ArrayView2<f64>,
+    y: &ArrayView1<f64>,
+    rows: &mut [usize],
+    bounds: Bounds,
+    constraints: &[Monotonic],
+) -> Option<Split> {
+    let n = rows.len() as f64;
+    let total: f64 = rows.iter().map(|&i| y[i]).sum();
+    let unsplit = total * total / n;
+
+    let mut best: Option<(Split, f64)> = None;
+    for feature in 0..x.ncols() {
+        let constraint = constraints.get(feature).copied().unwrap_or_default();
+        // The rows that have the feature, in order, then those missing it
+        rows.sort_unstable_by(|&a, &b| {
+            let (a, b) = (x[[a, feature]], x[[b, feature]]);
+            a.is_nan().cmp(&b.is_nan()).then(a.total_cmp(&b))
+        });
+        let n_present = rows
+            .iter()
+            .take_while(|&&i| !x[[i, feature]].is_nan())
+            .count();
+        let (present, missing) = rows.split_at(n_present);
+        let missing_sum: f64 = missing.iter().map(|&i| y[i]).sum();
+        let sides: &[bool] = if missing.is_empty() {
+            &[false]
+        } else {
+            &[false, true]
+        };
+
+        let mut present_left_sum = 0.0;
+        for k in 0..present.len() {
+            present_left_sum += y[present[k]];
+            let here = x[[present[k], feature]];
+            let threshold = match present.get(k + 1) {
+                Some(&next) if x[[next, feature]] == here => continue,
+                Some(&next) => (here + x[[next, feature]]) / 2.0,
+                // Every row that has the feature on the left, and every row missing it on the right
+                None if !missing.is_empty() => here,
+                None => break,
+            };
+
+            for &missing_left in sides {
+                if missing_left && k + 1 == present.len() {
+                    continue;
+                }
+                let (left_sum, n_left) = if missing_left {
+                    (
+                        present_left_sum + missing_sum,
+                        (k + 1 + missing.len()) as f64,
+                    )
+                } else {
+                    (present_left_sum, (k + 1) as f64)
+                };
+                let right_sum = total - left_sum;
+                let score = left_sum * left_sum / n_left + right_sum * right_sum / (n - n_left);
+                if best
+                    .as_ref()
+                    .is_some_and(|(_, best_score)| score <= *best_score)
+                {
+                    continue;
+                }
+
+                let left_value = bounds.clamp(left_sum / n_left);
+                let right_value = bounds.clamp(right_sum / (n - n_left));
+                let allowed = match constraint {
+                    Monotonic::None => true,
+                    Monotonic::Increasing => left_value <= right_value,
+                    Monotonic::Decreasing => left_value >= right_value,
+                };
+                if allowed {
+                    let split = Split {
+                        feature,
+                        threshold,
+                        missing_left,
+                        left_value,
+                        right_value,
+                    };
+                    best = Some((split, score));
+                }
+            }
+        }
+    }
+
+    // A split that doesn't reduce the error (beyond rounding) isn't worth making
+    best.filter(|(_, score)| score - unsplit > 1e-9 * unsplit.abs().max(1.0))
+        .map(|(split, _)| split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DataError;
+    use ndarray::{arr1, arr2};
+
+    /// y is 1 for x < 5 and 10 above, plus a feature that doesn't matter
+    fn step() -> (Array2<f64>, Array1<f64>) {
+        let x = Array2::from_shape_fn((20, 2), |(i, j)| if j == 0 { i as f64 / 2.0 } else { 3.0 });
+        let y = x.column(0).mapv(|v| if v < 5.0 { 1.0 } else { 10.0 });
+
+        (x, y)
+    }
+
+    #[test]
+    fn test_fits_a_step() {
+        let (x, y) = step();
+        let model = RandomForestRegressor::new()
+            .n_estimators(20)
+            .random_state(1)
+            .fit(x, y)
+            .unwrap();
+
+        let predictions = model.predict(&arr2(&[[0.0, 3.0], [9.0, 3.0]]));
+        assert!((predictions[0] - 1.0).abs() < 1.0, "{predictions}");
+        assert!((predictions[1] - 10.0).abs() < 1.0, "{predictions}");
+    }
+
+    #[test]
+    fn test_depth_one_is_a_single_split() {
+        let x = arr2(&[[0.0], [1.0], [2.0], [3.0]]);
+        let y = arr1(&[1.0, 2.0, 6.0, 7.0]);
+        let model = RandomForestRegressor::new()
+            .n_estimators(1)
+            .max_depth(1)
+            .bootstrap_proportion(1.0)
+            .random_state(3)
+            .fit(&x, y)
+            .unwrap();
+
+        // One tree with one split, so at most two means of the rows its bootstrap sample drew
+        let predictions = model.predict(&x);
+        let mut distinct = predictions.to_vec();
+        distinct.dedup();
+        assert!(distinct.len() <= 2, "{predictions}");
+        assert!(predictions.iter().all(|p| (1.0..=7.0).contains(p)));
+    }
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let (x, y) = step();
+        let fit = || {
+            RandomForestRegressor::new()
+                .n_estimators(5)
+                .bootstrap_proportion(0.5)
+                .random_state(7)
+                .fit(&x, &y)
+                .unwrap()
+        };
+
+        assert_eq!(fit().predict(&x), fit().predict(&x));
+    }
+
+    #[test]
+    fn test_quantiles_bound_the_spread_of_the_targets() {
+        // The step again, with each target 1 above or below it
+        let (x, step) = step();
+        let y = Array1::from_shape_fn(step.len(), |i| {
+            step[i] + if i % 2 == 0 { -1.0 } else { 1.0 }
+        });
+        let model = RandomForestRegressor::new()
+            .n_estimators(20)
+            .max_depth(1)
+            .random_state(1)
+            .fit(&x, &y)
+            .unwrap();
+
+        let quantiles = model
+            .predict_quantiles(&arr2(&[[0.0, 3.0], [9.0, 3.0]]), &[0.0, 0.1, 0.9, 1.0])
+            .unwrap();
+        assert_eq!(quantiles.row(0).to_vec(), [0.0, 0.0, 2.0, 2.0]);
+        assert_eq!(quantiles.row(1).to_vec(), [9.0, 9.0, 11.0, 11.0]);
+        assert!(matches!(
+            model.predict_quantiles(&x, &[0.5, 1.5]),
+            Err(DataError::InvalidQuantile(1.5))
+        ));
+    }
+
+    #[test]
+    fn test_monotonic_constraints_hold_for_every_prediction() {
+        // Falling with the first feature but for a bump, plus a feature that doesn't matter
+        let x = Array2::from_shape_fn((80, 2), |(i, j)| match j {
+            0 => (i / 2) as f64,
+            _ => (i % 5) as f64,
+        });
+        let y = x
+            .column(0)
+            .mapv(|v| -v / 10.0 + if (20.0..25.0).contains(&v) { 3.0 } else { 0.0 });
+        let forest = RandomForestRegressor::new()
+            .n_estimators(10)
+            .random_state(1);
+        let falls_with_the_first_feature = |model: &FittedRandomForestRegressor| {
+            (0..5).all(|other| {
+                let grid = Array2::from_shape_fn((45, 2), |(i, j)| match j {
+                    0 => i as f64 - 2.5,
+                    _ => other as f64,
+                });
+                model
+                    .predict(&grid)
+                    .windows(2)
+                    .into_iter()
+                    .all(|w| w[1] <= w[0])
+            })
+        };
+
+        assert!(!falls_with_the_first_feature(&forest.fit(&x, &y).unwrap()));
+        let constrained =
+            forest.monotonic_constraints(vec![Monotonic::Decreasing, Monotonic::None]);
+        let model = constrained.fit(&x, &y).unwrap();
+        assert!(falls_with_the_first_feature(&model));
+        // Away from the bump it still follows the trend
+        let ends = model.predict(&arr2(&[[0.0, 0.0], [39.0, 0.0]]));
+        assert!(ends[0] - ends[1] > 2.0, "{ends}");
+
+        assert!(matches!(
+            constrained
+                .monotonic_constraints(vec![Monotonic::Increasing])
+                .fit(&x, &y),
+            Err(FitError::MonotonicConstraintCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_missing_values_learn_their_own_branch() {
+        // y follows x, except that the rows missing it all have a target of 50
+        let x = Array2::from_shape_fn(
+            (30, 1),
+            |(i, _)| if i % 3 == 0 { f64::NAN } else { i as f64 },
+        );
+        let y = x.column(0).mapv(|v| if v.is_nan() { 50.0 } else { v });
+        let forest = RandomForestRegressor::new()
+            .n_estimators(10)
+            .random_state(4);
+
+        let learned = forest.missing(Missing::Learn).fit(&x, &y).unwrap();
+        let predictions = learned.predict(&arr2(&[[f64::NAN], [1.0], [28.0]]));
+        assert!((predictions[0] - 50.0).abs() < 5.0, "{predictions}");
+        assert!(
+            predictions[1] < 10.0 && predictions[2] > 20.0,
+            "{predictions}"
+        );
+
+        // As 0s, they can't be told apart from a real 0
+        let forest = RandomForestRegressor::new()
+            .n_estimators(10)
+            .random_state(4);
+        let zeroed = forest.missing(Missing::Zero).fit(&x, &y).unwrap();
+        let predictions = zeroed.predict(&arr2(&[[f64::NAN], [0.0]]));
+        assert_eq!(predictions[0], predictions[1]);
+
+        assert!(matches!(
+            RandomForestRegressor::new().fit(&x, &y),
+            Err(FitError::Data(DataError::NonFinite { row: 0, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_nonsensical_settings_are_refused() {
+        assert!(RandomForestRegressor::new().validate().is_ok());
+        assert!(matches!(
+            RandomForestRegressor::new().n_estimators(0).validate(),
+            Err(FitError::NoEstimators)
+        ));
+        assert!(matches!(
+            RandomForestRegressor::new().max_depth(0).validate(),
+            Err(FitError::ZeroMaxDepth)
+        ));
+        for proportion in [0.0, -0.5, 1.5, f64::NAN] {
+            assert!(matches!(
+                RandomForestRegressor::new()
+                    .bootstrap_proportion(proportion)
+                    .validate(),
+                Err(FitError::InvalidBootstrapProportion(_))
+            ));
+        }
+
+        // Fitting refuses them before looking at the data
+        let (x, _) = step();
+        assert!(matches!(
+            RandomForestRegressor::new()
+                .max_depth(0)
+                .fit(x, Array1::zeros(3)),
+            Err(FitError::ZeroMaxDepth)
+        ));
+
+        // Bootstrap samples round up, as the classifier's do
+        assert_eq!(bootstrap_size(10, 0.25), 3);
+        assert_eq!(bootstrap_size(10, 0.01), 1);
+    }
+
+    #[test]
+    fn test_rejects_mismatched_targets() {
+        let (x, _) = step();
+
+        assert!(matches!(
+            RandomForestRegressor::new().fit(x, Array1::zeros(3)),
+            Err(FitError::Data(DataError::LengthMismatch {
+                samples: 20,
+                labels: 3
+            }))
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+//! Saving and loading a trained model together with its preprocessing state
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, path::Path};
+
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::model_file;
+use crate::{
+    feature_schema::FeatureSchema,
+    random_forest::{FittedRandomForest, assert_send_sync},
+};
+
+/// Predictions for a batch of raw rows, as returned by `serve` and the wasm bindings
+#[derive(Debug, Serialize)]
+pub struct Predictions {
+    pub classes: Vec<usize>,
+    pub predictions: Vec<usize>,
+    /// One row per sample, columns ordered like `classes`
+    pub probabilities: Vec<Vec<f64>>,
+}
+
+/// Everything needed to turn raw feature rows into predictions
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedModel {
+    feature_schema: FeatureSchema,
+    forest: FittedRandomForest<usize>,
+}
+
+// `serve` and `grpc` answer requests on many threads from one `Arc<SavedModel>`
+const _: () = assert_send_sync::<SavedModel>();
+
+impl SavedModel {
+    pub fn new(feature_schema: FeatureSchema, forest: FittedRandomForest<usize>) -> Self {
+        Self {
+            feature_schema,
+            forest,
+        }
+    }
+
+    /// Read a model written with `save` or `save_json`, by this or an older version
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        model_file::read(path)
+            .with_context(|| format!("Failed to load model file {}", path.display()))
+    }
+
+    /// Write the model in the compressed, versioned `model_file` format
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(model_file::write(self, path)?)
+    }
+
+    /// Write the model as plain JSON, which is what the wasm bindings read
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(file, self)?;
+
+        Ok(())
+    }
+
+    /// Encode JSON rows of raw, named features and predict them
+    pub fn predict_json_rows(&self, rows: &[Map<String, Value>]) -> Result<Predictions> {
+        let x = self.feature_schema.encode_json_rows(rows)?;
+        // The labels come from the probabilities, so the trees only vote once
+        let probabilities = self.forest.predict_proba(&x);
+
+        Ok(Predictions {
+            classes: self.forest.classes().to_vec(),
+            predictions: self.forest.most_likely(&probabilities).to_vec(),
+            probabilities: probabilities
+                .rows()
+                .into_iter()
+                .map(|row| row.to_vec())
+                .collect(),
+        })
+    }
+
+    pub fn feature_schema(&self) -> &FeatureSchema {
+        &self.feature_schema
+    }
+
+    pub fn forest(&self) -> &FittedRandomForest<usize> {
+        &self.forest
+    }
+}
@@ -0,0 +1,114 @@
+//! Batch scoring of arbitrary CSVs with a saved model
+//!
+//! Unlike the Kaggle submission (just `PassengerId` and `Survived`), scoring keeps every input
+//! column and appends the model's output, so any CSV with the expected feature columns can be
+//! scored.
+
+use std::path::Path;
+
+use anyhow::Result;
+use polars::prelude::*;
+
+use crate::{conversions::write_csv_file, saved_model::SavedModel};
+
+/// Append a `prediction` column and one `probability_<class>` column per class to `df`
+///
+/// # Arguments
+/// * `model` - The saved model (its feature schema decides which columns are read)
+/// * `df` - Rows to score; must contain the model's source feature columns
+///
+/// # Returns
+/// * `Result<DataFrame>` - `df` with the new columns on the right
+pub fn score(model: &SavedModel, mut df: DataFrame) -> Result<DataFrame> {
+    let x = model.feature_schema().encode_frame(&df)?;
+    let forest = model.forest();
+
+    let predictions: Vec<i64> = forest.predict(&x).iter().map(|&p| p as i64).collect();
+    df.with_column(Series::new("prediction".into(), predictions))?;
+
+    let probabilities = forest.predict_proba(&x);
+    for (class, column) in forest.classes().iter().zip(probabilities.columns()) {
+        df.with_column(Series::new(
+            format!("probability_{class}").into(),
+            column.to_vec(),
+        ))?;
+    }
+
+    Ok(df)
+}
+
+/// Score the CSV at `input` and write the result to `output`
+pub fn score_csv_file(model: &SavedModel, input: &Path, output: &Path) -> Result<DataFrame> {
+    let df = LazyCsvReader::new(PlPath::new(&input.to_string_lossy()))
+        .with_has_header(true)
+        .finish()?
+        .collect()?;
+
+    let mut scored = score(model, df)?;
+    write_csv_file(&mut scored, &output.to_string_lossy())?;
+
+    Ok(scored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{feature_schema::FeatureSchema, random_forest::RandomForestClassifier};
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_score() {
+        let raw = df!("Fare" => [1.0, 2.0, 10.0, 20.0]).unwrap();
+        let schema = FeatureSchema::infer(&raw, &raw).unwrap();
+        let forest = RandomForestClassifier::new()
+            .n_estimators(5)
+            .random_state(1)
+            .fit(
+                arr2(&[[1.0], [2.0], [10.0], [20.0]]),
+                arr1(&[0usize, 0, 1, 1]),
+            )
+            .unwrap();
+        let model = SavedModel::new(schema, forest);
+
+        let df = df!(
+            "PassengerId" => [1i64, 2],
+            "Fare" => [1.5, 15.0],
+        )
+        .unwrap();
+        let scored = score(&model, df).unwrap();
+
+        assert_eq!(
+            scored.get_column_names(),
+            [
+                "PassengerId",
+                "Fare",
+                "prediction",
+                "probability_0",
+                "probability_1"
+            ]
+        );
+        assert_eq!(scored.height(), 2);
+
+        // The columns hold what the forest predicts for the same rows
+        let x = arr2(&[[1.5], [15.0]]);
+        let column = |name: &str| -> Vec<f64> {
+            scored
+                .column(name)
+                .unwrap()
+                .cast(&DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect()
+        };
+        let predictions = model.forest().predict(&x);
+        assert_eq!(
+            column("prediction"),
+            predictions.iter().map(|&p| p as f64).collect::<Vec<_>>()
+        );
+        let probabilities = model.forest().predict_proba(&x);
+        assert_eq!(column("probability_0"), probabilities.column(0).to_vec());
+        assert_eq!(column("probability_1"), probabilities.column(1).to_vec());
+    }
+}
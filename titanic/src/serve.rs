@@ -0,0 +1,50 @@
+//! A small HTTP server for demoing a saved model
+//!
+//! `POST /predict` takes raw, named features and returns predicted classes and probabilities:
+//!
+//! ```text
+//! $ curl -s localhost:3000/predict -H 'content-type: application/json' \
+//!     -d '{"rows": [{"Pclass": 3, "Sex": "male", "Age": 22, "Fare": 7.25,
+//!                    "SibSp": 1, "Parch": 0}]}'
+//! {"classes":[0,1],"predictions":[0],"probabilities":[[0.87,0.13]]}
+//! ```
+
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::saved_model::{Predictions, SavedModel};
+
+#[derive(Deserialize)]
+struct PredictRequest {
+    rows: Vec<Map<String, Value>>,
+}
+
+/// Serve `model` on `addr` until the process is stopped
+pub async fn run(model: Arc<SavedModel>, addr: SocketAddr) -> anyhow::Result<()> {
+    let app = router(model);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+fn router(model: Arc<SavedModel>) -> Router {
+    Router::new()
+        .route("/predict", post(predict))
+        .with_state(model)
+}
+
+async fn predict(
+    State(model): State<Arc<SavedModel>>,
+    Json(request): Json<PredictRequest>,
+) -> Result<Json<Predictions>, (StatusCode, String)> {
+    model
+        .predict_json_rows(&request.rows)
+        .map(Json)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+}
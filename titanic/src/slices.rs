@@ -0,0 +1,207 @@
+//! Evaluating a saved model on slices of labelled data, to find where it does worst
+//!
+//! One accuracy for every passenger can hide a group the model gets badly wrong: children, say,
+//! or third class. `evaluate_slices` scores the model on the rows matching each of a list of
+//! Polars expressions (e.g. `col("Age").lt(lit(16))` or `col("Pclass").eq(lit(3))`), next to
+//! all the rows together, and returns a table with a row per slice. Its `Accuracy gap` column is
+//! each slice's accuracy minus the accuracy on all rows, so the slices to worry about are the
+//! most negative. `evaluate_named_slices` does the same with names of the caller's choosing,
+//! which read better than Polars' printing of an expression.
+//!
+//! Rows without a label are left out of every slice, and an empty slice has NaN metrics.
+
+use anyhow::{Result, bail};
+use metrics::metric::{Accuracy, F1, LogLoss, Metric, RocAuc};
+use ndarray::{Array1, Array2};
+use polars::prelude::*;
+
+use crate::{conversions::series_to_array1_i64, data::Data, saved_model::SavedModel};
+
+/// The column of labels the model is scored against
+const LABEL_COLUMN: &str = "Survived";
+
+/// What the slice of every row is called
+pub const ALL_ROWS: &str = "all rows";
+
+/// Score `model` on the rows of `data` matching each of `slices`, and on all of them
+///
+/// # Returns
+/// * `Result<DataFrame>` - A `Slice` column (`ALL_ROWS` first, then each expression as Polars
+///   prints it), its number of `Rows` and `Positive rate`, a column per metric (`accuracy`, `f1`,
+///   `roc auc` and `log loss`) and the `Accuracy gap`
+///
+/// # Example
+/// ```ignore
+/// let model = SavedModel::load("data/model.bin")?;
+/// let data = TrainingData::try_from_path("data/train.csv")?;
+/// let slices = [col("Age").lt(lit(16)), col("Pclass").eq(lit(3))];
+/// let table = evaluate_slices(&model, &data, &slices)?;
+/// println!("{table}");
+/// ```
+pub fn evaluate_slices(model: &SavedModel, data: &impl Data, slices: &[Expr]) -> Result<DataFrame> {
+    let named: Vec<(String, Expr)> = slices
+        .iter()
+        .map(|slice| (slice.to_string(), slice.clone()))
+        .collect();
+
+    evaluate_named_slices(model, data, &named)
+}
+
+/// `evaluate_slices`, with a name of the caller's for each slice, such as the SQL it was parsed
+/// from (Polars prints `col("Age").lt(lit(16))` as `[(col("Age")) < (dyn int: 16)]`)
+pub fn evaluate_named_slices(
+    model: &SavedModel,
+    data: &impl Data,
+    slices: &[(String, Expr)],
+) -> Result<DataFrame> {
+    let metrics: [&dyn Metric; 4] = [&Accuracy, &F1::default(), &RocAuc::default(), &LogLoss];
+    let labelled = data
+        .lazy_frame_cloned()
+        .filter(col(LABEL_COLUMN).is_not_null());
+
+    let everything = (ALL_ROWS.to_string(), lit(true));
+    let mut names = Vec::with_capacity(slices.len() + 1);
+    let mut rows = Vec::with_capacity(slices.len() + 1);
+    let mut positive_rates = Vec::with_capacity(slices.len() + 1);
+    let mut scores: Vec<Vec<f64>> = vec![Vec::with_capacity(slices.len() + 1); metrics.len()];
+    for (name, slice) in std::iter::once(&everything).chain(slices) {
+        names.push(name.as_str());
+        let df = labelled.clone().filter(slice.clone()).collect()?;
+        rows.push(df.height() as u32);
+        if df.height() == 0 {
+            positive_rates.push(f64::NAN);
+            for scores in &mut scores {
+                scores.push(f64::NAN);
+            }
+            continue;
+        }
+
+        let actual = labels(&df)?;
+        let positives = actual.iter().filter(|&&label| label == 1).count();
+        positive_rates.push(positives as f64 / actual.len() as f64);
+        let probabilities = class_probabilities(model, &df)?;
+        for (metric, scores) in metrics.iter().zip(&mut scores) {
+            scores.push(metric.compute(&probabilities, &actual));
+        }
+    }
+
+    let overall_accuracy = scores[0][0];
+    let accuracy_gap: Vec<f64> = scores[0]
+        .iter()
+        .map(|accuracy| accuracy - overall_accuracy)
+        .collect();
+    let mut columns = vec![
+        Column::new("Slice".into(), names),
+        Column::new("Rows".into(), rows),
+        Column::new("Positive rate".into(), positive_rates),
+    ];
+    for (metric, scores) in metrics.iter().zip(scores) {
+        columns.push(Column::new(metric.name().into(), scores));
+    }
+    columns.push(Column::new("Accuracy gap".into(), accuracy_gap));
+
+    Ok(DataFrame::new(columns)?)
+}
+
+/// The rows' labels, which (unlike `series_to_array1`'s) needn't include every class, as a slice
+/// may well have only survivors
+fn labels(df: &DataFrame) -> Result<Array1<usize>> {
+    series_to_array1_i64(df.column(LABEL_COLUMN)?.as_materialized_series())?
+        .iter()
+        .map(|&label| match usize::try_from(label) {
+            Ok(label) => Ok(label),
+            Err(_) => bail!("Labels can't be negative, got {label}"),
+        })
+        .collect()
+}
+
+/// `model`'s probabilities for the rows of `df`, with column `k` for class `k` (0 for a class it
+/// never saw), as `Metric`s expect
+fn class_probabilities(model: &SavedModel, df: &DataFrame) -> Result<Array2<f64>> {
+    let x = model.feature_schema().encode_frame(df)?;
+    let forest = model.forest();
+    let probabilities = forest.predict_proba(&x);
+
+    let n_classes = forest
+        .classes()
+        .iter()
+        .max()
+        .map_or(0, |&max| max + 1)
+        .max(2);
+    let mut by_class = Array2::zeros((df.height(), n_classes));
+    for (&class, column) in forest.classes().iter().zip(probabilities.columns()) {
+        by_class.column_mut(class).assign(&column);
+    }
+
+    Ok(by_class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{feature_schema::FeatureSchema, random_forest::RandomForestClassifier};
+    use ndarray::arr1;
+
+    #[test]
+    fn test_evaluate_slices() {
+        // The model predicts survival from Fare alone, which is wrong for the rows with Age < 10
+        let fares = [10.0, 12.0, 15.0, 20.0, 80.0, 85.0, 90.0, 95.0];
+        let raw = df!("Fare" => fares).unwrap();
+        let schema = FeatureSchema::infer(&raw, &raw).unwrap();
+        let forest = RandomForestClassifier::new()
+            .n_estimators(10)
+            .random_state(1)
+            .fit(
+                Array2::from_shape_vec((8, 1), fares.to_vec()).unwrap(),
+                arr1(&[0usize, 0, 0, 0, 1, 1, 1, 1]),
+            )
+            .unwrap();
+        let model = SavedModel::new(schema, forest);
+        let data = df!(
+            "Fare" => [10.0, 15.0, 85.0, 95.0, 12.0, 90.0],
+            "Age" => [30, 40, 35, 50, 5, 8],
+            "Survived" => [Some(0i64), Some(0), Some(1), Some(1), Some(1), None],
+        )
+        .unwrap();
+
+        let table = evaluate_slices(
+            &model,
+            &data,
+            &[col("Age").lt(lit(10)), col("Age").gt(lit(100))],
+        )
+        .unwrap();
+        assert_eq!(
+            table.get_column_names(),
+            [
+                "Slice",
+                "Rows",
+                "Positive rate",
+                "accuracy",
+                "f1",
+                "roc auc",
+                "log loss",
+                "Accuracy gap"
+            ]
+        );
+        let column = |name: &str| -> Vec<Option<f64>> {
+            table
+                .column(name)
+                .unwrap()
+                .cast(&DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+        // The unlabelled row is left out
+        assert_eq!(column("Rows"), [Some(5.0), Some(1.0), Some(0.0)]);
+        assert_eq!(column("accuracy")[..2], [Some(0.8), Some(0.0)]);
+        assert_eq!(column("Accuracy gap")[1], Some(-0.8));
+        assert!(column("accuracy")[2].unwrap().is_nan());
+        assert_eq!(
+            table.column("Slice").unwrap().str().unwrap().get(0),
+            Some(ALL_ROWS)
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Ways of splitting rows into training and testing sets
+//!
+//! A `Splitter` turns a dataset's labels into one or more `Fold`s, each listing which rows to
+//! train on and which to test on. Cross-validation and anything else that scores a model on
+//! held-out rows takes a `Splitter` rather than a particular strategy, so changing a holdout set
+//! to k-fold, or k-fold to stratified or grouped k-fold, is a change of argument:
+//!
+//! - `Holdout` is a single cut, like `conversions::train_test_split`
+//! - `KFold` cuts the rows into `k` test sets of (nearly) equal size
+//! - `StratifiedKFold` does the same, keeping each label's proportion the same in every fold
+//! - `GroupKFold` keeps rows that share a group (e.g. a family, or a patient) in the same fold, so
+//!   a model is never tested on a group it was trained on
+//!
+//! The folds depend only on the labels and the seed, so every model scored with the same
+//! splitter is scored on exactly the same rows.
+
+use ndarray::Array1;
+use thiserror::Error;
+
+use crate::{conversions::group_by_label, seed::Seed};
+
+/// Why rows couldn't be split as asked
+#[derive(Debug, Error)]
+pub enum SplitError {
+    #[error("k-fold cross-validation needs at least 2 folds, got {0}")]
+    TooFewFolds(usize),
+
+    #[error("Can't make {folds} folds from {samples} samples")]
+    TooFewSamples { folds: usize, samples: usize },
+
+    #[error("Can't make {folds} folds from {groups} groups")]
+    TooFewGroups { folds: usize, groups: usize },
+
+    #[error("Got {groups} groups for {samples} samples")]
+    GroupCountMismatch { groups: usize, samples: usize },
+
+    #[error("The training ratio must be between 0 and 1, got {0}")]
+    InvalidRatio(f32),
+}
+
+/// One split's row indices
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    pub train: Vec<usize>,
+    pub test: Vec<usize>,
+}
+
+/// A strategy for splitting rows labelled `L` into training and testing sets
+pub trait Splitter<L = usize> {
+    /// Each fold's training and testing rows, as indices into `y` (and its features)
+    fn split(&self, y: &Array1<L>) -> Result<impl Iterator<Item = Fold>, SplitError>;
+}
+
+/// Row indices in order, or shuffled by `seed`
+fn row_order(n_samples: usize, shuffle: Option<Seed>) -> Vec<usize> {
+    match shuffle {
+        Some(seed) => seed.permutation(n_samples).to_vec(),
+        None => (0..n_samples).collect(),
+    }
+}
+
+/// The folds where fold `f` tests the rows `fold_of` maps to `f`, with rows in `order`'s order
+fn folds_from_assignment(
+    order: Vec<usize>,
+    fold_of: Vec<usize>,
+    k: usize,
+) -> impl Iterator<Item = Fold> {
+    (0..k).map(move |fold| {
+        let (test, train) = order.iter().partition(|&&row| fold_of[row] == fold);
+        Fold { train, test }
+    })
+}
+
+fn check_k(k: usize, n_samples: usize) -> Result<(), SplitError> {
+    if k < 2 {
+        return Err(SplitError::TooFewFolds(k));
+    }
+    if k > n_samples {
+        return Err(SplitError::TooFewSamples {
+            folds: k,
+            samples: n_samples,
+        });
+    }
+
+    Ok(())
+}
+
+/// Train on the first `ratio` of the rows and test on the rest, in one fold
+#[derive(Debug, Clone)]
+pub struct Holdout {
+    ratio: f32,
+    shuffle: Option<Seed>,
+}
+
+impl Holdout {
+    pub fn new(ratio: f32) -> Self {
+        Self {
+            ratio,
+            shuffle: None,
+        }
+    }
+
+    /// Shuffle the rows before cutting them; the same seed always gives the same fold
+    pub fn shuffle(mut self, seed: impl Into<Seed>) -> Self {
+        self.shuffle = Some(seed.into());
+        self
+    }
+}
+
+impl<L> Splitter<L> for Holdout {
+    fn split(&self, y: &Array1<L>) -> Result<impl Iterator<Item = Fold>, SplitError> {
+        if !(0.0..=1.0).contains(&self.ratio) {
+            return Err(SplitError::InvalidRatio(self.ratio));
+        }
+
+        let mut train = row_order(y.len(), self.shuffle);
+        let test = train.split_off((y.len() as f32 * self.ratio) as usize);
+
+        Ok(std::iter::once(Fold { train, test }))
+    }
+}
+
+/// Cut the rows into `k` test sets of (nearly) equal size; each fold trains on all the others
+///
+/// Every row is in exactly one test set.
+#[derive(Debug, Clone)]
+pub struct KFold {
+    k: usize,
+    shuffle: Option<Seed>,
+}
+
+impl KFold {
+    pub fn new(k: usize) -> Self {
+        Self { k, shuffle: None }
+    }
+
+    /// Shuffle the rows before cutting them; the same seed always gives the same folds
+    pub fn shuffle(mut self, seed: impl Into<Seed>) -> Self {
+        self.shuffle = Some(seed.into());
+        self
+    }
+}
+
+impl<L> Splitter<L> for KFold {
+    fn split(&self, y: &Array1<L>) -> Result<impl Iterator<Item = Fold>, SplitError> {
+        let (k, n_samples) = (self.k, y.len());
+        check_k(k, n_samples)?;
+
+        let order = row_order(n_samples, self.shuffle);
+        let mut fold_of = vec![0; n_samples];
+        for fold in 0..k {
+            // Fold sizes differ by at most one
+            for &row in &order[fold * n_samples / k..(fold + 1) * n_samples / k] {
+                fold_of[row] = fold;
+            }
+        }
+
+        Ok(folds_from_assignment(order, fold_of, k))
+    }
+}
+
+/// `KFold`, with each label's rows dealt out across the folds so every fold has (nearly) the same
+/// label proportions as the whole
+///
+/// Worth it when one label is rare, where plain k-fold can leave a fold with hardly any of it.
+#[derive(Debug, Clone)]
+pub struct StratifiedKFold {
+    k: usize,
+    shuffle: Option<Seed>,
+}
+
+impl StratifiedKFold {
+    pub fn new(k: usize) -> Self {
+        Self { k, shuffle: None }
+    }
+
+    /// Shuffle each label's rows before dealing them out; the same seed always gives the same
+    /// folds
+    pub fn shuffle(mut self, seed: impl Into<Seed>) -> Self {
+        self.shuffle = Some(seed.into());
+        self
+    }
+}
+
+impl<L: PartialEq> Splitter<L> for StratifiedKFold {
+    fn split(&self, y: &Array1<L>) -> Result<impl Iterator<Item = Fold>, SplitError> {
+        let (k, n_samples) = (self.k, y.len());
+        check_k(k, n_samples)?;
+
+        let order = row_order(n_samples, self.shuffle);
+        let mut fold_of = vec![0; n_samples];
+        // Dealing the labels one after another, round the folds, keeps both each fold's size and
+        // its count of each label within one of the others'
+        for (position, row) in group_by_label(&order, y).into_iter().flatten().enumerate() {
+            fold_of[row] = position % k;
+        }
+
+        Ok(folds_from_assignment(order, fold_of, k))
+    }
+}
+
+/// `KFold` over groups rather than rows: rows with the same group are always tested together
+///
+/// Groups go to folds largest first, each to the fold with the fewest rows so far, so the folds
+/// come out as even as the group sizes allow.
+#[derive(Debug, Clone)]
+pub struct GroupKFold<G> {
+    k: usize,
+    groups: Array1<G>,
+}
+
+impl<G: PartialEq> GroupKFold<G> {
+    /// Split into `k` folds, where row `i` belongs to `groups[i]`
+    pub fn new(k: usize, groups: impl IntoIterator<Item = G>) -> Self {
+        Self {
+            k,
+            groups: groups.into_iter().collect(),
+        }
+    }
+}
+
+impl<L, G: PartialEq> Splitter<L> for GroupKFold<G> {
+    fn split(&self, y: &Array1<L>) -> Result<impl Iterator<Item = Fold>, SplitError> {
+        let (k, n_samples) = (self.k, y.len());
+        if self.groups.len() != n_samples {
+            return Err(SplitError::GroupCountMismatch {
+                groups: self.groups.len(),
+                samples: n_samples,
+            });
+        }
+        check_k(k, n_samples)?;
+
+        let order: Vec<usize> = (0..n_samples).collect();
+        let mut groups = group_by_label(&order, &self.groups);
+        if groups.len() < k {
+            return Err(SplitError::TooFewGroups {
+                folds: k,
+                groups: groups.len(),
+            });
+        }
+
+        // Stable, so equal-sized groups keep the order they first appear in
+        groups.sort_by_key(|rows| std::cmp::Reverse(rows.len()));
+        let mut fold_sizes = vec![0; k];
+        let mut fold_of = vec![0; n_samples];
+        for rows in groups {
+            let (fold, _) = fold_sizes
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &size)| size)
+                .expect("k is at least 2");
+            fold_sizes[fold] += rows.len();
+            for row in rows {
+                fold_of[row] = fold;
+            }
+        }
+
+        Ok(folds_from_assignment(order, fold_of, k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every row is tested exactly once across the folds, and never trained on in the same fold
+    fn assert_partitions(folds: &[Fold], n_samples: usize) {
+        let mut tested: Vec<_> = folds.iter().flat_map(|fold| fold.test.clone()).collect();
+        tested.sort_unstable();
+        assert_eq!(tested, (0..n_samples).collect::<Vec<_>>());
+
+        for fold in folds {
+            assert_eq!(fold.train.len() + fold.test.len(), n_samples);
+            assert!(fold.train.iter().all(|i| !fold.test.contains(i)));
+        }
+    }
+
+    #[test]
+    fn test_k_fold_covers_every_sample_once() {
+        let y = Array1::from_elem(11, 0);
+        let folds: Vec<_> = KFold::new(3).shuffle(2).split(&y).unwrap().collect();
+
+        let sizes: Vec<_> = folds.iter().map(|fold| fold.test.len()).collect();
+        assert_eq!(sizes, [3, 4, 4]);
+        assert_partitions(&folds, 11);
+
+        // In order, each fold tests the next run of rows
+        let folds: Vec<_> = KFold::new(2)
+            .split(&Array1::from_elem(4, 0))
+            .unwrap()
+            .collect();
+        assert_eq!(
+            folds,
+            [
+                Fold {
+                    train: vec![2, 3],
+                    test: vec![0, 1]
+                },
+                Fold {
+                    train: vec![0, 1],
+                    test: vec![2, 3]
+                },
+            ]
+        );
+
+        assert!(matches!(
+            KFold::new(1).split(&y).map(|_| ()),
+            Err(SplitError::TooFewFolds(1))
+        ));
+        assert!(matches!(
+            KFold::new(12).split(&y).map(|_| ()),
+            Err(SplitError::TooFewSamples { .. })
+        ));
+    }
+
+    #[test]
+    fn test_holdout_stratified_and_grouped_splits() {
+        let y = Array1::from_iter((0..20).map(|i| usize::from(i % 5 == 0)));
+
+        let folds: Vec<_> = Holdout::new(0.75).shuffle(1).split(&y).unwrap().collect();
+        assert_eq!(folds.len(), 1);
+        assert_eq!((folds[0].train.len(), folds[0].test.len()), (15, 5));
+        assert!(Holdout::new(1.5).split(&y).is_err());
+
+        // 4 of the 20 rows are 1s: one in each fold
+        let folds: Vec<_> = StratifiedKFold::new(4)
+            .shuffle(1)
+            .split(&y)
+            .unwrap()
+            .collect();
+        assert_partitions(&folds, 20);
+        for fold in &folds {
+            assert_eq!(fold.test.iter().filter(|&&row| y[row] == 1).count(), 1);
+        }
+
+        // Groups of 4, 3, 2 and 1 rows; no group is split between folds
+        let groups = ["a", "a", "b", "c", "a", "b", "d", "c", "b", "a"];
+        let y = Array1::from_elem(groups.len(), 0);
+        let folds: Vec<_> = GroupKFold::new(2, groups).split(&y).unwrap().collect();
+        assert_partitions(&folds, groups.len());
+        for fold in &folds {
+            for &row in &fold.test {
+                assert!(fold.train.iter().all(|&other| groups[other] != groups[row]));
+            }
+        }
+        let sizes: Vec<_> = folds.iter().map(|fold| fold.test.len()).collect();
+        assert_eq!(sizes, [5, 5]);
+
+        assert!(matches!(
+            GroupKFold::new(5, groups).split(&y).map(|_| ()),
+            Err(SplitError::TooFewGroups {
+                folds: 5,
+                groups: 4
+            })
+        ));
+        assert!(matches!(
+            GroupKFold::new(2, ["a", "b"]).split(&y).map(|_| ()),
+            Err(SplitError::GroupCountMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+//! Writing predictions out as a submission file
+//!
+//! `SubmissionWriter` defaults to Kaggle's Titanic format (`PassengerId`, `Survived`, CSV), but the
+//! column names, output format and probability columns are all configurable.
+
+use std::{fs::File, path::Path};
+
+use anyhow::{Result, bail};
+use ndarray::{Array1, Array2};
+use polars::prelude::*;
+
+/// File format for `SubmissionWriter::write`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Parquet,
+    /// A JSON array of row objects
+    Json,
+}
+
+impl OutputFormat {
+    /// Guess the format from a file extension (`.csv`, `.parquet`, `.json`)
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Builds and writes submission DataFrames
+pub struct SubmissionWriter {
+    id_column: String,
+    prediction_column: String,
+    probability_column: String,
+    probability_prefix: String,
+    format: OutputFormat,
+}
+
+impl SubmissionWriter {
+    /// Create a writer for Kaggle's Titanic format
+    pub fn new() -> Self {
+        Self {
+            id_column: "PassengerId".to_string(),
+            prediction_column: "Survived".to_string(),
+            probability_column: "Probability".to_string(),
+            probability_prefix: "probability_".to_string(),
+            format: OutputFormat::Csv,
+        }
+    }
+
+    /// Set the name of the id column (default: `PassengerId`)
+    pub fn id_column(mut self, name: impl Into<String>) -> Self {
+        self.id_column = name.into();
+        self
+    }
+
+    /// Set the name of the predicted label column (default: `Survived`)
+    pub fn prediction_column(mut self, name: impl Into<String>) -> Self {
+        self.prediction_column = name.into();
+        self
+    }
+
+    /// Set the name of the column written by `to_dataframe_with_probability`
+    /// (default: `Probability`)
+    pub fn probability_column(mut self, name: impl Into<String>) -> Self {
+        self.probability_column = name.into();
+        self
+    }
+
+    /// Set the prefix for probability columns, which are named `<prefix><class>`
+    /// (default: `probability_`)
+    pub fn probability_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.probability_prefix = prefix.into();
+        self
+    }
+
+    /// Set the output file format (default: CSV)
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn id_column_name(&self) -> &str {
+        &self.id_column
+    }
+
+    pub fn prediction_column_name(&self) -> &str {
+        &self.prediction_column
+    }
+
+    pub fn probability_column_name(&self) -> &str {
+        &self.probability_column
+    }
+
+    /// Build a DataFrame with the id and prediction columns
+    ///
+    /// # Arguments
+    /// * `ids` - One id per prediction; any dtype, renamed to the configured id column
+    /// * `predictions` - Predicted class labels
+    ///
+    /// # Example
+    /// ```ignore
+    /// let ids = testing_data.get_col_as_series("PassengerId")?;
+    /// let submission_df = SubmissionWriter::new().to_dataframe(&ids, &predictions)?;
+    /// ```
+    pub fn to_dataframe(&self, ids: &Series, predictions: &Array1<usize>) -> Result<DataFrame> {
+        if ids.len() != predictions.len() {
+            bail!(
+                "Got {} ids but {} predictions",
+                ids.len(),
+                predictions.len()
+            );
+        }
+
+        let predictions: Vec<i64> = predictions.iter().map(|&x| x as i64).collect();
+
+        let df = DataFrame::new(vec![
+            ids.clone().with_name(self.id_column.as_str().into()).into(),
+            Series::new(self.prediction_column.as_str().into(), predictions).into(),
+        ])?;
+
+        Ok(df)
+    }
+
+    /// Like `to_dataframe`, plus a single probability column
+    ///
+    /// Meant for binary problems, where keeping the positive class's probability next to the hard
+    /// label lets several submission files be blended later.
+    ///
+    /// # Arguments
+    /// * `probability` - One probability per prediction, e.g. a column of `predict_proba`
+    pub fn to_dataframe_with_probability(
+        &self,
+        ids: &Series,
+        predictions: &Array1<usize>,
+        probability: &Array1<f64>,
+    ) -> Result<DataFrame> {
+        if probability.len() != predictions.len() {
+            bail!(
+                "Got {} probabilities but {} predictions",
+                probability.len(),
+                predictions.len()
+            );
+        }
+
+        let mut df = self.to_dataframe(ids, predictions)?;
+        df.with_column(Series::new(
+            self.probability_column.as_str().into(),
+            probability.to_vec(),
+        ))?;
+
+        Ok(df)
+    }
+
+    /// Like `to_dataframe`, plus one probability column per class
+    ///
+    /// # Arguments
+    /// * `classes` - The class labels, ordered like the columns of `probabilities`
+    /// * `probabilities` - Shape (n_samples, n_classes), e.g. from `predict_proba`
+    pub fn to_dataframe_with_probabilities(
+        &self,
+        ids: &Series,
+        predictions: &Array1<usize>,
+        classes: &[usize],
+        probabilities: &Array2<f64>,
+    ) -> Result<DataFrame> {
+        if classes.len() != probabilities.ncols() {
+            bail!(
+                "Got {} classes but {} probability columns",
+                classes.len(),
+                probabilities.ncols()
+            );
+        }
+        if probabilities.nrows() != predictions.len() {
+            bail!(
+                "Got {} rows of probabilities but {} predictions",
+                probabilities.nrows(),
+                predictions.len()
+            );
+        }
+
+        let mut df = self.to_dataframe(ids, predictions)?;
+
+        for (class, column) in classes.iter().zip(probabilities.columns()) {
+            let name = format!("{}{class}", self.probability_prefix);
+            df.with_column(Series::new(name.into(), column.to_vec()))?;
+        }
+
+        Ok(df)
+    }
+
+    /// Write `df` to `path` in the configured format
+    pub fn write(&self, df: &mut DataFrame, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = File::create(path)?;
+
+        match self.format {
+            OutputFormat::Csv => CsvWriter::new(&mut file)
+                .include_header(true)
+                .with_separator(b',')
+                .finish(df)?,
+            OutputFormat::Parquet => {
+                ParquetWriter::new(&mut file).finish(df)?;
+            }
+            OutputFormat::Json => JsonWriter::new(&mut file)
+                .with_json_format(JsonFormat::Json)
+                .finish(df)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SubmissionWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_to_dataframe() {
+        let predictions = arr1(&[0, 1, 0, 1]);
+        let ids = Series::new("id".into(), [892i64, 893, 894, 895]);
+
+        let df = SubmissionWriter::new()
+            .to_dataframe(&ids, &predictions)
+            .unwrap();
+
+        assert_eq!(df.height(), 4);
+        assert_eq!(df.get_column_names(), ["PassengerId", "Survived"]);
+    }
+
+    #[test]
+    fn test_to_dataframe_with_probabilities() {
+        let predictions = arr1(&[0, 1]);
+        let probabilities = arr2(&[[0.75, 0.25], [0.1, 0.9]]);
+        let ids = Series::new("id".into(), ["a", "b"]);
+
+        let df = SubmissionWriter::new()
+            .id_column("Id")
+            .prediction_column("Label")
+            .probability_prefix("p_")
+            .to_dataframe_with_probabilities(&ids, &predictions, &[0, 1], &probabilities)
+            .unwrap();
+
+        assert_eq!(df.get_column_names(), ["Id", "Label", "p_0", "p_1"]);
+        assert_eq!(df.column("Id").unwrap().dtype(), &DataType::String);
+        assert_eq!(df.column("p_1").unwrap().f64().unwrap().get(1), Some(0.9));
+    }
+
+    #[test]
+    fn test_to_dataframe_with_multiclass_probabilities() {
+        let ids = Series::new("id".into(), [1i64, 2]);
+        let probabilities = arr2(&[[0.1, 0.2, 0.7], [0.6, 0.3, 0.1]]);
+        let writer = SubmissionWriter::new().prediction_column("Species");
+
+        let df = writer
+            .to_dataframe_with_probabilities(&ids, &arr1(&[2, 0]), &[0, 1, 2], &probabilities)
+            .unwrap();
+        assert_eq!(
+            df.get_column_names(),
+            [
+                "PassengerId",
+                "Species",
+                "probability_0",
+                "probability_1",
+                "probability_2"
+            ]
+        );
+
+        assert!(
+            writer
+                .to_dataframe_with_probabilities(&ids, &arr1(&[2]), &[0, 1, 2], &probabilities)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_to_dataframe_with_probability() {
+        let ids = Series::new("id".into(), [1i64, 2]);
+
+        let df = SubmissionWriter::new()
+            .to_dataframe_with_probability(&ids, &arr1(&[0, 1]), &arr1(&[0.2, 0.7]))
+            .unwrap();
+
+        assert_eq!(
+            df.get_column_names(),
+            ["PassengerId", "Survived", "Probability"]
+        );
+    }
+
+    #[test]
+    fn test_to_dataframe_length_mismatch() {
+        let ids = Series::new("id".into(), [1i64]);
+
+        assert!(
+            SubmissionWriter::new()
+                .to_dataframe(&ids, &arr1(&[0, 1]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_output_format_from_path() {
+        assert_eq!(
+            OutputFormat::from_path("out/sub.parquet"),
+            Some(OutputFormat::Parquet)
+        );
+        assert_eq!(OutputFormat::from_path("sub.txt"), None);
+    }
+}
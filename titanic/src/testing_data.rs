@@ -1,6 +1,6 @@
-use polars::prelude::{LazyCsvReader, LazyFileListReader, LazyFrame, PlPath};
+use polars::prelude::{IntoLazy, LazyFrame, col};
 
-use crate::data::Data;
+use crate::data::{CsvScanOptions, Data};
 
 pub struct TestingData {
     lazy_frame: LazyFrame,
@@ -8,11 +8,47 @@ pub struct TestingData {
 
 impl TestingData {
     pub fn try_new() -> anyhow::Result<Self> {
-        let lazy_frame = LazyCsvReader::new(PlPath::from_str("data/test.csv"))
-            .with_has_header(true)
-            .finish()?;
+        Self::try_from_path("data/test.csv")
+    }
+
+    /// Load Kaggle's `test.csv` from somewhere other than `data/`
+    pub fn try_from_path(path: &str) -> anyhow::Result<Self> {
+        Self::try_from_path_with(path, &CsvScanOptions::new())
+    }
+
+    /// `try_from_path`, scanning the file with `options`
+    pub fn try_from_path_with(path: &str, options: &CsvScanOptions) -> anyhow::Result<Self> {
+        Ok(Self {
+            lazy_frame: options.scan(path)?,
+        })
+    }
+
+    /// Use data that's already loaded, e.g. `demo::testing_data`'s embedded sample
+    pub fn from_lazy_frame(lazy_frame: LazyFrame) -> Self {
+        Self { lazy_frame }
+    }
+
+    /// Parse the CSV now and keep its rows in memory
+    ///
+    /// Until then every column or feature matrix asked for scans the file again, so call this
+    /// once before a run that reads the data more than once.
+    pub fn materialize(self) -> anyhow::Result<Self> {
+        Ok(Self {
+            lazy_frame: self.lazy_frame.collect()?.lazy(),
+        })
+    }
+
+    /// `materialize`, but only parse `columns`; the rest of the file is skipped, and can't be
+    /// asked for afterwards
+    pub fn materialize_columns<'a>(
+        self,
+        columns: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<Self> {
+        let columns: Vec<_> = columns.into_iter().map(col).collect();
 
-        Ok(Self { lazy_frame })
+        Ok(Self {
+            lazy_frame: self.lazy_frame.select(columns).collect()?.lazy(),
+        })
     }
 }
 
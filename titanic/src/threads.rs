@@ -0,0 +1,46 @@
+//! How many threads fitting and predicting use
+//!
+//! Fitting a forest, predicting with one and converting DataFrames all run on rayon. By default
+//! that's whichever pool the caller is already running in: rayon's global pool, sized by the
+//! `RAYON_NUM_THREADS` environment variable or else one thread per core, or an application's own
+//! pool if the call is made from inside its `install`. An application that keeps its threads for
+//! other work can instead give a forest a thread count with `RandomForestClassifier::n_threads`,
+//! or run any other call (prediction, a round of validation) on a pool of its own size with
+//! `install`.
+
+use std::num::NonZeroUsize;
+
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+
+/// Run `f` with its parallel work spread over `n_threads` threads, or over the current pool's if
+/// `None`
+///
+/// A pool of `n_threads` is built for the call and dropped after it, which costs about as much as
+/// starting that many threads.
+pub fn install<T: Send>(
+    n_threads: Option<NonZeroUsize>,
+    f: impl FnOnce() -> T + Send,
+) -> Result<T, ThreadPoolBuildError> {
+    match n_threads {
+        Some(n_threads) => Ok(ThreadPoolBuilder::new()
+            .num_threads(n_threads.get())
+            .build()?
+            .install(f)),
+        None => Ok(f()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install() {
+        let two = NonZeroUsize::new(2).unwrap();
+        assert_eq!(install(Some(two), rayon::current_num_threads).unwrap(), 2);
+        assert_eq!(
+            install(None, rayon::current_num_threads).unwrap(),
+            rayon::current_num_threads()
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Wall-clock time spent in each phase of a run
+//!
+//! A run reads the CSVs, selects and encodes features with Polars, converts them to arrays, fits
+//! forests and predicts with them. Which of those dominates changes with the size of the data,
+//! so the pipeline adds up the time spent in each in a `Timings` that a run can print or save as
+//! JSON.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+/// A step of the pipeline that time is charged to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Reading the CSVs
+    Load,
+    /// Selecting, encoding and splitting features in Polars
+    Preprocess,
+    /// Turning DataFrames into arrays
+    Convert,
+    /// Fitting (and pruning) forests
+    Fit,
+    /// Predicting with fitted forests
+    Predict,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Load => "load",
+            Self::Preprocess => "preprocess",
+            Self::Convert => "convert",
+            Self::Fit => "fit",
+            Self::Predict => "predict",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How long each phase took, in total over a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    durations: BTreeMap<Phase, Duration>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f`, charging the time it takes to `phase`
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+
+        result
+    }
+
+    /// Add `duration` to the time spent in `phase`
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        *self.durations.entry(phase).or_default() += duration;
+    }
+
+    /// The time spent in `phase`
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.durations.get(&phase).copied().unwrap_or_default()
+    }
+
+    /// The time spent in every phase
+    pub fn total(&self) -> Duration {
+        self.durations.values().sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    /// One line per phase with its share of the total, e.g. `fit        1.234s  80.1%`, then the
+    /// total
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().as_secs_f64();
+        for (phase, duration) in &self.durations {
+            let seconds = duration.as_secs_f64();
+            let share = if total > 0.0 { seconds / total } else { 0.0 };
+            writeln!(
+                f,
+                "{:<10} {seconds:>8.3}s {:>5.1}%",
+                phase.name(),
+                share * 100.0
+            )?;
+        }
+
+        write!(f, "{:<10} {total:>8.3}s", "total")
+    }
+}
+
+impl Serialize for Timings {
+    /// Seconds per phase, and in total, e.g. `{"load": 0.01, "fit": 0.9, "total": 0.91}`
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.durations
+                .iter()
+                .map(|(phase, duration)| (phase.name(), duration.as_secs_f64()))
+                .chain([("total", self.total().as_secs_f64())]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_display_and_json() {
+        let mut timings = Timings::new();
+        timings.record(Phase::Fit, Duration::from_millis(600));
+        timings.record(Phase::Load, Duration::from_millis(100));
+        timings.record(Phase::Fit, Duration::from_millis(300));
+        assert_eq!(timings.time(Phase::Predict, || 2 + 2), 4);
+
+        assert_eq!(timings.get(Phase::Fit), Duration::from_millis(900));
+        assert_eq!(timings.get(Phase::Convert), Duration::ZERO);
+        assert!(timings.total() >= Duration::from_millis(1000));
+
+        let text = timings.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("load"));
+        assert!(lines[1].starts_with("fit"));
+        assert!(lines[1].contains("0.900s"));
+        assert!(lines[3].starts_with("total"));
+
+        let json = serde_json::to_value(&timings).unwrap();
+        assert_eq!(json["load"], 0.1);
+        assert_eq!(json["fit"], 0.9);
+        assert!(json["total"].as_f64().unwrap() >= 1.0);
+    }
+}
@@ -1,6 +1,12 @@
-use polars::prelude::{LazyCsvReader, LazyFileListReader, LazyFrame, PlPath, col, lit};
+use polars::{
+    frame::DataFrame,
+    prelude::{Expr, IntoLazy, LazyFrame, col, lit},
+};
 
-use crate::data::Data;
+use crate::{
+    data::{CsvScanOptions, Data},
+    error::DataError,
+};
 
 pub struct TrainingData {
     lazy_frame: LazyFrame,
@@ -8,26 +14,64 @@ pub struct TrainingData {
 
 impl TrainingData {
     pub fn try_new() -> anyhow::Result<Self> {
-        let lazy_frame = LazyCsvReader::new(PlPath::from_str("data/train.csv"))
-            .with_has_header(true)
-            .finish()?;
+        Self::try_from_path("data/train.csv")
+    }
+
+    /// Load Kaggle's `train.csv` from somewhere other than `data/`, e.g. from the book crates
+    pub fn try_from_path(path: &str) -> anyhow::Result<Self> {
+        Self::try_from_path_with(path, &CsvScanOptions::new())
+    }
+
+    /// `try_from_path`, scanning the file with `options`
+    pub fn try_from_path_with(path: &str, options: &CsvScanOptions) -> anyhow::Result<Self> {
+        Ok(Self {
+            lazy_frame: options.scan(path)?,
+        })
+    }
+
+    /// Use data that's already loaded, e.g. `demo::training_data`'s embedded sample
+    pub fn from_lazy_frame(lazy_frame: LazyFrame) -> Self {
+        Self { lazy_frame }
+    }
+
+    /// Parse the CSV now and keep its rows in memory
+    ///
+    /// Until then every column or feature matrix asked for scans the file again, so call this
+    /// once before a run that reads the data more than once.
+    pub fn materialize(self) -> anyhow::Result<Self> {
+        Ok(Self {
+            lazy_frame: self.lazy_frame.collect()?.lazy(),
+        })
+    }
+
+    /// `materialize`, but only parse `columns`; the rest of the file is skipped, and can't be
+    /// asked for afterwards
+    pub fn materialize_columns<'a>(
+        self,
+        columns: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<Self> {
+        let columns: Vec<_> = columns.into_iter().map(col).collect();
+
+        Ok(Self {
+            lazy_frame: self.lazy_frame.select(columns).collect()?.lazy(),
+        })
+    }
 
-        Ok(Self { lazy_frame })
+    /// The survival rate of each group of passengers `by` picks out, e.g.
+    /// `[col("Pclass"), col("Sex")]`; see `Data::rate_by`
+    pub fn survival_rate_by<E: AsRef<[Expr]>>(&self, by: E) -> Result<DataFrame, DataError> {
+        self.rate_by("Survived", by)
     }
 
     pub fn percentage_of_sex_who_survived(&self, sex: &str) -> anyhow::Result<()> {
-        let women = self
-            .lazy_frame
-            .clone()
+        let by_sex = self.survival_rate_by([col("Sex")])?;
+        let rate = by_sex
+            .lazy()
             .filter(col("Sex").eq(lit(sex)))
-            .select([
-                col("Survived").sum().alias("survived_sum"),
-                col("Survived").count().alias("total_count"),
-            ])
-            .collect()?;
-
-        let rate = women.column("survived_sum")?.get(0)?.try_extract::<f64>()?
-            / women.column("total_count")?.get(0)?.try_extract::<f64>()?;
+            .collect()?
+            .column("rate")?
+            .get(0)?
+            .try_extract::<f64>()?;
 
         println!(
             "Percentage of {sex}s who survived: {:.2}% ({rate})",
@@ -43,3 +87,43 @@ impl Data for TrainingData {
         self.lazy_frame.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_materialize_reads_the_file_once() {
+        let path = std::env::temp_dir().join("titanic-ml-materialize-test.csv");
+        std::fs::write(&path, "PassengerId,Survived\n1,0\n2,1\n").unwrap();
+
+        let data = TrainingData::try_from_path(&path.to_string_lossy())
+            .unwrap()
+            .materialize()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The file is gone, so this only works if the rows were kept
+        assert_eq!(data.get_col_as_series("Survived").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_materialize_columns_keeps_only_those_columns() {
+        let path = std::env::temp_dir().join("titanic-ml-materialize-columns-test.csv");
+        std::fs::write(
+            &path,
+            "PassengerId,Survived,Name\n1,0,Braund\n2,1,Cumings\n",
+        )
+        .unwrap();
+
+        let data = TrainingData::try_from_path(&path.to_string_lossy())
+            .unwrap()
+            .materialize_columns(["Survived", "PassengerId"])
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let df = data.lazy_frame_cloned().collect().unwrap();
+        assert_eq!(df.get_column_names(), ["Survived", "PassengerId"]);
+        assert_eq!(df.height(), 2);
+    }
+}
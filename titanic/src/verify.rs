@@ -0,0 +1,210 @@
+//! Golden-prediction checks, a guard for refactors
+//!
+//! `golden_predictions` retrains with a fixed seed and fixed settings and predicts the Kaggle test
+//! set. A refactor of the conversions, the one-hot encoding order or the forest shouldn't change a
+//! single one of those predictions, so `compare` checks them against a stored copy and lists every
+//! passenger whose label or survival probability moved.
+
+use std::{fmt, path::Path};
+
+use anyhow::{Result, bail};
+use polars::prelude::*;
+
+use crate::{
+    conversion_report::ConversionReport, data::Data, feature_set::Preset,
+    random_forest::RandomForestClassifier, seed::Seed, submission::SubmissionWriter,
+    testing_data::TestingData, training_data::TrainingData,
+};
+
+/// Where the golden predictions are kept by default
+pub const GOLDEN_PATH: &str = "data/golden_predictions.csv";
+
+/// The seed every golden run uses
+pub const GOLDEN_SEED: u64 = 1;
+
+/// Probabilities are vote fractions, so anything beyond CSV rounding is a real change
+const PROBABILITY_TOLERANCE: f64 = 1e-9;
+
+/// Train on all of `training_data` with `GOLDEN_SEED` and predict `testing_data`
+///
+/// # Returns
+/// * `Result<DataFrame>` - `PassengerId`, `Survived` and `Probability` (of surviving) columns
+pub fn golden_predictions(
+    training_data: &TrainingData,
+    testing_data: &TestingData,
+) -> Result<DataFrame> {
+    let mut train = Preset::Baseline.features().select(training_data)?;
+    train.with_column(training_data.get_col_as_series("Survived")?)?;
+    let saved = RandomForestClassifier::new()
+        .n_estimators(100)
+        .max_depth(5)
+        .random_state(Seed::new(GOLDEN_SEED).derive("golden forest"))
+        .fit_frame(&train, "Survived")?;
+    let model = saved.forest();
+
+    let x_test =
+        testing_data.get_encoded_features(saved.feature_schema(), &mut ConversionReport::new())?;
+    let Some(survived_idx) = model.classes().iter().position(|&class| class == 1) else {
+        bail!("The model never saw a survivor");
+    };
+    let probability = model.predict_proba(&x_test).column(survived_idx).to_owned();
+
+    SubmissionWriter::new().to_dataframe_with_probability(
+        &testing_data.get_col_as_series("PassengerId")?,
+        &model.predict(&x_test),
+        &probability,
+    )
+}
+
+/// Read golden predictions written from `golden_predictions`
+pub fn read_golden(path: &Path) -> Result<DataFrame> {
+    let df = LazyCsvReader::new(PlPath::new(&path.to_string_lossy()))
+        .with_has_header(true)
+        .finish()?
+        .collect()?;
+
+    Ok(df)
+}
+
+/// One passenger whose prediction changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub passenger_id: i64,
+    pub expected_label: i64,
+    pub actual_label: i64,
+    pub expected_probability: f64,
+    pub actual_probability: f64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PassengerId {}: expected {} ({:.4}), got {} ({:.4})",
+            self.passenger_id,
+            self.expected_label,
+            self.expected_probability,
+            self.actual_label,
+            self.actual_probability
+        )
+    }
+}
+
+/// Every row where `actual` differs from the golden `expected`
+///
+/// Both frames need `PassengerId`, `Survived` and `Probability` columns, with the same passengers
+/// in the same order; anything else is an error rather than a list of divergences, since then the
+/// rows can't be lined up at all.
+pub fn compare(expected: &DataFrame, actual: &DataFrame) -> Result<Vec<Divergence>> {
+    if expected.height() != actual.height() {
+        bail!(
+            "Expected {} predictions but got {}",
+            expected.height(),
+            actual.height()
+        );
+    }
+
+    let columns = |df: &DataFrame| -> Result<(Vec<i64>, Vec<i64>, Vec<f64>)> {
+        let ints = |name: &str| -> Result<Vec<i64>> {
+            let column = df.column(name)?.cast(&DataType::Int64)?;
+            Ok(column.i64()?.into_no_null_iter().collect())
+        };
+        let probability = df.column("Probability")?.cast(&DataType::Float64)?;
+
+        Ok((
+            ints("PassengerId")?,
+            ints("Survived")?,
+            probability.f64()?.into_no_null_iter().collect(),
+        ))
+    };
+    let (expected_ids, expected_labels, expected_probabilities) = columns(expected)?;
+    let (actual_ids, actual_labels, actual_probabilities) = columns(actual)?;
+
+    if expected_ids != actual_ids {
+        bail!("The predictions are for different passengers than the golden file's");
+    }
+
+    let divergences = (0..expected_ids.len())
+        .filter(|&i| {
+            expected_labels[i] != actual_labels[i]
+                || (expected_probabilities[i] - actual_probabilities[i]).abs()
+                    > PROBABILITY_TOLERANCE
+        })
+        .map(|i| Divergence {
+            passenger_id: expected_ids[i],
+            expected_label: expected_labels[i],
+            actual_label: actual_labels[i],
+            expected_probability: expected_probabilities[i],
+            actual_probability: actual_probabilities[i],
+        })
+        .collect();
+
+    Ok(divergences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn predictions(survived: [i64; 3], probability: [f64; 3]) -> DataFrame {
+        df!(
+            "PassengerId" => [892i64, 893, 894],
+            "Survived" => survived,
+            "Probability" => probability,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let golden = predictions([0, 1, 0], [0.1, 0.7, 0.3]);
+
+        assert!(compare(&golden, &golden).unwrap().is_empty());
+
+        // One label flipped, one probability moved without flipping its label
+        let changed = predictions([1, 1, 0], [0.6, 0.7, 0.31]);
+        let divergences = compare(&golden, &changed).unwrap();
+        let ids: Vec<_> = divergences.iter().map(|d| d.passenger_id).collect();
+        assert_eq!(ids, [892, 894]);
+        assert_eq!(
+            divergences[0].to_string(),
+            "PassengerId 892: expected 0 (0.1000), got 1 (0.6000)"
+        );
+    }
+
+    #[test]
+    fn test_compare_rejects_different_passengers() {
+        let golden = predictions([0, 1, 0], [0.1, 0.7, 0.3]);
+        let mut other = golden.clone();
+        other
+            .with_column(Series::new("PassengerId".into(), [1i64, 2, 3]))
+            .unwrap();
+
+        assert!(compare(&golden, &other).is_err());
+        assert!(compare(&golden, &golden.head(Some(2))).is_err());
+    }
+
+    #[test]
+    fn test_update_then_verify_agree() {
+        let retrain = || {
+            golden_predictions(
+                &TrainingData::try_new().unwrap().materialize().unwrap(),
+                &TestingData::try_new().unwrap().materialize().unwrap(),
+            )
+            .unwrap()
+        };
+
+        // As `verify --update` writes the golden file and `verify` reads it back
+        let path = std::env::temp_dir().join("titanic-ml-golden-test.csv");
+        SubmissionWriter::new()
+            .write(&mut retrain(), &path)
+            .unwrap();
+        let updated = read_golden(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let predictions = retrain();
+        assert!(compare(&updated, &predictions).unwrap().is_empty());
+        let committed = read_golden(Path::new(GOLDEN_PATH)).unwrap();
+        assert!(compare(&committed, &predictions).unwrap().is_empty());
+    }
+}
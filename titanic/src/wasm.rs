@@ -0,0 +1,52 @@
+//! JavaScript bindings for running a saved model in the browser (`--features wasm`)
+//!
+//! Only the prediction path (feature encoding + the fitted forest) is built for
+//! `wasm32-unknown-unknown`; the Polars-based data loading stays native-only.
+//!
+//! ```js
+//! // wasm-pack build --target web -- --features wasm
+//! import init, { TitanicModel } from "./pkg/titanic_ml.js";
+//!
+//! await init();
+//! // cargo run -- --json-model --model data/model.json
+//! const model = new TitanicModel(await (await fetch("model.json")).text());
+//! const { predictions, probabilities } = JSON.parse(
+//!   model.predict(JSON.stringify([{ Pclass: 3, Sex: "male", Age: 22, Fare: 7.25 }])),
+//! );
+//! ```
+
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::saved_model::SavedModel;
+
+/// A model saved by the `titanic-ml` binary
+#[wasm_bindgen]
+pub struct TitanicModel {
+    inner: SavedModel,
+}
+
+#[wasm_bindgen]
+impl TitanicModel {
+    /// Load from the contents of a model file saved as plain JSON (`train --json-model`); the
+    /// compressed format needs zstd, which isn't built for `wasm32`
+    #[wasm_bindgen(constructor)]
+    pub fn new(model_json: &str) -> Result<TitanicModel, JsError> {
+        Ok(Self {
+            inner: serde_json::from_str(model_json)?,
+        })
+    }
+
+    /// Predict a JSON array of raw feature rows
+    ///
+    /// Returns JSON with `classes`, `predictions` and `probabilities`, like `POST /predict`.
+    pub fn predict(&self, rows_json: &str) -> Result<String, JsError> {
+        let rows: Vec<Map<String, Value>> = serde_json::from_str(rows_json)?;
+        let predictions = self
+            .inner
+            .predict_json_rows(&rows)
+            .map_err(|e| JsError::new(&format!("{e:#}")))?;
+
+        Ok(serde_json::to_string(&predictions)?)
+    }
+}